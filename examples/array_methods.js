@@ -0,0 +1,27 @@
+var a = [3, 1, , 10, 2]
+a.forEach(function(x, i) { console.log(i, x) })
+console.log(a.map(function(x) { return x * 2 }))
+console.log(a.filter(function(x) { return x > 1 }))
+console.log(a.reduce(function(acc, x) { return acc + x }))
+console.log(a.reduceRight(function(acc, x) { return acc + ',' + x }))
+console.log(a.some(function(x) { return x > 5 }), a.every(function(x) { return x > 0 }))
+console.log(a.find(function(x) { return x > 2 }), a.findIndex(function(x) { return x === undefined }))
+console.log(a.indexOf(10), a.lastIndexOf(3), a.includes(undefined), a.indexOf(undefined))
+console.log(a.slice(1, -1), a.slice(-2))
+
+var b = [1, 2, 3, 4, 5]
+console.log(b.splice(1, 2, 'x', 'y', 'z'))
+console.log(b)
+console.log(b.concat([6, , 7], 8), b.join('-'), [1, null, undefined, 2].join())
+console.log(b.reverse())
+console.log(b.shift())
+console.log(b.unshift(0, 0.5))
+console.log(b)
+
+console.log([5, 1, 10, 2].sort(), [5, 1, 10, 2].sort(function(x, y) { return x - y }))
+console.log([3, undefined, , 1].sort())
+console.log((new Array(3)).fill(7), [1, 2, 3, 4].fill(0, 1, -1))
+
+console.log(Array.isArray([]), Array.isArray({}), Array.of(1, 2, 3))
+console.log(Array.from('abc'), Array.from({length: 2, 0: 'a', 1: 'b'}))
+console.log(Array.from([1, 2, 3], function(x) { return x * 10 }))
@@ -0,0 +1,51 @@
+var a = 123456789012345678901234567890n;
+var b = 987654321n;
+console.log(a, b, -a);
+console.log(a + b, a - b, a * b);
+// Division truncates toward zero.
+console.log(a / b, a % b, -a / b, -a % b);
+console.log(2n ** 100n, (-2n) ** 3n, 0n ** 0n);
+console.log(1n << 70n, -5n >> 1n, 5n << -1n);
+// Bitwise operators work as if in infinite-precision two's complement.
+console.log(-6n & 5n, -6n | 5n, -6n ^ 5n, ~5n, ~-1n);
+console.log(0xffn, 0o17n, 0b101n, 0n);
+
+// Number literals are rounded only once.
+console.log(9007199254740993, 0x20000000000001, 12345678901234567890);
+
+console.log(BigInt(42), BigInt("  0x1f  "), BigInt(true), BigInt(1e21), BigInt("-12"));
+console.log(BigInt.asIntN(8, 255n), BigInt.asUintN(8, -1n), BigInt.asUintN(64, -1n));
+console.log(BigInt.asIntN(64, 2n ** 63n));
+console.log(a.toString(), a.toString(16), (-255n).toString(2), b.valueOf());
+
+console.log(1n < 2, 2n > 1.5, 1n <= 1, 3n >= 4, 1n < 1.5, 10n > "9", 10n < "x");
+console.log(1n == 1, 1n != 1, 2n == "2", 1n === 1, 1n === 1n, 0n == "");
+console.log(1n + "", "x" + 2n, [1n, 2n]);
+console.log(Number(a), Number(3n), String(10n), !0n, !1n);
+console.log(Object.prototype.toString.call(1n));
+
+var m = new Map();
+m.set(1n, "one");
+console.log(m.get(1n), m.get(1));
+
+var x = 3;
+console.log(x ** 2, 1 ** Infinity, ~x, x ^ 1);
+
+function test(f) {
+  try {
+    console.log(f());
+  } catch (e) {
+    console.log(e.name);
+  }
+}
+test(function() { return 1n + 1; });
+test(function() { return 1 * 2n; });
+test(function() { return 1n / 0n; });
+test(function() { return 2n ** -1n; });
+test(function() { return 1n >>> 0n; });
+test(function() { return +1n; });
+test(function() { return BigInt(1.5); });
+test(function() { return BigInt("1.5"); });
+test(function() { return BigInt(undefined); });
+test(function() { return new BigInt(1); });
+test(function() { return JSON.stringify({ a: 1n }); });
@@ -0,0 +1,39 @@
+var d = new Date(Date.UTC(2019, 0, 31, 1, 20, 30, 400));
+console.log(d.getTime(), d.toISOString(), d.toUTCString());
+console.log(d.getUTCFullYear(), d.getUTCMonth(), d.getUTCDate(), d.getUTCDay());
+console.log(d.getUTCHours(), d.getUTCMinutes(), d.getUTCSeconds(), d.getUTCMilliseconds());
+console.log(JSON.stringify({ date: d }));
+
+// Local time depends on the TZ environment variable.
+var local = new Date(2019, 0, 31, 10, 20, 30);
+console.log(local.getFullYear(), local.getMonth(), local.getDate(), local.getDay());
+console.log(local.getHours(), local.getMinutes(), local.getSeconds());
+console.log(local.getTime() - Date.UTC(2019, 0, 31, 10, 20, 30) === local.getTimezoneOffset() * 60000);
+console.log(Date.parse(local.toString()) === local.getTime());
+
+console.log(Date.parse('2019-01-31T10:20:30.400+09:00'));
+console.log(Date.parse('Thu, 31 Jan 2019 01:20:30 GMT'));
+console.log(Date.parse('2019-01-31'), Date.parse('not a date'));
+
+// Setters take the following fields as well and carry over out-of-range values.
+d.setUTCHours(25, 0);
+console.log(d.toISOString());
+d.setUTCMonth(13);
+console.log(d.toISOString());
+console.log(d.setUTCFullYear(2000, 1, 29));
+console.log(d.toISOString());
+d.setTime(0);
+console.log(d.toISOString());
+
+var invalid = new Date(NaN);
+console.log(invalid.getTime(), invalid.toString(), invalid.toJSON());
+try {
+  invalid.toISOString();
+} catch (e) {
+  console.log(e.name);
+}
+invalid.setFullYear(2019);
+console.log(invalid.getMonth(), invalid.getDate());
+
+console.log((new Date(d)).getTime(), (new Date(99, 0)).getFullYear(), new Date(8.64e15 + 1));
+console.log(Date.now() > 1500000000000, Object.prototype.toString.call(d));
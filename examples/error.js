@@ -0,0 +1,51 @@
+try {
+  throw 1
+} catch (e) {
+  console.log('caught', e)
+}
+
+function f(x) {
+  if (x > 2) return x.foo()
+  return f(x + 1)
+}
+
+try {
+  f(0)
+} catch (e) {
+  console.log(e.name, TypeError.prototype.isPrototypeOf(e), Error.prototype.isPrototypeOf(e))
+  console.log(e.stack)
+}
+
+var e = new RangeError('out of range')
+console.log(e.toString(), e.name, e.message, Object.keys(e).length)
+console.log(Error('no new').message, (new Error()).toString())
+
+function g() {
+  try {
+    return 'try'
+  } finally {
+    console.log('finally')
+  }
+}
+console.log(g())
+
+for (var i = 0; i < 3; i++) {
+  try {
+    if (i == 1) continue
+    if (i == 2) break
+    console.log('body', i)
+  } finally {
+    console.log('finally', i)
+  }
+}
+
+try {
+  [1, 2, 3].map(function(x) {
+    if (x == 2) throw new SyntaxError('in callback')
+    return x
+  })
+} catch (err) {
+  console.log(err.toString())
+}
+
+throw new Error('uncaught')
@@ -0,0 +1,53 @@
+console.log(eval('1 + 2'), eval('var a = 1; a + 1;'), eval(42), eval(''));
+
+function direct() {
+  var local = 10;
+  eval('var declared = local * 2; function twice(n) { return n * 2; }');
+  return twice(declared);
+}
+console.log(direct());
+
+x = 'global x';
+function indirect() {
+  var x = 'local x';
+  var geval = eval;
+  return [eval('x'), geval('x')];
+}
+console.log(indirect());
+
+function counter() {
+  var n = 0;
+  eval('var inc = function() { n++; return n; }');
+  inc();
+  return inc();
+}
+console.log(counter());
+
+function thisInEval() {
+  return eval('this.v');
+}
+console.log(thisInEval.call({ v: 'this' }));
+
+// Strict mode code declares its variables in a scope of its own.
+function strict() {
+  eval('"use strict"; var hidden = 1;');
+  try {
+    return hidden;
+  } catch (e) {
+    return e.name;
+  }
+}
+console.log(strict());
+
+try {
+  eval('var = 1');
+} catch (e) {
+  console.log(e.name);
+}
+try {
+  eval('throw new RangeError("thrown")');
+} catch (e) {
+  console.log(e.name, e.message);
+}
+var obj = { eval: function(s) { return 'not eval: ' + s; } };
+console.log(obj.eval('1'), eval('if (true) { "yes"; } else { "no"; }'));
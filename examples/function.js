@@ -0,0 +1,54 @@
+function add(a, b) {
+  return a + b;
+}
+console.log(add.name, add.length, add.toString());
+
+console.log((function(x, ...rest) {}).length, (function() {}).name === '');
+
+function Point(x, y) {
+  this.x = x;
+  this.y = y;
+}
+Point.prototype.sum = function() {
+  return this.x + this.y;
+};
+
+var add1 = add.bind(null, 1);
+console.log(add1(2), add1.name, add1.length);
+var obj = { n: 10 };
+var getN = function() {
+  return this.n;
+}.bind(obj);
+console.log(getN(), getN.call({ n: 20 }));
+
+var P1 = Point.bind(null, 3);
+var p = new P1(4);
+console.log(p.x, p.y, p.sum(), p.constructor === Point);
+var P2 = P1.bind(null, 5);
+console.log((new P2()).sum(), P2.name, P2.length);
+
+var mul = new Function('a', 'b', 'return a * b');
+console.log(mul(6, 7), mul.name, mul.length);
+console.log(mul.toString());
+var noArgs = Function('return 1 + 1');
+console.log(noArgs());
+x = 'global';
+function scope() {
+  var x = 'local';
+  return Function('return x')();
+}
+console.log(scope());
+
+try {
+  Function('return }');
+} catch (e) {
+  console.log(e.name);
+}
+try {
+  Function.prototype.bind.call(1);
+} catch (e) {
+  console.log(e.name);
+}
+console.log(Object.getPrototypeOf(add) === Function.prototype, add.constructor === Function);
+console.log(Object.keys(add).length, add.hasOwnProperty('name'));
+console.log(add1.toString());
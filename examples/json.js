@@ -0,0 +1,17 @@
+var o = JSON.parse('{"a": [1, 2.5e3, -0.5, true, false, null], "b": {"c": "x\\ny\\u00e9\\ud83d\\ude00"}, "d": ""}')
+console.log(o.a, o.b.c, o.a.length, o.d === "")
+console.log(JSON.stringify(o))
+console.log(JSON.stringify(o, null, 2))
+console.log(JSON.stringify([1, , undefined, function () {}, "s"]))
+console.log(JSON.stringify({ u: undefined, f: function () {}, n: NaN, i: -Infinity, z: 0 }))
+console.log(JSON.stringify("a\"b\\c\u0001"), JSON.stringify(undefined), JSON.stringify(function () {}), JSON.stringify(null))
+console.log(JSON.stringify({ a: 1, b: [1, 2], c: { d: 3 } }, null, "--"))
+console.log(JSON.stringify({ a: 1, b: 2, c: 3 }, ["c", "a", "a"]))
+console.log(JSON.stringify({ a: 1, b: "x", c: [2] }, function (k, v) { if (k === "a" || k === "0") { return v * 10 } return v }))
+console.log(JSON.stringify({ x: { toJSON: function (k) { return "key:" + k } } }))
+console.log(JSON.stringify({}, null, 4), JSON.stringify([], null, 4), JSON.stringify([[]], null, 1))
+var r = JSON.parse('{"a": 1, "b": [1, 2, {"c": 3}]}', function (k, v) { if (k === "a" || k === "c") { return v + 1 } return v })
+console.log(JSON.stringify(r))
+var r2 = JSON.parse('{"a": 1, "b": 2}', function (k, v) { if (k === "a") { return undefined } return v })
+console.log(JSON.stringify(r2))
+console.log(JSON.parse(' 12 '), JSON.parse('"s"'), JSON.parse('[]').length, JSON.parse('-0'))
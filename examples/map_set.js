@@ -0,0 +1,66 @@
+var m = new Map([[1, 'one'], ['1', 'string one']]);
+var key = {};
+m.set(key, 'object').set(NaN, 'not a number').set(-0, 'zero');
+console.log(m.size, m.get(1), m.get('1'), m.get(key), m.get({}));
+console.log(m.get(NaN), m.get(0), m.has(+0), m.has(-0));
+console.log(m.delete(1));
+console.log(m.delete(1), m.size);
+
+m.forEach(function(value, k, map) {
+  console.log(k, '=>', value, map === m);
+});
+
+var it = m.entries();
+var step = it.next();
+while (!step.done) {
+  console.log(step.value);
+  step = it.next();
+}
+m.set('late', 1);
+console.log(it.next().done);
+
+// Entries added during iteration are visited, deleted ones are skipped.
+var s = new Set([1, 2, 2, 3]);
+s.forEach(function(v) {
+  if (v === 1) {
+    s.delete(2);
+    s.add(4);
+  }
+  console.log(v);
+});
+console.log(s.size, s.has(2), s.keys === s.values);
+console.log(new Set('hello'));
+console.log(new Map(m));
+
+var values = s.values();
+console.log(values.next().value);
+console.log(values.next().value);
+s.clear();
+console.log(values.next(), s.size);
+
+var wm = new WeakMap();
+var k1 = {};
+var k2 = [];
+wm.set(k1, 1).set(k2, 2);
+console.log(wm.get(k1), wm.get(k2), wm.has({}));
+console.log(wm.delete(k2));
+console.log(wm.has(k2));
+var ws = new WeakSet([k1]);
+console.log(ws.has(k1), ws.has(k2));
+try {
+  wm.set(1, 1);
+} catch (e) {
+  console.log(e.name);
+}
+try {
+  Map();
+} catch (e) {
+  console.log(e.name);
+}
+console.log(Object.prototype.toString.call(m), wm);
+
+// Keys of weak collections don't keep their values alive.
+for (var i = 0; i < 2000; i++) {
+  wm.set({ i: i }, [i]);
+}
+console.log(wm.has(k1));
@@ -0,0 +1,18 @@
+console.log(Math.atan2(1, 2), Math.atanh(0.5), Math.ceil(1.2));
+console.log(Math.clz32(1), Math.clz32(0), Math.clz32(-1));
+console.log(Math.exp(1), Math.expm1(1), Math.log(10), Math.log1p(1));
+console.log(Math.log10(1000), Math.log2(8), Math.sqrt(2), Math.abs("-2"));
+console.log(Math.E, Math.LN2, Math.LN10, Math.LOG2E);
+console.log(Math.LOG10E, Math.PI, Math.SQRT1_2, Math.SQRT2);
+console.log(Math.imul(3, 4), Math.imul(0xffffffff, 5), Math.imul(0x7fffffff, 2));
+
+// Halves are rounded up.
+console.log(Math.round(2.5), Math.round(-2.5), Math.round(-0.2), Math.round(0.49999999999999994));
+
+console.log(Math.max(), Math.min(), Math.max(1, NaN, 3), Math.max(-0, 0), Math.min(0, -0));
+console.log(Math.fround(5.5), Math.fround(5.05), Math.sign(-3), Math.sign(-0));
+console.log(Math.hypot(3, 4), Math.hypot(), Math.hypot(NaN, Infinity));
+console.log(Math.pow(2, 10), Math.pow(1, Infinity), Math.pow(NaN, 0));
+
+var r = Math.random();
+console.log(r >= 0 && r < 1);
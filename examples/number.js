@@ -0,0 +1,15 @@
+console.log(1e21, 1e-7, 0.000001, 123.456, 0.1 + 0.2, -0, 1/3, 2e-7, 100, -1e21, -Infinity)
+console.log(String(1e21), String(0.000001), String(-0), "" + 5e-324, "" + 1.7976931348623157e308)
+console.log((255).toString(16), (255).toString(2), (-255).toString(36), (0.5).toString(2), (3.14159).toString(8), (1e21).toString(7))
+console.log((0.1).toString(3), (NaN).toString(2), (Infinity).toString(16))
+console.log((1.005).toFixed(2), (0.5).toFixed(0), (2.5).toFixed(0), (-2.5).toFixed(0), (1234.5678).toFixed(2), (1e21).toFixed(2), (0).toFixed(2), (0.000001).toFixed(7))
+console.log((123.456).toPrecision(4), (0.00001234).toPrecision(2), (123456).toPrecision(2), (1.5).toPrecision(1), (99.99).toPrecision(3), (0).toPrecision(3), (5e-7).toPrecision(1))
+console.log((123456).toExponential(2), (0).toExponential(), (1.5).toExponential(0), (123.456).toExponential(), (-0.00015).toExponential(1))
+console.log(Number("  42 "), Number(""), Number("0x1f"), Number("0b101"), Number("1e3"), Number("12px"), Number("Infinity"), Number("-.5"), Number("1."), Number(), Number(true))
+console.log(parseInt("  42px"), parseInt("0x1f"), parseInt("ff", 16), parseInt("101", 2), parseInt("z", 37), parseInt("-12.9"), parseInt(""), parseInt("123456789012345678901234567890"))
+console.log(parseFloat("3.14abc"), parseFloat(".5e1x"), parseFloat("-Infinityx"), parseFloat("abc"), parseFloat("1.e2"), parseFloat("1e"))
+console.log(isNaN("abc"), isNaN("12"), isFinite("12"), isFinite(Infinity), Number.isNaN("abc"), Number.isFinite("12"))
+console.log(Number.isInteger(5), Number.isInteger(5.5), Number.isSafeInteger(Math.pow(2, 53)), Number.isSafeInteger(Math.pow(2, 53) - 1), Number.EPSILON > 0, Number.MAX_SAFE_INTEGER)
+console.log(NaN, Infinity, Number.MIN_VALUE, Number.MAX_VALUE, (5).valueOf(), Number.parseInt === parseInt)
+var x = 42
+console.log(x.toString(), x.toFixed(1), [1.5, 2e21])
@@ -0,0 +1,19 @@
+var o = {a: 1, b: 'x'}
+console.log(Object.keys(o), Object.values(o), Object.entries(o))
+console.log(o.hasOwnProperty('a'), o.hasOwnProperty('z'), o.hasOwnProperty('hasOwnProperty'))
+console.log(o.toString(), [1].hasOwnProperty(0), Object.prototype.toString.call([1]))
+var n = Object.create(null)
+console.log(Object.getPrototypeOf(n), n.hasOwnProperty)
+var p = Object.create(o, {c: {value: 3, enumerable: true}})
+console.log(p.a, p.c, Object.keys(p), o.isPrototypeOf(p), Object.prototype.isPrototypeOf(p))
+console.log(Object.getPrototypeOf(p) === o)
+Object.setPrototypeOf(p, null)
+console.log(p.a, Object.getPrototypeOf(p))
+console.log(Object.assign({}, {x: 1}, {y: 2}))
+console.log(Object.getOwnPropertyNames([1, 2]))
+console.log(Object.is(0 / 0, 0 / 0), Object.is(0, -0), Object.is(o, o), Object.is(o, {}))
+console.log(o.propertyIsEnumerable('a'), o.valueOf() === o, new Object(), Object(o) === o)
+function F() { this.q = 1 }
+var f = new F()
+console.log(f.hasOwnProperty('q'), F.prototype.isPrototypeOf(f), Object.getPrototypeOf(f) === F.prototype)
+console.log({}.hasOwnProperty('x'), [].hasOwnProperty('length'))
@@ -0,0 +1,28 @@
+var o = {a: 1, b: 2}
+Object.defineProperty(o, 'c', {value: 3})
+console.log(o, o.c)
+o.c = 10
+console.log(o.c)
+console.log(Object.getOwnPropertyDescriptor(o, 'c'))
+console.log(Object.getOwnPropertyDescriptor(o, 'a'))
+Object.freeze(o)
+o.a = 100
+console.log(o.a, Object.isFrozen(o), Object.isSealed(o), Object.isExtensible(o))
+delete o.a
+console.log(o)
+var p = {x: 1}
+delete p.x
+console.log(p)
+Object.seal(p)
+p.y = 3
+console.log(p, Object.isSealed(p))
+var a = [1, 2, 3]
+Object.freeze(a)
+a[0] = 5
+console.log(a)
+function f() {
+  'use strict'
+  var q = Object.freeze({k: 1})
+  q.k = 2
+}
+f()
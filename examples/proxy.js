@@ -0,0 +1,141 @@
+var log = [];
+var target = { a: 1, b: 2 };
+var handler = {
+  get: function(t, key, receiver) {
+    log.push('get ' + key);
+    if (key === 'missing') return 'default';
+    return t[key];
+  },
+  set: function(t, key, value) {
+    log.push('set ' + key);
+    if (key === 'readonly') return false;
+    t[key] = value * 10;
+    return true;
+  },
+  has: function(t, key) {
+    return key === 'magic' || Reflect.has(t, key);
+  },
+  deleteProperty: function(t, key) {
+    log.push('delete ' + key);
+    return Reflect.deleteProperty(t, key);
+  },
+  ownKeys: function(t) {
+    return Reflect.ownKeys(t).concat(['extra']);
+  },
+  getPrototypeOf: function(t) {
+    return Array.prototype;
+  },
+  defineProperty: function(t, key, desc) {
+    log.push('define ' + key);
+    return Reflect.defineProperty(t, key, desc);
+  }
+};
+var p = new Proxy(target, handler);
+var a = p.a;
+var missing = p.missing;
+console.log(a, missing, p['b']);
+p.c = 3;
+console.log(target.c, p.c);
+console.log(delete p.a);
+console.log(target.a);
+console.log(Reflect.has(p, 'magic'), Reflect.has(p, 'b'), Reflect.has(p, 'zzz'));
+console.log(Object.keys(p), Object.getOwnPropertyNames(p), Reflect.ownKeys(p));
+console.log(Object.getPrototypeOf(p) === Array.prototype);
+Object.defineProperty(p, 'd', { value: 4, enumerable: true });
+console.log(target.d);
+console.log(log.join(', '));
+
+// Strict mode code throws when a trap returns false.
+(function() {
+  'use strict';
+  try {
+    p.readonly = 1;
+  } catch (e) {
+    console.log(e.name);
+  }
+})();
+p.readonly = 1;
+console.log(Reflect.set(p, 'readonly', 1), Reflect.set(p, 'x', 1));
+console.log(target.x);
+
+// Function proxies
+function sum(a, b) {
+  return a + b;
+}
+var calls = 0;
+var traced = new Proxy(sum, {
+  apply: function(t, thisArg, args) {
+    calls++;
+    return t.apply(thisArg, args) * 2;
+  }
+});
+console.log(traced(1, 2), traced.call(null, 3, 4), traced.apply(null, [5, 6]));
+console.log(calls);
+console.log([1, 2, 3].map(new Proxy(function(x) { return x + 1; }, {})));
+
+function Point(x, y) {
+  this.x = x;
+  this.y = y;
+}
+var P = new Proxy(Point, {
+  construct: function(t, args, newTarget) {
+    return { made: args.length, by: newTarget === P };
+  }
+});
+var made = new P(1, 2);
+console.log(made.made, made.by);
+var Q = new Proxy(Point, {});
+var q = new Q(3, 4);
+console.log(q.x, q.y, Object.getPrototypeOf(q) === Point.prototype);
+
+// A proxy without traps forwards everything to the target.
+var plain = new Proxy([1, 2, 3], {});
+console.log(plain.length, plain[1], Array.isArray(plain), JSON.stringify(plain));
+plain.push(4);
+console.log(plain.length, JSON.stringify({ p: new Proxy({ q: 1 }, {}) }));
+
+// Methods are called with the proxy as 'this'.
+var greeter = {
+  name: 'obj',
+  hello: function() {
+    return 'hello from ' + this.name;
+  }
+};
+var obj = new Proxy(greeter, {
+  get: function(t, key) {
+    return key === 'name' ? 'proxy' : t[key];
+  }
+});
+console.log(obj.hello());
+
+var r = Proxy.revocable({ a: 1 }, {});
+console.log(r.proxy.a);
+r.revoke();
+try {
+  r.proxy.a;
+} catch (e) {
+  console.log(e.name);
+}
+try {
+  Proxy({}, {});
+} catch (e) {
+  console.log(e.name);
+}
+try {
+  new Proxy(1, {});
+} catch (e) {
+  console.log(e.name);
+}
+
+console.log(Reflect.apply(Math.max, null, [1, 3, 2]), Reflect.construct(Point, [5, 6]).y);
+console.log(Reflect.get({ a: 1 }, 'a'), Reflect.getPrototypeOf([]) === Array.prototype);
+var frozen = Object.freeze({ a: 1 });
+console.log(Reflect.set(frozen, 'a', 2), Reflect.deleteProperty(frozen, 'a'), Reflect.isExtensible(frozen));
+console.log(Reflect.defineProperty(frozen, 'b', { value: 1 }));
+var desc = Reflect.getOwnPropertyDescriptor(frozen, 'a');
+console.log(desc.value, desc.writable, desc.configurable);
+var proto = {};
+console.log(Reflect.setPrototypeOf(proto, Array.prototype), Reflect.setPrototypeOf(frozen, proto));
+var m = new Map();
+m.set(p, 1);
+console.log(m.get(p), p === p, new Proxy({}, {}) === new Proxy({}, {}));
@@ -0,0 +1,11 @@
+var s = 'Hello, World'
+console.log(s.length, s[4], s.charAt(1), s.charAt(100), s.charCodeAt(0), s.codePointAt(1))
+console.log(s.indexOf('o'), s.indexOf('o', 5), s.lastIndexOf('o'), s.indexOf('xyz'))
+console.log(s.slice(7), s.slice(-5, -1), s.substring(5, 0), s.substr(-5, 3))
+console.log(s.split(', '), 'a,b,,c'.split(','), 'abc'.split(''), 'a b c'.split(' ', 2))
+console.log(s.toUpperCase(), s.toLowerCase())
+console.log('[' + '  pad  '.trim() + ']', '[' + '  pad  '.trimStart() + ']', '[' + '  pad  '.trimEnd() + ']')
+console.log('5'.padStart(3, '0'), 'abc'.padEnd(6, '12'), 'ab'.repeat(3))
+console.log(s.startsWith('Hell'), s.endsWith('World'), s.includes('lo, W'), s.startsWith('World', 7))
+console.log('a'.concat('b', 1, true), String.fromCharCode(72, 105), String(123), String(null))
+console.log(String.prototype.toUpperCase.call('call'), 'str'.toString(), 'x'.hasOwnProperty('length'))
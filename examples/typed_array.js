@@ -0,0 +1,68 @@
+var bytes = new Uint8Array([1, 2, 255, 256, -1]);
+console.log(bytes, bytes.length, bytes[2], bytes[5]);
+
+// Integers wrap around while Uint8ClampedArray saturates.
+var i8 = new Int8Array(4);
+i8[0] = 200;
+i8[1] = -129;
+i8[2] = 1.9;
+i8[3] = NaN;
+console.log(i8);
+console.log(new Uint8ClampedArray([300, -5, 2.5, 3.5]));
+console.log(new Uint32Array([-1]), new Int16Array([40000]));
+var f32 = new Float32Array([0.1]);
+var f64 = new Float64Array([0.1]);
+console.log(f32[0], f64[0]);
+
+// Views share the bytes of an ArrayBuffer.
+var buffer = new ArrayBuffer(8);
+var u8 = new Uint8Array(buffer);
+var u32 = new Uint32Array(buffer, 4, 1);
+u32[0] = 0xdeadbeef;
+console.log(u8.join(), buffer.byteLength, u32.byteOffset, u32.buffer === buffer);
+console.log(buffer.slice(4));
+
+var sub = u8.subarray(2, -1);
+sub.fill(7);
+console.log(sub, sub.byteOffset, u8.join());
+var copy = u8.slice(-2);
+copy[0] = 0;
+console.log(copy, u8[6]);
+u8.set([9, 8], 1);
+u8.set(new Int8Array([-1]), 7);
+console.log(u8.join('-'), u8.subarray(0, 3).toString());
+console.log(buffer.slice(1, 3), ArrayBuffer.isView(u8), ArrayBuffer.isView(buffer));
+
+// DataView is big endian by default.
+var packet = new ArrayBuffer(12);
+var view = new DataView(packet);
+view.setUint16(0, 0x1234);
+view.setUint16(2, 0x1234, true);
+view.setInt32(4, -2);
+view.setFloat32(8, 1.5, true);
+var packetBytes = new Uint8Array(packet);
+console.log(packetBytes.join(' '));
+console.log(view.getUint16(0), view.getUint16(0, true), view.getInt32(4), view.getUint32(4));
+console.log(view.getFloat32(8, true), view.getInt8(4), view.byteLength);
+var header = new DataView(packet, 4, 4);
+console.log(header.getUint8(3), header.byteOffset, header.byteLength);
+
+try {
+  view.getUint32(10);
+} catch (e) {
+  console.log(e.name);
+}
+try {
+  new Int32Array(new ArrayBuffer(6), 2);
+} catch (e) {
+  console.log(e.name);
+}
+try {
+  u8.set([1, 2], 7);
+} catch (e) {
+  console.log(e.name);
+}
+
+console.log(Object.keys(new Int16Array(3)), JSON.stringify(new Uint8Array([1, 2])));
+console.log(Array.from(new Float64Array([1.5, 2])), Int32Array.BYTES_PER_ELEMENT);
+console.log(Object.prototype.toString.call(u8), Object.prototype.toString.call(view));
@@ -0,0 +1,9 @@
+var s = "a\u0000" + "b"
+console.log(s.length, s.charCodeAt(1), s.indexOf("b"))
+var e = "x😀y"
+console.log(e.length, e[1].charCodeAt(0), e.charCodeAt(2), e.codePointAt(1), e[9])
+console.log(Array.from(e).length, e.split("").length)
+console.log(String.fromCharCode(55357, 56832) === e.slice(1, 3))
+console.log("ÄbC".toLowerCase(), e.toUpperCase())
+console.log(["x", "y"].join("\u0000").length)
+console.log(e.slice(1, 2) + e.slice(2, 3) === e.slice(1, 3))
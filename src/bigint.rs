@@ -0,0 +1,603 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+// Results longer than this are refused (as a RangeError) rather than eating up the memory.
+pub const MAX_BITS: u64 = 1 << 30;
+
+// An arbitrary-precision integer for BigInt values, in sign-magnitude form. 'digits' is the
+// magnitude in base 2^32, the least significant digit first, without leading zeros. Zero is
+// never negative.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut digits: Vec<u32>) -> BigInt {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigInt {
+            negative: negative && !digits.is_empty(),
+            digits: digits,
+        }
+    }
+
+    pub fn zero() -> BigInt {
+        BigInt::new(false, vec![])
+    }
+
+    pub fn from_i64(n: i64) -> BigInt {
+        let m = if n < 0 {
+            (!(n as u64)).wrapping_add(1)
+        } else {
+            n as u64
+        };
+        BigInt::new(n < 0, vec![m as u32, (m >> 32) as u32])
+    }
+
+    // https://tc39.github.io/proposal-bigint/#sec-numbertobigint
+    // Returns None if 'n' is not an integer.
+    pub fn from_f64(n: f64) -> Option<BigInt> {
+        if !n.is_finite() || n.trunc() != n {
+            return None;
+        }
+        // n = mantissa * 2^(exponent - 1075). Integers other than zero are never subnormal.
+        let bits = n.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        if exponent == 0 {
+            return Some(BigInt::zero());
+        }
+        let mantissa = BigInt::from_i64(((bits & 0xf_ffff_ffff_ffff) | 1 << 52) as i64);
+        let shift = exponent - 1075;
+        let m = if shift >= 0 {
+            BigInt::new(false, shl_mag(&mantissa.digits, shift as u64))
+        } else {
+            BigInt::new(false, shr_mag(&mantissa.digits, (-shift) as u64))
+        };
+        Some(if n < 0.0 { m.neg() } else { m })
+    }
+
+    // Parses digits in 'radix' without a sign or a prefix. Returns None if there are no digits or
+    // there is an invalid one.
+    pub fn parse(s: &str, radix: u32) -> Option<BigInt> {
+        if s.is_empty() {
+            return None;
+        }
+        let mut digits = vec![];
+        for c in s.chars() {
+            let d = c.to_digit(radix)?;
+            let mut carry = d as u64;
+            for digit in &mut digits {
+                let t = *digit as u64 * radix as u64 + carry;
+                *digit = t as u32;
+                carry = t >> 32;
+            }
+            if carry > 0 {
+                digits.push(carry as u32);
+            }
+        }
+        Some(BigInt::new(false, digits))
+    }
+
+    // https://tc39.github.io/proposal-bigint/#sec-stringtobigint
+    // 's' must be trimmed. An empty string is zero. Returns None if it's not an integer literal.
+    pub fn from_string(s: &str) -> Option<BigInt> {
+        if s.is_empty() {
+            return Some(BigInt::zero());
+        }
+        let radix = match &s[..s.len().min(2)] {
+            "0x" | "0X" => 16,
+            "0o" | "0O" => 8,
+            "0b" | "0B" => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            return BigInt::parse(&s[2..], radix);
+        }
+        match s.as_bytes()[0] {
+            b'+' => BigInt::parse(&s[1..], 10),
+            b'-' => BigInt::parse(&s[1..], 10).map(|n| n.neg()),
+            _ => BigInt::parse(s, 10),
+        }
+    }
+
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        let mut digits = self.digits.clone();
+        let mut chars = vec![];
+        while !digits.is_empty() {
+            let mut rem = 0u64;
+            for digit in digits.iter_mut().rev() {
+                let cur = rem << 32 | *digit as u64;
+                *digit = (cur / radix as u64) as u32;
+                rem = cur % radix as u64;
+            }
+            while digits.last() == Some(&0) {
+                digits.pop();
+            }
+            chars.push(::std::char::from_digit(rem as u32, radix).unwrap());
+        }
+        if self.negative {
+            chars.push('-');
+        }
+        chars.iter().rev().collect()
+    }
+
+    // https://tc39.github.io/proposal-bigint/#sec-number-constructor-number-value
+    // Rounds to the nearest Number, ties to even.
+    pub fn to_f64(&self) -> f64 {
+        let bits = self.bit_length();
+        let n = if bits <= 64 {
+            self.low_u64() as f64
+        } else {
+            // The bits below the top 64 bits only matter as a sticky bit for the rounding.
+            let shift = bits - 64;
+            let top = BigInt::new(false, shr_mag(&self.digits, shift)).low_u64();
+            let sticky = low_bits_nonzero(&self.digits, shift) as u64;
+            (top | sticky) as f64 * 2f64.powi(shift as i32)
+        };
+        if self.negative {
+            -n
+        } else {
+            n
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    // The number of bits of the magnitude.
+    pub fn bit_length(&self) -> u64 {
+        match self.digits.last() {
+            Some(top) => (self.digits.len() as u64 - 1) * 32 + 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    // The lowest 64 bits of the magnitude.
+    fn low_u64(&self) -> u64 {
+        self.digits
+            .iter()
+            .take(2)
+            .rev()
+            .fold(0, |n, digit| n << 32 | *digit as u64)
+    }
+
+    // The magnitude, or u64::MAX if it doesn't fit.
+    fn to_u64_saturating(&self) -> u64 {
+        if self.digits.len() > 2 {
+            ::std::u64::MAX
+        } else {
+            self.low_u64()
+        }
+    }
+
+    pub fn neg(&self) -> BigInt {
+        BigInt::new(!self.negative, self.digits.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_mag(&self.digits, &other.digits));
+        }
+        match cmp_mag(&self.digits, &other.digits) {
+            Ordering::Less => BigInt::new(other.negative, sub_mag(&other.digits, &self.digits)),
+            _ => BigInt::new(self.negative, sub_mag(&self.digits, &other.digits)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::new(
+            self.negative != other.negative,
+            mul_mag(&self.digits, &other.digits),
+        )
+    }
+
+    // Division truncating toward zero, and the remainder that has the sign of the dividend.
+    // Returns None if 'other' is zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = div_mag(&self.digits, &other.digits);
+        Some((
+            BigInt::new(self.negative != other.negative, q),
+            BigInt::new(self.negative, r),
+        ))
+    }
+
+    pub fn pow(&self, exponent: &BigInt) -> Result<BigInt, &'static str> {
+        if exponent.negative {
+            return Err("exponent must be non-negative");
+        }
+        if self.is_zero() || exponent.is_zero() || self.digits == [1] {
+            let negative = self.negative && exponent.digits.first().map_or(false, |d| d & 1 == 1);
+            return Ok(match (self.is_zero(), exponent.is_zero()) {
+                (_, true) => BigInt::from_i64(1),
+                (true, _) => BigInt::zero(),
+                _ => BigInt::new(negative, vec![1]),
+            });
+        }
+        let mut e = exponent.to_u64_saturating();
+        if (self.bit_length() - 1).saturating_mul(e) > MAX_BITS {
+            return Err("maximum BigInt size exceeded");
+        }
+        let mut base = self.clone();
+        let mut result = BigInt::from_i64(1);
+        while e > 0 {
+            if e & 1 == 1 {
+                result = result.mul(&base);
+            }
+            e >>= 1;
+            if e > 0 {
+                base = base.mul(&base);
+            }
+        }
+        Ok(result)
+    }
+
+    // Shifts to the left by 'n' bits, or to the right if 'n' is negative.
+    pub fn shl(&self, n: &BigInt) -> Result<BigInt, &'static str> {
+        if n.negative {
+            return Ok(self.shr_bits(n.to_u64_saturating()));
+        }
+        if self.is_zero() {
+            return Ok(BigInt::zero());
+        }
+        let n = n.to_u64_saturating();
+        if self.bit_length().saturating_add(n) > MAX_BITS {
+            return Err("maximum BigInt size exceeded");
+        }
+        Ok(BigInt::new(self.negative, shl_mag(&self.digits, n)))
+    }
+
+    // Arithmetic right shift, i.e. the division by 2^n rounding toward negative infinity.
+    pub fn shr(&self, n: &BigInt) -> Result<BigInt, &'static str> {
+        if n.negative {
+            return self.shl(&n.neg());
+        }
+        Ok(self.shr_bits(n.to_u64_saturating()))
+    }
+
+    fn shr_bits(&self, n: u64) -> BigInt {
+        if self.negative {
+            // -(((|a| - 1) >> n) + 1)
+            let m = sub_mag(&self.digits, &[1]);
+            BigInt::new(true, add_mag(&shr_mag(&trim(m), n), &[1]))
+        } else {
+            BigInt::new(false, shr_mag(&self.digits, n))
+        }
+    }
+
+    // The bitwise operators work as if the values were in infinite-precision two's complement.
+
+    pub fn bitand(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a & b)
+    }
+
+    pub fn bitor(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a | b)
+    }
+
+    pub fn bitxor(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+
+    pub fn bitnot(&self) -> BigInt {
+        self.neg().sub(&BigInt::from_i64(1))
+    }
+
+    fn bitwise<F>(&self, other: &BigInt, f: F) -> BigInt
+    where
+        F: Fn(u32, u32) -> u32,
+    {
+        let len = self.digits.len().max(other.digits.len()) + 1;
+        let a = self.to_twos_complement(len);
+        let b = other.to_twos_complement(len);
+        from_twos_complement(a.iter().zip(&b).map(|(a, b)| f(*a, *b)).collect())
+    }
+
+    // 'len' digits of the two's complement. 'len' must be greater than the length of the
+    // magnitude.
+    fn to_twos_complement(&self, len: usize) -> Vec<u32> {
+        let mut digits = self.digits.clone();
+        digits.resize(len, 0);
+        if self.negative {
+            negate_twos_complement(&mut digits);
+        }
+        digits
+    }
+
+    // https://tc39.github.io/proposal-bigint/#sec-bigint.asuintn
+    pub fn as_uint_n(&self, bits: u64) -> Result<BigInt, &'static str> {
+        if !self.negative && self.bit_length() <= bits {
+            return Ok(self.clone());
+        }
+        if bits > MAX_BITS {
+            return Err("maximum BigInt size exceeded");
+        }
+        let len = ((bits + 31) / 32) as usize;
+        let mut digits = self.to_twos_complement(len.max(self.digits.len() + 1));
+        digits.truncate(len);
+        if bits % 32 != 0 {
+            if let Some(top) = digits.last_mut() {
+                *top &= (1 << (bits % 32)) - 1;
+            }
+        }
+        Ok(BigInt::new(false, digits))
+    }
+
+    // https://tc39.github.io/proposal-bigint/#sec-bigint.asintn
+    pub fn as_int_n(&self, bits: u64) -> BigInt {
+        if bits == 0 {
+            return BigInt::zero();
+        }
+        if self.bit_length() < bits {
+            return self.clone();
+        }
+        // Now 'bits' is small enough.
+        let n = self.as_uint_n(bits).unwrap();
+        if n.bit_length() == bits {
+            n.sub(&BigInt::new(false, shl_mag(&[1], bits)))
+        } else {
+            n
+        }
+    }
+
+    // Compares with a Number. Returns None if 'n' is NaN.
+    pub fn cmp_f64(&self, n: f64) -> Option<Ordering> {
+        if n.is_nan() {
+            return None;
+        }
+        if n.is_infinite() {
+            return Some(if n > 0.0 {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            });
+        }
+        let floor = BigInt::from_f64(n.floor()).unwrap();
+        match self.cmp(&floor) {
+            Ordering::Equal if n.floor() != n => Some(Ordering::Less),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.digits, &other.digits),
+            (true, true) => cmp_mag(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_radix(10))
+    }
+}
+
+// The functions below work on magnitudes. They take normalized ones (without leading zeros) but
+// may return denormalized ones.
+
+fn trim(mut digits: Vec<u32>) -> Vec<u32> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len() {
+        let t = a[i] as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(t as u32);
+        carry = t >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+// 'a' must not be less than 'b'.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut diff = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for i in 0..a.len() {
+        let t = a[i] as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = if t < 0 { 1 } else { 0 };
+        diff.push((t + (borrow << 32)) as u32);
+    }
+    diff
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut product = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let t = *x as u64 * *y as u64 + product[i + j] as u64 + carry;
+            product[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        product[i + b.len()] = carry as u32;
+    }
+    product
+}
+
+// Returns the quotient and the remainder. 'b' must not be zero.
+fn div_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (vec![], a.to_vec());
+    }
+    let mut quotient = vec![0u32; a.len()];
+    if b.len() == 1 {
+        let d = b[0] as u64;
+        let mut rem = 0u64;
+        for i in (0..a.len()).rev() {
+            let cur = rem << 32 | a[i] as u64;
+            quotient[i] = (cur / d) as u32;
+            rem = cur % d;
+        }
+        return (quotient, vec![rem as u32]);
+    }
+    // Long division bit by bit.
+    let mut rem: Vec<u32> = vec![];
+    for i in (0..a.len() * 32).rev() {
+        let mut carry = a[i / 32] >> (i % 32) & 1;
+        for digit in &mut rem {
+            let next = *digit >> 31;
+            *digit = *digit << 1 | carry;
+            carry = next;
+        }
+        if carry > 0 {
+            rem.push(carry);
+        }
+        if cmp_mag(&rem, b) != Ordering::Less {
+            rem = trim(sub_mag(&rem, b));
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+    (quotient, rem)
+}
+
+fn shl_mag(a: &[u32], n: u64) -> Vec<u32> {
+    let bits = (n % 32) as u32;
+    let mut shifted = vec![0; (n / 32) as usize];
+    if bits == 0 {
+        shifted.extend_from_slice(a);
+        return shifted;
+    }
+    let mut carry = 0;
+    for digit in a {
+        shifted.push(digit << bits | carry);
+        carry = digit >> (32 - bits);
+    }
+    shifted.push(carry);
+    shifted
+}
+
+fn shr_mag(a: &[u32], n: u64) -> Vec<u32> {
+    if n / 32 >= a.len() as u64 {
+        return vec![];
+    }
+    let a = &a[(n / 32) as usize..];
+    let bits = (n % 32) as u32;
+    if bits == 0 {
+        return a.to_vec();
+    }
+    (0..a.len())
+        .map(|i| a[i] >> bits | a.get(i + 1).map_or(0, |next| next << (32 - bits)))
+        .collect()
+}
+
+// Returns true if any of the lowest 'n' bits is set.
+fn low_bits_nonzero(a: &[u32], n: u64) -> bool {
+    let words = (n / 32) as usize;
+    a[..words.min(a.len())].iter().any(|digit| *digit != 0)
+        || (n % 32 != 0
+            && a.get(words)
+                .map_or(false, |digit| digit & ((1 << (n % 32)) - 1) != 0))
+}
+
+fn negate_twos_complement(digits: &mut Vec<u32>) {
+    let mut carry = 1u64;
+    for digit in digits {
+        let t = (!*digit) as u64 + carry;
+        *digit = t as u32;
+        carry = t >> 32;
+    }
+}
+
+fn from_twos_complement(mut digits: Vec<u32>) -> BigInt {
+    let negative = digits.last().map_or(false, |top| top >> 31 == 1);
+    if negative {
+        negate_twos_complement(&mut digits);
+    }
+    BigInt::new(negative, digits)
+}
+
+#[test]
+fn arithmetic() {
+    let n = |s: &str| BigInt::from_string(s).unwrap();
+    let big = n("123456789012345678901234567890");
+    assert_eq!(big.to_string(), "123456789012345678901234567890");
+    assert_eq!(n("0x1f").to_string(), "31");
+    assert_eq!(BigInt::from_string("-0x1f"), None);
+    assert_eq!(n("0b101").to_string_radix(2), "101");
+    assert_eq!(BigInt::from_string("12a"), None);
+
+    assert_eq!(big.add(&n("-123456789012345678901234567891")), n("-1"));
+    assert_eq!(big.sub(&big), BigInt::zero());
+    assert_eq!(
+        big.mul(&big).to_string(),
+        "15241578753238836750495351562536198787501905199875019052100"
+    );
+    let (q, r) = big.mul(&big).add(&n("7")).div_rem(&big).unwrap();
+    assert_eq!((q, r), (big.clone(), n("7")));
+    assert_eq!(n("-7").div_rem(&n("2")), Some((n("-3"), n("-1"))));
+    assert_eq!(n("7").div_rem(&BigInt::zero()), None);
+    assert_eq!(
+        n("2").pow(&n("100")).unwrap().to_string(),
+        "1267650600228229401496703205376"
+    );
+    assert_eq!(n("-1").pow(&n("3")).unwrap(), n("-1"));
+    assert!(n("2").pow(&n("-1")).is_err());
+
+    assert_eq!(n("1").shl(&n("64")).unwrap(), n("18446744073709551616"));
+    assert_eq!(n("-5").shr(&n("1")).unwrap(), n("-3"));
+    assert_eq!(n("-5").shl(&n("-1")).unwrap(), n("-3"));
+    assert_eq!(n("-1").shr(&n("1000")).unwrap(), n("-1"));
+    assert_eq!(n("-6").bitand(&n("5")), n("0"));
+    assert_eq!(n("-6").bitor(&n("5")), n("-1"));
+    assert_eq!(n("-6").bitxor(&n("5")), n("-1"));
+    assert_eq!(n("5").bitnot(), n("-6"));
+
+    assert_eq!(n("255").as_int_n(8), n("-1"));
+    assert_eq!(n("-1").as_uint_n(64).unwrap(), n("18446744073709551615"));
+    assert_eq!(n("-129").as_int_n(8), n("127"));
+    assert_eq!(n("128").as_int_n(64), n("128"));
+
+    assert_eq!(
+        BigInt::from_f64(1e21).unwrap().to_string(),
+        "1000000000000000000000"
+    );
+    assert_eq!(BigInt::from_f64(-3.0), Some(n("-3")));
+    assert_eq!(BigInt::from_f64(0.5), None);
+    assert_eq!(n("9007199254740993").to_f64(), 9007199254740992.0);
+    assert_eq!(n("9007199254740995").to_f64(), 9007199254740996.0);
+    assert_eq!(big.to_f64(), 1.2345678901234568e29);
+    assert_eq!(n("1").cmp_f64(1.5), Some(Ordering::Less));
+    assert_eq!(n("-2").cmp_f64(-2.5), Some(Ordering::Greater));
+    assert_eq!(n("2").cmp_f64(2.0), Some(Ordering::Equal));
+}
@@ -1,57 +1,330 @@
+use bigint::BigInt;
+use bytecode_gen::ByteCode;
+use collection::{normalize_key, Collection, CollectionKind};
+use date;
+use extract_anony_func;
 use gc;
-use vm::{call_function, ArrayValue, CallObject, RawStringPtr, Value, ValueBase, VM};
+use id;
+use js_string::JsString;
+use node::{Node, NodeBase};
+use parser;
+use proxy::{self, ProxyValue};
+use typed_array::{ArrayBuffer, TypedArray, TypedArrayKind};
+use vm::{
+    call_function, construct_object, enter_call, is_internal_property, is_js_whitespace, js_pow,
+    number_to_string, parse_decimal_prefix, shortest_digits, ArrayValue, CallObject, CallObjectRef,
+    ConstantTable, DebugInfo, FrameKind, FuncId, PropertyDescriptor, PropertyMap, RawStringPtr,
+    RuntimeError, Value, ValueBase, ARRAY_BUFFER_DATA, ARRAY_BUFFER_OBJ, BIGINT_PROTOTYPE,
+    COLLECTION_DATA, COLLECTION_ITERATOR_PROTOTYPE, DATA_VIEW_DATA, DATE_VALUE, ERROR_NAMES,
+    ERROR_OBJS, FUNCTION_PROTOTYPE, ITERATED_COLLECTION, ITERATOR_KIND, ITERATOR_NEXT_INDEX,
+    TYPED_ARRAY_DATA, TYPED_ARRAY_KINDS, TYPED_ARRAY_OBJS, VM,
+};
+use vm_codegen::{has_use_strict_directive, VMCodeGen};
 
 use libc;
-use rand::random;
-
-use rustc_hash::FxHashMap;
-use std::ffi::CString;
-
-pub const CONSOLE_LOG: usize = 0;
-pub const PROCESS_STDOUT_WRITE: usize = 1;
-pub const ARRAY_NEW: usize = 2;
-pub const ARRAY_PUSH: usize = 3;
-pub const ARRAY_POP: usize = 4;
-pub const ARRAY_MAP: usize = 5;
-pub const MATH_FLOOR: usize = 6;
-pub const MATH_RANDOM: usize = 7;
-pub const MATH_POW: usize = 8;
-pub const MATH_ABS: usize = 9;
-pub const MATH_ACOS: usize = 10;
-pub const MATH_ACOSH: usize = 11;
-pub const MATH_ASIN: usize = 12;
-pub const MATH_ASINH: usize = 13;
-pub const MATH_ATAN: usize = 14;
-pub const MATH_ATANH: usize = 15;
-pub const MATH_ATAN2: usize = 15;
-pub const MATH_CBRT: usize = 17;
-pub const MATH_CEIL: usize = 18;
-pub const MATH_CLZ32: usize = 18;
-pub const MATH_COS: usize = 20;
-pub const MATH_COSH: usize = 21;
-pub const MATH_EXP: usize = 22;
-pub const MATH_EXPM1: usize = 22;
-pub const MATH_FROUND: usize = 24;
-pub const MATH_HYPOT: usize = 25;
-pub const MATH_LOG: usize = 26;
-pub const MATH_LOG1P: usize = 26;
-pub const MATH_LOG10: usize = 27;
-pub const MATH_LOG2: usize = 28;
-pub const MATH_MAX: usize = 30;
-pub const MATH_MIN: usize = 31;
-pub const MATH_ROUND: usize = 32;
-pub const MATH_SIGN: usize = 33;
-pub const MATH_SIN: usize = 34;
-pub const MATH_SINH: usize = 35;
-pub const MATH_SQRT: usize = 36;
-pub const MATH_TAN: usize = 37;
-pub const MATH_TANH: usize = 38;
-pub const MATH_TRUNC: usize = 39;
-pub const FUNCTION_PROTOTYPE_APPLY: usize = 40;
-pub const FUNCTION_PROTOTYPE_CALL: usize = 41;
-pub const REQUIRE: usize = 42;
-
-pub unsafe fn console_log(_: CallObject, args: Vec<Value>, self_: &mut VM) {
+use std::mem;
+use std::rc::Rc;
+
+pub type BuiltinFunction = unsafe fn(CallObject, Vec<Value>, &mut VM) -> Result<(), RuntimeError>;
+
+// Declares the id constant of each builtin function and 'builtin_functions()', the table that
+// 'VM::builtin_functions' dispatches with. Ids are the positions in the list, so they never
+// collide and always point at the function they are named after. Each entry also gives the
+// 'name' and 'length' properties of the function object, returned by 'builtin_name_and_length()'.
+macro_rules! builtin_registry {
+    ($($id:ident => $func:ident($name:expr, $length:expr),)*) => {
+        #[allow(non_camel_case_types)]
+        enum BuiltinId {
+            $($id,)*
+        }
+
+        $(pub const $id: usize = BuiltinId::$id as usize;)*
+
+        pub fn builtin_functions() -> Vec<BuiltinFunction> {
+            vec![$($func,)*]
+        }
+
+        pub fn builtin_name_and_length(id: usize) -> Option<(&'static str, usize)> {
+            const TABLE: &[(&str, usize)] = &[$(($name, $length),)*];
+            TABLE.get(id).cloned()
+        }
+    };
+}
+
+builtin_registry! {
+    CONSOLE_LOG => console_log("log", 0),
+    PROCESS_STDOUT_WRITE => process_stdout_write("write", 1),
+    ARRAY_NEW => array_new("Array", 1),
+    ARRAY_PUSH => array_push("push", 1),
+    ARRAY_POP => array_pop("pop", 0),
+    ARRAY_MAP => array_map("map", 1),
+    MATH_FLOOR => math_floor("floor", 1),
+    MATH_RANDOM => math_random("random", 0),
+    MATH_POW => math_pow("pow", 2),
+    MATH_ABS => math_abs("abs", 1),
+    MATH_ACOS => math_acos("acos", 1),
+    MATH_ACOSH => math_acosh("acosh", 1),
+    MATH_ASIN => math_asin("asin", 1),
+    MATH_ASINH => math_asinh("asinh", 1),
+    MATH_ATAN => math_atan("atan", 1),
+    MATH_ATANH => math_atanh("atanh", 1),
+    MATH_ATAN2 => math_atan2("atan2", 2),
+    MATH_CBRT => math_cbrt("cbrt", 1),
+    MATH_CEIL => math_ceil("ceil", 1),
+    MATH_CLZ32 => math_clz32("clz32", 1),
+    MATH_COS => math_cos("cos", 1),
+    MATH_COSH => math_cosh("cosh", 1),
+    MATH_EXP => math_exp("exp", 1),
+    MATH_EXPM1 => math_expm1("expm1", 1),
+    MATH_FROUND => math_fround("fround", 1),
+    MATH_HYPOT => math_hypot("hypot", 2),
+    MATH_IMUL => math_imul("imul", 2),
+    MATH_LOG => math_log("log", 1),
+    MATH_LOG1P => math_log1p("log1p", 1),
+    MATH_LOG10 => math_log10("log10", 1),
+    MATH_LOG2 => math_log2("log2", 1),
+    MATH_MAX => math_max("max", 2),
+    MATH_MIN => math_min("min", 2),
+    MATH_ROUND => math_round("round", 1),
+    MATH_SIGN => math_sign("sign", 1),
+    MATH_SIN => math_sin("sin", 1),
+    MATH_SINH => math_sinh("sinh", 1),
+    MATH_SQRT => math_sqrt("sqrt", 1),
+    MATH_TAN => math_tan("tan", 1),
+    MATH_TANH => math_tanh("tanh", 1),
+    MATH_TRUNC => math_trunc("trunc", 1),
+    FUNCTION_PROTOTYPE_APPLY => function_prototype_apply("apply", 2),
+    FUNCTION_PROTOTYPE_CALL => function_prototype_call("call", 1),
+    REQUIRE => require("require", 1),
+    OBJECT_DEFINE_PROPERTY => object_define_property("defineProperty", 3),
+    OBJECT_DEFINE_PROPERTIES => object_define_properties("defineProperties", 2),
+    OBJECT_GET_OWN_PROPERTY_DESCRIPTOR =>
+        object_get_own_property_descriptor("getOwnPropertyDescriptor", 2),
+    OBJECT_FREEZE => object_freeze("freeze", 1),
+    OBJECT_SEAL => object_seal("seal", 1),
+    OBJECT_PREVENT_EXTENSIONS => object_prevent_extensions("preventExtensions", 1),
+    OBJECT_IS_FROZEN => object_is_frozen("isFrozen", 1),
+    OBJECT_IS_SEALED => object_is_sealed("isSealed", 1),
+    OBJECT_IS_EXTENSIBLE => object_is_extensible("isExtensible", 1),
+    OBJECT_NEW => object_new("Object", 1),
+    OBJECT_KEYS => object_keys("keys", 1),
+    OBJECT_VALUES => object_values("values", 1),
+    OBJECT_ENTRIES => object_entries("entries", 1),
+    OBJECT_ASSIGN => object_assign("assign", 2),
+    OBJECT_CREATE => object_create("create", 2),
+    OBJECT_GET_PROTOTYPE_OF => object_get_prototype_of("getPrototypeOf", 1),
+    OBJECT_SET_PROTOTYPE_OF => object_set_prototype_of("setPrototypeOf", 2),
+    OBJECT_GET_OWN_PROPERTY_NAMES => object_get_own_property_names("getOwnPropertyNames", 1),
+    OBJECT_IS => object_is("is", 2),
+    OBJECT_PROTOTYPE_HAS_OWN_PROPERTY => object_prototype_has_own_property("hasOwnProperty", 1),
+    OBJECT_PROTOTYPE_IS_PROTOTYPE_OF => object_prototype_is_prototype_of("isPrototypeOf", 1),
+    OBJECT_PROTOTYPE_PROPERTY_IS_ENUMERABLE =>
+        object_prototype_property_is_enumerable("propertyIsEnumerable", 1),
+    OBJECT_PROTOTYPE_TO_STRING => object_prototype_to_string("toString", 0),
+    OBJECT_PROTOTYPE_VALUE_OF => object_prototype_value_of("valueOf", 0),
+    ARRAY_FOR_EACH => array_for_each("forEach", 1),
+    ARRAY_FILTER => array_filter("filter", 1),
+    ARRAY_REDUCE => array_reduce("reduce", 1),
+    ARRAY_REDUCE_RIGHT => array_reduce_right("reduceRight", 1),
+    ARRAY_SOME => array_some("some", 1),
+    ARRAY_EVERY => array_every("every", 1),
+    ARRAY_FIND => array_find("find", 1),
+    ARRAY_FIND_INDEX => array_find_index("findIndex", 1),
+    ARRAY_INDEX_OF => array_index_of("indexOf", 1),
+    ARRAY_LAST_INDEX_OF => array_last_index_of("lastIndexOf", 1),
+    ARRAY_INCLUDES => array_includes("includes", 1),
+    ARRAY_SLICE => array_slice("slice", 2),
+    ARRAY_SPLICE => array_splice("splice", 2),
+    ARRAY_CONCAT => array_concat("concat", 1),
+    ARRAY_JOIN => array_join("join", 1),
+    ARRAY_REVERSE => array_reverse("reverse", 0),
+    ARRAY_SHIFT => array_shift("shift", 0),
+    ARRAY_UNSHIFT => array_unshift("unshift", 1),
+    ARRAY_SORT => array_sort("sort", 1),
+    ARRAY_FILL => array_fill("fill", 1),
+    ARRAY_TO_STRING => array_to_string("toString", 0),
+    ARRAY_FROM => array_from("from", 1),
+    ARRAY_IS_ARRAY => array_is_array("isArray", 1),
+    ARRAY_OF => array_of("of", 0),
+    STRING_NEW => string_new("String", 1),
+    STRING_FROM_CHAR_CODE => string_from_char_code("fromCharCode", 1),
+    STRING_PROTOTYPE_CHAR_AT => string_prototype_char_at("charAt", 1),
+    STRING_PROTOTYPE_CHAR_CODE_AT => string_prototype_char_code_at("charCodeAt", 1),
+    STRING_PROTOTYPE_CODE_POINT_AT => string_prototype_code_point_at("codePointAt", 1),
+    STRING_PROTOTYPE_INDEX_OF => string_prototype_index_of("indexOf", 1),
+    STRING_PROTOTYPE_LAST_INDEX_OF => string_prototype_last_index_of("lastIndexOf", 1),
+    STRING_PROTOTYPE_SLICE => string_prototype_slice("slice", 2),
+    STRING_PROTOTYPE_SUBSTRING => string_prototype_substring("substring", 2),
+    STRING_PROTOTYPE_SUBSTR => string_prototype_substr("substr", 2),
+    STRING_PROTOTYPE_SPLIT => string_prototype_split("split", 2),
+    STRING_PROTOTYPE_TO_UPPER_CASE => string_prototype_to_upper_case("toUpperCase", 0),
+    STRING_PROTOTYPE_TO_LOWER_CASE => string_prototype_to_lower_case("toLowerCase", 0),
+    STRING_PROTOTYPE_TRIM => string_prototype_trim("trim", 0),
+    STRING_PROTOTYPE_TRIM_START => string_prototype_trim_start("trimStart", 0),
+    STRING_PROTOTYPE_TRIM_END => string_prototype_trim_end("trimEnd", 0),
+    STRING_PROTOTYPE_PAD_START => string_prototype_pad_start("padStart", 1),
+    STRING_PROTOTYPE_PAD_END => string_prototype_pad_end("padEnd", 1),
+    STRING_PROTOTYPE_REPEAT => string_prototype_repeat("repeat", 1),
+    STRING_PROTOTYPE_STARTS_WITH => string_prototype_starts_with("startsWith", 1),
+    STRING_PROTOTYPE_ENDS_WITH => string_prototype_ends_with("endsWith", 1),
+    STRING_PROTOTYPE_INCLUDES => string_prototype_includes("includes", 1),
+    STRING_PROTOTYPE_CONCAT => string_prototype_concat("concat", 1),
+    STRING_PROTOTYPE_TO_STRING => string_prototype_to_string("toString", 0),
+    NUMBER_NEW => number_new("Number", 1),
+    NUMBER_IS_INTEGER => number_is_integer("isInteger", 1),
+    NUMBER_IS_SAFE_INTEGER => number_is_safe_integer("isSafeInteger", 1),
+    NUMBER_IS_FINITE => number_is_finite("isFinite", 1),
+    NUMBER_IS_NAN => number_is_nan("isNaN", 1),
+    NUMBER_PROTOTYPE_TO_STRING => number_prototype_to_string("toString", 1),
+    NUMBER_PROTOTYPE_TO_FIXED => number_prototype_to_fixed("toFixed", 1),
+    NUMBER_PROTOTYPE_TO_PRECISION => number_prototype_to_precision("toPrecision", 1),
+    NUMBER_PROTOTYPE_TO_EXPONENTIAL => number_prototype_to_exponential("toExponential", 1),
+    NUMBER_PROTOTYPE_VALUE_OF => number_prototype_value_of("valueOf", 0),
+    PARSE_INT => parse_int("parseInt", 2),
+    PARSE_FLOAT => parse_float("parseFloat", 1),
+    IS_NAN => is_nan("isNaN", 1),
+    IS_FINITE => is_finite("isFinite", 1),
+    JSON_PARSE => json_parse("parse", 2),
+    JSON_STRINGIFY => json_stringify("stringify", 3),
+    // The constructors from ERROR_NEW to URI_ERROR_NEW must be in the order of vm::ERROR_NAMES.
+    ERROR_NEW => error_new("Error", 1),
+    TYPE_ERROR_NEW => type_error_new("TypeError", 1),
+    REFERENCE_ERROR_NEW => reference_error_new("ReferenceError", 1),
+    RANGE_ERROR_NEW => range_error_new("RangeError", 1),
+    SYNTAX_ERROR_NEW => syntax_error_new("SyntaxError", 1),
+    EVAL_ERROR_NEW => eval_error_new("EvalError", 1),
+    URI_ERROR_NEW => uri_error_new("URIError", 1),
+    ERROR_PROTOTYPE_TO_STRING => error_prototype_to_string("toString", 0),
+    // Map, Set, WeakMap and WeakSet constructors, in the order of vm::COLLECTION_KINDS
+    MAP_NEW => map_new("Map", 0),
+    SET_NEW => set_new("Set", 0),
+    WEAK_MAP_NEW => weak_map_new("WeakMap", 0),
+    WEAK_SET_NEW => weak_set_new("WeakSet", 0),
+    COLLECTION_PROTOTYPE_GET => collection_prototype_get("get", 1),
+    COLLECTION_PROTOTYPE_SET => collection_prototype_set("set", 2),
+    COLLECTION_PROTOTYPE_ADD => collection_prototype_add("add", 1),
+    COLLECTION_PROTOTYPE_HAS => collection_prototype_has("has", 1),
+    COLLECTION_PROTOTYPE_DELETE => collection_prototype_delete("delete", 1),
+    COLLECTION_PROTOTYPE_CLEAR => collection_prototype_clear("clear", 0),
+    COLLECTION_PROTOTYPE_FOR_EACH => collection_prototype_for_each("forEach", 1),
+    COLLECTION_PROTOTYPE_KEYS => collection_prototype_keys("keys", 0),
+    COLLECTION_PROTOTYPE_VALUES => collection_prototype_values("values", 0),
+    COLLECTION_PROTOTYPE_ENTRIES => collection_prototype_entries("entries", 0),
+    COLLECTION_ITERATOR_NEXT => collection_iterator_next("next", 0),
+    DATE_NEW => date_new("Date", 7),
+    DATE_NOW => date_now("now", 0),
+    DATE_PARSE => date_parse("parse", 1),
+    DATE_UTC => date_utc("UTC", 7),
+    DATE_PROTOTYPE_GET_TIME => date_prototype_get_time("getTime", 0),
+    DATE_PROTOTYPE_SET_TIME => date_prototype_set_time("setTime", 1),
+    DATE_PROTOTYPE_GET_TIMEZONE_OFFSET =>
+        date_prototype_get_timezone_offset("getTimezoneOffset", 0),
+    DATE_PROTOTYPE_TO_ISO_STRING => date_prototype_to_iso_string("toISOString", 0),
+    DATE_PROTOTYPE_TO_STRING => date_prototype_to_string("toString", 0),
+    DATE_PROTOTYPE_TO_UTC_STRING => date_prototype_to_utc_string("toUTCString", 0),
+    DATE_PROTOTYPE_TO_JSON => date_prototype_to_json("toJSON", 1),
+    DATE_PROTOTYPE_GET_FULL_YEAR => date_prototype_get_full_year("getFullYear", 0),
+    DATE_PROTOTYPE_GET_MONTH => date_prototype_get_month("getMonth", 0),
+    DATE_PROTOTYPE_GET_DATE => date_prototype_get_date("getDate", 0),
+    DATE_PROTOTYPE_GET_DAY => date_prototype_get_day("getDay", 0),
+    DATE_PROTOTYPE_GET_HOURS => date_prototype_get_hours("getHours", 0),
+    DATE_PROTOTYPE_GET_MINUTES => date_prototype_get_minutes("getMinutes", 0),
+    DATE_PROTOTYPE_GET_SECONDS => date_prototype_get_seconds("getSeconds", 0),
+    DATE_PROTOTYPE_GET_MILLISECONDS => date_prototype_get_milliseconds("getMilliseconds", 0),
+    DATE_PROTOTYPE_GET_UTC_FULL_YEAR => date_prototype_get_utc_full_year("getUTCFullYear", 0),
+    DATE_PROTOTYPE_GET_UTC_MONTH => date_prototype_get_utc_month("getUTCMonth", 0),
+    DATE_PROTOTYPE_GET_UTC_DATE => date_prototype_get_utc_date("getUTCDate", 0),
+    DATE_PROTOTYPE_GET_UTC_DAY => date_prototype_get_utc_day("getUTCDay", 0),
+    DATE_PROTOTYPE_GET_UTC_HOURS => date_prototype_get_utc_hours("getUTCHours", 0),
+    DATE_PROTOTYPE_GET_UTC_MINUTES => date_prototype_get_utc_minutes("getUTCMinutes", 0),
+    DATE_PROTOTYPE_GET_UTC_SECONDS => date_prototype_get_utc_seconds("getUTCSeconds", 0),
+    DATE_PROTOTYPE_GET_UTC_MILLISECONDS =>
+        date_prototype_get_utc_milliseconds("getUTCMilliseconds", 0),
+    DATE_PROTOTYPE_SET_FULL_YEAR => date_prototype_set_full_year("setFullYear", 3),
+    DATE_PROTOTYPE_SET_MONTH => date_prototype_set_month("setMonth", 2),
+    DATE_PROTOTYPE_SET_DATE => date_prototype_set_date("setDate", 1),
+    DATE_PROTOTYPE_SET_HOURS => date_prototype_set_hours("setHours", 4),
+    DATE_PROTOTYPE_SET_MINUTES => date_prototype_set_minutes("setMinutes", 3),
+    DATE_PROTOTYPE_SET_SECONDS => date_prototype_set_seconds("setSeconds", 2),
+    DATE_PROTOTYPE_SET_MILLISECONDS => date_prototype_set_milliseconds("setMilliseconds", 1),
+    DATE_PROTOTYPE_SET_UTC_FULL_YEAR => date_prototype_set_utc_full_year("setUTCFullYear", 3),
+    DATE_PROTOTYPE_SET_UTC_MONTH => date_prototype_set_utc_month("setUTCMonth", 2),
+    DATE_PROTOTYPE_SET_UTC_DATE => date_prototype_set_utc_date("setUTCDate", 1),
+    DATE_PROTOTYPE_SET_UTC_HOURS => date_prototype_set_utc_hours("setUTCHours", 4),
+    DATE_PROTOTYPE_SET_UTC_MINUTES => date_prototype_set_utc_minutes("setUTCMinutes", 3),
+    DATE_PROTOTYPE_SET_UTC_SECONDS => date_prototype_set_utc_seconds("setUTCSeconds", 2),
+    DATE_PROTOTYPE_SET_UTC_MILLISECONDS =>
+        date_prototype_set_utc_milliseconds("setUTCMilliseconds", 1),
+    FUNCTION_NEW => function_new("Function", 1),
+    FUNCTION_PROTOTYPE_BIND => function_prototype_bind("bind", 1),
+    FUNCTION_PROTOTYPE_TO_STRING => function_prototype_to_string("toString", 0),
+    BOUND_FUNCTION_CALL => bound_function_call("", 0),
+    EVAL => eval("eval", 1),
+    PROXY_NEW => proxy_new("Proxy", 2),
+    PROXY_REVOCABLE => proxy_revocable("revocable", 2),
+    PROXY_REVOKE => proxy_revoke("", 0),
+    REFLECT_APPLY => reflect_apply("apply", 3),
+    REFLECT_CONSTRUCT => reflect_construct("construct", 2),
+    REFLECT_DEFINE_PROPERTY => reflect_define_property("defineProperty", 3),
+    REFLECT_DELETE_PROPERTY => reflect_delete_property("deleteProperty", 2),
+    REFLECT_GET => reflect_get("get", 2),
+    REFLECT_GET_OWN_PROPERTY_DESCRIPTOR =>
+        reflect_get_own_property_descriptor("getOwnPropertyDescriptor", 2),
+    REFLECT_GET_PROTOTYPE_OF => reflect_get_prototype_of("getPrototypeOf", 1),
+    REFLECT_HAS => reflect_has("has", 2),
+    REFLECT_IS_EXTENSIBLE => reflect_is_extensible("isExtensible", 1),
+    REFLECT_OWN_KEYS => reflect_own_keys("ownKeys", 1),
+    REFLECT_PREVENT_EXTENSIONS => reflect_prevent_extensions("preventExtensions", 1),
+    REFLECT_SET => reflect_set("set", 3),
+    REFLECT_SET_PROTOTYPE_OF => reflect_set_prototype_of("setPrototypeOf", 2),
+    ARRAY_BUFFER_NEW => array_buffer_new("ArrayBuffer", 1),
+    ARRAY_BUFFER_IS_VIEW => array_buffer_is_view("isView", 1),
+    ARRAY_BUFFER_PROTOTYPE_SLICE => array_buffer_prototype_slice("slice", 2),
+    // Int8Array, Uint8Array and the other typed array constructors, in the order of
+    // vm::TYPED_ARRAY_KINDS
+    INT8_ARRAY_NEW => int8_array_new("Int8Array", 3),
+    UINT8_ARRAY_NEW => uint8_array_new("Uint8Array", 3),
+    UINT8_CLAMPED_ARRAY_NEW => uint8_clamped_array_new("Uint8ClampedArray", 3),
+    INT16_ARRAY_NEW => int16_array_new("Int16Array", 3),
+    UINT16_ARRAY_NEW => uint16_array_new("Uint16Array", 3),
+    INT32_ARRAY_NEW => int32_array_new("Int32Array", 3),
+    UINT32_ARRAY_NEW => uint32_array_new("Uint32Array", 3),
+    FLOAT32_ARRAY_NEW => float32_array_new("Float32Array", 3),
+    FLOAT64_ARRAY_NEW => float64_array_new("Float64Array", 3),
+    TYPED_ARRAY_PROTOTYPE_SUBARRAY => typed_array_prototype_subarray("subarray", 2),
+    TYPED_ARRAY_PROTOTYPE_SET => typed_array_prototype_set("set", 1),
+    TYPED_ARRAY_PROTOTYPE_SLICE => typed_array_prototype_slice("slice", 2),
+    TYPED_ARRAY_PROTOTYPE_FILL => typed_array_prototype_fill("fill", 1),
+    TYPED_ARRAY_PROTOTYPE_JOIN => typed_array_prototype_join("join", 1),
+    DATA_VIEW_NEW => data_view_new("DataView", 1),
+    DATA_VIEW_PROTOTYPE_GET_INT8 => data_view_prototype_get_int8("getInt8", 1),
+    DATA_VIEW_PROTOTYPE_GET_UINT8 => data_view_prototype_get_uint8("getUint8", 1),
+    DATA_VIEW_PROTOTYPE_GET_INT16 => data_view_prototype_get_int16("getInt16", 1),
+    DATA_VIEW_PROTOTYPE_GET_UINT16 => data_view_prototype_get_uint16("getUint16", 1),
+    DATA_VIEW_PROTOTYPE_GET_INT32 => data_view_prototype_get_int32("getInt32", 1),
+    DATA_VIEW_PROTOTYPE_GET_UINT32 => data_view_prototype_get_uint32("getUint32", 1),
+    DATA_VIEW_PROTOTYPE_GET_FLOAT32 => data_view_prototype_get_float32("getFloat32", 1),
+    DATA_VIEW_PROTOTYPE_GET_FLOAT64 => data_view_prototype_get_float64("getFloat64", 1),
+    DATA_VIEW_PROTOTYPE_SET_INT8 => data_view_prototype_set_int8("setInt8", 2),
+    DATA_VIEW_PROTOTYPE_SET_UINT8 => data_view_prototype_set_uint8("setUint8", 2),
+    DATA_VIEW_PROTOTYPE_SET_INT16 => data_view_prototype_set_int16("setInt16", 2),
+    DATA_VIEW_PROTOTYPE_SET_UINT16 => data_view_prototype_set_uint16("setUint16", 2),
+    DATA_VIEW_PROTOTYPE_SET_INT32 => data_view_prototype_set_int32("setInt32", 2),
+    DATA_VIEW_PROTOTYPE_SET_UINT32 => data_view_prototype_set_uint32("setUint32", 2),
+    DATA_VIEW_PROTOTYPE_SET_FLOAT32 => data_view_prototype_set_float32("setFloat32", 2),
+    DATA_VIEW_PROTOTYPE_SET_FLOAT64 => data_view_prototype_set_float64("setFloat64", 2),
+    BIGINT => bigint("BigInt", 1),
+    BIGINT_AS_INT_N => bigint_as_int_n("asIntN", 2),
+    BIGINT_AS_UINT_N => bigint_as_uint_n("asUintN", 2),
+    BIGINT_PROTOTYPE_TO_STRING => bigint_prototype_to_string("toString", 0),
+    BIGINT_PROTOTYPE_VALUE_OF => bigint_prototype_value_of("valueOf", 0),
+}
+
+pub unsafe fn console_log(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
     let args_len = args.len();
     for i in 0..args_len {
         debug_print(&args[i], false);
@@ -60,10 +333,15 @@ pub unsafe fn console_log(_: CallObject, args: Vec<Value>, self_: &mut VM) {
         }
     }
     libc::puts(b"\0".as_ptr() as RawStringPtr);
-    self_.state.stack.push(Value::undefined())
+    self_.state.stack.push(Value::undefined());
+    Ok(())
 }
 
-pub unsafe fn process_stdout_write(_: CallObject, args: Vec<Value>, self_: &mut VM) {
+pub unsafe fn process_stdout_write(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
     let args_len = args.len();
     for i in 0..args_len {
         debug_print(&args[i], false);
@@ -71,16 +349,16 @@ pub unsafe fn process_stdout_write(_: CallObject, args: Vec<Value>, self_: &mut
             libc::printf(b" \0".as_ptr() as RawStringPtr);
         }
     }
-    self_.state.stack.push(Value::undefined())
+    self_.state.stack.push(Value::undefined());
+    Ok(())
 }
 
 pub unsafe fn debug_print(val: &Value, nest: bool) {
     unsafe fn show_obj(sorted_key_val: Vec<(&String, &Value)>) {
         for (i, (key, val)) in sorted_key_val.iter().enumerate() {
-            libc::printf(
-                "'%s'\0".as_ptr() as RawStringPtr,
-                CString::new(key.as_str()).unwrap().into_raw(),
-            );
+            libc::printf("'\0".as_ptr() as RawStringPtr);
+            JsString::from_key(key).print();
+            libc::printf("'\0".as_ptr() as RawStringPtr);
             libc::printf(": \0".as_ptr() as RawStringPtr);
             debug_print(&val, true);
             libc::printf(if i != sorted_key_val.len() - 1 {
@@ -105,19 +383,50 @@ pub unsafe fn debug_print(val: &Value, nest: bool) {
             libc::printf(b"false\0".as_ptr() as RawStringPtr);
         }
         ValueBase::Number(n) => {
-            if n.is_nan() {
-                libc::printf("NaN\0".as_ptr() as RawStringPtr);
-            } else if n.is_infinite() {
-                libc::printf("Infinity\0".as_ptr() as RawStringPtr);
+            // Unlike ToString, negative zero is shown as it is.
+            if n == 0.0 && n.is_sign_negative() {
+                libc::printf("-0\0".as_ptr() as RawStringPtr);
             } else {
-                libc::printf("%.15g\0".as_ptr() as RawStringPtr, n);
+                JsString::from(number_to_string(n)).print();
             }
         }
+        ValueBase::BigInt(ref n) => {
+            JsString::from(format!("{}n", n)).print();
+        }
         ValueBase::String(ref s) => {
-            libc::printf(
-                if nest { "'%s'\0" } else { "%s\0" }.as_ptr() as RawStringPtr,
-                s.as_ptr(),
-            );
+            if nest {
+                libc::printf("'\0".as_ptr() as RawStringPtr);
+            }
+            s.print();
+            if nest {
+                libc::printf("'\0".as_ptr() as RawStringPtr);
+            }
+        }
+        ValueBase::Object(ref values) if (**values).contains_key(COLLECTION_DATA) => {
+            if let Some(Value {
+                val: ValueBase::Collection(collection),
+                ..
+            }) = (**values).get(COLLECTION_DATA)
+            {
+                debug_print_collection(&**collection);
+            }
+        }
+        ValueBase::Object(ref values)
+            if (**values).contains_key(TYPED_ARRAY_DATA)
+                || (**values).contains_key(DATA_VIEW_DATA)
+                || (**values).contains_key(ARRAY_BUFFER_DATA) =>
+        {
+            debug_print_buffer(&**values);
+        }
+        ValueBase::Object(ref values) if (**values).contains_key(DATE_VALUE) => {
+            if let ValueBase::Number(t) = (**values).get(DATE_VALUE).unwrap().val {
+                JsString::from(if t.is_finite() {
+                    date::to_iso_string(t)
+                } else {
+                    "Invalid Date".to_string()
+                })
+                .print();
+            }
         }
         ValueBase::Object(ref values) => {
             libc::printf("{ \0".as_ptr() as RawStringPtr);
@@ -125,7 +434,8 @@ pub unsafe fn debug_print(val: &Value, nest: bool) {
             let key_val = &**values;
             let mut sorted_key_val = key_val.iter().collect::<Vec<(&String, &Value)>>();
             sorted_key_val.sort_by(|(key1, _), (key2, _)| key1.as_str().cmp(key2.as_str()));
-            sorted_key_val.retain(|(ref key, _)| key != &"__proto__");
+            sorted_key_val
+                .retain(|(ref key, ref val)| !is_internal_property(key) && val.enumerable);
 
             show_obj(sorted_key_val);
 
@@ -141,7 +451,8 @@ pub unsafe fn debug_print(val: &Value, nest: bool) {
             let key_val = &arr.obj;
             let mut sorted_key_val = key_val.iter().collect::<Vec<(&String, &Value)>>();
             sorted_key_val.sort_by(|(key1, _), (key2, _)| key1.as_str().cmp(key2.as_str()));
-            sorted_key_val.retain(|(ref key, _)| key != &"__proto__");
+            sorted_key_val
+                .retain(|(ref key, ref val)| !is_internal_property(key) && val.enumerable);
 
             while i < arr.length {
                 let mut empty_elems = 0;
@@ -159,7 +470,8 @@ pub unsafe fn debug_print(val: &Value, nest: bool) {
                             " \0"
                         } else {
                             ", \0"
-                        }.as_ptr() as RawStringPtr,
+                        }
+                        .as_ptr() as RawStringPtr,
                     );
 
                     if is_last_idx(i - 1) {
@@ -173,7 +485,8 @@ pub unsafe fn debug_print(val: &Value, nest: bool) {
                         " \0"
                     } else {
                         ", \0"
-                    }.as_ptr() as RawStringPtr,
+                    }
+                    .as_ptr() as RawStringPtr,
                 );
 
                 i += 1;
@@ -186,11 +499,116 @@ pub unsafe fn debug_print(val: &Value, nest: bool) {
         ValueBase::Function(_) | ValueBase::BuiltinFunction(_) => {
             libc::printf("[Function]\0".as_ptr() as RawStringPtr);
         }
+        // Shows the target like Node.js does.
+        ValueBase::Proxy(p) => debug_print(&(*p).target, nest),
+        _ => {}
+    }
+}
+
+// Shows a collection like Node.js does, e.g. "Map(1) { 'a' => 1 }".
+unsafe fn debug_print_collection(collection: &Collection) {
+    JsString::from(collection.kind.name()).print();
+    if collection.kind.is_weak() {
+        libc::printf(" { <items unknown> }\0".as_ptr() as RawStringPtr);
+        return;
+    }
+    libc::printf("(%u) {\0".as_ptr() as RawStringPtr, collection.size());
+    for (i, (key, val)) in collection.iter().enumerate() {
+        libc::printf(if i == 0 { " \0" } else { ", \0" }.as_ptr() as RawStringPtr);
+        debug_print(key, true);
+        if collection.kind == CollectionKind::Map {
+            libc::printf(" => \0".as_ptr() as RawStringPtr);
+            debug_print(val, true);
+        }
+    }
+    libc::printf(
+        if collection.size() == 0 {
+            "}\0"
+        } else {
+            " }\0"
+        }
+        .as_ptr() as RawStringPtr,
+    );
+}
+
+// Shows an ArrayBuffer, a typed array or a DataView like Node.js does, e.g.
+// "Uint8Array(2) [ 1, 2 ]" and "ArrayBuffer { [Uint8Contents]: <01 02>, byteLength: 2 }".
+unsafe fn debug_print_buffer(obj: &PropertyMap) {
+    match (
+        obj.get(TYPED_ARRAY_DATA),
+        obj.get(DATA_VIEW_DATA),
+        obj.get(ARRAY_BUFFER_DATA),
+    ) {
+        (
+            Some(Value {
+                val: ValueBase::TypedArray(typed_array),
+                ..
+            }),
+            _,
+            _,
+        ) => {
+            let typed_array = &**typed_array;
+            JsString::from(typed_array.kind.name()).print();
+            libc::printf("(%u) [\0".as_ptr() as RawStringPtr, typed_array.length);
+            for (i, n) in typed_array.to_vec().into_iter().enumerate() {
+                libc::printf(if i == 0 { " \0" } else { ", \0" }.as_ptr() as RawStringPtr);
+                debug_print(&Value::number(n), true);
+            }
+            libc::printf(
+                if typed_array.length == 0 {
+                    "]\0"
+                } else {
+                    " ]\0"
+                }
+                .as_ptr() as RawStringPtr,
+            );
+        }
+        (
+            _,
+            Some(Value {
+                val: ValueBase::TypedArray(view),
+                ..
+            }),
+            _,
+        ) => {
+            let view = &**view;
+            libc::printf(
+                "DataView { byteLength: %u, byteOffset: %u, buffer: \0".as_ptr() as RawStringPtr,
+                view.byte_length(),
+                view.byte_offset,
+            );
+            debug_print(&view.buffer, true);
+            libc::printf(" }\0".as_ptr() as RawStringPtr);
+        }
+        (
+            _,
+            _,
+            Some(Value {
+                val: ValueBase::ArrayBuffer(buffer),
+                ..
+            }),
+        ) => {
+            let bytes = &(**buffer).bytes;
+            JsString::from(format!(
+                "ArrayBuffer {{ [Uint8Contents]: <{}>, byteLength: {} }}",
+                bytes
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                bytes.len()
+            ))
+            .print();
+        }
         _ => {}
     }
 }
 
-pub unsafe fn array_new(_callobj: CallObject, args: Vec<Value>, self_: &mut VM) {
+pub unsafe fn array_new(
+    _callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
     let args_len = args.len();
 
     if args_len == 0 {
@@ -199,7 +617,7 @@ pub unsafe fn array_new(_callobj: CallObject, args: Vec<Value>, self_: &mut VM)
             .stack
             .push(Value::array(gc::new(ArrayValue::new(vec![]))));
         gc::mark_and_sweep(&self_.state);
-        return;
+        return Ok(());
     }
 
     let mut elems = vec![];
@@ -223,381 +641,6144 @@ pub unsafe fn array_new(_callobj: CallObject, args: Vec<Value>, self_: &mut VM)
         .push(Value::array(gc::new(ArrayValue::new(elems))));
 
     gc::mark_and_sweep(&self_.state);
+    Ok(())
 }
 
-pub unsafe fn array_push(callobj: CallObject, args: Vec<Value>, self_: &mut VM) {
-    let array = if let ValueBase::Array(ref array) = callobj.this.val {
-        &mut **array
+// Binds '$array' to the array 'this' refers to. Array methods are generic: called on another
+// object, they work on a copy of its properties named by the indices below 'length', which the
+// methods modifying the array write back with store_array_like().
+macro_rules! this_array {
+    ($array:ident, $callobj:expr, $self_:expr) => {
+        let mut array_like;
+        let $array: *mut ArrayValue = match $callobj.this.val {
+            ValueBase::Array(array) => array,
+            _ => {
+                array_like = load_array_like(&$callobj.this, $self_)?;
+                &mut array_like
+            }
+        };
+    };
+}
+
+// https://tc39.github.io/ecma262/#sec-tolength
+fn to_length(val: &ValueBase) -> usize {
+    let n = val.to_number();
+    if n.is_nan() || n <= 0.0 {
+        0
     } else {
-        self_.state.stack.push(Value::undefined());
-        return;
+        n.trunc().min(9007199254740991.0) as usize
+    }
+}
+
+// Copies the elements of an array-like object into a new array. Missing ones become holes.
+unsafe fn load_array_like(this: &Value, self_: &VM) -> Result<ArrayValue, RuntimeError> {
+    let elems = match this.val {
+        ValueBase::Undefined | ValueBase::Null => {
+            return Err(RuntimeError::Type(
+                "type error: Array.prototype method called on undefined or null".to_string(),
+            ))
+        }
+        ValueBase::String(ref s) => (0..s.len())
+            .map(|i| Value::string(s.substring(i, i + 1)))
+            .collect(),
+        _ => {
+            let scope = self_.function_scope();
+            let callobjref = Some(&scope);
+            let len = to_length(
+                &this
+                    .get_property(ValueBase::String(JsString::from("length")), callobjref)
+                    .val,
+            );
+            (0..len)
+                .map(|i| {
+                    if this.has_property(i.to_string().as_str()) {
+                        let val = this.get_property(ValueBase::Number(i as f64), callobjref);
+                        Value::new(val.val)
+                    } else {
+                        Value::empty()
+                    }
+                })
+                .collect()
+        }
     };
+    Ok(ArrayValue::new(elems))
+}
 
+// Writes the elements and the length of 'array' back to the array-like object 'this', whose
+// length was 'old_len'. Arrays are modified in place, so nothing is done for them.
+unsafe fn store_array_like(
+    this: &Value,
+    array: &ArrayValue,
+    old_len: usize,
+) -> Result<(), RuntimeError> {
+    if let ValueBase::Array(_) = this.val {
+        return Ok(());
+    }
+    for i in 0..array.length.max(old_len) {
+        let key = ValueBase::Number(i as f64);
+        match array.elems.get(i) {
+            Some(elem) if i < array.length && elem.val != ValueBase::Empty => {
+                this.set_property(key, Value::new(elem.val.clone()), None, true)?
+            }
+            _ => {
+                this.delete_property(key, true)?;
+            }
+        }
+    }
+    this.set_property(
+        ValueBase::String(JsString::from("length")),
+        Value::number(array.length as f64),
+        None,
+        true,
+    )
+}
+
+pub unsafe fn array_push(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &mut *array;
+    let len = array.length;
+
+    if !array.is_extensible() {
+        return Err(RuntimeError::Type(
+            "type error: cannot add property, object is not extensible".to_string(),
+        ));
+    }
+    check_length_writable(array)?;
+
+    array.elems.truncate(len);
+    array.reserve_elems(len + args.len())?;
     for val in &args {
         array.elems.push(val.clone());
     }
 
     array.length += args.len();
+    store_array_like(&callobj.this, array, len)?;
 
-    self_.state.stack.push(Value::number(array.length as f64))
+    self_.state.stack.push(Value::number(array.length as f64));
+    Ok(())
 }
 
-pub unsafe fn array_pop(callobj: CallObject, _args: Vec<Value>, self_: &mut VM) {
-    let array = if let ValueBase::Array(ref array) = callobj.this.val {
-        &mut **array
-    } else {
+pub unsafe fn array_pop(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &mut *array;
+    let len = array.length;
+
+    if len == 0 {
+        store_array_like(&callobj.this, array, len)?;
         self_.state.stack.push(Value::undefined());
-        return;
-    };
+        return Ok(());
+    }
 
-    if let Some(val) = array.elems.pop() {
-        array.length -= 1;
-        self_.state.stack.push(val);
-        return;
+    check_length_writable(array)?;
+    if !array.elems[len - 1].configurable {
+        return Err(RuntimeError::Type(format!(
+            "type error: cannot delete property '{}'",
+            len - 1
+        )));
     }
 
-    self_.state.stack.push(Value::undefined())
+    array.elems.truncate(len);
+    let last = array.elems.pop().unwrap();
+    array.length -= 1;
+    store_array_like(&callobj.this, array, len)?;
+
+    self_.state.stack.push(match last.val {
+        ValueBase::Empty => Value::undefined(),
+        val => Value::new(val),
+    });
+    Ok(())
 }
 
-pub unsafe fn array_map(callobj: CallObject, args: Vec<Value>, self_: &mut VM) {
-    let array = if let ValueBase::Array(ref array) = callobj.this.val {
-        &mut **array
-    } else {
-        self_.state.stack.push(Value::undefined());
-        return;
-    };
+fn check_length_writable(array: &ArrayValue) -> Result<(), RuntimeError> {
+    if !array.length_writable {
+        return Err(RuntimeError::Type(
+            "type error: cannot assign to read only property 'length' of object".to_string(),
+        ));
+    }
+    Ok(())
+}
 
-    let mut new_array = ArrayValue::new(vec![]);
-    let callback = &args[0];
+pub unsafe fn array_map(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let callback = get_arg(&args, 0);
+    let this_arg = get_arg(&args, 1);
+    let len = (*array).length;
 
-    let mut args_for_callback = vec![
-        Value::undefined(),
-        Value::number(0.0),
-        /* array itself = */ (*callobj.this).clone(),
-    ];
+    // Keep the arrays reachable from the GC while the callback runs.
+    let sp = self_.state.stack.len();
+    self_.state.stack.push((*callobj.this).clone());
+    let new_array = gc::new(ArrayValue::new(vec![Value::empty(); len]));
+    self_.state.stack.push(Value::array(new_array));
 
-    for i in 0..array.length {
-        args_for_callback[0] = array.elems[i].clone();
-        args_for_callback[1].set_number_if_possible(i as f64);
+    for i in 0..len {
+        let val = match array_elem(array, i) {
+            Some(val) => val,
+            None => continue,
+        };
+        let args = vec![val, Value::number(i as f64), (*callobj.this).clone()];
+        let val = call_callback(self_, &callback, this_arg.clone(), &args)?;
+        (&mut *new_array).elems[i] = val;
+    }
 
-        match callback.val {
-            ValueBase::BuiltinFunction(box (id, _, ref callobj)) => {
-                let mut callobj = callobj.clone();
-                // *callobj.this = arg_this;
-                self_.builtin_functions[id](callobj, args_for_callback.clone(), self_);
-            }
-            ValueBase::Function(box (id, ref iseq, _, ref callobj)) => {
-                let mut callobj = callobj.clone();
-                // *callobj.this = arg_this;
-                (*callobj.vals).clear();
-                call_function(self_, id, iseq, &args_for_callback, callobj).unwrap();
-            }
-            _ => self_.state.stack.push(Value::undefined()),
-        }
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(Value::array(new_array));
+    Ok(())
+}
 
-        let val = self_.state.stack.pop().unwrap();
-        new_array.push(val);
+// Returns the n-th element of the array, or None if it's a hole or out of range.
+unsafe fn array_elem(array: *mut ArrayValue, n: usize) -> Option<Value> {
+    let array = &*array;
+    if n >= array.length {
+        return None;
+    }
+    match array.elems[n].val {
+        ValueBase::Empty => None,
+        ref val => Some(Value::new(val.clone())),
     }
+}
 
-    self_.state.stack.push(Value::array(gc::new(new_array)))
+// Calls 'callee' with 'this' and 'args', and returns what it returned.
+pub unsafe fn call_callback(
+    self_: &mut VM,
+    callee: &Value,
+    this: Value,
+    args: &Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    match callee.val {
+        ValueBase::BuiltinFunction(box (id, _, ref callobj)) => {
+            let mut callobj = callobj.clone();
+            *callobj.this = this;
+            self_.call_builtin_function(id, callobj, args.clone())?;
+        }
+        ValueBase::Function(box (id, ref iseq, _, ref callobj)) => {
+            let mut callobj = callobj.clone();
+            *callobj.this = this;
+            callobj.vals = gc::new(PropertyMap::default());
+            call_function(self_, id, iseq, args, callobj)?;
+        }
+        ValueBase::Proxy(p) => return proxy::call(self_, p, this, args.clone()),
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: callback is not a function".to_string(),
+            ))
+        }
+    }
+    Ok(self_.state.stack.pop().unwrap())
 }
 
-macro_rules! simple_math {
-    ($name:ident, $f:ident) => {
-        pub unsafe fn $name(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-            if let ValueBase::Number(n) = args[0].val {
-                return self_.state.stack.push(Value::number(n.$f()));
+// Converts 'val' into a primitive value, calling toString() and valueOf() of objects in the order
+// 'hint' ("string", "number" or "default") prefers. Dates take "default" as "string".
+// https://tc39.github.io/ecma262/#sec-toprimitive
+pub unsafe fn to_primitive(self_: &mut VM, val: &Value, hint: &str) -> Result<Value, RuntimeError> {
+    if !val.is_object() {
+        return Ok(val.clone());
+    }
+    let is_date = match val.val {
+        ValueBase::Object(obj) => (*obj).contains_key(DATE_VALUE),
+        _ => false,
+    };
+    let methods = if hint == "string" || hint == "default" && is_date {
+        ["toString", "valueOf"]
+    } else {
+        ["valueOf", "toString"]
+    };
+    for name in &methods {
+        let method =
+            proxy::get_property(self_, val, ValueBase::String(JsString::from(*name)), val)?;
+        if is_callable(&method) {
+            let result = call_callback(self_, &method, val.clone(), &vec![])?;
+            if !result.is_object() {
+                return Ok(result);
             }
-            self_.state.stack.push(Value::undefined())
         }
-    };
+    }
+    Err(RuntimeError::Type(
+        "type error: cannot convert object to primitive value".to_string(),
+    ))
 }
 
-simple_math!(math_floor, floor);
-simple_math!(math_abs, abs);
-simple_math!(math_acos, acos);
-simple_math!(math_acosh, acosh);
-simple_math!(math_asin, asin);
-simple_math!(math_asinh, asinh);
-simple_math!(math_atan, atan);
-simple_math!(math_atanh, atanh);
-
-pub unsafe fn math_atan2(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    if let ValueBase::Number(n1) = args[0].val {
-        if let ValueBase::Number(n2) = args[1].val {
-            return self_.state.stack.push(Value::number(n1.atan2(n2)));
-        }
-        self_.state.stack.push(Value::undefined())
+// https://tc39.github.io/ecma262/#sec-isarray
+fn is_array(val: &Value) -> bool {
+    match val.val {
+        ValueBase::Array(_) => true,
+        ValueBase::Proxy(p) => is_array(unsafe { &(*p).target }),
+        _ => false,
     }
 }
-simple_math!(math_cbrt, cbrt);
-simple_math!(math_ceil, ceil);
 
-pub unsafe fn math_clz32(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    if let ValueBase::Number(n) = args[0].val {
-        return self_.state.stack.push(Value::number(if n == 0.0 {
-            32.0
-        } else {
-            // TODO: >> ? >>> ?
-            31.0 - ((n as i32 >> 0) as f64 * ::std::f64::consts::LOG2_E)
-                .log(::std::f64::consts::E)
-                .floor()
-        }));
+pub fn is_callable(val: &Value) -> bool {
+    match val.val {
+        ValueBase::Function(_) | ValueBase::BuiltinFunction(_) => true,
+        ValueBase::Proxy(p) => is_callable(unsafe { &(*p).target }),
+        _ => false,
     }
-    self_.state.stack.push(Value::undefined())
 }
-simple_math!(math_cos, cos);
-simple_math!(math_cosh, cosh);
-simple_math!(math_exp, exp);
-simple_math!(math_expm1, exp_m1);
-simple_math!(math_fround, round);
 
-pub unsafe fn math_hypot(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    let mut sum2 = 0.0;
-    for n in args {
-        if let ValueBase::Number(n) = n.val {
-            sum2 += n * n;
-        }
+// Converts a relative index argument (negative means from the end) into an index in [0, len].
+fn to_relative_index(val: &Value, len: usize, default: usize) -> Result<usize, RuntimeError> {
+    if val.val == ValueBase::Undefined {
+        return Ok(default);
     }
-    self_.state.stack.push(Value::number(sum2.sqrt()));
+    let n = to_integer(val)?;
+    Ok(if n < 0.0 {
+        (len as f64 + n).max(0.0) as usize
+    } else {
+        n.min(len as f64) as usize
+    })
 }
 
-pub unsafe fn math_log(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    if let ValueBase::Number(n1) = args[0].val {
-        return self_
-            .state
-            .stack
-            .push(Value::number(n1.log(::std::f64::consts::E)));
+// Methods modifying an array raise a TypeError rather than silently ignoring the property
+// attributes (like strict mode assignments do).
+fn check_array_mutable(array: &ArrayValue, grows: bool, shrinks: bool) -> Result<(), RuntimeError> {
+    if grows && !array.is_extensible() {
+        return Err(RuntimeError::Type(
+            "type error: cannot add property, object is not extensible".to_string(),
+        ));
+    }
+    if grows || shrinks {
+        check_length_writable(array)?;
+    }
+    for (i, elem) in array.elems[0..array.length].iter().enumerate() {
+        if elem.val == ValueBase::Empty {
+            continue;
+        }
+        if !elem.writable {
+            return Err(RuntimeError::Type(format!(
+                "type error: cannot assign to read only property '{}' of object",
+                i
+            )));
+        }
+        if shrinks && !elem.configurable {
+            return Err(RuntimeError::Type(format!(
+                "type error: cannot delete property '{}'",
+                i
+            )));
+        }
     }
-    self_.state.stack.push(Value::undefined())
+    Ok(())
 }
 
-pub unsafe fn math_log1p(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    if let ValueBase::Number(n1) = args[0].val {
-        return self_
-            .state
-            .stack
-            .push(Value::number(n1.log(1.0 + ::std::f64::consts::E)));
+pub unsafe fn array_for_each(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let callback = get_arg(&args, 0);
+    let this_arg = get_arg(&args, 1);
+    let len = (*array).length;
+
+    let sp = self_.state.stack.len();
+    self_.state.stack.push((*callobj.this).clone());
+
+    for i in 0..len {
+        if let Some(val) = array_elem(array, i) {
+            let args = vec![val, Value::number(i as f64), (*callobj.this).clone()];
+            call_callback(self_, &callback, this_arg.clone(), &args)?;
+        }
     }
-    self_.state.stack.push(Value::undefined())
+
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(Value::undefined());
+    Ok(())
 }
 
-simple_math!(math_log10, log10);
-simple_math!(math_log2, log2);
+pub unsafe fn array_filter(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let callback = get_arg(&args, 0);
+    let this_arg = get_arg(&args, 1);
+    let len = (*array).length;
 
-pub unsafe fn math_max(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    let mut max = if let ValueBase::Number(n) = args[0].val {
-        n
-    } else {
-        0.0
-    };
-    for n in args[1..].iter() {
-        if let ValueBase::Number(n) = n.val {
-            if n > max {
-                max = n;
+    let sp = self_.state.stack.len();
+    self_.state.stack.push((*callobj.this).clone());
+    let new_array = gc::new(ArrayValue::new(vec![]));
+    self_.state.stack.push(Value::array(new_array));
+
+    for i in 0..len {
+        if let Some(val) = array_elem(array, i) {
+            let args = vec![
+                val.clone(),
+                Value::number(i as f64),
+                (*callobj.this).clone(),
+            ];
+            if call_callback(self_, &callback, this_arg.clone(), &args)?
+                .val
+                .to_boolean()
+            {
+                (*new_array).push(val);
             }
         }
     }
-    self_.state.stack.push(Value::number(max));
+
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(Value::array(new_array));
+    Ok(())
 }
 
-pub unsafe fn math_min(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    let mut min = if let ValueBase::Number(n) = args[0].val {
-        n
+unsafe fn array_reduce_(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+    from_right: bool,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let callback = get_arg(&args, 0);
+    let len = (*array).length;
+    let mut indices: Box<Iterator<Item = usize>> = if from_right {
+        Box::new((0..len).rev())
     } else {
-        0.0
+        Box::new(0..len)
     };
-    for n in args[1..].iter() {
-        if let ValueBase::Number(n) = n.val {
-            if n < min {
-                min = n;
+
+    let acc = if args.len() >= 2 {
+        args[1].clone()
+    } else {
+        match indices.by_ref().filter_map(|i| array_elem(array, i)).next() {
+            Some(val) => val,
+            None => {
+                return Err(RuntimeError::Type(
+                    "type error: reduce of empty array with no initial value".to_string(),
+                ))
             }
         }
+    };
+
+    // The accumulator lives in the stack so that the GC can find it.
+    let sp = self_.state.stack.len();
+    self_.state.stack.push((*callobj.this).clone());
+    self_.state.stack.push(acc);
+
+    for i in indices {
+        if let Some(val) = array_elem(array, i) {
+            let args = vec![
+                self_.state.stack[sp + 1].clone(),
+                val,
+                Value::number(i as f64),
+                (*callobj.this).clone(),
+            ];
+            self_.state.stack[sp + 1] = call_callback(self_, &callback, Value::undefined(), &args)?;
+        }
     }
-    self_.state.stack.push(Value::number(min));
+
+    let acc = self_.state.stack[sp + 1].clone();
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(acc);
+    Ok(())
 }
 
-simple_math!(math_round, round);
+pub unsafe fn array_reduce(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    array_reduce_(callobj, args, self_, false)
+}
 
-pub unsafe fn math_sign(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    if let ValueBase::Number(n) = args[0].val {
-        return self_.state.stack.push(Value::number(if n == 0.0 {
-            n
-        } else if n > 0.0 {
-            1.0
-        } else {
-            -1.0
-        }));
+pub unsafe fn array_reduce_right(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    array_reduce_(callobj, args, self_, true)
+}
+
+// Array.prototype.some() if 'every' is false, otherwise Array.prototype.every().
+unsafe fn array_some_or_every(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+    every: bool,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let callback = get_arg(&args, 0);
+    let this_arg = get_arg(&args, 1);
+    let len = (*array).length;
+
+    let sp = self_.state.stack.len();
+    self_.state.stack.push((*callobj.this).clone());
+
+    let mut result = every;
+    for i in 0..len {
+        if let Some(val) = array_elem(array, i) {
+            let args = vec![val, Value::number(i as f64), (*callobj.this).clone()];
+            let ret = call_callback(self_, &callback, this_arg.clone(), &args)?;
+            if ret.val.to_boolean() != every {
+                result = !every;
+                break;
+            }
+        }
     }
-    self_.state.stack.push(Value::undefined())
+
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(Value::bool(result));
+    Ok(())
 }
 
-simple_math!(math_sin, sin);
-simple_math!(math_sinh, sinh);
-simple_math!(math_sqrt, sqrt);
-simple_math!(math_tan, tan);
-simple_math!(math_tanh, tanh);
-simple_math!(math_trunc, trunc);
+pub unsafe fn array_some(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    array_some_or_every(callobj, args, self_, false)
+}
 
-pub unsafe fn math_random(_: CallObject, _args: Vec<Value>, self_: &mut VM) {
-    self_.state.stack.push(Value::number(random::<f64>()))
+pub unsafe fn array_every(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    array_some_or_every(callobj, args, self_, true)
 }
 
-pub unsafe fn math_pow(_: CallObject, args: Vec<Value>, self_: &mut VM) {
-    if let ValueBase::Number(f1) = args[0].val {
-        if let ValueBase::Number(f2) = args[1].val {
-            return self_.state.stack.push(Value::number(f1.powf(f2)));
+// Returns the index of the first element that 'callback' returns true for. Unlike most of the
+// other methods, holes are visited as undefined.
+unsafe fn array_find_(
+    callobj: &CallObject,
+    args: &Vec<Value>,
+    self_: &mut VM,
+    array: *mut ArrayValue,
+) -> Result<Option<(usize, Value)>, RuntimeError> {
+    let callback = get_arg(args, 0);
+    let this_arg = get_arg(args, 1);
+    let len = (*array).length;
+
+    let sp = self_.state.stack.len();
+    self_.state.stack.push((*callobj.this).clone());
+
+    let mut found = None;
+    for i in 0..len {
+        let val = array_elem(array, i).unwrap_or_else(|| Value::undefined());
+        let args = vec![
+            val.clone(),
+            Value::number(i as f64),
+            (*callobj.this).clone(),
+        ];
+        if call_callback(self_, &callback, this_arg.clone(), &args)?
+            .val
+            .to_boolean()
+        {
+            found = Some((i, val));
+            break;
         }
     }
-    self_.state.stack.push(Value::undefined())
+
+    self_.state.stack.truncate(sp);
+    Ok(found)
 }
 
-pub unsafe fn function_prototype_apply(callobj: CallObject, args: Vec<Value>, self_: &mut VM) {
-    let arg_this = args[0].clone();
-    let arg = match args[1].val {
-        ValueBase::Array(aryval) => {
-            let aryval = &*aryval;
-            let mut elems = vec![];
-            for i in 0..aryval.length {
-                elems.push(aryval.elems[i].clone());
-            }
-            elems
-        }
-        ValueBase::Arguments => {
-            let mut elems = vec![];
-            let callobj = &**self_.state.scope.last().unwrap();
-            let length = callobj.get_arguments_length();
-            for i in 0..length {
-                elems.push(callobj.get_arguments_nth_value(i).unwrap());
-            }
-            elems
+pub unsafe fn array_find(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let found = array_find_(&callobj, &args, self_, array)?;
+    self_
+        .state
+        .stack
+        .push(found.map_or_else(|| Value::undefined(), |(_, val)| val));
+    Ok(())
+}
+
+pub unsafe fn array_find_index(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let found = array_find_(&callobj, &args, self_, array)?;
+    self_
+        .state
+        .stack
+        .push(Value::number(found.map_or(-1.0, |(i, _)| i as f64)));
+    Ok(())
+}
+
+pub unsafe fn array_index_of(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let search = get_arg(&args, 0);
+    let len = (*array).length;
+    let from = to_relative_index(&get_arg(&args, 1), len, 0)?;
+
+    let idx = (from..len)
+        .find(|&i| array_elem(array, i).map_or(false, |val| val.val.strict_equals(&search.val)));
+    self_
+        .state
+        .stack
+        .push(Value::number(idx.map_or(-1.0, |i| i as f64)));
+    Ok(())
+}
+
+pub unsafe fn array_last_index_of(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let search = get_arg(&args, 0);
+    let len = (*array).length;
+
+    // Unlike the other methods, fromIndex here is inclusive and can be len - 1 at most.
+    let end = if args.len() >= 2 {
+        let n = args[1].val.to_number();
+        let n = if n.is_nan() { 0.0 } else { n.trunc() };
+        if n < 0.0 {
+            (len as f64 + n + 1.0).max(0.0) as usize
+        } else {
+            (n + 1.0).min(len as f64) as usize
         }
-        _ => vec![],
+    } else {
+        len
     };
-    let callee = *callobj.this;
 
-    match callee.val {
-        ValueBase::BuiltinFunction(box (id, _, callobj)) => {
-            let mut callobj = callobj.clone();
-            *callobj.this = arg_this;
-            callobj.vals = gc::new(FxHashMap::default());
-            self_.builtin_functions[id](callobj, arg, self_);
-        }
-        ValueBase::Function(box (id, ref iseq, _, ref callobj)) => {
-            let mut callobj = callobj.clone();
-            *callobj.this = arg_this;
-            callobj.vals = gc::new(FxHashMap::default());
-            call_function(self_, id, iseq, &arg, callobj).unwrap();
-        }
-        _ => self_.state.stack.push(Value::undefined()),
-    }
+    let idx = (0..end)
+        .rev()
+        .find(|&i| array_elem(array, i).map_or(false, |val| val.val.strict_equals(&search.val)));
+    self_
+        .state
+        .stack
+        .push(Value::number(idx.map_or(-1.0, |i| i as f64)));
+    Ok(())
 }
 
-pub unsafe fn function_prototype_call(callobj: CallObject, args: Vec<Value>, self_: &mut VM) {
-    let callee = *callobj.this;
-    let arg_this = args[0].clone();
-    match callee.val {
-        ValueBase::BuiltinFunction(box (id, _, ref callobj)) => {
-            let mut callobj = callobj.clone();
-            *callobj.this = arg_this;
-            callobj.vals = gc::new(FxHashMap::default());
-            self_.builtin_functions[id](callobj, args[1..].to_vec(), self_);
-        }
-        ValueBase::Function(box (id, ref iseq, _, ref callobj)) => {
-            let mut callobj = callobj.clone();
-            *callobj.this = arg_this;
-            callobj.vals = gc::new(FxHashMap::default());
-            call_function(self_, id, iseq, &args[1..].to_vec(), callobj).unwrap();
-        }
-        _ => self_.state.stack.push(Value::undefined()),
-    }
+pub unsafe fn array_includes(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let search = get_arg(&args, 0);
+    let len = (*array).length;
+    let from = to_relative_index(&get_arg(&args, 1), len, 0)?;
+
+    let found = (from..len).any(|i| {
+        array_elem(array, i)
+            .unwrap_or_else(|| Value::undefined())
+            .val
+            .same_value_zero(&search.val)
+    });
+    self_.state.stack.push(Value::bool(found));
+    Ok(())
 }
 
-pub unsafe fn require(_callobj: CallObject, args: Vec<Value>, self_: &mut VM) {
-    // TODO: REFINE CODE!!!!
-    use ansi_term::Colour;
-    use extract_anony_func;
-    use parser;
-    use parser::Error::*;
-    use std::ffi::CString;
-    use std::fs::OpenOptions;
-    use std::io::prelude::*;
-    use vm;
-    use vm_codegen;
+pub unsafe fn array_slice(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &*array;
+    let len = array.length;
+    let start = to_relative_index(&get_arg(&args, 0), len, 0)?;
+    let end = to_relative_index(&get_arg(&args, 1), len, len)?;
 
-    let file_name = match args[0].val {
-        ValueBase::String(ref s) => s.to_str().unwrap().clone(),
-        _ => panic!(),
+    let elems = if start < end {
+        array.elems[start..end]
+            .iter()
+            .map(|val| Value::new(val.val.clone()))
+            .collect()
+    } else {
+        vec![]
     };
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(elems))));
 
-    let mut file_body = String::new();
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
 
-    match OpenOptions::new().read(true).open(file_name) {
-        Ok(mut ok) => match ok.read_to_string(&mut file_body).ok() {
-            Some(x) => x,
-            None => {
-                eprintln!(
-                    "{}: Couldn't read the file '{}'",
-                    Colour::Red.bold().paint("error"),
-                    file_name,
-                );
-                return;
-            }
-        },
-        Err(_e) => {
-            eprintln!(
-                "{}: No such file or directory '{}'",
-                Colour::Red.bold().paint("error"),
-                file_name,
-            );
-            return;
+pub unsafe fn array_splice(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &mut *array;
+    let len = array.length;
+    let start = to_relative_index(&get_arg(&args, 0), len, 0)?;
+    let delete_count = match args.len() {
+        0 => 0,
+        1 => len - start,
+        _ => {
+            let n = args[1].val.to_number();
+            let n = if n.is_nan() { 0.0 } else { n.trunc() };
+            n.max(0.0).min((len - start) as f64) as usize
         }
     };
+    let items = args
+        .iter()
+        .skip(2)
+        .map(|val| Value::new(val.val.clone()))
+        .collect::<Vec<Value>>();
 
-    if file_body.len() == 0 {
-        return;
-    }
+    check_array_mutable(
+        array,
+        items.len() > delete_count,
+        items.len() < delete_count,
+    )?;
 
-    if file_body.as_bytes()[0] == b'#' {
-        let first_ln = file_body.find('\n').unwrap_or(file_body.len());
-        file_body.drain(..first_ln);
+    array.elems.truncate(len);
+    let removed = array
+        .elems
+        .splice(start..start + delete_count, items)
+        .collect::<Vec<Value>>();
+    array.length = array.elems.len();
+    store_array_like(&callobj.this, array, len)?;
+
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(removed))));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn array_concat(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let mut elems = vec![];
+    for val in ::std::iter::once(&*callobj.this).chain(args.iter()) {
+        match val.val {
+            ValueBase::Array(ary) => {
+                let ary = &*ary;
+                for elem in &ary.elems[0..ary.length] {
+                    elems.push(Value::new(elem.val.clone()));
+                }
+            }
+            ref val => elems.push(Value::new(val.clone())),
+        }
     }
 
-    let mut parser = parser::Parser::new(file_body);
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(elems))));
 
-    let mut node = match parser.parse_all() {
-        Ok(ok) => ok,
-        Err(NormalEOF) => unreachable!(),
-        Err(Expect(pos, kind, msg))
-        | Err(UnexpectedEOF(pos, kind, msg))
-        | Err(UnexpectedToken(pos, kind, msg)) => {
-            parser.show_error_at(pos, kind, msg.as_str());
-            self_.state.stack.push(Value::undefined());
-            return;
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn array_join(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &*array;
+    let separator = match get_arg(&args, 0).val {
+        ValueBase::Undefined => ",".to_string(),
+        sep => sep.to_string(),
+    };
+    let joined = JsString::from(array.join(separator.as_str()));
+    gc::grow(joined.len() * mem::size_of::<u16>())?;
+    self_.state.stack.push(Value::string(joined));
+    Ok(())
+}
+
+pub unsafe fn array_to_string(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &*array;
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from(array.to_string())));
+    Ok(())
+}
+
+pub unsafe fn array_reverse(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &mut *array;
+    check_array_mutable(array, false, false)?;
+    let len = array.length;
+    array.elems[0..len].reverse();
+    store_array_like(&callobj.this, array, len)?;
+    self_.state.stack.push(*callobj.this);
+    Ok(())
+}
+
+pub unsafe fn array_shift(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &mut *array;
+    let len = array.length;
+    if len == 0 {
+        store_array_like(&callobj.this, array, len)?;
+        self_.state.stack.push(Value::undefined());
+        return Ok(());
+    }
+
+    check_array_mutable(array, false, true)?;
+    let first = array.elems.remove(0);
+    array.length -= 1;
+    store_array_like(&callobj.this, array, len)?;
+
+    self_.state.stack.push(match first.val {
+        ValueBase::Empty => Value::undefined(),
+        val => Value::new(val),
+    });
+    Ok(())
+}
+
+pub unsafe fn array_unshift(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &mut *array;
+    let len = array.length;
+    if args.len() > 0 {
+        check_array_mutable(array, true, false)?;
+        let len = array.elems.len() + args.len();
+        array.reserve_elems(len)?;
+        let items = args.iter().map(|val| Value::new(val.val.clone()));
+        array.elems.truncate(len);
+        array.elems.splice(0..0, items);
+        array.length = array.elems.len();
+    }
+    store_array_like(&callobj.this, array, len)?;
+    self_.state.stack.push(Value::number(array.length as f64));
+    Ok(())
+}
+
+// https://www.ecma-international.org/ecma-262/7.0/#sec-sortcompare
+unsafe fn sort_compare(
+    self_: &mut VM,
+    comparefn: &Value,
+    x: &Value,
+    y: &Value,
+) -> Result<f64, RuntimeError> {
+    if comparefn.val == ValueBase::Undefined {
+        return Ok(match x.to_string().cmp(&y.to_string()) {
+            ::std::cmp::Ordering::Less => -1.0,
+            ::std::cmp::Ordering::Equal => 0.0,
+            ::std::cmp::Ordering::Greater => 1.0,
+        });
+    }
+    let ret = call_callback(
+        self_,
+        comparefn,
+        Value::undefined(),
+        &vec![x.clone(), y.clone()],
+    )?;
+    let n = ret.val.to_number();
+    Ok(if n.is_nan() { 0.0 } else { n })
+}
+
+// Stable merge sort whose comparison can fail (comparefn may throw).
+unsafe fn merge_sort(
+    self_: &mut VM,
+    mut vals: Vec<Value>,
+    comparefn: &Value,
+) -> Result<Vec<Value>, RuntimeError> {
+    if vals.len() <= 1 {
+        return Ok(vals);
+    }
+
+    let right = vals.split_off(vals.len() / 2);
+    let left = merge_sort(self_, vals, comparefn)?;
+    let right = merge_sort(self_, right, comparefn)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if sort_compare(self_, comparefn, &left[i], &right[j])? > 0.0 {
+            merged.push(right[j].clone());
+            j += 1;
+        } else {
+            merged.push(left[i].clone());
+            i += 1;
         }
-        Err(UnsupportedFeature(pos)) => {
-            parser.enhanced_show_error_at(pos, "unsupported feature");
-            self_.state.stack.push(Value::undefined());
-            return;
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    Ok(merged)
+}
+
+pub unsafe fn array_sort(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let comparefn = get_arg(&args, 0);
+    if comparefn.val != ValueBase::Undefined && !is_callable(&comparefn) {
+        return Err(RuntimeError::Type(
+            "type error: the comparison function must be either a function or undefined"
+                .to_string(),
+        ));
+    }
+    check_array_mutable(&*array, false, false)?;
+
+    // Holes go to the end, undefined values just before them. Neither is passed to comparefn.
+    let len = (*array).length;
+    let mut vals = vec![];
+    let mut undefineds = 0;
+    let elems = &(*array).elems;
+    for elem in &elems[0..len] {
+        match elem.val {
+            ValueBase::Empty => {}
+            ValueBase::Undefined => undefineds += 1,
+            ref val => vals.push(Value::new(val.clone())),
+        }
+    }
+
+    let sp = self_.state.stack.len();
+    self_.state.stack.push((*callobj.this).clone());
+    let mut sorted = merge_sort(self_, vals, &comparefn)?;
+    self_.state.stack.truncate(sp);
+
+    for _ in 0..undefineds {
+        sorted.push(Value::undefined());
+    }
+    // comparefn may have shrunk the array.
+    let array = &mut *array;
+    let len = array.length;
+    sorted.resize(len, Value::empty());
+    for (elem, val) in array.elems[0..len].iter_mut().zip(sorted.into_iter()) {
+        elem.val = val.val;
+    }
+    store_array_like(&callobj.this, array, len)?;
+
+    self_.state.stack.push(*callobj.this);
+    Ok(())
+}
+
+pub unsafe fn array_fill(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    this_array!(array, callobj, self_);
+    let array = &mut *array;
+    let val = get_arg(&args, 0);
+    let len = array.length;
+    let start = to_relative_index(&get_arg(&args, 1), len, 0)?;
+    let end = to_relative_index(&get_arg(&args, 2), len, len)?;
+
+    check_array_mutable(array, false, false)?;
+    for i in start..end {
+        array.elems[i] = Value::new(val.val.clone());
+    }
+    store_array_like(&callobj.this, array, len)?;
+
+    self_.state.stack.push(*callobj.this);
+    Ok(())
+}
+
+pub unsafe fn array_from(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let items = get_arg(&args, 0);
+    let mapfn = get_arg(&args, 1);
+    let this_arg = get_arg(&args, 2);
+
+    if mapfn.val != ValueBase::Undefined && !is_callable(&mapfn) {
+        return Err(RuntimeError::Type(
+            "type error: Array.from: when provided, the second argument must be a function"
+                .to_string(),
+        ));
+    }
+
+    let elems = match items.val {
+        ValueBase::Undefined | ValueBase::Null => {
+            return Err(RuntimeError::Type(
+                "type error: Array.from called on undefined or null".to_string(),
+            ))
+        }
+        ValueBase::Array(ary) => (0..(*ary).length)
+            .map(|i| array_elem(ary, i).unwrap_or_else(|| Value::undefined()))
+            .collect(),
+        // Strings are iterated by code point.
+        ValueBase::String(ref s) => {
+            let mut elems = vec![];
+            let mut i = 0;
+            while let Some(cp) = s.code_point_at(i) {
+                let next = if cp > 0xffff { i + 2 } else { i + 1 };
+                elems.push(Value::string(s.substring(i, next)));
+                i = next;
+            }
+            elems
+        }
+        _ => {
+            // Array-like objects
+            let scope = self_.function_scope();
+            let callobjref = Some(&scope);
+            let len = items
+                .get_property(ValueBase::String(JsString::from("length")), callobjref)
+                .val
+                .to_uint32() as usize;
+            (0..len)
+                .map(|i| {
+                    let val = items.get_property(ValueBase::Number(i as f64), callobjref);
+                    Value::new(val.val)
+                })
+                .collect()
+        }
+    };
+
+    let new_array = gc::new(ArrayValue::new(elems));
+    if mapfn.val != ValueBase::Undefined {
+        let sp = self_.state.stack.len();
+        self_.state.stack.push(Value::array(new_array));
+        for i in 0..(*new_array).length {
+            let args = vec![(&*new_array).elems[i].clone(), Value::number(i as f64)];
+            let val = call_callback(self_, &mapfn, this_arg.clone(), &args)?;
+            (&mut *new_array).elems[i] = val;
+        }
+        self_.state.stack.truncate(sp);
+    }
+
+    self_.state.stack.push(Value::array(new_array));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn array_is_array(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let is_array = is_array(&get_arg(&args, 0));
+    self_.state.stack.push(Value::bool(is_array));
+    Ok(())
+}
+
+pub unsafe fn array_of(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let elems = args.into_iter().map(|val| Value::new(val.val)).collect();
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(elems))));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-function-properties-of-the-math-object
+// A Math function of one Number. The argument is converted with ToNumber().
+macro_rules! math_function {
+    ($name:ident, $f:expr) => {
+        pub unsafe fn $name(
+            _: CallObject,
+            args: Vec<Value>,
+            self_: &mut VM,
+        ) -> Result<(), RuntimeError> {
+            let f: fn(f64) -> f64 = $f;
+            let n = get_arg(&args, 0).val.try_to_number()?;
+            self_.state.stack.push(Value::number(f(n)));
+            Ok(())
+        }
+    };
+}
+
+math_function!(math_abs, f64::abs);
+math_function!(math_acos, f64::acos);
+math_function!(math_acosh, f64::acosh);
+math_function!(math_asin, f64::asin);
+math_function!(math_asinh, f64::asinh);
+math_function!(math_atan, f64::atan);
+math_function!(math_atanh, f64::atanh);
+math_function!(math_cbrt, f64::cbrt);
+math_function!(math_ceil, f64::ceil);
+math_function!(
+    math_clz32,
+    |n| (ValueBase::Number(n).to_uint32() as u32).leading_zeros() as f64
+);
+math_function!(math_cos, f64::cos);
+math_function!(math_cosh, f64::cosh);
+math_function!(math_exp, f64::exp);
+math_function!(math_expm1, f64::exp_m1);
+math_function!(math_floor, f64::floor);
+math_function!(math_fround, |n| n as f32 as f64);
+math_function!(math_log, f64::ln);
+math_function!(math_log1p, f64::ln_1p);
+math_function!(math_log10, f64::log10);
+math_function!(math_log2, f64::log2);
+math_function!(math_round, math_round_f64);
+math_function!(math_sign, |n| if n == 0.0 || n.is_nan() {
+    n
+} else {
+    n.signum()
+});
+math_function!(math_sin, f64::sin);
+math_function!(math_sinh, f64::sinh);
+math_function!(math_sqrt, f64::sqrt);
+math_function!(math_tan, f64::tan);
+math_function!(math_tanh, f64::tanh);
+math_function!(math_trunc, f64::trunc);
+
+// https://tc39.github.io/ecma262/#sec-math.round
+// Rounds half up, unlike f64::round() which rounds half away from zero. Keeps -0 for (-0.5, -0].
+fn math_round_f64(n: f64) -> f64 {
+    if !n.is_finite() || n.trunc() == n {
+        return n;
+    }
+    if n < 0.0 && n >= -0.5 {
+        return -0.0;
+    }
+    let floor = n.floor();
+    if n - floor >= 0.5 {
+        floor + 1.0
+    } else {
+        floor
+    }
+}
+
+pub unsafe fn math_atan2(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let y = get_arg(&args, 0).val.try_to_number()?;
+    let x = get_arg(&args, 1).val.try_to_number()?;
+    self_.state.stack.push(Value::number(y.atan2(x)));
+    Ok(())
+}
+
+pub unsafe fn math_pow(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let base = get_arg(&args, 0).val.try_to_number()?;
+    let exponent = get_arg(&args, 1).val.try_to_number()?;
+    self_
+        .state
+        .stack
+        .push(Value::number(js_pow(base, exponent)));
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-math.imul
+pub unsafe fn math_imul(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let a = ValueBase::Number(get_arg(&args, 0).val.try_to_number()?).to_uint32() as u32 as i32;
+    let b = ValueBase::Number(get_arg(&args, 1).val.try_to_number()?).to_uint32() as u32 as i32;
+    self_
+        .state
+        .stack
+        .push(Value::number(a.wrapping_mul(b) as f64));
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-math.hypot
+pub unsafe fn math_hypot(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    // f64::hypot() gives Infinity if either is infinite, even if the other is NaN.
+    let mut n = 0.0f64;
+    for arg in &args {
+        n = n.hypot(arg.val.try_to_number()?);
+    }
+    self_.state.stack.push(Value::number(n));
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-math.max
+pub unsafe fn math_max(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let mut max = ::std::f64::NEG_INFINITY;
+    for arg in &args {
+        let n = arg.val.try_to_number()?;
+        if n.is_nan() || max.is_nan() {
+            max = ::std::f64::NAN;
+        } else if n > max || (n == max && max.is_sign_negative()) {
+            // +0 is larger than -0.
+            max = n;
+        }
+    }
+    self_.state.stack.push(Value::number(max));
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-math.min
+pub unsafe fn math_min(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let mut min = ::std::f64::INFINITY;
+    for arg in &args {
+        let n = arg.val.try_to_number()?;
+        if n.is_nan() || min.is_nan() {
+            min = ::std::f64::NAN;
+        } else if n < min || (n == min && n.is_sign_negative()) {
+            // -0 is smaller than +0.
+            min = n;
+        }
+    }
+    self_.state.stack.push(Value::number(min));
+    Ok(())
+}
+
+// Shares the generator of the realm with the JIT-compiled code, so that seeding it (see
+// VM::seed_random()) makes the whole sequence reproducible.
+pub unsafe fn math_random(
+    _: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    self_.state.stack.push(Value::number(self_.realm.random()));
+    Ok(())
+}
+
+pub unsafe fn function_prototype_apply(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (callee, this, args) =
+        call_or_apply_target(self_, FUNCTION_PROTOTYPE_APPLY, &callobj, args);
+    if enter_call(self_, callee, this, args)? {
+        self_.run_frames()?;
+    }
+    Ok(())
+}
+
+pub unsafe fn function_prototype_call(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (callee, this, args) = call_or_apply_target(self_, FUNCTION_PROTOTYPE_CALL, &callobj, args);
+    if enter_call(self_, callee, this, args)? {
+        self_.run_frames()?;
+    }
+    Ok(())
+}
+
+// Returns the function that Function.prototype.apply() or call() ('id') calls when it's called
+// with 'callobj' and 'args', with the 'this' value and the arguments to call it with. The VM calls
+// it in its own loop when call() or apply() is called directly.
+pub unsafe fn call_or_apply_target(
+    self_: &VM,
+    id: usize,
+    callobj: &CallObject,
+    mut args: Vec<Value>,
+) -> (Value, Value, Vec<Value>) {
+    let callee = (*callobj.this).clone();
+    let arg_this = get_arg(&args, 0);
+    if id == FUNCTION_PROTOTYPE_CALL {
+        let args = if args.len() > 1 {
+            args.split_off(1)
+        } else {
+            vec![]
+        };
+        return (callee, arg_this, args);
+    }
+
+    let args = match get_arg(&args, 1).val {
+        ValueBase::Array(aryval) => {
+            let aryval = &*aryval;
+            let mut elems = vec![];
+            for i in 0..aryval.length {
+                elems.push(aryval.elems[i].clone());
+            }
+            elems
+        }
+        ValueBase::Arguments => {
+            let mut elems = vec![];
+            let callobj = &*self_.function_scope();
+            let length = callobj.get_arguments_length();
+            for i in 0..length {
+                elems.push(callobj.get_arguments_nth_value(i).unwrap());
+            }
+            elems
         }
+        _ => vec![],
     };
+    (callee, arg_this, args)
+}
+
+// Parses the source code given to Function() or eval(), and extracts the anonymous functions.
+fn parse_source(source: String) -> Result<(parser::Parser, Node), RuntimeError> {
+    use parser::Error::*;
 
+    let mut parser = parser::Parser::new(source);
+    let mut node = match parser.parse_all() {
+        Ok(node) => node,
+        Err(Expect(_, _, msg))
+        | Err(UnexpectedEOF(_, _, msg))
+        | Err(UnexpectedToken(_, _, msg)) => {
+            return Err(RuntimeError::Syntax(format!("syntax error: {}", msg)))
+        }
+        Err(NormalEOF) | Err(UnsupportedFeature(_)) => {
+            return Err(RuntimeError::Syntax(
+                "syntax error: unsupported feature".to_string(),
+            ))
+        }
+    };
     extract_anony_func::AnonymousFunctionExtractor::new().run_toplevel(&mut node);
+    Ok((parser, node))
+}
+
+// Compiles 'node' parsed by 'parser' into the VM running now, sharing its constant table. The
+// functions declared at the top level are defined in 'scope'. Returns the instructions of the top
+// level code, and the id its debug info is registered with as 'name'.
+unsafe fn compile_in_scope(
+    self_: &mut VM,
+    parser: &parser::Parser,
+    node: &Node,
+    scope: CallObjectRef,
+    name: &str,
+    use_value: bool,
+) -> (ByteCode, FuncId) {
+    let mut vm_codegen = VMCodeGen::with_realm(self_.realm.clone());
+    vm_codegen.global_varmap = scope;
+    vm_codegen.bytecode_gen.const_table =
+        mem::replace(&mut self_.const_table, ConstantTable::new());
+    // compile() registers the debug info of the top level code as the one of the main code.
+    let main_info = vm_codegen.bytecode_gen.const_table.debug_info.remove(&0);
+    vm_codegen.set_source(format!("<{}>", name).as_str(), &parser.lexer);
 
-    let mut vm_codegen = vm_codegen::VMCodeGen::new();
     let mut iseq = vec![];
-    vm_codegen.bytecode_gen.const_table = self_.const_table.clone();
-    vm_codegen.compile(&node, &mut iseq, false);
-    self_.const_table = vm_codegen.bytecode_gen.const_table.clone();
+    vm_codegen.compile(node, &mut iseq, use_value);
 
-    let mut vm = vm::VM::new(vm_codegen.global_varmap);
-    vm.const_table = vm_codegen.bytecode_gen.const_table;
-    // TODO: Do not unwrap
-    vm.run(iseq).unwrap();
+    let mut const_table = vm_codegen.bytecode_gen.const_table;
+    let id = id::get_unique_id();
+    if let Some(info) = const_table.debug_info.remove(&0) {
+        const_table.debug_info.insert(
+            id,
+            DebugInfo {
+                name: name.to_string(),
+                ..info
+            },
+        );
+    }
+    if let Some(info) = main_info {
+        const_table.debug_info.insert(0, info);
+    }
+    self_.const_table = const_table;
 
-    let module_exports = (**vm.state.scope.last().unwrap())
-        .get_value(&"module".to_string())
-        .unwrap()
-        .get_property(Value::string(CString::new("exports").unwrap()).val, None);
-    self_.state.stack.push(module_exports);
+    // compile() gives the functions the variables of 'scope' as 'this', which is only right for
+    // the global scope.
+    let global_this = (*(*self_.state.scope[0]).this).clone();
+    for name in vm_codegen.functions.keys() {
+        if let Some(Value {
+            val: ValueBase::Function(box (_, _, _, ref mut callobj)),
+            ..
+        }) = (*(*scope).vals).get_mut(name)
+        {
+            *callobj.this = global_this.clone();
+        }
+    }
+
+    (iseq, id)
+}
+
+// https://tc39.github.io/ecma262/#sec-function-p1-p2-pn-body
+// The function is compiled in the global scope, whatever scope Function() is called in.
+pub unsafe fn function_new(
+    _callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (params, body) = match args.split_last() {
+        Some((body, params)) => (params.to_vec(), body.to_string()),
+        None => (vec![], String::new()),
+    };
+    let params = params
+        .iter()
+        .map(|param| param.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+    let source = format!("function anonymous({}\n) {{\n{}\n}}", params, body);
+
+    // Parsed as a function expression so that 'anonymous' isn't declared in the global scope.
+    let (parser, node) = parse_source(format!("({})", source))?;
+    // Trailing code in the body like "}, function() {" would make another statement.
+    let name = match node.base {
+        NodeBase::StatementList(ref stmts) => match stmts[0].base {
+            NodeBase::SetCurCallObj(ref name) if stmts.len() == 2 => name.clone(),
+            _ => {
+                return Err(RuntimeError::Syntax(
+                    "syntax error: invalid function body".to_string(),
+                ))
+            }
+        },
+        _ => unreachable!(),
+    };
+
+    let global = self_.state.scope[0];
+    // The function is strict mode code only if its body has the directive, and the code calling
+    // Function() stays what it was.
+    let strict = (*global).strict;
+    compile_in_scope(self_, &parser, &node, global, "anonymous", false);
+    (*global).strict = strict;
+
+    let function = (*global).get_value(&name)?;
+    self_.state.stack.push(function);
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+// The call object of a bound function holds the target function, 'this' and the arguments.
+const BOUND_TARGET: &str = "__bound_target__";
+const BOUND_THIS: &str = "__bound_this__";
+const BOUND_ARGS: &str = "__bound_args__";
+
+// https://tc39.github.io/ecma262/#sec-function.prototype.bind
+pub unsafe fn function_prototype_bind(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = (*callobj.this).clone();
+    if !is_callable(&target) {
+        return Err(RuntimeError::Type(
+            "type error: Bind must be called on a function".to_string(),
+        ));
+    }
+    let bound_args = args.iter().skip(1).cloned().collect::<Vec<Value>>();
+
+    let name = match target
+        .get_property(ValueBase::String(JsString::from("name")), None)
+        .val
+    {
+        ValueBase::String(name) => name.to_string(),
+        _ => String::new(),
+    };
+    let length = match target
+        .get_property(ValueBase::String(JsString::from("length")), None)
+        .val
+    {
+        ValueBase::Number(length) if length > bound_args.len() as f64 => {
+            length - bound_args.len() as f64
+        }
+        _ => 0.0,
+    };
+
+    let bound_callobj = CallObject::new(Value::undefined());
+    for (key, val) in vec![
+        (BOUND_TARGET, target),
+        (BOUND_THIS, get_arg(&args, 0)),
+        (
+            BOUND_ARGS,
+            Value::array(gc::new(ArrayValue::new(bound_args))),
+        ),
+    ] {
+        (*bound_callobj.vals).insert(key.to_string(), val);
+    }
+
+    let mut obj = PropertyMap::default();
+    obj.insert(
+        "__proto__".to_string(),
+        Value::object(FUNCTION_PROTOTYPE.with(|x| x.clone())),
+    );
+    for (key, val) in vec![
+        (
+            "name",
+            Value::string(JsString::from(format!("bound {}", name))),
+        ),
+        ("length", Value::number(length)),
+    ] {
+        obj.insert(
+            key.to_string(),
+            Value {
+                writable: false,
+                enumerable: false,
+                ..val
+            },
+        );
+    }
+
+    self_
+        .state
+        .stack
+        .push(Value::new(ValueBase::BuiltinFunction(Box::new((
+            BOUND_FUNCTION_CALL,
+            gc::new(obj),
+            bound_callobj,
+        )))));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+// Returns the target function, 'this' and the arguments of the bound function whose call object
+// is 'callobj'.
+pub unsafe fn bound_function_parts(callobj: &CallObject) -> (Value, Value, Vec<Value>) {
+    let val_of = |key: &str| (*callobj.vals).get(key).unwrap().clone();
+    let bound_args = match val_of(BOUND_ARGS).val {
+        ValueBase::Array(ary) => (*ary).elems.clone(),
+        _ => unreachable!(),
+    };
+    (val_of(BOUND_TARGET), val_of(BOUND_THIS), bound_args)
+}
+
+pub unsafe fn bound_function_call(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (target, this, mut bound_args) = bound_function_parts(&callobj);
+    bound_args.extend(args);
+
+    let sp = self_.state.stack.len();
+    self_.state.stack.push(target.clone());
+    let val = call_callback(self_, &target, this, &bound_args)?;
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(val);
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-function.prototype.tostring
+pub unsafe fn function_prototype_to_string(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let source = match callobj.this.val {
+        ValueBase::Function(box (id, _, _, _)) => match self_.const_table.debug_info.get(&id) {
+            Some(info) if !info.source.is_empty() => info.source.clone(),
+            _ => "function () { [native code] }".to_string(),
+        },
+        ValueBase::BuiltinFunction(box (id, _, _)) => match builtin_name_and_length(id) {
+            Some((name, _)) => format!("function {}() {{ [native code] }}", name),
+            None => "function () { [native code] }".to_string(),
+        },
+        ValueBase::Proxy(_) if is_callable(&callobj.this) => {
+            "function () { [native code] }".to_string()
+        }
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: Function.prototype.toString requires that 'this' be a Function"
+                    .to_string(),
+            ))
+        }
+    };
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from(source.as_str())));
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-eval-x
+// An indirect eval, which runs the code in the global scope. Direct evals are done by call_eval().
+pub unsafe fn eval(
+    _callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let global = self_.state.scope[0];
+    eval_in_scope(self_, args.first().cloned(), global)
+}
+
+// Runs 'source' in 'scope' and pushes its completion value. The variables and functions declared
+// in it are defined in 'scope', unless the code is strict mode code that has its own scope.
+pub unsafe fn eval_in_scope(
+    self_: &mut VM,
+    source: Option<Value>,
+    scope: CallObjectRef,
+) -> Result<(), RuntimeError> {
+    let source = match source {
+        Some(Value {
+            val: ValueBase::String(ref source),
+            ..
+        }) => source.to_string(),
+        // Values other than strings are returned as they are.
+        source => {
+            self_
+                .state
+                .stack
+                .push(source.unwrap_or_else(|| Value::undefined()));
+            return Ok(());
+        }
+    };
+
+    let (parser, node) = parse_source(source)?;
+
+    let scope = if (*scope).strict || has_use_strict_directive(&node) {
+        let mut callobj = CallObject::new((*(*scope).this).clone());
+        callobj.parent = Some(scope);
+        callobj.strict = true;
+        gc::new(callobj)
+    } else {
+        scope
+    };
+
+    run_in_scope(self_, &parser, &node, scope, "eval")?;
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+// Compiles and runs the top level code 'node' parsed by 'parser' in 'scope', and pushes its
+// completion value. 'name' is the name of the code in stack traces.
+pub unsafe fn run_in_scope(
+    self_: &mut VM,
+    parser: &parser::Parser,
+    node: &Node,
+    scope: CallObjectRef,
+    name: &str,
+) -> Result<(), RuntimeError> {
+    // Strict mode code makes 'scope' strict only while it runs (see VMCodeGen::compile).
+    let strict = (*scope).strict;
+    let (iseq, id) = compile_in_scope(self_, parser, node, scope, name, true);
+
+    let sp = self_.state.stack.len();
+    let result = self_
+        .push_frame(Rc::new(iseq), id, scope, FrameKind::TopLevel)
+        .and_then(|()| self_.run_frames());
+    (*scope).strict = strict;
+    result?;
+    self_.pop_frame();
+
+    // Like the REPL, the value of the last expression statement is the completion value.
+    let completion = match self_.state.stack.drain(sp..).last() {
+        Some(Value {
+            val: ValueBase::Empty,
+            ..
+        })
+        | None => Value::undefined(),
+        Some(val) => val,
+    };
+    self_.state.stack.push(completion);
+    Ok(())
+}
+
+pub unsafe fn require(
+    _callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    // TODO: REFINE CODE!!!!
+    use ansi_term::Colour;
+    use extract_anony_func;
+    use parser;
+    use parser::Error::*;
+    use std::fs::OpenOptions;
+    use std::io::prelude::*;
+    use vm;
+    use vm_codegen;
+
+    let file_name = match args[0].val {
+        ValueBase::String(ref s) => s.to_string(),
+        _ => panic!(),
+    };
+
+    let mut file_body = String::new();
+
+    match OpenOptions::new().read(true).open(&file_name) {
+        Ok(mut ok) => match ok.read_to_string(&mut file_body).ok() {
+            Some(x) => x,
+            None => {
+                eprintln!(
+                    "{}: Couldn't read the file '{}'",
+                    Colour::Red.bold().paint("error"),
+                    file_name,
+                );
+                return Ok(());
+            }
+        },
+        Err(_e) => {
+            eprintln!(
+                "{}: No such file or directory '{}'",
+                Colour::Red.bold().paint("error"),
+                file_name,
+            );
+            return Ok(());
+        }
+    };
+
+    if file_body.len() == 0 {
+        return Ok(());
+    }
+
+    if file_body.as_bytes()[0] == b'#' {
+        let first_ln = file_body.find('\n').unwrap_or(file_body.len());
+        file_body.drain(..first_ln);
+    }
+
+    let mut parser = parser::Parser::new(file_body);
+
+    let mut node = match parser.parse_all() {
+        Ok(ok) => ok,
+        Err(NormalEOF) => unreachable!(),
+        Err(Expect(pos, kind, msg))
+        | Err(UnexpectedEOF(pos, kind, msg))
+        | Err(UnexpectedToken(pos, kind, msg)) => {
+            parser.show_error_at(pos, kind, msg.as_str());
+            self_.state.stack.push(Value::undefined());
+            return Ok(());
+        }
+        Err(UnsupportedFeature(pos)) => {
+            parser.enhanced_show_error_at(pos, "unsupported feature");
+            self_.state.stack.push(Value::undefined());
+            return Ok(());
+        }
+    };
+
+    extract_anony_func::AnonymousFunctionExtractor::new().run_toplevel(&mut node);
+
+    let mut vm_codegen = vm_codegen::VMCodeGen::with_realm(self_.realm.clone());
+    let mut iseq = vec![];
+    vm_codegen.bytecode_gen.const_table = self_.const_table.clone();
+    vm_codegen.set_source(file_name.as_str(), &parser.lexer);
+    vm_codegen.compile(&node, &mut iseq, false);
+    self_.const_table = vm_codegen.bytecode_gen.const_table.clone();
+
+    let mut vm = vm::VM::with_realm(vm_codegen.global_varmap, self_.realm.clone());
+    vm.const_table = vm_codegen.bytecode_gen.const_table;
+    vm.clock = self_.clock.clone();
+    vm.host_functions = self_.host_functions.clone();
+    vm.share_limits(self_);
+    vm.run(iseq)?;
+
+    let module_exports = (**vm.state.scope.last().unwrap())
+        .get_value(&"module".to_string())
+        .unwrap()
+        .get_property(Value::string(JsString::from("exports")).val, None);
+    self_.state.stack.push(module_exports);
+    Ok(())
+}
+
+fn get_arg(args: &Vec<Value>, n: usize) -> Value {
+    args.get(n).cloned().unwrap_or_else(|| Value::undefined())
+}
+
+pub fn to_property_descriptor(desc: &Value) -> Result<PropertyDescriptor, RuntimeError> {
+    match desc.val {
+        ValueBase::Object(_)
+        | ValueBase::Array(_)
+        | ValueBase::Function(_)
+        | ValueBase::BuiltinFunction(_)
+        | ValueBase::Proxy(_) => {}
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: property description must be an object".to_string(),
+            ))
+        }
+    }
+
+    if desc.has_property("get") || desc.has_property("set") {
+        return Err(RuntimeError::Unimplemented);
+    }
+
+    let field = |name: &str| -> Option<ValueBase> {
+        if desc.has_property(name) {
+            Some(
+                desc.get_property(ValueBase::String(JsString::from(name)), None)
+                    .val,
+            )
+        } else {
+            None
+        }
+    };
+
+    Ok(PropertyDescriptor {
+        value: field("value"),
+        writable: field("writable").map(|v| v.to_boolean()),
+        enumerable: field("enumerable").map(|v| v.to_boolean()),
+        configurable: field("configurable").map(|v| v.to_boolean()),
+    })
+}
+
+fn to_object_or_throw(val: &Value, func_name: &str) -> Result<(), RuntimeError> {
+    match val.val {
+        ValueBase::Undefined | ValueBase::Null => Err(RuntimeError::Type(format!(
+            "type error: {} called on undefined or null",
+            func_name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// Returns the pairs of own enumerable property name and its value.
+fn enumerable_own_properties(obj: &Value) -> Vec<(String, Value)> {
+    obj.own_property_keys()
+        .into_iter()
+        .filter_map(|key| match obj.get_own_property(key.as_str()) {
+            Some(ref prop) if prop.enumerable => Some((key, Value::new(prop.val.clone()))),
+            _ => None,
+        })
+        .collect()
+}
+
+// The names of own enumerable properties, which are got through the 'ownKeys' trap of proxies.
+unsafe fn enumerable_own_keys(self_: &mut VM, obj: &Value) -> Result<Vec<String>, RuntimeError> {
+    Ok(proxy::own_property_keys(self_, obj)?
+        .into_iter()
+        .filter(|key| {
+            obj.get_own_property(key.as_str())
+                .map_or(false, |prop| prop.enumerable)
+        })
+        .collect())
+}
+
+fn define_properties(obj: &Value, props: &Value) -> Result<(), RuntimeError> {
+    // Validate all the descriptors before defining any property.
+    let mut descs = vec![];
+    for (key, prop) in enumerable_own_properties(props) {
+        descs.push((key, to_property_descriptor(&prop)?));
+    }
+
+    for (key, desc) in descs {
+        obj.define_own_property(key, desc)?;
+    }
+
+    Ok(())
+}
+
+pub unsafe fn object_define_property(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    let key = get_arg(&args, 1).val.to_property_key();
+    if !proxy::define_own_property(self_, &obj, key.clone(), &get_arg(&args, 2))? {
+        return Err(RuntimeError::Type(format!(
+            "type error: 'defineProperty' on proxy: trap returned falsish for property '{}'",
+            JsString::from_key(&key)
+        )));
+    }
+    self_.state.stack.push(obj);
+    Ok(())
+}
+
+pub unsafe fn object_define_properties(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    let props = get_arg(&args, 1);
+
+    define_properties(&obj, &props)?;
+
+    self_.state.stack.push(obj);
+    Ok(())
+}
+
+pub unsafe fn object_get_own_property_descriptor(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    let key = get_arg(&args, 1).val.to_property_key();
+
+    let prop = match proxy::get_own_property(self_, &obj, key.as_str())? {
+        Some(prop) => prop,
+        None => {
+            self_.state.stack.push(Value::undefined());
+            return Ok(());
+        }
+    };
+
+    let mut map = PropertyMap::default();
+    map.insert("value".to_string(), Value::new(prop.val));
+    map.insert("writable".to_string(), Value::bool(prop.writable));
+    map.insert("enumerable".to_string(), Value::bool(prop.enumerable));
+    map.insert("configurable".to_string(), Value::bool(prop.configurable));
+    self_.state.stack.push(Value::plain_object(map));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn object_freeze(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    obj.seal_or_freeze(true);
+    self_.state.stack.push(obj);
+    Ok(())
+}
+
+pub unsafe fn object_seal(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    obj.seal_or_freeze(false);
+    self_.state.stack.push(obj);
+    Ok(())
+}
+
+pub unsafe fn object_prevent_extensions(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    obj.prevent_extensions();
+    self_.state.stack.push(obj);
+    Ok(())
+}
+
+pub unsafe fn object_is_frozen(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    self_
+        .state
+        .stack
+        .push(Value::bool(obj.is_sealed_or_frozen(true)));
+    Ok(())
+}
+
+pub unsafe fn object_is_sealed(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    self_
+        .state
+        .stack
+        .push(Value::bool(obj.is_sealed_or_frozen(false)));
+    Ok(())
+}
+
+pub unsafe fn object_is_extensible(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    self_.state.stack.push(Value::bool(obj.is_extensible()));
+    Ok(())
+}
+
+pub unsafe fn object_new(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let val = get_arg(&args, 0);
+    match val.val {
+        ValueBase::Undefined | ValueBase::Null => {
+            self_
+                .state
+                .stack
+                .push(Value::plain_object(PropertyMap::default()));
+            gc::mark_and_sweep(&self_.state);
+        }
+        // TODO: Wrap primitive values in objects.
+        _ => self_.state.stack.push(val),
+    }
+    Ok(())
+}
+
+pub unsafe fn object_keys(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    to_object_or_throw(&obj, "Object.keys")?;
+
+    let keys = enumerable_own_keys(self_, &obj)?
+        .into_iter()
+        .map(|key| Value::string(JsString::from_key(&key)))
+        .collect();
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(keys))));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn object_values(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    to_object_or_throw(&obj, "Object.values")?;
+
+    let values = enumerable_own_properties(&obj)
+        .into_iter()
+        .map(|(_, val)| val)
+        .collect();
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(values))));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn object_entries(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    to_object_or_throw(&obj, "Object.entries")?;
+
+    let entries = enumerable_own_properties(&obj)
+        .into_iter()
+        .map(|(key, val)| {
+            Value::array(gc::new(ArrayValue::new(vec![
+                Value::string(JsString::from_key(&key)),
+                val,
+            ])))
+        })
+        .collect();
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(entries))));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn object_assign(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = get_arg(&args, 0);
+    to_object_or_throw(&target, "Object.assign")?;
+
+    for source in args.iter().skip(1) {
+        for (key, val) in enumerable_own_properties(source) {
+            target.set_property(ValueBase::String(JsString::from_key(&key)), val, None, true)?;
+        }
+    }
+
+    self_.state.stack.push(target);
+    Ok(())
+}
+
+pub unsafe fn object_create(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let proto = get_arg(&args, 0);
+    let props = get_arg(&args, 1);
+
+    let mut map = PropertyMap::default();
+    match proto.val {
+        ValueBase::Null => {}
+        _ if proto.is_object() => {
+            map.insert("__proto__".to_string(), proto);
+        }
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: object prototype may only be an object or null".to_string(),
+            ))
+        }
+    }
+
+    let obj = Value::object(gc::new(map));
+    if props.val != ValueBase::Undefined {
+        define_properties(&obj, &props)?;
+    }
+
+    self_.state.stack.push(obj);
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn object_get_prototype_of(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    to_object_or_throw(&obj, "Object.getPrototypeOf")?;
+    let proto = proxy::get_prototype(self_, &obj)?;
+    self_.state.stack.push(proto);
+    Ok(())
+}
+
+pub unsafe fn object_set_prototype_of(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    let proto = get_arg(&args, 1);
+    to_object_or_throw(&obj, "Object.setPrototypeOf")?;
+
+    if proto.val != ValueBase::Null && !proto.is_object() {
+        return Err(RuntimeError::Type(
+            "type error: object prototype may only be an object or null".to_string(),
+        ));
+    }
+
+    obj.set_prototype(proto)?;
+    self_.state.stack.push(obj);
+    Ok(())
+}
+
+pub unsafe fn object_get_own_property_names(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let obj = get_arg(&args, 0);
+    to_object_or_throw(&obj, "Object.getOwnPropertyNames")?;
+
+    let names = proxy::own_property_keys(self_, &obj)?
+        .into_iter()
+        .map(|key| Value::string(JsString::from_key(&key)))
+        .collect();
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(names))));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn object_is(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let x = get_arg(&args, 0);
+    let y = get_arg(&args, 1);
+    self_
+        .state
+        .stack
+        .push(Value::bool(x.val.same_value(&y.val)));
+    Ok(())
+}
+
+pub unsafe fn object_prototype_has_own_property(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let key = get_arg(&args, 0).val.to_property_key();
+    let has = callobj.this.get_own_property(key.as_str()).is_some();
+    self_.state.stack.push(Value::bool(has));
+    Ok(())
+}
+
+pub unsafe fn object_prototype_is_prototype_of(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let mut proto = get_arg(&args, 0).get_prototype();
+    while proto.is_object() {
+        if proto.val.same_value(&callobj.this.val) {
+            self_.state.stack.push(Value::bool(true));
+            return Ok(());
+        }
+        proto = proto.get_prototype();
+    }
+    self_.state.stack.push(Value::bool(false));
+    Ok(())
+}
+
+pub unsafe fn object_prototype_property_is_enumerable(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let key = get_arg(&args, 0).val.to_property_key();
+    let enumerable = callobj
+        .this
+        .get_own_property(key.as_str())
+        .map_or(false, |prop| prop.enumerable);
+    self_.state.stack.push(Value::bool(enumerable));
+    Ok(())
+}
+
+pub unsafe fn object_prototype_to_string(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let tag = match callobj.this.val {
+        ValueBase::Undefined => "Undefined",
+        ValueBase::Null => "Null",
+        ValueBase::Bool(_) => "Boolean",
+        ValueBase::Number(_) => "Number",
+        ValueBase::BigInt(_) => "BigInt",
+        ValueBase::String(_) => "String",
+        ValueBase::Array(_) => "Array",
+        ValueBase::Function(_) | ValueBase::BuiltinFunction(_) => "Function",
+        ValueBase::Arguments => "Arguments",
+        // Stands in for @@toStringTag of Map.prototype and friends.
+        ValueBase::Object(obj) => match (*obj).get(COLLECTION_DATA) {
+            Some(Value {
+                val: ValueBase::Collection(collection),
+                ..
+            }) => (**collection).kind.name(),
+            _ if (*obj).contains_key(DATE_VALUE) => "Date",
+            _ if (*obj).contains_key(ARRAY_BUFFER_DATA) => "ArrayBuffer",
+            _ if (*obj).contains_key(DATA_VIEW_DATA) => "DataView",
+            _ => match (*obj).get(TYPED_ARRAY_DATA) {
+                Some(Value {
+                    val: ValueBase::TypedArray(typed_array),
+                    ..
+                }) => (**typed_array).kind.name(),
+                _ => "Object",
+            },
+        },
+        _ => "Object",
+    };
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from(format!("[object {}]", tag))));
+    Ok(())
+}
+
+pub unsafe fn object_prototype_value_of(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    self_.state.stack.push(*callobj.this);
+    Ok(())
+}
+
+// https://www.ecma-international.org/ecma-262/7.0/#sec-tointeger
+fn to_integer(val: &Value) -> Result<f64, RuntimeError> {
+    let n = val.val.try_to_number()?;
+    Ok(if n.is_nan() { 0.0 } else { n.trunc() })
+}
+
+// Clamps an integer argument into [0, len]. Unlike to_relative_index, negative values mean 0.
+fn to_clamped_index(val: &Value, len: usize, default: usize) -> Result<usize, RuntimeError> {
+    if val.val == ValueBase::Undefined {
+        return Ok(default);
+    }
+    Ok(to_integer(val)?.max(0.0).min(len as f64) as usize)
+}
+
+// Returns the string 'this' refers to.
+fn this_string(callobj: &CallObject, func_name: &str) -> Result<JsString, RuntimeError> {
+    match callobj.this.val {
+        ValueBase::Undefined | ValueBase::Null => Err(RuntimeError::Type(format!(
+            "type error: String.prototype.{} called on null or undefined",
+            func_name
+        ))),
+        ref this => Ok(this.to_js_string()),
+    }
+}
+
+fn is_whitespace_unit(unit: u16) -> bool {
+    ::std::char::from_u32(unit as u32).map_or(false, is_js_whitespace)
+}
+
+// Converts every character in 's' by 'f'. Unpaired surrogates are left as they are.
+fn map_chars<I, F>(s: &JsString, f: F) -> JsString
+where
+    I: Iterator<Item = char>,
+    F: Fn(char) -> I,
+{
+    let mut units = vec![];
+    for c in ::std::char::decode_utf16(s.units().iter().cloned()) {
+        match c {
+            Ok(c) => {
+                for c in f(c) {
+                    let mut buf = [0; 2];
+                    units.extend_from_slice(c.encode_utf16(&mut buf));
+                }
+            }
+            Err(e) => units.push(e.unpaired_surrogate()),
+        }
+    }
+    JsString::from_units(units)
+}
+
+// Removes whitespace at the start of 's' if 'start' is true, and at the end if 'end' is true.
+fn trim_whitespace(s: &JsString, start: bool, end: bool) -> JsString {
+    let units = s.units();
+    let mut from = 0;
+    let mut to = units.len();
+    while start && from < to && is_whitespace_unit(units[from]) {
+        from += 1;
+    }
+    while end && from < to && is_whitespace_unit(units[to - 1]) {
+        to -= 1;
+    }
+    s.substring(from, to)
+}
+
+pub unsafe fn string_new(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    // TODO: 'new String()' should create a String object wrapping the primitive.
+    let s = if args.len() == 0 {
+        JsString::new()
+    } else {
+        to_primitive(self_, &args[0], "string")?.to_js_string()
+    };
+    self_.state.stack.push(Value::string(s));
+    Ok(())
+}
+
+pub unsafe fn string_from_char_code(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let units = args
+        .iter()
+        .map(|code| code.val.to_uint32() as u32 as u16)
+        .collect::<Vec<u16>>();
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from_units(units)));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_char_at(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "charAt")?;
+    let pos = to_integer(&get_arg(&args, 0))?;
+    self_
+        .state
+        .stack
+        .push(Value::string(if 0.0 <= pos && pos < s.len() as f64 {
+            s.substring(pos as usize, pos as usize + 1)
+        } else {
+            JsString::new()
+        }));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_char_code_at(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "charCodeAt")?;
+    let pos = to_integer(&get_arg(&args, 0))?;
+    self_
+        .state
+        .stack
+        .push(if 0.0 <= pos && pos < s.len() as f64 {
+            Value::number(s.at(pos as usize).unwrap() as f64)
+        } else {
+            Value::number(::std::f64::NAN)
+        });
+    Ok(())
+}
+
+pub unsafe fn string_prototype_code_point_at(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "codePointAt")?;
+    let pos = to_integer(&get_arg(&args, 0))?;
+    self_
+        .state
+        .stack
+        .push(if 0.0 <= pos && pos < s.len() as f64 {
+            Value::number(s.code_point_at(pos as usize).unwrap() as f64)
+        } else {
+            Value::undefined()
+        });
+    Ok(())
+}
+
+pub unsafe fn string_prototype_index_of(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "indexOf")?;
+    let search = get_arg(&args, 0).to_js_string();
+    let from = to_clamped_index(&get_arg(&args, 1), s.len(), 0)?;
+    self_.state.stack.push(Value::number(
+        s.find(&search, from).map_or(-1.0, |i| i as f64),
+    ));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_last_index_of(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "lastIndexOf")?;
+    let search = get_arg(&args, 0).to_js_string();
+    let from = match get_arg(&args, 1).val.to_number() {
+        n if n.is_nan() => s.len(),
+        n => n.trunc().max(0.0).min(s.len() as f64) as usize,
+    };
+    self_.state.stack.push(Value::number(
+        s.rfind(&search, from).map_or(-1.0, |i| i as f64),
+    ));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_slice(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "slice")?;
+    let start = to_relative_index(&get_arg(&args, 0), s.len(), 0)?;
+    let end = to_relative_index(&get_arg(&args, 1), s.len(), s.len())?;
+    self_.state.stack.push(Value::string(if start < end {
+        s.substring(start, end)
+    } else {
+        JsString::new()
+    }));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_substring(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "substring")?;
+    let start = to_clamped_index(&get_arg(&args, 0), s.len(), 0)?;
+    let end = to_clamped_index(&get_arg(&args, 1), s.len(), s.len())?;
+    let (start, end) = if start < end {
+        (start, end)
+    } else {
+        (end, start)
+    };
+    self_
+        .state
+        .stack
+        .push(Value::string(s.substring(start, end)));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_substr(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "substr")?;
+    let start = to_relative_index(&get_arg(&args, 0), s.len(), 0)?;
+    let len = to_clamped_index(&get_arg(&args, 1), s.len() - start, s.len() - start)?;
+    self_
+        .state
+        .stack
+        .push(Value::string(s.substring(start, start + len)));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_split(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "split")?;
+    let separator = get_arg(&args, 0);
+    let limit = match get_arg(&args, 1).val {
+        ValueBase::Undefined => ::std::u32::MAX as usize,
+        limit => limit.to_uint32() as usize,
+    };
+
+    let mut elems = vec![];
+    match separator.val {
+        ValueBase::Undefined => elems.push(Value::string(s)),
+        _ => {
+            let separator = separator.to_js_string();
+            if separator.len() == 0 {
+                for i in 0..s.len() {
+                    elems.push(Value::string(s.substring(i, i + 1)));
+                }
+            } else {
+                let mut start = 0;
+                while let Some(i) = s.find(&separator, start) {
+                    elems.push(Value::string(s.substring(start, i)));
+                    start = i + separator.len();
+                }
+                elems.push(Value::string(s.substring(start, s.len())));
+            }
+        }
+    }
+    elems.truncate(limit);
+
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(elems))));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn string_prototype_to_upper_case(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "toUpperCase")?;
+    self_
+        .state
+        .stack
+        .push(Value::string(map_chars(&s, |c| c.to_uppercase())));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_to_lower_case(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "toLowerCase")?;
+    self_
+        .state
+        .stack
+        .push(Value::string(map_chars(&s, |c| c.to_lowercase())));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_trim(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "trim")?;
+    self_
+        .state
+        .stack
+        .push(Value::string(trim_whitespace(&s, true, true)));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_trim_start(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "trimStart")?;
+    self_
+        .state
+        .stack
+        .push(Value::string(trim_whitespace(&s, true, false)));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_trim_end(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "trimEnd")?;
+    self_
+        .state
+        .stack
+        .push(Value::string(trim_whitespace(&s, false, true)));
+    Ok(())
+}
+
+// String.prototype.padStart() if 'at_start' is true, otherwise String.prototype.padEnd().
+unsafe fn string_pad(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+    at_start: bool,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, if at_start { "padStart" } else { "padEnd" })?;
+    let max_len = to_integer(&get_arg(&args, 0))?;
+    let filler = match get_arg(&args, 1).val {
+        ValueBase::Undefined => JsString::from(" "),
+        ref filler => filler.to_js_string(),
+    };
+
+    if max_len <= s.len() as f64 || filler.len() == 0 {
+        self_.state.stack.push(Value::string(s));
+        return Ok(());
+    }
+
+    gc::grow((max_len as usize).saturating_mul(mem::size_of::<u16>()))?;
+    let fill_len = max_len as usize - s.len();
+    let padding = JsString::from_units(
+        filler
+            .units()
+            .iter()
+            .cycle()
+            .take(fill_len)
+            .cloned()
+            .collect(),
+    );
+    let padded = if at_start {
+        padding.concat(&s)
+    } else {
+        s.concat(&padding)
+    };
+    self_.state.stack.push(Value::string(padded));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_pad_start(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    string_pad(callobj, args, self_, true)
+}
+
+pub unsafe fn string_prototype_pad_end(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    string_pad(callobj, args, self_, false)
+}
+
+pub unsafe fn string_prototype_repeat(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "repeat")?;
+    let count = to_integer(&get_arg(&args, 0))?;
+    if count < 0.0 || count.is_infinite() {
+        return Err(RuntimeError::Range(format!(
+            "range error: invalid count value: {}",
+            Value::number(count).to_string()
+        )));
+    }
+    let len = s.len().saturating_mul(count as usize);
+    gc::grow(len.saturating_mul(mem::size_of::<u16>()))?;
+    let mut repeated = Vec::with_capacity(len);
+    for _ in 0..count as usize {
+        repeated.extend_from_slice(s.units());
+    }
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from_units(repeated)));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_starts_with(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "startsWith")?;
+    let search = get_arg(&args, 0).to_js_string();
+    let start = to_clamped_index(&get_arg(&args, 1), s.len(), 0)?;
+    self_.state.stack.push(Value::bool(
+        s.substring(start, s.len()).starts_with(&search),
+    ));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_ends_with(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "endsWith")?;
+    let search = get_arg(&args, 0).to_js_string();
+    let end = to_clamped_index(&get_arg(&args, 1), s.len(), s.len())?;
+    self_
+        .state
+        .stack
+        .push(Value::bool(s.substring(0, end).ends_with(&search)));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_includes(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = this_string(&callobj, "includes")?;
+    let search = get_arg(&args, 0).to_js_string();
+    let start = to_clamped_index(&get_arg(&args, 1), s.len(), 0)?;
+    self_
+        .state
+        .stack
+        .push(Value::bool(s.find(&search, start).is_some()));
+    Ok(())
+}
+
+pub unsafe fn string_prototype_concat(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let mut s = this_string(&callobj, "concat")?;
+    for arg in &args {
+        let arg = arg.to_js_string();
+        gc::grow((s.len() + arg.len()) * mem::size_of::<u16>())?;
+        s = s.concat(&arg);
+    }
+    self_.state.stack.push(Value::string(s));
+    Ok(())
+}
+
+// String.prototype.toString() and String.prototype.valueOf()
+pub unsafe fn string_prototype_to_string(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    match callobj.this.val {
+        ValueBase::String(_) => {
+            self_.state.stack.push(*callobj.this);
+            Ok(())
+        }
+        _ => Err(RuntimeError::Type(
+            "type error: String.prototype.toString requires that 'this' be a String".to_string(),
+        )),
+    }
+}
+
+fn this_number(callobj: &CallObject, func_name: &str) -> Result<f64, RuntimeError> {
+    match callobj.this.val {
+        ValueBase::Number(n) => Ok(n),
+        _ => Err(RuntimeError::Type(format!(
+            "type error: Number.prototype.{} requires that 'this' be a Number",
+            func_name
+        ))),
+    }
+}
+
+// Rounds the digits up if 'next', the first digit dropped, is 5 or more. Returns true if the
+// carry overflows the first digit, e.g. "99" becomes "00".
+fn round_digits(digits: &mut [u8], next: u8) -> bool {
+    if next < b'5' {
+        return false;
+    }
+    for digit in digits.iter_mut().rev() {
+        if *digit == b'9' {
+            *digit = b'0';
+        } else {
+            *digit += 1;
+            return false;
+        }
+    }
+    true
+}
+
+// Returns 'precision' significant digits of 'n' (>= 0) and the exponent 'e' such that 'n' is
+// about d.ddd * 10^e. Halfway cases are rounded up, as toPrecision() and toExponential() do.
+fn round_to_precision(n: f64, precision: usize) -> (String, i32) {
+    // The exact decimal expansion of a double has at most 767 significant digits.
+    let repr = format!("{:.1100e}", n);
+    let (mantissa, exp) = repr.split_at(repr.find('e').unwrap());
+    let mut e = exp[1..].parse::<i32>().unwrap();
+    let mut digits = mantissa.replace(".", "").into_bytes();
+    let next = digits[precision];
+    digits.truncate(precision);
+    if round_digits(&mut digits, next) {
+        digits.insert(0, b'1');
+        digits.pop();
+        e += 1;
+    }
+    (String::from_utf8(digits).unwrap(), e)
+}
+
+// Formats digits 'd.ddd' and exponent 'e' as 'd.ddde+e'.
+fn exponential_notation(digits: &str, e: i32) -> String {
+    format!(
+        "{}{}{}e{}{}",
+        &digits[..1],
+        if digits.len() > 1 { "." } else { "" },
+        &digits[1..],
+        if e < 0 { "-" } else { "+" },
+        e.abs()
+    )
+}
+
+// Returns the string for NaN and infinities, which every Number.prototype.toXxx() handles alike.
+fn non_finite_to_string(n: f64) -> Option<String> {
+    if n.is_finite() {
+        None
+    } else {
+        Some(number_to_string(n))
+    }
+}
+
+// Number.prototype.toString() with a radix other than 10. Digits are generated until they
+// identify the number uniquely.
+fn number_to_radix_string(n: f64, radix: u32) -> String {
+    let to_char = |digit: u32| ::std::char::from_digit(digit, radix).unwrap();
+    let negative = n < 0.0;
+    let n = n.abs();
+
+    let mut integer = n.floor();
+    let mut fraction = n - integer;
+    // Half the distance to the next double: digits below this are not significant.
+    let next_double = f64::from_bits(n.to_bits() + 1);
+    let mut delta = (0.5 * (next_double - n)).max(f64::from_bits(1));
+    let mut fraction_digits = vec![];
+    if fraction >= delta {
+        loop {
+            fraction *= radix as f64;
+            delta *= radix as f64;
+            let digit = fraction as u32;
+            fraction_digits.push(digit);
+            fraction -= digit as f64;
+            if fraction > 0.5 || (fraction == 0.5 && digit & 1 == 1) {
+                if fraction + delta > 1.0 {
+                    // Round up, propagating the carry.
+                    loop {
+                        match fraction_digits.pop() {
+                            None => {
+                                integer += 1.0;
+                                break;
+                            }
+                            Some(digit) if digit + 1 < radix => {
+                                fraction_digits.push(digit + 1);
+                                break;
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    break;
+                }
+            }
+            if fraction < delta {
+                break;
+            }
+        }
+    }
+
+    // Digits beyond the precision of a double are filled with zeros.
+    let mut integer_digits = vec![];
+    while integer / radix as f64 >= 9007199254740992.0 {
+        integer /= radix as f64;
+        integer_digits.push('0');
+    }
+    loop {
+        let remainder = integer % radix as f64;
+        integer_digits.push(to_char(remainder as u32));
+        integer = (integer - remainder) / radix as f64;
+        if integer <= 0.0 {
+            break;
+        }
+    }
+
+    let mut s = if negative {
+        "-".to_string()
+    } else {
+        "".to_string()
+    };
+    s.extend(integer_digits.iter().rev());
+    if fraction_digits.len() > 0 {
+        s.push('.');
+        s.extend(fraction_digits.iter().map(|d| to_char(*d)));
+    }
+    s
+}
+
+pub unsafe fn number_new(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    // TODO: 'new Number()' should create a Number object wrapping the primitive.
+    // Unlike ToNumber(), Number() converts BigInts.
+    let n = match args.get(0) {
+        None => 0.0,
+        Some(Value {
+            val: ValueBase::BigInt(ref n),
+            ..
+        }) => n.to_f64(),
+        Some(arg) => arg.val.to_number(),
+    };
+    self_.state.stack.push(Value::number(n));
+    Ok(())
+}
+
+pub unsafe fn number_is_integer(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    self_
+        .state
+        .stack
+        .push(Value::bool(match get_arg(&args, 0).val {
+            ValueBase::Number(n) => n.is_finite() && n.trunc() == n,
+            _ => false,
+        }));
+    Ok(())
+}
+
+pub unsafe fn number_is_safe_integer(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    self_
+        .state
+        .stack
+        .push(Value::bool(match get_arg(&args, 0).val {
+            ValueBase::Number(n) => n.trunc() == n && n.abs() <= 9007199254740991.0,
+            _ => false,
+        }));
+    Ok(())
+}
+
+// Unlike the global isFinite(), the argument is not converted to a number.
+pub unsafe fn number_is_finite(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    self_
+        .state
+        .stack
+        .push(Value::bool(match get_arg(&args, 0).val {
+            ValueBase::Number(n) => n.is_finite(),
+            _ => false,
+        }));
+    Ok(())
+}
+
+// Unlike the global isNaN(), the argument is not converted to a number.
+pub unsafe fn number_is_nan(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    self_
+        .state
+        .stack
+        .push(Value::bool(match get_arg(&args, 0).val {
+            ValueBase::Number(n) => n.is_nan(),
+            _ => false,
+        }));
+    Ok(())
+}
+
+pub unsafe fn number_prototype_to_string(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let n = this_number(&callobj, "toString")?;
+    let radix = match get_arg(&args, 0).val {
+        ValueBase::Undefined => 10.0,
+        _ => to_integer(&get_arg(&args, 0))?,
+    };
+    if radix < 2.0 || radix > 36.0 {
+        return Err(RuntimeError::Range(
+            "range error: toString() radix must be between 2 and 36".to_string(),
+        ));
+    }
+    let s = if radix == 10.0 {
+        number_to_string(n)
+    } else {
+        non_finite_to_string(n).unwrap_or_else(|| number_to_radix_string(n, radix as u32))
+    };
+    self_.state.stack.push(Value::string(JsString::from(s)));
+    Ok(())
+}
+
+pub unsafe fn number_prototype_to_fixed(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let n = this_number(&callobj, "toFixed")?;
+    let fraction_digits = to_integer(&get_arg(&args, 0))?;
+    if fraction_digits < 0.0 || fraction_digits > 100.0 {
+        return Err(RuntimeError::Range(
+            "range error: toFixed() digits argument must be between 0 and 100".to_string(),
+        ));
+    }
+    let fraction_digits = fraction_digits as usize;
+
+    let s = if !n.is_finite() || n.abs() >= 1e21 {
+        number_to_string(n)
+    } else {
+        // The exact decimal expansion of a double has at most 1074 fractional digits.
+        let repr = format!("{:.1100}", n.abs());
+        let point = repr.find('.').unwrap();
+        let mut digits = repr.replace(".", "").into_bytes();
+        let next = digits[point + fraction_digits];
+        digits.truncate(point + fraction_digits);
+        if round_digits(&mut digits, next) {
+            digits.insert(0, b'1');
+        }
+        let int_len = digits.len() - fraction_digits;
+        let digits = String::from_utf8(digits).unwrap();
+        format!(
+            "{}{}{}{}",
+            if n < 0.0 { "-" } else { "" },
+            &digits[..int_len],
+            if fraction_digits > 0 { "." } else { "" },
+            &digits[int_len..]
+        )
+    };
+    self_.state.stack.push(Value::string(JsString::from(s)));
+    Ok(())
+}
+
+pub unsafe fn number_prototype_to_precision(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let n = this_number(&callobj, "toPrecision")?;
+    if get_arg(&args, 0).val == ValueBase::Undefined {
+        self_
+            .state
+            .stack
+            .push(Value::string(JsString::from(number_to_string(n))));
+        return Ok(());
+    }
+    let precision = to_integer(&get_arg(&args, 0))?;
+    if let Some(s) = non_finite_to_string(n) {
+        self_.state.stack.push(Value::string(JsString::from(s)));
+        return Ok(());
+    }
+    if precision < 1.0 || precision > 100.0 {
+        return Err(RuntimeError::Range(
+            "range error: toPrecision() argument must be between 1 and 100".to_string(),
+        ));
+    }
+    let precision = precision as i32;
+
+    let (digits, e) = round_to_precision(n.abs(), precision as usize);
+    let s = if e < -6 || e >= precision {
+        exponential_notation(digits.as_str(), e)
+    } else if e == precision - 1 {
+        digits
+    } else if e >= 0 {
+        format!(
+            "{}.{}",
+            &digits[..e as usize + 1],
+            &digits[e as usize + 1..]
+        )
+    } else {
+        format!("0.{}{}", "0".repeat((-e - 1) as usize), digits)
+    };
+    self_.state.stack.push(Value::string(JsString::from(format!(
+        "{}{}",
+        if n < 0.0 { "-" } else { "" },
+        s
+    ))));
+    Ok(())
+}
+
+pub unsafe fn number_prototype_to_exponential(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let n = this_number(&callobj, "toExponential")?;
+    let fraction_digits = to_integer(&get_arg(&args, 0))?;
+    if let Some(s) = non_finite_to_string(n) {
+        self_.state.stack.push(Value::string(JsString::from(s)));
+        return Ok(());
+    }
+    if fraction_digits < 0.0 || fraction_digits > 100.0 {
+        return Err(RuntimeError::Range(
+            "range error: toExponential() argument must be between 0 and 100".to_string(),
+        ));
+    }
+
+    let (digits, e) = if n == 0.0 && get_arg(&args, 0).val == ValueBase::Undefined {
+        ("0".to_string(), 0)
+    } else if get_arg(&args, 0).val == ValueBase::Undefined {
+        shortest_digits(n.abs())
+    } else {
+        round_to_precision(n.abs(), fraction_digits as usize + 1)
+    };
+    self_.state.stack.push(Value::string(JsString::from(format!(
+        "{}{}",
+        if n < 0.0 { "-" } else { "" },
+        exponential_notation(digits.as_str(), e)
+    ))));
+    Ok(())
+}
+
+pub unsafe fn number_prototype_value_of(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let n = this_number(&callobj, "valueOf")?;
+    self_.state.stack.push(Value::number(n));
+    Ok(())
+}
+
+// https://tc39.github.io/proposal-bigint/#sec-tobigint
+fn to_bigint(val: &Value) -> Result<BigInt, RuntimeError> {
+    let n = match val.val {
+        ValueBase::BigInt(ref n) => Some(n.clone()),
+        ValueBase::Bool(b) => Some(BigInt::from_i64(b as i64)),
+        ValueBase::String(ref s) => {
+            let n = BigInt::from_string(s.to_string().trim_matches(is_js_whitespace));
+            if n.is_none() {
+                return Err(RuntimeError::Syntax(format!(
+                    "syntax error: cannot convert {} to a BigInt",
+                    s.to_string()
+                )));
+            }
+            n
+        }
+        _ => None,
+    };
+    n.ok_or_else(|| {
+        RuntimeError::Type(format!(
+            "type error: cannot convert {} to a BigInt",
+            val.to_string()
+        ))
+    })
+}
+
+fn this_bigint(callobj: &CallObject, func_name: &str) -> Result<BigInt, RuntimeError> {
+    match callobj.this.val {
+        ValueBase::BigInt(ref n) => Ok(n.clone()),
+        _ => Err(RuntimeError::Type(format!(
+            "type error: BigInt.prototype.{} requires that 'this' be a BigInt",
+            func_name
+        ))),
+    }
+}
+
+// https://tc39.github.io/proposal-bigint/#sec-bigint-constructor-number-value
+pub unsafe fn bigint(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    // 'new BigInt()' gives a new object inheriting BigInt.prototype as 'this'.
+    if let ValueBase::Object(obj) = callobj.this.val {
+        if let Some(Value {
+            val: ValueBase::Object(proto),
+            ..
+        }) = (*obj).get("__proto__")
+        {
+            if *proto == BIGINT_PROTOTYPE.with(|x| *x) {
+                return Err(RuntimeError::Type(
+                    "type error: BigInt is not a constructor".to_string(),
+                ));
+            }
+        }
+    }
+
+    let val = get_arg(&args, 0);
+    let n = match val.val {
+        ValueBase::Number(n) => BigInt::from_f64(n).ok_or_else(|| {
+            RuntimeError::Range(format!(
+                "range error: the number {} cannot be converted to a BigInt because it is not an integer",
+                number_to_string(n)
+            ))
+        })?,
+        _ => to_bigint(&val)?,
+    };
+    self_.state.stack.push(Value::bigint(n));
+    Ok(())
+}
+
+// https://tc39.github.io/proposal-bigint/#sec-bigint.asintn
+pub unsafe fn bigint_as_int_n(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let bits = to_bit_count(&get_arg(&args, 0))?;
+    let n = to_bigint(&get_arg(&args, 1))?;
+    self_.state.stack.push(Value::bigint(n.as_int_n(bits)));
+    Ok(())
+}
+
+// https://tc39.github.io/proposal-bigint/#sec-bigint.asuintn
+pub unsafe fn bigint_as_uint_n(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let bits = to_bit_count(&get_arg(&args, 0))?;
+    let n = to_bigint(&get_arg(&args, 1))?
+        .as_uint_n(bits)
+        .map_err(|msg| RuntimeError::Range(format!("range error: {}", msg)))?;
+    self_.state.stack.push(Value::bigint(n));
+    Ok(())
+}
+
+// The number of bits given to BigInt.asIntN() and BigInt.asUintN(), converted by ToIndex().
+fn to_bit_count(val: &Value) -> Result<u64, RuntimeError> {
+    let n = to_integer(val)?;
+    if n < 0.0 || n > 9007199254740991.0 {
+        return Err(RuntimeError::Range(
+            "range error: invalid value: not (convertible to) a safe integer".to_string(),
+        ));
+    }
+    Ok(n as u64)
+}
+
+pub unsafe fn bigint_prototype_to_string(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let n = this_bigint(&callobj, "toString")?;
+    let radix = match get_arg(&args, 0).val {
+        ValueBase::Undefined => 10.0,
+        _ => to_integer(&get_arg(&args, 0))?,
+    };
+    if radix < 2.0 || radix > 36.0 {
+        return Err(RuntimeError::Range(
+            "range error: toString() radix must be between 2 and 36".to_string(),
+        ));
+    }
+    self_.state.stack.push(Value::string(JsString::from(
+        n.to_string_radix(radix as u32),
+    )));
+    Ok(())
+}
+
+pub unsafe fn bigint_prototype_value_of(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let n = this_bigint(&callobj, "valueOf")?;
+    self_.state.stack.push(Value::bigint(n));
+    Ok(())
+}
+
+// https://www.ecma-international.org/ecma-262/9.0/index.html#sec-parseint-string-radix
+pub unsafe fn parse_int(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = get_arg(&args, 0).to_string();
+    let mut s = s.trim_start_matches(is_js_whitespace);
+    let sign = if s.starts_with('-') { -1.0 } else { 1.0 };
+    if s.starts_with('-') || s.starts_with('+') {
+        s = &s[1..];
+    }
+
+    let mut radix = get_arg(&args, 1).val.to_uint32() as u32 as i32;
+    let mut strip_prefix = true;
+    if radix != 0 {
+        if radix < 2 || radix > 36 {
+            self_.state.stack.push(Value::number(::std::f64::NAN));
+            return Ok(());
+        }
+        strip_prefix = radix == 16;
+    } else {
+        radix = 10;
+    }
+    if strip_prefix && (s.starts_with("0x") || s.starts_with("0X")) {
+        s = &s[2..];
+        radix = 16;
+    }
+
+    let radix = radix as u32;
+    let digits = &s[..s.find(|c: char| !c.is_digit(radix)).unwrap_or(s.len())];
+    let n = if digits.len() == 0 {
+        ::std::f64::NAN
+    } else if radix == 10 {
+        // Let Rust round long decimal numbers correctly.
+        digits.parse::<f64>().unwrap()
+    } else {
+        digits.chars().fold(0.0, |n, c| {
+            n * radix as f64 + c.to_digit(radix).unwrap() as f64
+        })
+    };
+    self_.state.stack.push(Value::number(sign * n));
+    Ok(())
+}
+
+// https://www.ecma-international.org/ecma-262/9.0/index.html#sec-parsefloat-string
+pub unsafe fn parse_float(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = get_arg(&args, 0).to_string();
+    let n = parse_decimal_prefix(s.trim_start_matches(is_js_whitespace))
+        .map_or(::std::f64::NAN, |(n, _)| n);
+    self_.state.stack.push(Value::number(n));
+    Ok(())
+}
+
+pub unsafe fn is_nan(_: CallObject, args: Vec<Value>, self_: &mut VM) -> Result<(), RuntimeError> {
+    let n = get_arg(&args, 0).val.try_to_number()?;
+    self_.state.stack.push(Value::bool(n.is_nan()));
+    Ok(())
+}
+
+pub unsafe fn is_finite(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let n = get_arg(&args, 0).val.try_to_number()?;
+    self_.state.stack.push(Value::bool(n.is_finite()));
+    Ok(())
+}
+
+// https://www.ecma-international.org/ecma-262/9.0/index.html#sec-json-object
+struct JsonParser<'a> {
+    text: &'a [u16],
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self) -> RuntimeError {
+        RuntimeError::Syntax(match self.text.get(self.pos) {
+            Some(unit) => format!(
+                "syntax error: Unexpected token {} in JSON at position {}",
+                String::from_utf16_lossy(&[*unit]),
+                self.pos
+            ),
+            None => "syntax error: Unexpected end of JSON input".to_string(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text
+            .get(self.pos)
+            .and_then(|unit| ::std::char::from_u32(*unit as u32))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), RuntimeError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, RuntimeError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Value::string(self.parse_string()?)),
+            Some('-') | Some('0'...'9') => self.parse_number(),
+            Some('t') => self.parse_literal("true", Value::bool(true)),
+            Some('f') => self.parse_literal("false", Value::bool(false)),
+            Some('n') => self.parse_literal("null", Value::null()),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn parse_literal(&mut self, word: &str, val: Value) -> Result<Value, RuntimeError> {
+        for c in word.chars() {
+            self.expect(c)?;
+        }
+        Ok(val)
+    }
+
+    fn parse_number(&mut self) -> Result<Value, RuntimeError> {
+        let start = self.pos;
+        let mut skip_digits = |parser: &mut JsonParser| -> Result<(), RuntimeError> {
+            match parser.peek() {
+                Some('0'...'9') => {}
+                _ => return Err(parser.error()),
+            }
+            while let Some('0'...'9') = parser.peek() {
+                parser.pos += 1;
+            }
+            Ok(())
+        };
+
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some('0') {
+            self.pos += 1;
+        } else {
+            skip_digits(self)?;
+        }
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            skip_digits(self)?;
+        }
+        if let Some('e') | Some('E') = self.peek() {
+            self.pos += 1;
+            if let Some('+') | Some('-') = self.peek() {
+                self.pos += 1;
+            }
+            skip_digits(self)?;
+        }
+
+        let literal = String::from_utf16_lossy(&self.text[start..self.pos]);
+        Ok(Value::number(literal.parse::<f64>().unwrap()))
+    }
+
+    fn parse_string(&mut self) -> Result<JsString, RuntimeError> {
+        self.expect('"')?;
+        let mut units = vec![];
+        loop {
+            let unit = match self.text.get(self.pos) {
+                Some(unit) if *unit >= 0x20 => *unit,
+                _ => return Err(self.error()),
+            };
+            self.pos += 1;
+            match unit {
+                0x22 /* " */ => return Ok(JsString::from_units(units)),
+                0x5c /* \ */ => {
+                    let escaped = match self.peek() {
+                        Some('"') => '"' as u16,
+                        Some('\\') => '\\' as u16,
+                        Some('/') => '/' as u16,
+                        Some('b') => 0x08,
+                        Some('f') => 0x0c,
+                        Some('n') => '\n' as u16,
+                        Some('r') => '\r' as u16,
+                        Some('t') => '\t' as u16,
+                        Some('u') => {
+                            let mut code = 0;
+                            for _ in 0..4 {
+                                self.pos += 1;
+                                match self.peek().and_then(|c| c.to_digit(16)) {
+                                    Some(digit) => code = code * 16 + digit as u16,
+                                    None => return Err(self.error()),
+                                }
+                            }
+                            code
+                        }
+                        _ => return Err(self.error()),
+                    };
+                    self.pos += 1;
+                    units.push(escaped);
+                }
+                unit => units.push(unit),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value, RuntimeError> {
+        self.expect('[')?;
+        let mut elems = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+        } else {
+            loop {
+                elems.push(self.parse_value()?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    Some(']') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.error()),
+                }
+            }
+        }
+        Ok(Value::array(gc::new(ArrayValue::new(elems))))
+    }
+
+    fn parse_object(&mut self) -> Result<Value, RuntimeError> {
+        self.expect('{')?;
+        let mut map = PropertyMap::default();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let key = self.parse_string()?;
+                self.skip_whitespace();
+                self.expect(':')?;
+                let val = self.parse_value()?;
+                map.insert(key.to_key(), val);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    Some('}') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.error()),
+                }
+            }
+        }
+        Ok(Value::plain_object(map))
+    }
+}
+
+// https://www.ecma-international.org/ecma-262/9.0/index.html#sec-internalizejsonproperty
+unsafe fn internalize_json_property(
+    self_: &mut VM,
+    reviver: &Value,
+    holder: &Value,
+    name: String,
+) -> Result<Value, RuntimeError> {
+    let val = Value::new(
+        holder
+            .get_property(ValueBase::String(JsString::from_key(&name)), None)
+            .val,
+    );
+
+    let keys = match val.val {
+        ValueBase::Array(ary) => (0..(*ary).length).map(|i| i.to_string()).collect(),
+        ValueBase::Object(_) => enumerable_own_properties(&val)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect(),
+        _ => vec![],
+    };
+    for key in keys {
+        let new_elem = internalize_json_property(self_, reviver, &val, key.clone())?;
+        let property = ValueBase::String(JsString::from_key(&key));
+        if new_elem.val == ValueBase::Undefined {
+            val.delete_property(property, false)?;
+        } else {
+            val.set_property(property, new_elem, None, false)?;
+        }
+    }
+
+    let args = vec![Value::string(JsString::from_key(&name)), val];
+    call_callback(self_, reviver, holder.clone(), &args)
+}
+
+pub unsafe fn json_parse(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let text = get_arg(&args, 0).to_js_string();
+    let mut parser = JsonParser {
+        text: text.units(),
+        pos: 0,
+    };
+    let val = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error());
+    }
+
+    let reviver = get_arg(&args, 1);
+    if !is_callable(&reviver) {
+        self_.state.stack.push(val);
+        return Ok(());
+    }
+
+    // Keep the parsed value reachable from the GC while the reviver runs.
+    let sp = self_.state.stack.len();
+    let root = Value::plain_object({
+        let mut map = PropertyMap::default();
+        map.insert("".to_string(), val);
+        map
+    });
+    self_.state.stack.push(root.clone());
+    self_.state.stack.push(reviver.clone());
+
+    let val = internalize_json_property(self_, &reviver, &root, "".to_string())?;
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(val);
+    Ok(())
+}
+
+struct JsonStringifier {
+    replacer: Option<Value>,
+    property_list: Option<Vec<String>>,
+    gap: Vec<u16>,
+    indent: Vec<u16>,
+    // Objects being serialized, to detect cycles
+    stack: Vec<usize>,
+}
+
+// https://www.ecma-international.org/ecma-262/9.0/index.html#sec-quotejsonstring
+fn quote_json_string(s: &JsString, out: &mut Vec<u16>) {
+    out.push('"' as u16);
+    let units = s.units();
+    for (i, &unit) in units.iter().enumerate() {
+        let escaped = match unit {
+            0x08 => "\\b".to_string(),
+            0x09 => "\\t".to_string(),
+            0x0a => "\\n".to_string(),
+            0x0c => "\\f".to_string(),
+            0x0d => "\\r".to_string(),
+            0x22 => "\\\"".to_string(),
+            0x5c => "\\\\".to_string(),
+            0...0x1f => format!("\\u{:04x}", unit),
+            // Unpaired surrogates
+            0xd800...0xdbff
+                if !units
+                    .get(i + 1)
+                    .map_or(false, |u| 0xdc00 <= *u && *u <= 0xdfff) =>
+            {
+                format!("\\u{:04x}", unit)
+            }
+            0xdc00...0xdfff if i == 0 || !(0xd800 <= units[i - 1] && units[i - 1] <= 0xdbff) => {
+                format!("\\u{:04x}", unit)
+            }
+            _ => {
+                out.push(unit);
+                continue;
+            }
+        };
+        out.extend(escaped.encode_utf16());
+    }
+    out.push('"' as u16);
+}
+
+impl JsonStringifier {
+    // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-serializejsonproperty
+    // Returns None if the value is not serializable, like undefined and functions.
+    unsafe fn serialize_property(
+        &mut self,
+        self_: &mut VM,
+        key: &str,
+        holder: &Value,
+    ) -> Result<Option<Vec<u16>>, RuntimeError> {
+        let property = ValueBase::String(JsString::from_key(key));
+        let mut val = Value::new(proxy::get_property(self_, holder, property, holder)?.val);
+
+        if let ValueBase::Object(_) | ValueBase::Array(_) = val.val {
+            let to_json = val.get_property(ValueBase::String(JsString::from("toJSON")), None);
+            if is_callable(&to_json) {
+                let args = vec![Value::string(JsString::from_key(key))];
+                val = call_callback(self_, &to_json, val.clone(), &args)?;
+                // Keep the new value reachable from the GC.
+                self_.state.stack.push(val.clone());
+            }
+        }
+        if let Some(replacer) = self.replacer.clone() {
+            let args = vec![Value::string(JsString::from_key(key)), val];
+            val = call_callback(self_, &replacer, holder.clone(), &args)?;
+            self_.state.stack.push(val.clone());
+        }
+
+        Ok(match val.val {
+            ValueBase::Null => Some("null".encode_utf16().collect()),
+            ValueBase::Bool(b) => Some(b.to_string().encode_utf16().collect()),
+            ValueBase::String(ref s) => {
+                let mut out = vec![];
+                quote_json_string(s, &mut out);
+                Some(out)
+            }
+            ValueBase::Number(n) if n.is_finite() => {
+                Some(number_to_string(n).encode_utf16().collect())
+            }
+            ValueBase::Number(_) => Some("null".encode_utf16().collect()),
+            ValueBase::BigInt(_) => {
+                return Err(RuntimeError::Type(
+                    "type error: do not know how to serialize a BigInt".to_string(),
+                ))
+            }
+            ValueBase::Object(obj) => Some(self.serialize_object(self_, &val, obj as usize)?),
+            ValueBase::Array(ary) => Some(self.serialize_array(self_, &val, ary as usize)?),
+            ValueBase::Proxy(p) if is_array(&val) => {
+                Some(self.serialize_array(self_, &val, p as usize)?)
+            }
+            ValueBase::Proxy(p) if !is_callable(&val) => {
+                Some(self.serialize_object(self_, &val, p as usize)?)
+            }
+            _ => None,
+        })
+    }
+
+    // Joins the serialized members of an object or array, indenting them if 'gap' is given.
+    fn join_members(
+        &self,
+        members: Vec<Vec<u16>>,
+        open: char,
+        close: char,
+        stepback: &[u16],
+    ) -> Vec<u16> {
+        let mut out = vec![open as u16];
+        if members.len() > 0 {
+            for (i, member) in members.iter().enumerate() {
+                if i > 0 {
+                    out.push(',' as u16);
+                }
+                if self.gap.len() > 0 {
+                    out.push('\n' as u16);
+                    out.extend_from_slice(&self.indent);
+                }
+                out.extend_from_slice(member);
+            }
+            if self.gap.len() > 0 {
+                out.push('\n' as u16);
+                out.extend_from_slice(stepback);
+            }
+        }
+        out.push(close as u16);
+        out
+    }
+
+    fn enter(&mut self, ptr: usize) -> Result<Vec<u16>, RuntimeError> {
+        if self.stack.contains(&ptr) {
+            return Err(RuntimeError::Type(
+                "type error: Converting circular structure to JSON".to_string(),
+            ));
+        }
+        self.stack.push(ptr);
+        let stepback = self.indent.clone();
+        self.indent.extend_from_slice(&self.gap.clone());
+        Ok(stepback)
+    }
+
+    fn leave(&mut self, stepback: Vec<u16>) {
+        self.stack.pop();
+        self.indent = stepback;
+    }
+
+    // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-serializejsonobject
+    unsafe fn serialize_object(
+        &mut self,
+        self_: &mut VM,
+        obj: &Value,
+        ptr: usize,
+    ) -> Result<Vec<u16>, RuntimeError> {
+        let stepback = self.enter(ptr)?;
+        let keys = match self.property_list {
+            Some(ref list) => list.clone(),
+            None => enumerable_own_keys(self_, obj)?,
+        };
+
+        let mut members = vec![];
+        for key in keys {
+            if let Some(val) = self.serialize_property(self_, key.as_str(), obj)? {
+                let mut member = vec![];
+                quote_json_string(&JsString::from_key(&key), &mut member);
+                member.push(':' as u16);
+                if self.gap.len() > 0 {
+                    member.push(' ' as u16);
+                }
+                member.extend(val);
+                members.push(member);
+            }
+        }
+
+        let out = self.join_members(members, '{', '}', &stepback);
+        self.leave(stepback);
+        Ok(out)
+    }
+
+    // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-serializejsonarray
+    unsafe fn serialize_array(
+        &mut self,
+        self_: &mut VM,
+        ary: &Value,
+        ptr: usize,
+    ) -> Result<Vec<u16>, RuntimeError> {
+        let stepback = self.enter(ptr)?;
+        let len = match ary.val {
+            ValueBase::Array(ary) => (*ary).length,
+            _ => {
+                let length = ValueBase::String(JsString::from("length"));
+                proxy::get_property(self_, ary, length, ary)?
+                    .val
+                    .to_uint32() as usize
+            }
+        };
+
+        let mut members = vec![];
+        for i in 0..len {
+            // Holes, undefined and functions become null.
+            members.push(
+                self.serialize_property(self_, i.to_string().as_str(), ary)?
+                    .unwrap_or_else(|| "null".encode_utf16().collect()),
+            );
+        }
+
+        let out = self.join_members(members, '[', ']', &stepback);
+        self.leave(stepback);
+        Ok(out)
+    }
+}
+
+pub unsafe fn json_stringify(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let replacer = get_arg(&args, 1);
+    let property_list = match replacer.val {
+        ValueBase::Array(ary) => {
+            let mut list: Vec<String> = vec![];
+            for i in 0..(*ary).length {
+                let key = match array_elem(ary, i).map(|val| val.val) {
+                    Some(ValueBase::String(s)) => s.to_key(),
+                    Some(ValueBase::Number(n)) => number_to_string(n),
+                    _ => continue,
+                };
+                if !list.contains(&key) {
+                    list.push(key);
+                }
+            }
+            Some(list)
+        }
+        _ => None,
+    };
+    let gap = match get_arg(&args, 2).val {
+        ValueBase::Number(n) => vec![' ' as u16; n.min(10.0).max(0.0) as usize],
+        ValueBase::String(ref s) => s.substring(0, s.len().min(10)).units().to_vec(),
+        _ => vec![],
+    };
+
+    let mut stringifier = JsonStringifier {
+        replacer: if is_callable(&replacer) {
+            Some(replacer.clone())
+        } else {
+            None
+        },
+        property_list: property_list,
+        gap: gap,
+        indent: vec![],
+        stack: vec![],
+    };
+
+    // Keep the value reachable from the GC while toJSON() and the replacer run.
+    let sp = self_.state.stack.len();
+    let wrapper = Value::plain_object({
+        let mut map = PropertyMap::default();
+        map.insert("".to_string(), get_arg(&args, 0));
+        map
+    });
+    self_.state.stack.push(wrapper.clone());
+    self_.state.stack.push(replacer);
+
+    let result = stringifier.serialize_property(self_, "", &wrapper)?;
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(match result {
+        Some(units) => Value::string(JsString::from_units(units)),
+        None => Value::undefined(),
+    });
+    Ok(())
+}
+
+// Creates an instance of the error constructor 'kind' (one of ERROR_NEW to URI_ERROR_NEW) whose
+// 'stack' shows where the VM is running now.
+pub fn create_error_object(self_: &VM, kind: usize, message: Option<JsString>) -> Value {
+    let i = kind - ERROR_NEW;
+    let prototype = ERROR_OBJS
+        .with(|x| x[i].get_property(ValueBase::String(JsString::from("prototype")), None));
+
+    let mut header = ERROR_NAMES[i].to_string();
+    let mut map = PropertyMap::default();
+    map.insert("__proto__".to_string(), prototype);
+    if let Some(message) = message {
+        if !message.is_empty() {
+            header = format!("{}: {}", header, message);
+        }
+        map.insert(
+            "message".to_string(),
+            Value {
+                enumerable: false,
+                ..Value::string(message)
+            },
+        );
+    }
+    map.insert(
+        "stack".to_string(),
+        Value {
+            enumerable: false,
+            ..Value::string(JsString::from(format!(
+                "{}\n{}",
+                header,
+                self_.stack_trace()
+            )))
+        },
+    );
+    Value::object(gc::new(map))
+}
+
+unsafe fn error_new_with_kind(
+    kind: usize,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    // 'Error(msg)' without 'new' creates an error as well.
+    let message = match get_arg(&args, 0).val {
+        ValueBase::Undefined => None,
+        message => Some(message.to_js_string()),
+    };
+    let error = create_error_object(self_, kind, message);
+    self_.state.stack.push(error);
+    Ok(())
+}
+
+macro_rules! error_constructor {
+    ($name:ident, $kind:ident) => {
+        pub unsafe fn $name(
+            _: CallObject,
+            args: Vec<Value>,
+            self_: &mut VM,
+        ) -> Result<(), RuntimeError> {
+            error_new_with_kind($kind, args, self_)
+        }
+    };
+}
+
+error_constructor!(error_new, ERROR_NEW);
+error_constructor!(type_error_new, TYPE_ERROR_NEW);
+error_constructor!(reference_error_new, REFERENCE_ERROR_NEW);
+error_constructor!(range_error_new, RANGE_ERROR_NEW);
+error_constructor!(syntax_error_new, SYNTAX_ERROR_NEW);
+error_constructor!(eval_error_new, EVAL_ERROR_NEW);
+error_constructor!(uri_error_new, URI_ERROR_NEW);
+
+// https://tc39.github.io/ecma262/#sec-error.prototype.tostring
+pub unsafe fn error_prototype_to_string(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let this = &*callobj.this;
+    match this.val {
+        ValueBase::Object(_)
+        | ValueBase::Array(_)
+        | ValueBase::Function(_)
+        | ValueBase::BuiltinFunction(_) => {}
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: Error.prototype.toString requires that 'this' be an Object"
+                    .to_string(),
+            ))
+        }
+    }
+
+    let get_string = |key: &str, default: &str| match this
+        .get_property(ValueBase::String(JsString::from(key)), None)
+        .val
+    {
+        ValueBase::Undefined => JsString::from(default),
+        val => val.to_js_string(),
+    };
+    let name = get_string("name", "Error");
+    let msg = get_string("message", "");
+
+    self_.state.stack.push(Value::string(if name.is_empty() {
+        msg
+    } else if msg.is_empty() {
+        name
+    } else {
+        name.concat(&JsString::from(": ")).concat(&msg)
+    }));
+    Ok(())
+}
+
+// Hidden properties that hold the internal state of collections and their iterators.
+fn internal_slot(val: ValueBase) -> Value {
+    Value {
+        val: val,
+        writable: false,
+        enumerable: false,
+        configurable: false,
+    }
+}
+
+// Returns the values 'iterable' yields, or None if it's not iterable.
+// TODO: Support the iterator protocol (Symbol.iterator) when symbols are implemented.
+unsafe fn iterate_values(iterable: &Value) -> Option<Vec<Value>> {
+    match iterable.val {
+        ValueBase::Array(array) => Some(
+            (0..(*array).length)
+                .map(|i| array_elem(array, i).unwrap_or_else(|| Value::undefined()))
+                .collect(),
+        ),
+        ValueBase::String(ref s) => {
+            let mut values = vec![];
+            let mut i = 0;
+            while let Some(code_point) = s.code_point_at(i) {
+                let len = if code_point >= 0x10000 { 2 } else { 1 };
+                values.push(Value::string(s.substring(i, i + len)));
+                i += len;
+            }
+            Some(values)
+        }
+        ValueBase::Object(obj) => match (*obj).get(COLLECTION_DATA) {
+            Some(Value {
+                val: ValueBase::Collection(collection),
+                ..
+            }) if !(**collection).kind.is_weak() => {
+                let is_map = (**collection).kind == CollectionKind::Map;
+                Some(
+                    (**collection)
+                        .iter()
+                        .map(|(key, val)| {
+                            if is_map {
+                                Value::array(gc::new(ArrayValue::new(vec![
+                                    key.clone(),
+                                    val.clone(),
+                                ])))
+                            } else {
+                                key.clone()
+                            }
+                        })
+                        .collect(),
+                )
+            }
+            _ => match (*obj).get(TYPED_ARRAY_DATA) {
+                Some(Value {
+                    val: ValueBase::TypedArray(typed_array),
+                    ..
+                }) => Some(
+                    (**typed_array)
+                        .to_vec()
+                        .into_iter()
+                        .map(|n| Value::number(n))
+                        .collect(),
+                ),
+                _ => None,
+            },
+        },
+        _ => None,
+    }
+}
+
+fn check_weak_key(kind: CollectionKind, key: &Value) -> Result<(), RuntimeError> {
+    if kind.is_weak() && !key.is_object() {
+        return Err(RuntimeError::Type(format!(
+            "type error: invalid value used {}",
+            if kind == CollectionKind::WeakMap {
+                "as weak map key"
+            } else {
+                "in weak set"
+            }
+        )));
+    }
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-map-iterable
+unsafe fn collection_new(
+    kind: CollectionKind,
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let this = match callobj.this.val {
+        ValueBase::Object(obj) if !(*obj).contains_key(COLLECTION_DATA) => obj,
+        _ => {
+            return Err(RuntimeError::Type(format!(
+                "type error: constructor {} requires 'new'",
+                kind.name()
+            )))
+        }
+    };
+
+    let mut collection = Collection::new(kind);
+    let iterable = get_arg(&args, 0);
+    match iterable.val {
+        ValueBase::Undefined | ValueBase::Null => {}
+        _ => {
+            let values = match iterate_values(&iterable) {
+                Some(values) => values,
+                None => {
+                    return Err(RuntimeError::Type(format!(
+                        "type error: {} is not iterable",
+                        iterable.to_string()
+                    )))
+                }
+            };
+            for val in values {
+                let (key, val) = match kind {
+                    CollectionKind::Map | CollectionKind::WeakMap => {
+                        if !val.is_object() {
+                            return Err(RuntimeError::Type(format!(
+                                "type error: iterator value {} is not an entry object",
+                                val.to_string()
+                            )));
+                        }
+                        (
+                            val.get_property(ValueBase::Number(0.0), None),
+                            val.get_property(ValueBase::Number(1.0), None),
+                        )
+                    }
+                    CollectionKind::Set | CollectionKind::WeakSet => {
+                        let val = normalize_key(val);
+                        (val.clone(), val)
+                    }
+                };
+                check_weak_key(kind, &key)?;
+                collection.set(key, val);
+            }
+        }
+    }
+
+    (*this).insert(
+        COLLECTION_DATA.to_string(),
+        internal_slot(ValueBase::Collection(gc::new_collection(collection))),
+    );
+    self_.state.stack.push(Value::object(this));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+macro_rules! collection_constructor {
+    ($name:ident, $kind:expr) => {
+        pub unsafe fn $name(
+            callobj: CallObject,
+            args: Vec<Value>,
+            self_: &mut VM,
+        ) -> Result<(), RuntimeError> {
+            collection_new($kind, callobj, args, self_)
+        }
+    };
+}
+
+collection_constructor!(map_new, CollectionKind::Map);
+collection_constructor!(set_new, CollectionKind::Set);
+collection_constructor!(weak_map_new, CollectionKind::WeakMap);
+collection_constructor!(weak_set_new, CollectionKind::WeakSet);
+
+// Returns the storage of 'this' if it is one of the collections 'kinds'.
+unsafe fn this_collection(
+    callobj: &CallObject,
+    kinds: &[CollectionKind],
+    method: &str,
+) -> Result<*mut Collection, RuntimeError> {
+    if let ValueBase::Object(obj) = callobj.this.val {
+        if let Some(Value {
+            val: ValueBase::Collection(collection),
+            ..
+        }) = (*obj).get(COLLECTION_DATA)
+        {
+            if kinds.contains(&(**collection).kind) {
+                return Ok(*collection);
+            }
+        }
+    }
+    Err(RuntimeError::Type(format!(
+        "type error: method {}.prototype.{} called on incompatible receiver",
+        kinds[0].name(),
+        method
+    )))
+}
+
+pub unsafe fn collection_prototype_get(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let collection = this_collection(
+        &callobj,
+        &[CollectionKind::Map, CollectionKind::WeakMap],
+        "get",
+    )?;
+    self_.state.stack.push(
+        (*collection)
+            .get(&get_arg(&args, 0))
+            .cloned()
+            .unwrap_or_else(|| Value::undefined()),
+    );
+    Ok(())
+}
+
+pub unsafe fn collection_prototype_set(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let collection = this_collection(
+        &callobj,
+        &[CollectionKind::Map, CollectionKind::WeakMap],
+        "set",
+    )?;
+    let key = normalize_key(get_arg(&args, 0));
+    check_weak_key((*collection).kind, &key)?;
+    (*collection).set(key, get_arg(&args, 1));
+    self_.state.stack.push((*callobj.this).clone());
+    Ok(())
+}
+
+pub unsafe fn collection_prototype_add(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let collection = this_collection(
+        &callobj,
+        &[CollectionKind::Set, CollectionKind::WeakSet],
+        "add",
+    )?;
+    let val = normalize_key(get_arg(&args, 0));
+    check_weak_key((*collection).kind, &val)?;
+    (*collection).set(val.clone(), val);
+    self_.state.stack.push((*callobj.this).clone());
+    Ok(())
+}
+
+const ALL_COLLECTION_KINDS: &[CollectionKind] = &[
+    CollectionKind::Map,
+    CollectionKind::Set,
+    CollectionKind::WeakMap,
+    CollectionKind::WeakSet,
+];
+
+pub unsafe fn collection_prototype_has(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let collection = this_collection(&callobj, ALL_COLLECTION_KINDS, "has")?;
+    self_
+        .state
+        .stack
+        .push(Value::bool((*collection).has(&get_arg(&args, 0))));
+    Ok(())
+}
+
+pub unsafe fn collection_prototype_delete(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let collection = this_collection(&callobj, ALL_COLLECTION_KINDS, "delete")?;
+    self_
+        .state
+        .stack
+        .push(Value::bool((*collection).delete(&get_arg(&args, 0))));
+    Ok(())
+}
+
+pub unsafe fn collection_prototype_clear(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let collection = this_collection(
+        &callobj,
+        &[CollectionKind::Map, CollectionKind::Set],
+        "clear",
+    )?;
+    (*collection).clear();
+    self_.state.stack.push(Value::undefined());
+    Ok(())
+}
+
+// Entries added during the iteration are visited as well, and deleted ones are skipped.
+pub unsafe fn collection_prototype_for_each(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let collection = this_collection(
+        &callobj,
+        &[CollectionKind::Map, CollectionKind::Set],
+        "forEach",
+    )?;
+    let callback = get_arg(&args, 0);
+    let this_arg = get_arg(&args, 1);
+
+    let sp = self_.state.stack.len();
+    self_.state.stack.push((*callobj.this).clone());
+
+    let mut i = 0;
+    while let Some((n, (key, val))) = (*collection)
+        .next_entry(i)
+        .map(|(n, entry)| (n, entry.clone()))
+    {
+        let args = vec![val, key, (*callobj.this).clone()];
+        call_callback(self_, &callback, this_arg.clone(), &args)?;
+        i = n + 1;
+    }
+
+    self_.state.stack.truncate(sp);
+    self_.state.stack.push(Value::undefined());
+    Ok(())
+}
+
+// What a collection iterator yields.
+const ITERATE_KEYS: f64 = 0.0;
+const ITERATE_VALUES: f64 = 1.0;
+const ITERATE_ENTRIES: f64 = 2.0;
+
+// https://tc39.github.io/ecma262/#sec-createmapiterator
+unsafe fn create_collection_iterator(
+    callobj: CallObject,
+    method: &str,
+    kind: f64,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let collection = this_collection(
+        &callobj,
+        &[CollectionKind::Map, CollectionKind::Set],
+        method,
+    )?;
+    let mut map = PropertyMap::default();
+    map.insert(
+        "__proto__".to_string(),
+        Value::object(COLLECTION_ITERATOR_PROTOTYPE.with(|x| x.clone())),
+    );
+    map.insert(
+        ITERATED_COLLECTION.to_string(),
+        internal_slot(ValueBase::Collection(collection)),
+    );
+    map.insert(
+        ITERATOR_NEXT_INDEX.to_string(),
+        internal_slot(ValueBase::Number(0.0)),
+    );
+    map.insert(
+        ITERATOR_KIND.to_string(),
+        internal_slot(ValueBase::Number(kind)),
+    );
+    self_.state.stack.push(Value::object(gc::new(map)));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn collection_prototype_keys(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    create_collection_iterator(callobj, "keys", ITERATE_KEYS, self_)
+}
+
+pub unsafe fn collection_prototype_values(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    create_collection_iterator(callobj, "values", ITERATE_VALUES, self_)
+}
+
+pub unsafe fn collection_prototype_entries(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    create_collection_iterator(callobj, "entries", ITERATE_ENTRIES, self_)
+}
+
+// https://tc39.github.io/ecma262/#sec-%mapiteratorprototype%.next
+pub unsafe fn collection_iterator_next(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let iterator = match callobj.this.val {
+        ValueBase::Object(obj) if (*obj).contains_key(ITERATOR_KIND) => obj,
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: next method called on incompatible receiver".to_string(),
+            ))
+        }
+    };
+    let number_of = |key: &str| (*iterator).get(key).unwrap().val.to_number();
+
+    // The iterated collection is forgotten once the iterator is done, so that entries added
+    // later are never visited.
+    let entry = match (*iterator).get(ITERATED_COLLECTION) {
+        Some(Value {
+            val: ValueBase::Collection(collection),
+            ..
+        }) => (**collection)
+            .next_entry(number_of(ITERATOR_NEXT_INDEX) as usize)
+            .map(|(n, entry)| (n, entry.clone())),
+        _ => None,
+    };
+
+    let result = match entry {
+        Some((n, (key, val))) => {
+            (*iterator).insert(
+                ITERATOR_NEXT_INDEX.to_string(),
+                internal_slot(ValueBase::Number((n + 1) as f64)),
+            );
+            let kind = number_of(ITERATOR_KIND);
+            let value = if kind == ITERATE_KEYS {
+                key
+            } else if kind == ITERATE_VALUES {
+                val
+            } else {
+                Value::array(gc::new(ArrayValue::new(vec![key, val])))
+            };
+            (value, false)
+        }
+        None => {
+            (*iterator).remove(ITERATED_COLLECTION);
+            (Value::undefined(), true)
+        }
+    };
+
+    self_.state.stack.push(Value::plain_object({
+        let mut map = PropertyMap::default();
+        map.insert("value".to_string(), result.0);
+        map.insert("done".to_string(), Value::bool(result.1));
+        map
+    }));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+// Makes a time value from the arguments (year, month[, date, hours, minutes, seconds, ms]) of
+// Date() and Date.UTC(). Years from 0 to 99 mean 1900 to 1999.
+fn time_from_date_args(args: &Vec<Value>) -> f64 {
+    let mut fields = [::std::f64::NAN, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+    for (i, arg) in args.iter().take(fields.len()).enumerate() {
+        fields[i] = arg.val.to_number();
+    }
+    let year = fields[date::YEAR].trunc();
+    if 0.0 <= year && year <= 99.0 {
+        fields[date::YEAR] = 1900.0 + year;
+    }
+    date::from_fields(&fields)
+}
+
+// https://tc39.github.io/ecma262/#sec-date-constructor
+pub unsafe fn date_new(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let now = date::time_clip((self_.clock)());
+    let this = match callobj.this.val {
+        ValueBase::Object(obj) if !(*obj).contains_key(DATE_VALUE) => obj,
+        // 'Date()' without 'new' returns the current time as a string.
+        _ => {
+            self_
+                .state
+                .stack
+                .push(Value::string(JsString::from(date::to_string(now))));
+            return Ok(());
+        }
+    };
+
+    let t = match args.len() {
+        0 => now,
+        1 => match args[0].val {
+            ValueBase::Object(obj) => match (*obj).get(DATE_VALUE) {
+                Some(Value {
+                    val: ValueBase::Number(t),
+                    ..
+                }) => *t,
+                _ => ::std::f64::NAN,
+            },
+            ValueBase::String(ref s) => date::parse(&s.to_string()),
+            ref val => val.to_number(),
+        },
+        _ => date::utc(time_from_date_args(&args)),
+    };
+
+    (*this).insert(
+        DATE_VALUE.to_string(),
+        internal_slot(ValueBase::Number(date::time_clip(t))),
+    );
+    self_.state.stack.push(Value::object(this));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn date_now(
+    _callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let now = date::time_clip((self_.clock)());
+    self_.state.stack.push(Value::number(now));
+    Ok(())
+}
+
+pub unsafe fn date_parse(
+    _callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let s = get_arg(&args, 0).val.to_string();
+    self_.state.stack.push(Value::number(date::parse(&s)));
+    Ok(())
+}
+
+pub unsafe fn date_utc(
+    _callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let t = date::time_clip(time_from_date_args(&args));
+    self_.state.stack.push(Value::number(t));
+    Ok(())
+}
+
+// Returns the Date object 'this' and its time value.
+unsafe fn this_time_value(callobj: &CallObject) -> Result<(*mut PropertyMap, f64), RuntimeError> {
+    if let ValueBase::Object(obj) = callobj.this.val {
+        if let Some(Value {
+            val: ValueBase::Number(t),
+            ..
+        }) = (*obj).get(DATE_VALUE)
+        {
+            return Ok((obj, *t));
+        }
+    }
+    Err(RuntimeError::Type(
+        "type error: this is not a Date object".to_string(),
+    ))
+}
+
+unsafe fn set_time_value(obj: *mut PropertyMap, t: f64, self_: &mut VM) {
+    (*obj).insert(DATE_VALUE.to_string(), internal_slot(ValueBase::Number(t)));
+    self_.state.stack.push(Value::number(t));
+}
+
+pub unsafe fn date_prototype_get_time(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (_, t) = this_time_value(&callobj)?;
+    self_.state.stack.push(Value::number(t));
+    Ok(())
+}
+
+pub unsafe fn date_prototype_set_time(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (obj, _) = this_time_value(&callobj)?;
+    let t = date::time_clip(get_arg(&args, 0).val.to_number());
+    set_time_value(obj, t, self_);
+    Ok(())
+}
+
+pub unsafe fn date_prototype_get_timezone_offset(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (_, t) = this_time_value(&callobj)?;
+    self_
+        .state
+        .stack
+        .push(Value::number((t - date::local_time(t)) / 60000.0));
+    Ok(())
+}
+
+pub unsafe fn date_prototype_to_iso_string(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (_, t) = this_time_value(&callobj)?;
+    if !t.is_finite() {
+        return Err(RuntimeError::Range(
+            "range error: invalid time value".to_string(),
+        ));
+    }
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from(date::to_iso_string(t))));
+    Ok(())
+}
+
+pub unsafe fn date_prototype_to_string(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (_, t) = this_time_value(&callobj)?;
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from(date::to_string(t))));
+    Ok(())
+}
+
+pub unsafe fn date_prototype_to_utc_string(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (_, t) = this_time_value(&callobj)?;
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from(date::to_utc_string(t))));
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-date.prototype.tojson
+// TODO: Generic 'this' that calls toISOString() isn't supported.
+pub unsafe fn date_prototype_to_json(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let (_, t) = this_time_value(&callobj)?;
+    self_.state.stack.push(if t.is_finite() {
+        Value::string(JsString::from(date::to_iso_string(t)))
+    } else {
+        Value::null()
+    });
+    Ok(())
+}
+
+macro_rules! date_getter {
+    ($name:ident, $field:expr, $utc:expr) => {
+        pub unsafe fn $name(
+            callobj: CallObject,
+            _args: Vec<Value>,
+            self_: &mut VM,
+        ) -> Result<(), RuntimeError> {
+            let (_, t) = this_time_value(&callobj)?;
+            let t = if $utc { t } else { date::local_time(t) };
+            self_
+                .state
+                .stack
+                .push(Value::number(date::to_fields(t)[$field]));
+            Ok(())
+        }
+    };
+}
+
+date_getter!(date_prototype_get_full_year, date::YEAR, false);
+date_getter!(date_prototype_get_month, date::MONTH, false);
+date_getter!(date_prototype_get_date, date::DATE, false);
+date_getter!(date_prototype_get_day, date::WEEK_DAY, false);
+date_getter!(date_prototype_get_hours, date::HOURS, false);
+date_getter!(date_prototype_get_minutes, date::MINUTES, false);
+date_getter!(date_prototype_get_seconds, date::SECONDS, false);
+date_getter!(date_prototype_get_milliseconds, date::MILLISECONDS, false);
+date_getter!(date_prototype_get_utc_full_year, date::YEAR, true);
+date_getter!(date_prototype_get_utc_month, date::MONTH, true);
+date_getter!(date_prototype_get_utc_date, date::DATE, true);
+date_getter!(date_prototype_get_utc_day, date::WEEK_DAY, true);
+date_getter!(date_prototype_get_utc_hours, date::HOURS, true);
+date_getter!(date_prototype_get_utc_minutes, date::MINUTES, true);
+date_getter!(date_prototype_get_utc_seconds, date::SECONDS, true);
+date_getter!(
+    date_prototype_get_utc_milliseconds,
+    date::MILLISECONDS,
+    true
+);
+
+// Replaces the fields from 'first' with the arguments, like setHours(hour[, min[, sec[, ms]]]).
+// At most 'max_args' arguments are used.
+unsafe fn date_set_fields(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+    first: usize,
+    max_args: usize,
+    utc: bool,
+) -> Result<(), RuntimeError> {
+    let (obj, t) = this_time_value(&callobj)?;
+    let t = if t.is_nan() && first == date::YEAR {
+        0.0 // setFullYear() on an invalid date sets the date to January 1.
+    } else if utc {
+        t
+    } else {
+        date::local_time(t)
+    };
+
+    let mut fields = date::to_fields(t);
+    for i in 0..args.len().min(max_args).max(1) {
+        fields[first + i] = get_arg(&args, i).val.to_number();
+    }
+    let t = date::from_fields(&fields);
+    let t = date::time_clip(if utc { t } else { date::utc(t) });
+    set_time_value(obj, t, self_);
+    Ok(())
+}
+
+macro_rules! date_setter {
+    ($name:ident, $first:expr, $max_args:expr, $utc:expr) => {
+        pub unsafe fn $name(
+            callobj: CallObject,
+            args: Vec<Value>,
+            self_: &mut VM,
+        ) -> Result<(), RuntimeError> {
+            date_set_fields(callobj, args, self_, $first, $max_args, $utc)
+        }
+    };
+}
+
+date_setter!(date_prototype_set_full_year, date::YEAR, 3, false);
+date_setter!(date_prototype_set_month, date::MONTH, 2, false);
+date_setter!(date_prototype_set_date, date::DATE, 1, false);
+date_setter!(date_prototype_set_hours, date::HOURS, 4, false);
+date_setter!(date_prototype_set_minutes, date::MINUTES, 3, false);
+date_setter!(date_prototype_set_seconds, date::SECONDS, 2, false);
+date_setter!(
+    date_prototype_set_milliseconds,
+    date::MILLISECONDS,
+    1,
+    false
+);
+date_setter!(date_prototype_set_utc_full_year, date::YEAR, 3, true);
+date_setter!(date_prototype_set_utc_month, date::MONTH, 2, true);
+date_setter!(date_prototype_set_utc_date, date::DATE, 1, true);
+date_setter!(date_prototype_set_utc_hours, date::HOURS, 4, true);
+date_setter!(date_prototype_set_utc_minutes, date::MINUTES, 3, true);
+date_setter!(date_prototype_set_utc_seconds, date::SECONDS, 2, true);
+date_setter!(
+    date_prototype_set_utc_milliseconds,
+    date::MILLISECONDS,
+    1,
+    true
+);
+
+// https://tc39.github.io/ecma262/#sec-proxycreate
+unsafe fn proxy_create(args: &Vec<Value>) -> Result<Value, RuntimeError> {
+    let target = get_arg(args, 0);
+    let handler = get_arg(args, 1);
+    if !target.is_object() || !handler.is_object() {
+        return Err(RuntimeError::Type(
+            "type error: cannot create proxy with a non-object as target or handler".to_string(),
+        ));
+    }
+    Ok(Value::new(ValueBase::Proxy(gc::new(ProxyValue::new(
+        target, handler,
+    )))))
+}
+
+pub unsafe fn proxy_new(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    match callobj.this.val {
+        ValueBase::Object(_) => {}
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: constructor Proxy requires 'new'".to_string(),
+            ))
+        }
+    }
+    let proxy = proxy_create(&args)?;
+    self_.state.stack.push(proxy);
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+// Hidden property of the revoke function returned by Proxy.revocable(), kept in its CallObject.
+const REVOCABLE_PROXY: &str = "__revocable_proxy__";
+
+pub unsafe fn proxy_revocable(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let proxy = proxy_create(&args)?;
+
+    let revoke_callobj = CallObject::new(Value::undefined());
+    (*revoke_callobj.vals).insert(REVOCABLE_PROXY.to_string(), proxy.clone());
+
+    let mut map = PropertyMap::default();
+    map.insert("proxy".to_string(), proxy);
+    map.insert(
+        "revoke".to_string(),
+        Value::builtin_function(PROXY_REVOKE, revoke_callobj),
+    );
+    self_.state.stack.push(Value::plain_object(map));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn proxy_revoke(
+    callobj: CallObject,
+    _args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    if let Some(proxy) = (*callobj.vals).insert(REVOCABLE_PROXY.to_string(), Value::null()) {
+        if let ValueBase::Proxy(p) = proxy.val {
+            (*p).revoke();
+        }
+    }
+    self_.state.stack.push(Value::undefined());
+    Ok(())
+}
+
+// The first argument of the Reflect functions, which must be an object.
+fn reflect_target(args: &Vec<Value>, name: &str) -> Result<Value, RuntimeError> {
+    let target = get_arg(args, 0);
+    if !target.is_object() {
+        return Err(RuntimeError::Type(format!(
+            "type error: Reflect.{} called on non-object",
+            name
+        )));
+    }
+    Ok(target)
+}
+
+// The Reflect functions return false where the operations on ordinary objects throw TypeError.
+fn succeeded<T>(result: Result<T, RuntimeError>) -> Result<Option<T>, RuntimeError> {
+    match result {
+        Ok(val) => Ok(Some(val)),
+        Err(RuntimeError::Type(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-createlistfromarraylike
+unsafe fn create_list_from_array_like(
+    self_: &mut VM,
+    val: &Value,
+) -> Result<Vec<Value>, RuntimeError> {
+    match val.val {
+        ValueBase::Array(ary) => Ok((0..(*ary).length)
+            .map(|i| array_elem(ary, i).unwrap_or_else(|| Value::undefined()))
+            .collect()),
+        _ if val.is_object() => {
+            let length = ValueBase::String(JsString::from("length"));
+            let len = proxy::get_property(self_, val, length, val)?
+                .val
+                .to_uint32() as usize;
+            let mut list = vec![];
+            for i in 0..len {
+                list.push(proxy::get_property(
+                    self_,
+                    val,
+                    ValueBase::Number(i as f64),
+                    val,
+                )?);
+            }
+            Ok(list)
+        }
+        _ => Err(RuntimeError::Type(
+            "type error: CreateListFromArrayLike called on non-object".to_string(),
+        )),
+    }
+}
+
+pub unsafe fn reflect_apply(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = get_arg(&args, 0);
+    if !is_callable(&target) {
+        return Err(RuntimeError::Type(
+            "type error: Reflect.apply called on non-function".to_string(),
+        ));
+    }
+    let list = create_list_from_array_like(self_, &get_arg(&args, 2))?;
+    let val = call_callback(self_, &target, get_arg(&args, 1), &list)?;
+    self_.state.stack.push(val);
+    Ok(())
+}
+
+// TODO: The third argument 'newTarget' is ignored.
+pub unsafe fn reflect_construct(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = get_arg(&args, 0);
+    if !is_callable(&target) {
+        return Err(RuntimeError::Type(
+            "type error: Reflect.construct called on non-constructor".to_string(),
+        ));
+    }
+    let list = create_list_from_array_like(self_, &get_arg(&args, 1))?;
+    construct_object(self_, target, list)
+}
+
+pub unsafe fn reflect_define_property(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "defineProperty")?;
+    let key = get_arg(&args, 1).val.to_property_key();
+    let desc = get_arg(&args, 2);
+    to_property_descriptor(&desc)?;
+    let defined = succeeded(proxy::define_own_property(self_, &target, key, &desc))?;
+    self_
+        .state
+        .stack
+        .push(Value::bool(defined.unwrap_or(false)));
+    Ok(())
+}
+
+pub unsafe fn reflect_delete_property(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "deleteProperty")?;
+    let key = get_arg(&args, 1).val;
+    let deleted = succeeded(proxy::delete_property(self_, &target, key, true))?;
+    self_
+        .state
+        .stack
+        .push(Value::bool(deleted.unwrap_or(false)));
+    Ok(())
+}
+
+pub unsafe fn reflect_get(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "get")?;
+    let receiver = args.get(2).cloned().unwrap_or_else(|| target.clone());
+    let val = proxy::get_property(self_, &target, get_arg(&args, 1).val, &receiver)?;
+    self_.state.stack.push(val);
+    Ok(())
+}
+
+pub unsafe fn reflect_get_own_property_descriptor(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    reflect_target(&args, "getOwnPropertyDescriptor")?;
+    object_get_own_property_descriptor(callobj, args, self_)
+}
+
+pub unsafe fn reflect_get_prototype_of(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "getPrototypeOf")?;
+    let proto = proxy::get_prototype(self_, &target)?;
+    self_.state.stack.push(proto);
+    Ok(())
+}
+
+pub unsafe fn reflect_has(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "has")?;
+    let key = get_arg(&args, 1).val.to_property_key();
+    let has = proxy::has_property(self_, &target, key.as_str())?;
+    self_.state.stack.push(Value::bool(has));
+    Ok(())
+}
+
+pub unsafe fn reflect_is_extensible(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "isExtensible")?;
+    self_.state.stack.push(Value::bool(target.is_extensible()));
+    Ok(())
+}
+
+pub unsafe fn reflect_own_keys(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "ownKeys")?;
+    let keys = proxy::own_property_keys(self_, &target)?
+        .into_iter()
+        .map(|key| Value::string(JsString::from_key(&key)))
+        .collect();
+    self_
+        .state
+        .stack
+        .push(Value::array(gc::new(ArrayValue::new(keys))));
+
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn reflect_prevent_extensions(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "preventExtensions")?;
+    target.prevent_extensions();
+    self_.state.stack.push(Value::bool(true));
+    Ok(())
+}
+
+pub unsafe fn reflect_set(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "set")?;
+    let receiver = args.get(3).cloned().unwrap_or_else(|| target.clone());
+    let key = get_arg(&args, 1).val;
+    let val = get_arg(&args, 2);
+    let set = succeeded(proxy::set_property(
+        self_, &target, key, val, &receiver, true,
+    ))?;
+    self_.state.stack.push(Value::bool(set.is_some()));
+    Ok(())
+}
+
+pub unsafe fn reflect_set_prototype_of(
+    _: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let target = reflect_target(&args, "setPrototypeOf")?;
+    let proto = get_arg(&args, 1);
+    if proto.val != ValueBase::Null && !proto.is_object() {
+        return Err(RuntimeError::Type(
+            "type error: object prototype may only be an object or null".to_string(),
+        ));
+    }
+    let set = succeeded(target.set_prototype(proto))?;
+    self_.state.stack.push(Value::bool(set.is_some()));
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-toindex
+fn to_index(val: &Value, msg: &str) -> Result<usize, RuntimeError> {
+    let n = to_integer(val)?;
+    if n < 0.0 || n > MAX_BYTE_LENGTH as f64 {
+        return Err(RuntimeError::Range(format!("range error: {}", msg)));
+    }
+    Ok(n as usize)
+}
+
+// ArrayBuffers larger than this fail to be allocated.
+const MAX_BYTE_LENGTH: usize = 0x7fff_ffff;
+
+fn allocate_bytes(len: usize) -> Result<Vec<u8>, RuntimeError> {
+    if len > MAX_BYTE_LENGTH {
+        return Err(RuntimeError::Range(
+            "range error: array buffer allocation failed".to_string(),
+        ));
+    }
+    Ok(vec![0; len])
+}
+
+// Returns the storage of 'val' if it is an ArrayBuffer object.
+unsafe fn array_buffer_data(val: &Value) -> Option<*mut ArrayBuffer> {
+    if let ValueBase::Object(obj) = val.val {
+        if let Some(Value {
+            val: ValueBase::ArrayBuffer(buffer),
+            ..
+        }) = (*obj).get(ARRAY_BUFFER_DATA)
+        {
+            return Some(*buffer);
+        }
+    }
+    None
+}
+
+// Returns the view of 'val' if it is a typed array (with 'slot' being TYPED_ARRAY_DATA) or a
+// DataView (with DATA_VIEW_DATA).
+unsafe fn view_data(val: &Value, slot: &str) -> Option<*mut TypedArray> {
+    if let ValueBase::Object(obj) = val.val {
+        if let Some(Value {
+            val: ValueBase::TypedArray(view),
+            ..
+        }) = (*obj).get(slot)
+        {
+            return Some(*view);
+        }
+    }
+    None
+}
+
+fn new_array_buffer_object(bytes: Vec<u8>) -> Value {
+    let prototype = ARRAY_BUFFER_OBJ
+        .with(|x| x.get_property(ValueBase::String(JsString::from("prototype")), None));
+    let mut map = PropertyMap::default();
+    map.insert("__proto__".to_string(), prototype);
+    map.insert(
+        ARRAY_BUFFER_DATA.to_string(),
+        internal_slot(ValueBase::ArrayBuffer(gc::new(ArrayBuffer {
+            bytes: bytes,
+        }))),
+    );
+    Value::object(gc::new(map))
+}
+
+// The hidden property of a view of 'buffer' (an ArrayBuffer object). The range must be in bounds.
+unsafe fn view_slot(
+    kind: TypedArrayKind,
+    buffer: Value,
+    byte_offset: usize,
+    length: usize,
+) -> Value {
+    let data = array_buffer_data(&buffer).unwrap();
+    internal_slot(ValueBase::TypedArray(gc::new(TypedArray {
+        kind: kind,
+        buffer: buffer,
+        data: data,
+        byte_offset: byte_offset,
+        length: length,
+    })))
+}
+
+// Creates a typed array whose prototype is the one of the constructor for 'kind'.
+unsafe fn new_typed_array_object(
+    kind: TypedArrayKind,
+    buffer: Value,
+    byte_offset: usize,
+    length: usize,
+) -> Value {
+    let i = TYPED_ARRAY_KINDS.iter().position(|k| *k == kind).unwrap();
+    let prototype = TYPED_ARRAY_OBJS
+        .with(|x| x[i].get_property(ValueBase::String(JsString::from("prototype")), None));
+    let mut map = PropertyMap::default();
+    map.insert("__proto__".to_string(), prototype);
+    map.insert(
+        TYPED_ARRAY_DATA.to_string(),
+        view_slot(kind, buffer, byte_offset, length),
+    );
+    Value::object(gc::new(map))
+}
+
+// Returns the numbers a typed array is initialized or set with: the elements of typed arrays,
+// the values of iterables or the elements of array-like objects.
+unsafe fn typed_array_source_values(source: &Value) -> Result<Vec<f64>, RuntimeError> {
+    if let Some(values) = iterate_values(source) {
+        return values.iter().map(|val| val.val.try_to_number()).collect();
+    }
+    let len = to_integer(&source.get_property(ValueBase::String(JsString::from("length")), None))?;
+    (0..len.max(0.0) as usize)
+        .map(|i| {
+            source
+                .get_property(ValueBase::Number(i as f64), None)
+                .val
+                .try_to_number()
+        })
+        .collect()
+}
+
+// https://tc39.github.io/ecma262/#sec-arraybuffer-length
+pub unsafe fn array_buffer_new(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let this = match callobj.this.val {
+        ValueBase::Object(obj) if !(*obj).contains_key(ARRAY_BUFFER_DATA) => obj,
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: constructor ArrayBuffer requires 'new'".to_string(),
+            ))
+        }
+    };
+    let len = to_index(&get_arg(&args, 0), "invalid array buffer length")?;
+    (*this).insert(
+        ARRAY_BUFFER_DATA.to_string(),
+        internal_slot(ValueBase::ArrayBuffer(gc::new(ArrayBuffer {
+            bytes: allocate_bytes(len)?,
+        }))),
+    );
+    self_.state.stack.push(Value::object(this));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn array_buffer_is_view(
+    _callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let val = get_arg(&args, 0);
+    let is_view =
+        view_data(&val, TYPED_ARRAY_DATA).is_some() || view_data(&val, DATA_VIEW_DATA).is_some();
+    self_.state.stack.push(Value::bool(is_view));
+    Ok(())
+}
+
+pub unsafe fn array_buffer_prototype_slice(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let buffer =
+        match array_buffer_data(&*callobj.this) {
+            Some(buffer) => buffer,
+            None => return Err(RuntimeError::Type(
+                "type error: method ArrayBuffer.prototype.slice called on incompatible receiver"
+                    .to_string(),
+            )),
+        };
+    let bytes = &(*buffer).bytes;
+    let start = to_relative_index(&get_arg(&args, 0), bytes.len(), 0)?;
+    let end = to_relative_index(&get_arg(&args, 1), bytes.len(), bytes.len())?;
+    let bytes = if start < end {
+        bytes[start..end].to_vec()
+    } else {
+        vec![]
+    };
+    self_.state.stack.push(new_array_buffer_object(bytes));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-typedarray-constructors
+// new TypedArray(length), new TypedArray(typedArray or iterable or arrayLike) and
+// new TypedArray(buffer[, byteOffset[, length]]).
+unsafe fn typed_array_new(
+    kind: TypedArrayKind,
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let this = match callobj.this.val {
+        ValueBase::Object(obj) if !(*obj).contains_key(TYPED_ARRAY_DATA) => obj,
+        _ => {
+            return Err(RuntimeError::Type(format!(
+                "type error: constructor {} requires 'new'",
+                kind.name()
+            )))
+        }
+    };
+
+    let size = kind.element_size();
+    let arg = get_arg(&args, 0);
+    let mut values = vec![];
+    let (buffer, byte_offset, length) = if let Some(data) = array_buffer_data(&arg) {
+        let buffer_len = (*data).bytes.len();
+        let byte_offset = to_index(&get_arg(&args, 1), "invalid typed array offset")?;
+        if byte_offset % size != 0 {
+            return Err(RuntimeError::Range(format!(
+                "range error: start offset of {} should be a multiple of {}",
+                kind.name(),
+                size
+            )));
+        }
+        let length = match get_arg(&args, 2) {
+            Value {
+                val: ValueBase::Undefined,
+                ..
+            } => {
+                if buffer_len % size != 0 {
+                    return Err(RuntimeError::Range(format!(
+                        "range error: byte length of {} should be a multiple of {}",
+                        kind.name(),
+                        size
+                    )));
+                }
+                if byte_offset > buffer_len {
+                    return Err(RuntimeError::Range(format!(
+                        "range error: start offset {} is outside the bounds of the buffer",
+                        byte_offset
+                    )));
+                }
+                (buffer_len - byte_offset) / size
+            }
+            length => {
+                let length = to_index(&length, "invalid typed array length")?;
+                if byte_offset + length * size > buffer_len {
+                    return Err(RuntimeError::Range(format!(
+                        "range error: invalid typed array length: {}",
+                        length
+                    )));
+                }
+                length
+            }
+        };
+        (arg, byte_offset, length)
+    } else {
+        let length = if arg.is_object() {
+            values = typed_array_source_values(&arg)?;
+            values.len()
+        } else {
+            to_index(&arg, "invalid typed array length")?
+        };
+        let byte_length = length.checked_mul(size).unwrap_or(MAX_BYTE_LENGTH + 1);
+        (
+            new_array_buffer_object(allocate_bytes(byte_length)?),
+            0,
+            length,
+        )
+    };
+
+    let slot = view_slot(kind, buffer, byte_offset, length);
+    if let ValueBase::TypedArray(typed_array) = slot.val {
+        for (i, n) in values.into_iter().enumerate() {
+            (*typed_array).set(i, n);
+        }
+    }
+    (*this).insert(TYPED_ARRAY_DATA.to_string(), slot);
+    self_.state.stack.push(Value::object(this));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+macro_rules! typed_array_constructor {
+    ($name:ident, $kind:expr) => {
+        pub unsafe fn $name(
+            callobj: CallObject,
+            args: Vec<Value>,
+            self_: &mut VM,
+        ) -> Result<(), RuntimeError> {
+            typed_array_new($kind, callobj, args, self_)
+        }
+    };
+}
+
+typed_array_constructor!(int8_array_new, TypedArrayKind::Int8);
+typed_array_constructor!(uint8_array_new, TypedArrayKind::Uint8);
+typed_array_constructor!(uint8_clamped_array_new, TypedArrayKind::Uint8Clamped);
+typed_array_constructor!(int16_array_new, TypedArrayKind::Int16);
+typed_array_constructor!(uint16_array_new, TypedArrayKind::Uint16);
+typed_array_constructor!(int32_array_new, TypedArrayKind::Int32);
+typed_array_constructor!(uint32_array_new, TypedArrayKind::Uint32);
+typed_array_constructor!(float32_array_new, TypedArrayKind::Float32);
+typed_array_constructor!(float64_array_new, TypedArrayKind::Float64);
+
+unsafe fn this_typed_array(callobj: &CallObject) -> Result<&TypedArray, RuntimeError> {
+    match view_data(&*callobj.this, TYPED_ARRAY_DATA) {
+        Some(typed_array) => Ok(&*typed_array),
+        None => Err(RuntimeError::Type(
+            "type error: this is not a typed array".to_string(),
+        )),
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-%typedarray%.prototype.subarray
+// The new typed array shares the buffer.
+pub unsafe fn typed_array_prototype_subarray(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let typed_array = this_typed_array(&callobj)?;
+    let len = typed_array.length;
+    let start = to_relative_index(&get_arg(&args, 0), len, 0)?;
+    let end = to_relative_index(&get_arg(&args, 1), len, len)?;
+    let subarray = new_typed_array_object(
+        typed_array.kind,
+        typed_array.buffer.clone(),
+        typed_array.byte_offset + start * typed_array.kind.element_size(),
+        if start < end { end - start } else { 0 },
+    );
+    self_.state.stack.push(subarray);
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-%typedarray%.prototype.set
+pub unsafe fn typed_array_prototype_set(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let typed_array = this_typed_array(&callobj)?;
+    let offset = to_integer(&get_arg(&args, 1))?;
+    // The values are read before written since the source may share the buffer.
+    let values = typed_array_source_values(&get_arg(&args, 0))?;
+    if offset < 0.0 || offset + values.len() as f64 > typed_array.length as f64 {
+        return Err(RuntimeError::Range(
+            "range error: offset is out of bounds".to_string(),
+        ));
+    }
+    for (i, n) in values.into_iter().enumerate() {
+        typed_array.set(offset as usize + i, n);
+    }
+    self_.state.stack.push(Value::undefined());
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-%typedarray%.prototype.slice
+// Unlike subarray(), the elements are copied into a new buffer.
+pub unsafe fn typed_array_prototype_slice(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let typed_array = this_typed_array(&callobj)?;
+    let kind = typed_array.kind;
+    let len = typed_array.length;
+    let start = to_relative_index(&get_arg(&args, 0), len, 0)?;
+    let end = to_relative_index(&get_arg(&args, 1), len, len)?;
+    let (start, end) = (start, end.max(start));
+    let byte_offset = typed_array.byte_offset;
+    let bytes = &(*typed_array.data).bytes;
+    let bytes = bytes
+        [byte_offset + start * kind.element_size()..byte_offset + end * kind.element_size()]
+        .to_vec();
+    let slice = new_typed_array_object(kind, new_array_buffer_object(bytes), 0, end - start);
+    self_.state.stack.push(slice);
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+pub unsafe fn typed_array_prototype_fill(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let typed_array = this_typed_array(&callobj)?;
+    let n = get_arg(&args, 0).val.try_to_number()?;
+    let len = typed_array.length;
+    let start = to_relative_index(&get_arg(&args, 1), len, 0)?;
+    let end = to_relative_index(&get_arg(&args, 2), len, len)?;
+    for i in start..end {
+        typed_array.set(i, n);
+    }
+    self_.state.stack.push((*callobj.this).clone());
+    Ok(())
+}
+
+pub unsafe fn typed_array_prototype_join(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let typed_array = this_typed_array(&callobj)?;
+    let separator = match get_arg(&args, 0).val {
+        ValueBase::Undefined => ",".to_string(),
+        separator => separator.to_string(),
+    };
+    let joined = typed_array
+        .to_vec()
+        .into_iter()
+        .map(|n| number_to_string(n))
+        .collect::<Vec<String>>()
+        .join(separator.as_str());
+    self_
+        .state
+        .stack
+        .push(Value::string(JsString::from(joined)));
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-dataview-buffer-byteoffset-bytelength
+pub unsafe fn data_view_new(
+    callobj: CallObject,
+    args: Vec<Value>,
+    self_: &mut VM,
+) -> Result<(), RuntimeError> {
+    let this = match callobj.this.val {
+        ValueBase::Object(obj) if !(*obj).contains_key(DATA_VIEW_DATA) => obj,
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: constructor DataView requires 'new'".to_string(),
+            ))
+        }
+    };
+    let buffer = get_arg(&args, 0);
+    let buffer_len = match array_buffer_data(&buffer) {
+        Some(data) => (*data).bytes.len(),
+        None => {
+            return Err(RuntimeError::Type(
+                "type error: first argument to DataView constructor must be an ArrayBuffer"
+                    .to_string(),
+            ))
+        }
+    };
+    let byte_offset = to_index(&get_arg(&args, 1), "invalid DataView offset")?;
+    if byte_offset > buffer_len {
+        return Err(RuntimeError::Range(format!(
+            "range error: start offset {} is outside the bounds of the buffer",
+            byte_offset
+        )));
+    }
+    let byte_length = match get_arg(&args, 2) {
+        Value {
+            val: ValueBase::Undefined,
+            ..
+        } => buffer_len - byte_offset,
+        byte_length => to_index(&byte_length, "invalid DataView length")?,
+    };
+    if byte_offset + byte_length > buffer_len {
+        return Err(RuntimeError::Range(format!(
+            "range error: invalid DataView length {}",
+            byte_length
+        )));
+    }
+    (*this).insert(
+        DATA_VIEW_DATA.to_string(),
+        view_slot(TypedArrayKind::Uint8, buffer, byte_offset, byte_length),
+    );
+    self_.state.stack.push(Value::object(this));
+    gc::mark_and_sweep(&self_.state);
+    Ok(())
+}
+
+unsafe fn this_data_view(callobj: &CallObject) -> Result<&TypedArray, RuntimeError> {
+    match view_data(&*callobj.this, DATA_VIEW_DATA) {
+        Some(view) => Ok(&*view),
+        None => Err(RuntimeError::Type(
+            "type error: this is not a DataView".to_string(),
+        )),
+    }
+}
+
+fn out_of_data_view() -> RuntimeError {
+    RuntimeError::Range("range error: offset is outside the bounds of the DataView".to_string())
+}
+
+// https://tc39.github.io/ecma262/#sec-getviewvalue
+// getInt8(byteOffset), getInt16(byteOffset[, littleEndian]) and so on. Big endian by default.
+macro_rules! data_view_getter {
+    ($name:ident, $kind:expr) => {
+        pub unsafe fn $name(
+            callobj: CallObject,
+            args: Vec<Value>,
+            self_: &mut VM,
+        ) -> Result<(), RuntimeError> {
+            let view = this_data_view(&callobj)?;
+            let index = to_index(
+                &get_arg(&args, 0),
+                "offset is outside the bounds of the DataView",
+            )?;
+            let little_endian = get_arg(&args, 1).val.to_boolean();
+            match view.get_value(index, $kind, little_endian) {
+                Some(n) => self_.state.stack.push(Value::number(n)),
+                None => return Err(out_of_data_view()),
+            }
+            Ok(())
+        }
+    };
+}
+
+data_view_getter!(data_view_prototype_get_int8, TypedArrayKind::Int8);
+data_view_getter!(data_view_prototype_get_uint8, TypedArrayKind::Uint8);
+data_view_getter!(data_view_prototype_get_int16, TypedArrayKind::Int16);
+data_view_getter!(data_view_prototype_get_uint16, TypedArrayKind::Uint16);
+data_view_getter!(data_view_prototype_get_int32, TypedArrayKind::Int32);
+data_view_getter!(data_view_prototype_get_uint32, TypedArrayKind::Uint32);
+data_view_getter!(data_view_prototype_get_float32, TypedArrayKind::Float32);
+data_view_getter!(data_view_prototype_get_float64, TypedArrayKind::Float64);
+
+// https://tc39.github.io/ecma262/#sec-setviewvalue
+// setInt8(byteOffset, value), setInt16(byteOffset, value[, littleEndian]) and so on.
+macro_rules! data_view_setter {
+    ($name:ident, $kind:expr) => {
+        pub unsafe fn $name(
+            callobj: CallObject,
+            args: Vec<Value>,
+            self_: &mut VM,
+        ) -> Result<(), RuntimeError> {
+            let view = this_data_view(&callobj)?;
+            let index = to_index(
+                &get_arg(&args, 0),
+                "offset is outside the bounds of the DataView",
+            )?;
+            let n = get_arg(&args, 1).val.try_to_number()?;
+            let little_endian = get_arg(&args, 2).val.to_boolean();
+            if !view.set_value(index, $kind, n, little_endian) {
+                return Err(out_of_data_view());
+            }
+            self_.state.stack.push(Value::undefined());
+            Ok(())
+        }
+    };
+}
+
+data_view_setter!(data_view_prototype_set_int8, TypedArrayKind::Int8);
+data_view_setter!(data_view_prototype_set_uint8, TypedArrayKind::Uint8);
+data_view_setter!(data_view_prototype_set_int16, TypedArrayKind::Int16);
+data_view_setter!(data_view_prototype_set_uint16, TypedArrayKind::Uint16);
+data_view_setter!(data_view_prototype_set_int32, TypedArrayKind::Int32);
+data_view_setter!(data_view_prototype_set_uint32, TypedArrayKind::Uint32);
+data_view_setter!(data_view_prototype_set_float32, TypedArrayKind::Float32);
+data_view_setter!(data_view_prototype_set_float64, TypedArrayKind::Float64);
+
+#[test]
+fn array_methods() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    let source = "
+        var a = [3, 1, , 10, 2], log = [];
+        a.forEach(function(x, i) { log.push(i + ':' + x) });
+        [log.join(' '), a.map(function(x) { return x * 2 }).join(),
+         a.filter(function(x) { return x > 1 }).join(),
+         a.reduce(function(acc, x) { return acc + x }),
+         a.reduceRight(function(acc, x) { return acc + '|' + x }),
+         a.some(function(x) { return x > 5 }), a.every(function(x) { return x > 0 }),
+         a.find(function(x) { return x > 2 }), a.findIndex(function(x) { return x === undefined }),
+         a.indexOf(10), a.lastIndexOf(3), a.includes(undefined), a.indexOf(undefined),
+         a.slice(1, -1).length, a.slice(-2).join()].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "0:3 1:1 3:10 4:2;6,2,,20,4;3,10,2;16;2|10|1|3;true;true;3;2;3;0;true;-1;3;10,2"
+    );
+
+    let source = "
+        var b = [1, 2, 3, 4, 5], removed = b.splice(1, 2, 'x', 'y', 'z'), spliced = b.join('-');
+        var concat = b.concat([6, , 7], 8), reversed = b.reverse().join(), shifted = b.shift();
+        var len = b.unshift(0, 0.5);
+        [removed.join(), spliced, concat.length, concat.hasOwnProperty(7),
+         [1, null, undefined, 2].join(), reversed, shifted, len, b.join()].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "2,3;1-x-y-z-4-5;10;false;1,,,2;5,4,z,y,x,1;5;7;0,0.5,4,z,y,x,1"
+    );
+
+    let source = "
+        [[5, 1, 10, 2].sort().join(), [5, 1, 10, 2].sort(function(x, y) { return x - y }).join(),
+         [3, undefined, , 1].sort().length, (new Array(3)).fill(7).join(),
+         [1, 2, 3, 4].fill(0, 1, -1).join(), Array.isArray([]), Array.isArray({}),
+         Array.of(1, 2, 3).join(), Array.from('abc').join(),
+         Array.from({length: 2, 0: 'a', 1: 'b'}).join(),
+         Array.from([1, 2, 3], function(x) { return x * 10 }).join()].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "1,10,2,5;1,2,5,10;4;7,7,7;1,0,0,4;true;false;1,2,3;a,b,c;a,b;10,20,30"
+    );
+
+    // The methods are generic and work on array-like objects too.
+    let source = "
+        var ap = Array.prototype, o = {length: 2, 0: 'a', 1: 'b'};
+        [ap.join.call(o), ap.map.call(o, function(x) { return x + x }).join(),
+         ap.indexOf.call(o, 'b'), ap.slice.call('xyz', 1).join(),
+         ap.join.call({length: 3, 1: 1}, '-'),
+         ap.reduce.call({length: 2, 0: 1, 1: 2}, function(x, y) { return x + y })].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "a,b;aa,bb;1;y,z;-1-;3");
+
+    let source = "
+        var pushed = ap.push.call(o, 'c'), popped = ap.pop.call(o);
+        ap.reverse.call(o);
+        var shifted = ap.shift.call(o), len = ap.unshift.call(o, 1, 2);
+        var p = {length: 3, 0: 3, 1: 1, 2: 2};
+        ap.sort.call(p);
+        var removed = ap.splice.call(p, 1, 1);
+        [pushed, popped, shifted, len, Object.keys(o).join(), ap.join.call(o), removed.join(),
+         p.length, p.hasOwnProperty(2), ap.join.call(p)].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "3;c;b;3;0,1,2,length;1,2,a;2;2;false;1,3");
+
+    let source = "Array.prototype.map.call(null, function(x) { return x })";
+    assert_eq!(runner.error_name(source), "TypeError");
+}
+
+#[test]
+fn string_methods() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    let source = "
+        var s = 'Hello, World';
+        [s.length, s[4], s.charAt(1), s.charAt(100), s.charCodeAt(0), s.codePointAt(1),
+         s.indexOf('o'), s.indexOf('o', 5), s.lastIndexOf('o'), s.indexOf('xyz'),
+         s.slice(7), s.slice(-5, -1), s.substring(5, 0), s.substr(-5, 3)].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "12;o;e;;72;101;4;8;8;-1;World;Worl;Hello;Wor"
+    );
+
+    let source = "
+        [s.split(', ').length, 'a,b,,c'.split(',').join('|'), 'abc'.split('').join('|'),
+         'a b c'.split(' ', 2).join('|'), s.toUpperCase(), s.toLowerCase(),
+         '[' + '  pad  '.trim() + ']', '[' + '  pad  '.trimStart() + ']',
+         '[' + '  pad  '.trimEnd() + ']', '5'.padStart(3, '0'), 'abc'.padEnd(6, '12'),
+         'ab'.repeat(3)].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "2;a|b||c;a|b|c;a|b;HELLO, WORLD;hello, world;[pad];[pad  ];[  pad];005;abc121;ababab"
+    );
+
+    let source = "
+        [s.startsWith('Hell'), s.endsWith('World'), s.includes('lo, W'), s.startsWith('World', 7),
+         'a'.concat('b', 1, true), String.fromCharCode(72, 105), String(123), String(null),
+         String.prototype.toUpperCase.call('call'), 'str'.toString(),
+         'x'.hasOwnProperty('length'), '\u{1F600}'.length, '\u{1F600}'.codePointAt(0)].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "true;true;true;true;ab1true;Hi;123;null;CALL;str;true;2;128512"
+    );
+
+    // Objects are converted through their own toString() and valueOf().
+    let source = "
+        var o = {toString: function() { return 'T' }, valueOf: function() { return 42 }};
+        var v = {valueOf: function() { return 'V' }, toString: function() { return {} }};
+        var d = new Date(0);
+        d.toString = function() { return 'D' };
+        [String(o), '' + o, o + 1, String(v), v + '', String([1, 2]), '' + d].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "T;42;43;V;V;1,2;D");
+
+    // Primitives other than strings are added as numbers.
+    let source = "
+        ['b' + undefined, undefined + 'b', null + 1, true + null, 1 + undefined, 'a' + null,
+         false + true, {valueOf: function() { return null }} + 2].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "bundefined;undefinedb;1;1;NaN;anull;1;2");
+
+    let source = "String({toString: function() { return {} }, valueOf: function() { return {} }})";
+    assert_eq!(runner.error_name(source), "TypeError");
+}
+
+#[test]
+fn number_conversions() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    for (source, expected) in &[
+        (
+            "[1e21, 1e-7, 0.000001, 123.456, 0.1 + 0.2, -0, 1 / 3, -1e21, 5e-324,
+              1.7976931348623157e308].join(' ')",
+            "1e+21 1e-7 0.000001 123.456 0.30000000000000004 0 0.3333333333333333 -1e+21 5e-324 \
+             1.7976931348623157e+308",
+        ),
+        (
+            "[(255).toString(16), (255).toString(2), (-255).toString(36), (0.5).toString(2),
+              (3.14159).toString(8), (NaN).toString(2), (Infinity).toString(16)].join(' ')",
+            "ff 11111111 -73 0.1 3.11037476006703156 NaN Infinity",
+        ),
+        (
+            "[(1.005).toFixed(2), (0.5).toFixed(0), (2.5).toFixed(0), (-2.5).toFixed(0),
+              (1234.5678).toFixed(2), (1e21).toFixed(2), (0.000001).toFixed(7)].join(' ')",
+            "1.00 1 3 -3 1234.57 1e+21 0.0000010",
+        ),
+        (
+            "[(123.456).toPrecision(4), (0.00001234).toPrecision(2), (123456).toPrecision(2),
+              (99.99).toPrecision(3), (0).toPrecision(3), (5e-7).toPrecision(1)].join(' ')",
+            "123.5 0.000012 1.2e+5 100 0.00 5e-7",
+        ),
+        (
+            "[(123456).toExponential(2), (0).toExponential(), (1.5).toExponential(0),
+              (123.456).toExponential(), (-0.00015).toExponential(1)].join(' ')",
+            "1.23e+5 0e+0 2e+0 1.23456e+2 -1.5e-4",
+        ),
+        (
+            "[Number('  42 '), Number(''), Number('0x1f'), Number('0b101'), Number('1e3'),
+              Number('12px'), Number('-.5'), Number('1.'), Number(), Number(true), Number(null),
+              Number(undefined)].join(' ')",
+            "42 0 31 5 1000 NaN -0.5 1 0 1 0 NaN",
+        ),
+        (
+            "[parseInt('  42px'), parseInt('0x1f'), parseInt('ff', 16), parseInt('z', 37),
+              parseInt('-12.9'), parseInt(''), parseInt('123456789012345678901234567890'),
+              parseFloat('3.14abc'), parseFloat('.5e1x'), parseFloat('-Infinityx'),
+              parseFloat('1.e2'), parseFloat('1e')].join(' ')",
+            "42 31 255 NaN -12 NaN 1.2345678901234568e+29 3.14 5 -Infinity 100 1",
+        ),
+        (
+            "[isNaN('abc'), isFinite('12'), Number.isNaN('abc'), Number.isFinite('12'),
+              Number.isInteger(5.5), Number.isSafeInteger(Math.pow(2, 53)),
+              Number.MAX_SAFE_INTEGER, Number.MIN_VALUE].join(' ')",
+            "true true false false false false 9007199254740991 5e-324",
+        ),
+    ] {
+        let val = runner.run(source).unwrap();
+        assert_eq!(val.to_string(), *expected, "{}", source);
+    }
+}
+
+#[test]
+fn json() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    let source = r#"
+        var o = JSON.parse('{"a": [1, 2.5e3, -0.5, true, false, null], ' +
+                           '"b": {"c": "x\\n\\u00e9\\ud83d\\ude00"}}');
+        [JSON.stringify(o), o.b.c.length, JSON.stringify(o, null, 2)].join(';')"#;
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "{\"a\":[1,2500,-0.5,true,false,null],\"b\":{\"c\":\"x\\né😀\"}};5;\
+         {\n  \"a\": [\n    1,\n    2500,\n    -0.5,\n    true,\n    false,\n    null\n  ],\
+         \n  \"b\": {\n    \"c\": \"x\\né😀\"\n  }\n}"
+    );
+
+    for (source, expected) in &[
+        (
+            "JSON.stringify([1, , undefined, function() {}, 's'])",
+            "[1,null,null,null,\"s\"]",
+        ),
+        (
+            "JSON.stringify({f: function() {}, i: -Infinity, n: NaN, u: undefined, z: -0})",
+            "{\"i\":null,\"n\":null,\"z\":0}",
+        ),
+        (
+            "JSON.stringify('a\"b\\\\c\\u0001')",
+            "\"a\\\"b\\\\c\\u0001\"",
+        ),
+        (
+            "[JSON.stringify(undefined), JSON.stringify(function() {}),
+              JSON.stringify(null)].join()",
+            ",,null",
+        ),
+        (
+            "JSON.stringify({a: 1, b: [1, 2], c: {}}, null, '--')",
+            "{\n--\"a\": 1,\n--\"b\": [\n----1,\n----2\n--],\n--\"c\": {}\n}",
+        ),
+        (
+            "JSON.stringify({a: 1, b: 2, c: 3}, ['c', 'a', 'a'])",
+            "{\"c\":3,\"a\":1}",
+        ),
+        (
+            "JSON.stringify({a: 1, b: 'x', c: [2]}, function(k, v) {
+                 return k === 'a' || k === '0' ? v * 10 : v })",
+            "{\"a\":10,\"b\":\"x\",\"c\":[20]}",
+        ),
+        (
+            "JSON.stringify({x: {toJSON: function(k) { return 'key:' + k }}})",
+            "{\"x\":\"key:x\"}",
+        ),
+        (
+            "[JSON.stringify([], null, 4), JSON.stringify([[]], null, 1),
+              JSON.stringify([1], null, 20).length].join()",
+            "[],[\n []\n],15",
+        ),
+        (
+            "JSON.stringify(JSON.parse('{\"a\": 1, \"b\": [1, {\"c\": 3}]}', function(k, v) {
+                 return k === 'a' || k === 'c' ? v + 1 : v }))",
+            "{\"a\":2,\"b\":[1,{\"c\":4}]}",
+        ),
+        (
+            "JSON.stringify(JSON.parse('{\"a\": 1, \"b\": 2}', function(k, v) {
+                 return k === 'a' ? undefined : v }))",
+            "{\"b\":2}",
+        ),
+        (
+            "[JSON.parse(' 12 '), JSON.parse('\"s\"'), 1 / JSON.parse('-0'),
+              JSON.parse('1E2')].join()",
+            "12,s,-Infinity,100",
+        ),
+    ] {
+        let val = runner.run(source).unwrap();
+        assert_eq!(val.to_string(), *expected, "{}", source);
+    }
+
+    // Own properties are serialized in the order of own_property_keys(), and '__proto__' is an
+    // ordinary own property of parsed objects.
+    let source = r#"
+        var o = JSON.parse('{"b": 1, "a": 2, "2": 3, "1": 4, "__proto__": 5}');
+        [JSON.stringify(o), JSON.stringify({z: 1, a: [{y: 2, x: 3}]}), o.__proto__,
+         o.hasOwnProperty('__proto__'), Object.getPrototypeOf(o) === Object.prototype,
+         Object.keys(o)].join(';')"#;
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "{\"1\":4,\"2\":3,\"b\":1,\"a\":2,\"__proto__\":5};{\"z\":1,\"a\":[{\"y\":2,\"x\":3}]};5;\
+         true;true;1,2,b,a,__proto__"
+    );
+
+    let source = "
+        var p = {x: 1}, q = {__proto__: p, y: 2};
+        var r = [q.x, q.__proto__ === p, Object.keys(q)];
+        q.__proto__ = null;
+        r.concat([q.x, Object.getPrototypeOf(q)]).join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "1,true,y,,");
+
+    for text in &[
+        r#"{"a": 1,}"#,
+        "{'a': 1}",
+        "[1, 2",
+        "01",
+        r#""\x""#,
+        "",
+        r#"{"a" 1}"#,
+        "1 2",
+        "NaN",
+    ] {
+        let source = format!("JSON.parse({:?})", text);
+        assert_eq!(runner.error_name(&source), "SyntaxError", "{}", source);
+    }
+
+    let source = "var cyclic = {}; cyclic.self = cyclic; JSON.stringify(cyclic)";
+    assert_eq!(runner.error_name(source), "TypeError");
+}
+
+#[test]
+fn collections() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    let source = "
+        var m = new Map([[1, 'one'], ['1', 'str']]), key = {};
+        m.set(key, 'obj').set(NaN, 'nan').set(-0, 'zero');
+        var r = [m.size, m.get(1), m.get('1'), m.get(key), m.get({}), m.get(NaN), m.get(0)];
+        r.push(m.delete(1));
+        r.push(m.delete(1), m.size);
+        var it = m.entries(), first = it.next().value;
+        m.set('late', 1);
+        var rest = [];
+        for (var step = it.next(); !step.done; step = it.next()) rest.push(step.value[0]);
+        r.concat([first.join(':'), rest.join(), it.next().done]).join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "5;one;str;obj;;nan;zero;true;false;4;1:str;[object Object],NaN,0,late;true"
+    );
+
+    // Entries added during forEach are visited, deleted ones are skipped.
+    let source = "
+        var s = new Set([1, 2, 2, 3]), seen = [];
+        s.forEach(function(v) { if (v === 1) { s.delete(2); s.add(4) } seen.push(v) });
+        var ws = new WeakSet([key]), wm = new WeakMap([[key, 1]]);
+        var r = [seen.join(), s.size, s.has(2), (new Set('hello')).size, ws.has(key), ws.has({}),
+                 wm.get(key)];
+        r.push(wm.delete(key));
+        r.push(wm.has(key));
+        r.join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "1,3,4;3;false;4;true;false;1;true;false");
+
+    // -0 is stored as +0, as the value of sets too.
+    let source = "
+        var s = new Set([-0]), t = new Set(), m = new Map([[-0, 1]]), n = new Map();
+        t.add(-0);
+        n.set(-0, 2);
+        [s.values().next().value, t.values().next().value, t.keys().next().value,
+         m.keys().next().value, n.entries().next().value[0]].map(function(v) { return 1 / v })";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "Infinity,Infinity,Infinity,Infinity,Infinity"
+    );
+
+    for source in &["new WeakMap().set(1, 1)", "Map()", "new Set(5)"] {
+        assert_eq!(runner.error_name(source), "TypeError", "{}", source);
+    }
+}
+
+#[test]
+fn function_bind() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    let source = "
+        function add(a, b) { return a + b }
+        var add1 = add.bind(null, 1), obj = {n: 10};
+        var getN = function() { return this.n }.bind(obj);
+        [add.name, add.length, add.toString(), add1(2), add1.name, add1.length, getN(),
+         getN.call({n: 20}), add1.toString()].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "add;2;function add(a, b) { return a + b };3;bound add;1;10;10;\
+         function () { [native code] }"
+    );
+
+    // Anonymous functions are named after the variables they are assigned to.
+    let source = "
+        var f = function() {}, g = function named() {}, h;
+        h = function() {};
+        try { throw 0 } catch (e) { e = function() {}; var caught = e }
+        [f.name, g.name, h.name, (function() {}).name, caught.name,
+         [function() {}][0].name].join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "f,named,h,,e,");
+
+    // So are the ones defined as properties in object literals.
+    let source = "
+        var m = 1, obj = {m: function() { return m }, 'a b': function() {}, n: function k() {}};
+        [obj.m.name, obj.m(), obj['a b'].name, obj.n.name].join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "m,1,a b,k");
+
+    // Builtin functions have their names and lengths too.
+    let source = "
+        var desc = Object.getOwnPropertyDescriptor(Math.max, 'name');
+        [Math.max.name, Math.max.length, Function.prototype.toString.call(Math.max),
+         Array.prototype.push.name, Function.prototype.call.name, Function.prototype.call.length,
+         Date.length, Object.name, JSON.stringify.length, desc.writable,
+         desc.enumerable].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "max;2;function max() { [native code] };push;call;1;7;Object;3;false;false"
+    );
+
+    // 'new' ignores the bound 'this' but keeps the bound arguments.
+    let source = "
+        function Point(x, y) { this.x = x; this.y = y }
+        Point.prototype.sum = function() { return this.x + this.y };
+        function Wrapper(v) { return {wrapped: v} }
+        var P1 = Point.bind({x: 'bound this'}, 3), p = new P1(4);
+        var P2 = P1.bind(null, 5), p2 = new P2();
+        var W = Wrapper.bind(null, 'w'), D = Date.bind(null, 0), A = Array.bind(null, 3);
+        [p.x, p.y, p.sum(), Object.getPrototypeOf(p) === Point.prototype, p.constructor === Point,
+         P1.prototype === undefined, p2.sum(), P2.name, P2.length, (new W()).wrapped,
+         (new D()).getTime(), (new A()).length].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "3;4;7;true;true;true;8;bound bound Point;0;w;0;3"
+    );
+
+    let source = "
+        var mul = new Function('a', 'b', 'return a * b'), errors = [];
+        x = 'global';
+        function scope() { var x = 'local'; return Function('return x')() }
+        try { Function('return }') } catch (e) { errors.push(e.name) }
+        try { Function.prototype.bind.call(1) } catch (e) { errors.push(e.name) }
+        [mul(6, 7), mul.name, mul.length, scope(), errors.join()].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "42;anonymous;2;global;SyntaxError,TypeError"
+    );
+}
+
+#[test]
+fn eval_scoping() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    let source = "
+        function direct() {
+            var local = 10;
+            eval('var declared = local * 2; function twice(n) { return n * 2 }');
+            return twice(declared);
+        }
+        function counter() {
+            var n = 0;
+            eval('var inc = function() { n++; return n }');
+            inc();
+            return inc();
+        }
+        function thisInEval() { return eval('this.v') }
+        function nested(a) { return function() { return eval('a + 1') } }
+        function strict() {
+            eval('\"use strict\"; var hidden = 1');
+            try { return hidden } catch (e) { return e.name }
+        }
+        var declares = eval('var a = 1; a + 1');
+        [eval('1 + 2'), declares, a, eval(42), eval(''), direct(), counter(),
+         thisInEval.call({v: 'this'}), nested(2)(), strict(),
+         eval('if (true) { \"yes\" } else { \"no\" }')].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "3;2;1;42;;40;2;this;3;ReferenceError;yes");
+
+    // Indirect eval runs in the global scope.
+    let source = "
+        x = 'global x';
+        function indirect() {
+            var x = 'local x', geval = eval;
+            return [eval('x'), geval('x')].join();
+        }
+        function declaresGlobal() { var geval = eval; geval('var fromIndirect = 1') }
+        declaresGlobal();
+        var obj = {eval: function(s) { return 'not eval: ' + s }};
+        [indirect(), fromIndirect, obj.eval('1')].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "local x,global x;1;not eval: 1");
+
+    let source = "
+        var errors = [];
+        try { eval('var = 1') } catch (e) { errors.push(e.name) }
+        try { eval('throw new RangeError(\"thrown\")') } catch (e) { errors.push(e.message) }
+        errors.join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "SyntaxError,thrown");
+}
+
+#[test]
+fn math() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    // These pairs used to share an id and called the same function.
+    let source = "
+        [Math.atanh(0.5), Math.atan2(1, 2), Math.ceil(1.2), Math.clz32(1), Math.exp(1),
+         Math.expm1(1), Math.log(10), Math.log1p(1)].join()";
+    let expected = [
+        0.5f64.atanh(),
+        1f64.atan2(2.0),
+        2.0,
+        31.0,
+        1f64.exp(),
+        1f64.exp_m1(),
+        10f64.ln(),
+        1f64.ln_1p(),
+    ];
+    let val = runner.run(source).unwrap();
+    let expected = expected
+        .iter()
+        .map(|n| Value::number(*n).to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(val.to_string(), expected.join(","));
+    for (id1, id2) in &[
+        (MATH_ATANH, MATH_ATAN2),
+        (MATH_CEIL, MATH_CLZ32),
+        (MATH_EXP, MATH_EXPM1),
+        (MATH_LOG, MATH_LOG1P),
+    ] {
+        assert_ne!(id1, id2);
+    }
+
+    let source = "
+        [Math.clz32(0), Math.clz32(-1), Math.imul(3, 4), Math.imul(0xffffffff, 5),
+         Math.imul(0x7fffffff, 2), Math.round(2.5), Math.round(-2.5), 1 / Math.round(-0.2),
+         Math.max(), Math.min(), Math.max(1, NaN, 3), 1 / Math.max(-0, 0), 1 / Math.min(0, -0),
+         Math.fround(5.05), Math.sign(-3), Math.hypot(3, 4), Math.hypot(NaN, Infinity),
+         Math.pow(1, Infinity), Math.pow(NaN, 0), Math.abs('-2')].join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "32,0,12,-5,-2,3,-2,-Infinity,-Infinity,Infinity,NaN,Infinity,-Infinity,5.050000190734863,\
+         -1,5,Infinity,NaN,1,2"
+    );
+    let source = "[Math.E, Math.LN2, Math.LN10, Math.LOG2E, Math.LOG10E, Math.PI, Math.SQRT1_2,
+                   Math.SQRT2].join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "2.718281828459045,0.6931471805599453,2.302585092994046,1.4426950408889634,\
+         0.4342944819032518,3.141592653589793,0.7071067811865476,1.4142135623730951"
+    );
+
+    // The same seed gives the same sequence, in [0, 1).
+    let source = "var r = 'x'.repeat(100).split('').map(function() { return Math.random() }); r";
+    runner.vm().seed_random(42);
+    let first = runner.run(source).unwrap().to_string();
+    runner.vm().seed_random(42);
+    assert_eq!(runner.run(source).unwrap().to_string(), first);
+    runner.vm().seed_random(43);
+    assert_ne!(runner.run(source).unwrap().to_string(), first);
+    let source = "r.every(function(x) { return x >= 0 && x < 1 })";
+    assert_eq!(runner.run(source), Ok(Value::bool(true)));
 }
@@ -55,17 +55,32 @@ pub mod VMInst {
     pub const DECL_VAR: u8 = 0x30;
     pub const COND_OP: u8 = 0x31;
     pub const LOOP_START: u8 = 0x32;
+    pub const DELETE: u8 = 0x33;
+    pub const ENTER_TRY: u8 = 0x34;
+    pub const LEAVE_TRY: u8 = 0x35;
+    pub const THROW: u8 = 0x36;
+    pub const CALL_EVAL: u8 = 0x37;
+    pub const EXP: u8 = 0x38;
+    pub const BITWISE_NOT: u8 = 0x39;
+    pub const GET_LOCAL: u8 = 0x3a;
+    pub const SET_LOCAL: u8 = 0x3b;
+    pub const GET_UPVAR: u8 = 0x3c;
+    pub const ENTER_CATCH: u8 = 0x3d;
+    pub const LEAVE_SCOPE: u8 = 0x3e;
 
     pub fn get_inst_size(inst: u8) -> Option<usize> {
         match inst {
             CREATE_CONTEXT => Some(1),
             CONSTRUCT | CREATE_OBJECT | PUSH_CONST | PUSH_INT32 | CREATE_ARRAY | JMP_IF_FALSE
-            | LOOP_START | JMP | DECL_VAR | SET_NAME | GET_NAME | CALL => Some(5),
+            | LOOP_START | JMP | DECL_VAR | SET_NAME | GET_NAME | CALL | ENTER_TRY | CALL_EVAL
+            | GET_LOCAL | SET_LOCAL | ENTER_CATCH => Some(5),
+            GET_UPVAR => Some(9),
             PUSH_INT8 => Some(2),
             PUSH_FALSE | END | PUSH_TRUE | PUSH_THIS | ADD | SUB | MUL | DIV | REM | LT
             | PUSH_ARGUMENTS | NEG | POSI | GT | LE | GE | EQ | NE | GET_MEMBER | RETURN | SNE
             | ZFSHR | POP | DOUBLE | AND | COND_OP | OR | SEQ | SET_MEMBER | SET_CUR_CALLOBJ
-            | PUSH_UNDEFINED | LAND | SHR | SHL | XOR | LOR => Some(1),
+            | PUSH_UNDEFINED | LAND | SHR | SHL | XOR | LOR | DELETE | LEAVE_TRY | THROW | EXP
+            | BITWISE_NOT | LEAVE_SCOPE => Some(1),
             _ => None,
         }
     }
@@ -234,6 +249,12 @@ impl ByteCodeGen {
     pub fn gen_zfshr(&self, iseq: &mut ByteCode) {
         iseq.push(VMInst::ZFSHR);
     }
+    pub fn gen_exp(&self, iseq: &mut ByteCode) {
+        iseq.push(VMInst::EXP);
+    }
+    pub fn gen_bitwise_not(&self, iseq: &mut ByteCode) {
+        iseq.push(VMInst::BITWISE_NOT);
+    }
 
     pub fn gen_land(&self, iseq: &mut ByteCode) {
         iseq.push(VMInst::LAND);
@@ -257,11 +278,20 @@ impl ByteCodeGen {
         iseq.push(VMInst::SET_MEMBER);
     }
 
+    pub fn gen_delete(&self, iseq: &mut ByteCode) {
+        iseq.push(VMInst::DELETE);
+    }
+
     pub fn gen_call(&self, argc: u32, iseq: &mut ByteCode) {
         iseq.push(VMInst::CALL);
         self.gen_int32(argc as i32, iseq);
     }
 
+    pub fn gen_call_eval(&self, argc: u32, iseq: &mut ByteCode) {
+        iseq.push(VMInst::CALL_EVAL);
+        self.gen_int32(argc as i32, iseq);
+    }
+
     pub fn gen_jmp(&self, dst: i32, iseq: &mut ByteCode) {
         iseq.push(VMInst::JMP);
         self.gen_int32(dst, iseq);
@@ -276,6 +306,40 @@ impl ByteCodeGen {
         iseq.push(VMInst::RETURN);
     }
 
+    pub fn gen_enter_try(&self, dst: i32, iseq: &mut ByteCode) {
+        iseq.push(VMInst::ENTER_TRY);
+        self.gen_int32(dst, iseq);
+    }
+
+    pub fn gen_leave_try(&self, iseq: &mut ByteCode) {
+        iseq.push(VMInst::LEAVE_TRY);
+    }
+
+    // Binds the exception on the stack top to 'param' in the scope of a catch block.
+    pub fn gen_enter_catch(&mut self, param: &String, iseq: &mut ByteCode) {
+        let id = (|| {
+            for (i, string) in self.const_table.string.iter().enumerate() {
+                if param == string {
+                    return i;
+                }
+            }
+
+            let id = self.const_table.string.len();
+            self.const_table.string.push(param.clone());
+            id
+        })();
+        iseq.push(VMInst::ENTER_CATCH);
+        self.gen_int32(id as i32, iseq);
+    }
+
+    pub fn gen_leave_scope(&self, iseq: &mut ByteCode) {
+        iseq.push(VMInst::LEAVE_SCOPE);
+    }
+
+    pub fn gen_throw(&self, iseq: &mut ByteCode) {
+        iseq.push(VMInst::THROW);
+    }
+
     pub fn gen_set_cur_callobj(&self, iseq: &mut ByteCode) {
         iseq.push(VMInst::SET_CUR_CALLOBJ);
     }
@@ -328,6 +392,34 @@ impl ByteCodeGen {
         self.gen_int32(id as i32, iseq);
     }
 
+    pub fn gen_get_local(&self, slot: usize, iseq: &mut ByteCode) {
+        iseq.push(VMInst::GET_LOCAL);
+        self.gen_int32(slot as i32, iseq);
+    }
+
+    pub fn gen_set_local(&self, slot: usize, iseq: &mut ByteCode) {
+        iseq.push(VMInst::SET_LOCAL);
+        self.gen_int32(slot as i32, iseq);
+    }
+
+    // Reads 'name' from the environment of the function 'depth' scopes out.
+    pub fn gen_get_upvar(&mut self, depth: usize, name: &String, iseq: &mut ByteCode) {
+        let id = (|| {
+            for (i, string) in self.const_table.string.iter().enumerate() {
+                if name == string {
+                    return i;
+                }
+            }
+
+            let id = self.const_table.string.len();
+            self.const_table.string.push(name.clone());
+            id
+        })();
+        iseq.push(VMInst::GET_UPVAR);
+        self.gen_int32(depth as i32, iseq);
+        self.gen_int32(id as i32, iseq);
+    }
+
     pub fn gen_cond_op(&mut self, iseq: &mut ByteCode) {
         iseq.push(VMInst::COND_OP);
     }
@@ -499,6 +591,54 @@ pub fn show(code: &ByteCode) {
                 println!("DeclVar");
                 i += 5;
             }
+            VMInst::DELETE => {
+                println!("Delete");
+                i += 1;
+            }
+            VMInst::ENTER_TRY => {
+                println!("EnterTry");
+                i += 5;
+            }
+            VMInst::LEAVE_TRY => {
+                println!("LeaveTry");
+                i += 1;
+            }
+            VMInst::THROW => {
+                println!("Throw");
+                i += 1;
+            }
+            VMInst::CALL_EVAL => {
+                println!("CallEval");
+                i += 5;
+            }
+            VMInst::EXP => {
+                println!("Exp");
+                i += 1;
+            }
+            VMInst::BITWISE_NOT => {
+                println!("BitwiseNot");
+                i += 1;
+            }
+            VMInst::GET_LOCAL => {
+                println!("GetLocal");
+                i += 5;
+            }
+            VMInst::SET_LOCAL => {
+                println!("SetLocal");
+                i += 5;
+            }
+            VMInst::GET_UPVAR => {
+                println!("GetUpvar");
+                i += 9;
+            }
+            VMInst::ENTER_CATCH => {
+                println!("EnterCatch");
+                i += 5;
+            }
+            VMInst::LEAVE_SCOPE => {
+                println!("LeaveScope");
+                i += 1;
+            }
             _ => unreachable!(),
         }
     }
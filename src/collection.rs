@@ -0,0 +1,191 @@
+use bigint::BigInt;
+use rustc_hash::FxHashMap;
+use vm::{Value, ValueBase};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollectionKind {
+    Map,
+    Set,
+    WeakMap,
+    WeakSet,
+}
+
+impl CollectionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CollectionKind::Map => "Map",
+            CollectionKind::Set => "Set",
+            CollectionKind::WeakMap => "WeakMap",
+            CollectionKind::WeakSet => "WeakSet",
+        }
+    }
+
+    pub fn is_weak(&self) -> bool {
+        *self == CollectionKind::WeakMap || *self == CollectionKind::WeakSet
+    }
+}
+
+// A key compared by SameValueZero: objects by identity, NaN equals NaN, and -0 equals +0.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CollectionKey {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(u64),
+    BigInt(BigInt),
+    String(Vec<u16>),
+    Object(usize),
+}
+
+// Normalizes -0 to +0 so that it is never seen by keys() and friends, nor by values() of sets.
+pub fn normalize_key(key: Value) -> Value {
+    match key.val {
+        ValueBase::Number(n) if n == 0.0 => Value::number(0.0),
+        _ => key,
+    }
+}
+
+impl CollectionKey {
+    pub fn new(key: &ValueBase) -> CollectionKey {
+        match key {
+            ValueBase::Null => CollectionKey::Null,
+            ValueBase::Bool(b) => CollectionKey::Bool(*b),
+            ValueBase::Number(n) if n.is_nan() => CollectionKey::Number(::std::f64::NAN.to_bits()),
+            ValueBase::Number(n) if *n == 0.0 => CollectionKey::Number(0f64.to_bits()),
+            ValueBase::Number(n) => CollectionKey::Number(n.to_bits()),
+            ValueBase::BigInt(n) => CollectionKey::BigInt(n.clone()),
+            ValueBase::String(s) => CollectionKey::String(s.units().to_vec()),
+            ValueBase::Function(box (_, _, obj, _))
+            | ValueBase::BuiltinFunction(box (_, obj, _)) => CollectionKey::Object(*obj as usize),
+            ValueBase::Object(obj) => CollectionKey::Object(*obj as usize),
+            ValueBase::Array(ary) => CollectionKey::Object(*ary as usize),
+            ValueBase::Proxy(p) => CollectionKey::Object(*p as usize),
+            _ => CollectionKey::Undefined,
+        }
+    }
+}
+
+// The entries of a Map, Set, WeakMap or WeakSet in insertion order.
+// Deleted entries are left as None so that the indices held by iterators stay valid, and entries
+// added during iteration are visited as the spec requires.
+#[derive(Clone, Debug)]
+pub struct Collection {
+    pub kind: CollectionKind,
+    pub entries: Vec<Option<(Value, Value)>>, // (key, value). Sets have the key as the value too.
+    index: FxHashMap<CollectionKey, usize>,
+}
+
+impl Collection {
+    pub fn new(kind: CollectionKind) -> Collection {
+        Collection {
+            kind: kind,
+            entries: vec![],
+            index: FxHashMap::default(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.index
+            .get(&CollectionKey::new(&key.val))
+            .and_then(|i| self.entries[*i].as_ref())
+            .map(|(_, val)| val)
+    }
+
+    pub fn has(&self, key: &Value) -> bool {
+        self.index.contains_key(&CollectionKey::new(&key.val))
+    }
+
+    pub fn set(&mut self, key: Value, val: Value) {
+        let collection_key = CollectionKey::new(&key.val);
+        if let Some(i) = self.index.get(&collection_key) {
+            if let Some((_, ref mut old)) = self.entries[*i] {
+                *old = val;
+            }
+            return;
+        }
+        self.index.insert(collection_key, self.entries.len());
+        self.entries.push(Some((normalize_key(key), val)));
+    }
+
+    pub fn delete(&mut self, key: &Value) -> bool {
+        match self.index.remove(&CollectionKey::new(&key.val)) {
+            Some(i) => {
+                self.entries[i] = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for entry in &mut self.entries {
+            *entry = None;
+        }
+        self.index.clear();
+    }
+
+    // Returns the first entry at or after the index 'n', and the index of it.
+    pub fn next_entry(&self, n: usize) -> Option<(usize, &(Value, Value))> {
+        self.entries
+            .iter()
+            .enumerate()
+            .skip(n)
+            .filter_map(|(i, entry)| entry.as_ref().map(|entry| (i, entry)))
+            .next()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Value, Value)> {
+        self.entries.iter().filter_map(|entry| entry.as_ref())
+    }
+
+    // Removes the entries whose keys don't satisfy 'f'. Used to drop dead keys of weak
+    // collections.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Value) -> bool,
+    {
+        for entry in &mut self.entries {
+            let dead = match entry {
+                Some((ref key, _)) => !f(key),
+                None => false,
+            };
+            if dead {
+                self.index
+                    .remove(&CollectionKey::new(&entry.as_ref().unwrap().0.val));
+                *entry = None;
+            }
+        }
+    }
+}
+
+#[test]
+fn same_value_zero() {
+    use js_string::JsString;
+    let mut map = Collection::new(CollectionKind::Map);
+    map.set(Value::number(::std::f64::NAN), Value::number(1.0));
+    map.set(Value::number(-0.0), Value::number(2.0));
+    map.set(Value::string(JsString::from("a")), Value::number(3.0));
+    assert_eq!(
+        map.get(&Value::number(::std::f64::NAN)),
+        Some(&Value::number(1.0))
+    );
+    assert_eq!(map.get(&Value::number(0.0)), Some(&Value::number(2.0)));
+    assert!(map.has(&Value::string(JsString::from("a"))));
+    assert!(!map.has(&Value::number(3.0)));
+    assert_eq!(map.size(), 3);
+
+    assert!(map.delete(&Value::number(0.0)));
+    assert!(!map.delete(&Value::number(0.0)));
+    assert_eq!(map.size(), 2);
+    assert_eq!(map.next_entry(1).map(|(i, _)| i), Some(2));
+
+    map.set(Value::number(-0.0), Value::number(4.0));
+    match map.iter().last().unwrap().0.val {
+        ValueBase::Number(n) => assert!(n == 0.0 && n.is_sign_positive()),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,680 @@
+use libc;
+use std::ffi::CStr;
+use std::mem;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Time values are milliseconds since 1970-01-01T00:00:00Z, as in the spec.
+// https://tc39.github.io/ecma262/#sec-time-values-and-time-range
+
+const MS_PER_SECOND: f64 = 1000.0;
+const MS_PER_MINUTE: f64 = 60000.0;
+const MS_PER_HOUR: f64 = 3600000.0;
+const MS_PER_DAY: f64 = 86400000.0;
+
+const WEEK_DAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// The indices of the date and time fields, as taken by from_fields().
+pub const YEAR: usize = 0;
+pub const MONTH: usize = 1;
+pub const DATE: usize = 2;
+pub const HOURS: usize = 3;
+pub const MINUTES: usize = 4;
+pub const SECONDS: usize = 5;
+pub const MILLISECONDS: usize = 6;
+pub const WEEK_DAY: usize = 7; // Can't be set
+
+// The current time. This is the default clock of VM.
+pub fn system_clock() -> f64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as f64 * MS_PER_SECOND + d.subsec_millis() as f64,
+        Err(e) => {
+            let d = e.duration();
+            -(d.as_secs() as f64 * MS_PER_SECOND + d.subsec_millis() as f64)
+        }
+    }
+}
+
+// 'x' modulo 'y' whose sign is the same as 'y'.
+fn modulo(x: f64, y: f64) -> f64 {
+    let r = x % y;
+    if r < 0.0 {
+        r + y
+    } else {
+        r + 0.0 // Converts -0 to +0
+    }
+}
+
+fn day(t: f64) -> f64 {
+    (t / MS_PER_DAY).floor()
+}
+
+fn day_from_year(y: f64) -> f64 {
+    365.0 * (y - 1970.0) + ((y - 1969.0) / 4.0).floor() - ((y - 1901.0) / 100.0).floor()
+        + ((y - 1601.0) / 400.0).floor()
+}
+
+fn is_leap_year(y: f64) -> bool {
+    (y % 4.0 == 0.0 && y % 100.0 != 0.0) || y % 400.0 == 0.0
+}
+
+fn year_from_time(t: f64) -> f64 {
+    let mut y = (day(t) / 365.2425).floor() + 1970.0;
+    while day_from_year(y) * MS_PER_DAY > t {
+        y -= 1.0;
+    }
+    while day_from_year(y + 1.0) * MS_PER_DAY <= t {
+        y += 1.0;
+    }
+    y
+}
+
+// The number of days in the year before the first day of each month.
+fn month_start_days(leap: bool) -> [f64; 13] {
+    let l = if leap { 1.0 } else { 0.0 };
+    [
+        0.0,
+        31.0,
+        59.0 + l,
+        90.0 + l,
+        120.0 + l,
+        151.0 + l,
+        181.0 + l,
+        212.0 + l,
+        243.0 + l,
+        273.0 + l,
+        304.0 + l,
+        334.0 + l,
+        365.0 + l,
+    ]
+}
+
+fn days_in_month(year: f64, month: usize) -> f64 {
+    let starts = month_start_days(is_leap_year(year));
+    starts[month + 1] - starts[month]
+}
+
+// Splits the time value 't' into the fields indexed by YEAR to WEEK_DAY. All of them are NaN if
+// 't' is NaN.
+pub fn to_fields(t: f64) -> [f64; 8] {
+    if !t.is_finite() {
+        return [::std::f64::NAN; 8];
+    }
+    let year = year_from_time(t);
+    let day_in_year = day(t) - day_from_year(year);
+    let starts = month_start_days(is_leap_year(year));
+    let month = (0..12).rev().find(|m| starts[*m] <= day_in_year).unwrap();
+    [
+        year,
+        month as f64,
+        day_in_year - starts[month] + 1.0,
+        modulo((t / MS_PER_HOUR).floor(), 24.0),
+        modulo((t / MS_PER_MINUTE).floor(), 60.0),
+        modulo((t / MS_PER_SECOND).floor(), 60.0),
+        modulo(t, MS_PER_SECOND),
+        modulo(day(t) + 4.0, 7.0),
+    ]
+}
+
+// https://tc39.github.io/ecma262/#sec-maketime
+fn make_time(hour: f64, min: f64, sec: f64, ms: f64) -> f64 {
+    if !hour.is_finite() || !min.is_finite() || !sec.is_finite() || !ms.is_finite() {
+        return ::std::f64::NAN;
+    }
+    hour.trunc() * MS_PER_HOUR
+        + min.trunc() * MS_PER_MINUTE
+        + sec.trunc() * MS_PER_SECOND
+        + ms.trunc()
+}
+
+// https://tc39.github.io/ecma262/#sec-makeday
+fn make_day(year: f64, month: f64, date: f64) -> f64 {
+    if !year.is_finite() || !month.is_finite() || !date.is_finite() {
+        return ::std::f64::NAN;
+    }
+    let y = year.trunc() + (month.trunc() / 12.0).floor();
+    // Far beyond the range of time values, which is about 285616 years from 1970.
+    if y.abs() > 400000.0 {
+        return ::std::f64::NAN;
+    }
+    let m = modulo(month.trunc(), 12.0) as usize;
+    day_from_year(y) + month_start_days(is_leap_year(y))[m] + date.trunc() - 1.0
+}
+
+// https://tc39.github.io/ecma262/#sec-makedate
+fn make_date(day: f64, time: f64) -> f64 {
+    if !day.is_finite() || !time.is_finite() {
+        return ::std::f64::NAN;
+    }
+    day * MS_PER_DAY + time
+}
+
+// https://tc39.github.io/ecma262/#sec-timeclip
+pub fn time_clip(t: f64) -> f64 {
+    if !t.is_finite() || t.abs() > 8.64e15 {
+        return ::std::f64::NAN;
+    }
+    t.trunc() + 0.0 // Converts -0 to +0
+}
+
+// Makes a time value (not clipped) from the fields indexed by YEAR to MILLISECONDS.
+pub fn from_fields(fields: &[f64]) -> f64 {
+    make_date(
+        make_day(fields[YEAR], fields[MONTH], fields[DATE]),
+        make_time(
+            fields[HOURS],
+            fields[MINUTES],
+            fields[SECONDS],
+            fields[MILLISECONDS],
+        ),
+    )
+}
+
+// The offset of the local time zone from UTC at the time value 't' in milliseconds, and the
+// abbreviation of the time zone like "JST". They are given by localtime_r(3), so the TZ
+// environment variable is respected.
+pub fn local_time_zone(t: f64) -> (f64, String) {
+    if !t.is_finite() {
+        return (0.0, "UTC".to_string());
+    }
+    unsafe {
+        let time = (t / MS_PER_SECOND).floor() as libc::time_t;
+        let mut tm: libc::tm = mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() || tm.tm_zone.is_null() {
+            return (0.0, "UTC".to_string());
+        }
+        (
+            tm.tm_gmtoff as f64 * MS_PER_SECOND,
+            CStr::from_ptr(tm.tm_zone).to_string_lossy().into_owned(),
+        )
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-localtime
+pub fn local_time(t: f64) -> f64 {
+    t + local_time_zone(t).0
+}
+
+// https://tc39.github.io/ecma262/#sec-utc-t
+// A local time repeated when the clock is turned back is taken as the earlier one, and one
+// skipped when the clock is turned forward is converted with the offset before the transition.
+pub fn utc(t: f64) -> f64 {
+    let guess = t - local_time_zone(t).0;
+    let before = local_time_zone(guess - MS_PER_HOUR).0;
+    let after = local_time_zone(guess + MS_PER_HOUR).0;
+    if local_time(t - before) == t {
+        t - before
+    } else if local_time(t - after) == t {
+        t - after
+    } else {
+        t - before
+    }
+}
+
+// Parses the format of toISOString() and its subsets.
+// https://tc39.github.io/ecma262/#sec-date-time-string-format
+fn parse_iso(s: &str) -> Option<f64> {
+    let b = s.as_bytes();
+    let mut p = 0;
+
+    let digits = |p: &mut usize, n: usize| -> Option<f64> {
+        if *p + n > b.len() || !b[*p..*p + n].iter().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let n_ = s[*p..*p + n].parse::<f64>().unwrap();
+        *p += n;
+        Some(n_)
+    };
+    let skip = |p: &mut usize, c: u8| -> bool {
+        if b.get(*p) == Some(&c) {
+            *p += 1;
+            true
+        } else {
+            false
+        }
+    };
+
+    let year = match b.get(0) {
+        Some(b'+') | Some(b'-') => {
+            p += 1;
+            let y = digits(&mut p, 6)?;
+            if b[0] == b'-' {
+                if y == 0.0 {
+                    return None; // -000000 is not allowed
+                }
+                -y
+            } else {
+                y
+            }
+        }
+        _ => digits(&mut p, 4)?,
+    };
+    let mut month = 1.0;
+    let mut date = 1.0;
+    if skip(&mut p, b'-') {
+        month = digits(&mut p, 2)?;
+        if skip(&mut p, b'-') {
+            date = digits(&mut p, 2)?;
+        }
+    }
+
+    let (mut hour, mut min, mut sec, mut ms) = (0.0, 0.0, 0.0, 0.0);
+    let mut offset = None;
+    let has_time = skip(&mut p, b'T');
+    if has_time {
+        hour = digits(&mut p, 2)?;
+        if !skip(&mut p, b':') {
+            return None;
+        }
+        min = digits(&mut p, 2)?;
+        if skip(&mut p, b':') {
+            sec = digits(&mut p, 2)?;
+            if skip(&mut p, b'.') {
+                let start = p;
+                while p < b.len() && b[p].is_ascii_digit() {
+                    p += 1;
+                }
+                if start == p {
+                    return None;
+                }
+                // Digits beyond milliseconds are ignored.
+                let frac = &s[start..p.min(start + 3)];
+                ms = frac.parse::<f64>().unwrap() * 10f64.powi(3 - frac.len() as i32);
+            }
+        }
+        match b.get(p) {
+            Some(b'Z') => {
+                p += 1;
+                offset = Some(0.0);
+            }
+            Some(&sign) if sign == b'+' || sign == b'-' => {
+                p += 1;
+                let h = digits(&mut p, 2)?;
+                if !skip(&mut p, b':') {
+                    return None;
+                }
+                let m = digits(&mut p, 2)?;
+                let sign = if sign == b'-' { -1.0 } else { 1.0 };
+                offset = Some(sign * (h * MS_PER_HOUR + m * MS_PER_MINUTE));
+            }
+            _ => {}
+        }
+    }
+
+    if p != b.len()
+        || month < 1.0
+        || month > 12.0
+        || date < 1.0
+        || date > days_in_month(year, month as usize - 1)
+        || hour > 24.0
+        || (hour == 24.0 && (min > 0.0 || sec > 0.0 || ms > 0.0))
+        || min > 59.0
+        || sec > 59.0
+    {
+        return None;
+    }
+
+    let t = from_fields(&[year, month - 1.0, date, hour, min, sec, ms]);
+    // Date-only forms are UTC, and date-time forms without an offset are local time.
+    Some(match offset {
+        Some(offset) => t - offset,
+        None if has_time => utc(t),
+        None => t,
+    })
+}
+
+// Parses the formats like "Tue, 01 Jan 2019 00:00:00 GMT" (RFC 2822), "Tue Jan 01 2019 09:00:00
+// GMT+0900 (JST)" (toString()), "January 1, 2019 10:00 PM" and "1/31/2019".
+fn parse_informal(s: &str) -> Option<f64> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut p = 0;
+    let (mut year, mut month, mut date) = (None, None, None);
+    let (mut hour, mut min, mut sec, mut ms) = (0.0, 0.0, 0.0, 0.0);
+    let mut pm = None;
+    let mut offset = None;
+    let mut seen_time = false;
+
+    let read_number = |p: &mut usize| -> (f64, usize) {
+        let start = *p;
+        while *p < chars.len() && chars[*p].is_ascii_digit() {
+            *p += 1;
+        }
+        let digits: String = chars[start..*p].iter().collect();
+        (digits.parse::<f64>().unwrap_or(::std::f64::NAN), *p - start)
+    };
+
+    while p < chars.len() {
+        let c = chars[p];
+        if c.is_whitespace() || c == ',' || c == '.' {
+            p += 1;
+        } else if c == '(' {
+            // A comment like "(Japan Standard Time)"
+            while p < chars.len() && chars[p] != ')' {
+                p += 1;
+            }
+            p += 1;
+        } else if c.is_ascii_alphabetic() {
+            let start = p;
+            while p < chars.len() && chars[p].is_ascii_alphabetic() {
+                p += 1;
+            }
+            let word: String = chars[start..p].iter().collect::<String>().to_lowercase();
+            let zone_offset = |hours: f64| Some(hours * MS_PER_HOUR);
+            match word.as_str() {
+                "am" | "pm" => pm = Some(word == "pm"),
+                "z" | "gmt" | "ut" | "utc" => offset = zone_offset(0.0),
+                "edt" => offset = zone_offset(-4.0),
+                "est" | "cdt" => offset = zone_offset(-5.0),
+                "cst" | "mdt" => offset = zone_offset(-6.0),
+                "mst" | "pdt" => offset = zone_offset(-7.0),
+                "pst" => offset = zone_offset(-8.0),
+                _ if word.len() >= 3 => {
+                    if let Some(m) = MONTH_NAMES
+                        .iter()
+                        .position(|name| word.starts_with(&name.to_lowercase()))
+                    {
+                        month = Some(m as f64);
+                    } else if !WEEK_DAY_NAMES
+                        .iter()
+                        .any(|name| word.starts_with(&name.to_lowercase()))
+                    {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        } else if (c == '+' || c == '-')
+            && (seen_time || offset.is_some())
+            && p + 1 < chars.len()
+            && chars[p + 1].is_ascii_digit()
+        {
+            // An offset like "+0900" or "-05:00"
+            p += 1;
+            let (n, len) = read_number(&mut p);
+            let (h, m) = if len <= 2 && p < chars.len() && chars[p] == ':' {
+                p += 1;
+                (n, read_number(&mut p).0)
+            } else if len <= 2 {
+                (n, 0.0)
+            } else {
+                ((n / 100.0).floor(), n % 100.0)
+            };
+            let sign = if c == '-' { -1.0 } else { 1.0 };
+            offset = Some(sign * (h * MS_PER_HOUR + m * MS_PER_MINUTE));
+        } else if c.is_ascii_digit() {
+            let (n, len) = read_number(&mut p);
+            if p < chars.len() && chars[p] == ':' {
+                // Time like "10:20", "10:20:30" or "10:20:30.400"
+                seen_time = true;
+                hour = n;
+                p += 1;
+                min = read_number(&mut p).0;
+                if p < chars.len() && chars[p] == ':' {
+                    p += 1;
+                    sec = read_number(&mut p).0;
+                    if p + 1 < chars.len() && chars[p] == '.' && chars[p + 1].is_ascii_digit() {
+                        p += 1;
+                        let (n, len) = read_number(&mut p);
+                        ms = (n * 10f64.powi(3 - len as i32)).trunc();
+                    }
+                }
+            } else if len == 4 && p < chars.len() && (chars[p] == '-' || chars[p] == '/') {
+                // "year-month-date" followed by something ISO 8601 doesn't allow, or
+                // "year/month/date"
+                let separator = chars[p];
+                year = Some(n);
+                p += 1;
+                month = Some(read_number(&mut p).0 - 1.0);
+                if p < chars.len() && chars[p] == separator {
+                    p += 1;
+                    date = Some(read_number(&mut p).0);
+                }
+            } else if p < chars.len() && chars[p] == '/' {
+                // "month/date/year"
+                month = Some(n - 1.0);
+                p += 1;
+                date = Some(read_number(&mut p).0);
+                if p < chars.len() && chars[p] == '/' {
+                    p += 1;
+                    year = Some(read_number(&mut p).0);
+                }
+            } else if len >= 3 || n > 31.0 || date.is_some() {
+                if year.is_some() {
+                    return None;
+                }
+                year = Some(if len <= 2 {
+                    // Two-digit years as most implementations do.
+                    if n < 50.0 {
+                        2000.0 + n
+                    } else {
+                        1900.0 + n
+                    }
+                } else {
+                    n
+                });
+            } else {
+                date = Some(n);
+            }
+        } else {
+            return None;
+        }
+    }
+
+    let (year, month, date) = (year?, month?, date.unwrap_or(1.0));
+    if month < 0.0
+        || month > 11.0
+        || date < 1.0
+        || date > 31.0
+        || hour > 24.0
+        || min > 59.0
+        || sec > 59.0
+    {
+        return None;
+    }
+    match pm {
+        Some(true) if hour < 12.0 => hour += 12.0,
+        Some(false) if hour == 12.0 => hour = 0.0,
+        _ => {}
+    }
+    let t = from_fields(&[year, month, date, hour, min, sec, ms]);
+    Some(match offset {
+        Some(offset) => t - offset,
+        None => utc(t),
+    })
+}
+
+// https://tc39.github.io/ecma262/#sec-date.parse
+pub fn parse(s: &str) -> f64 {
+    let s = s.trim();
+    match parse_iso(s).or_else(|| parse_informal(s)) {
+        Some(t) => time_clip(t),
+        None => ::std::f64::NAN,
+    }
+}
+
+fn year_to_string(year: f64) -> String {
+    if year < 0.0 {
+        format!("-{:04}", -year)
+    } else {
+        format!("{:04}", year)
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-date.prototype.toisostring
+// The time value must be finite.
+pub fn to_iso_string(t: f64) -> String {
+    let f = to_fields(t);
+    let year = if 0.0 <= f[YEAR] && f[YEAR] <= 9999.0 {
+        format!("{:04}", f[YEAR])
+    } else if f[YEAR] < 0.0 {
+        format!("-{:06}", -f[YEAR])
+    } else {
+        format!("+{:06}", f[YEAR])
+    };
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        f[MONTH] + 1.0,
+        f[DATE],
+        f[HOURS],
+        f[MINUTES],
+        f[SECONDS],
+        f[MILLISECONDS]
+    )
+}
+
+// https://tc39.github.io/ecma262/#sec-date.prototype.toutcstring
+pub fn to_utc_string(t: f64) -> String {
+    if !t.is_finite() {
+        return "Invalid Date".to_string();
+    }
+    let f = to_fields(t);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEK_DAY_NAMES[f[WEEK_DAY] as usize],
+        f[DATE],
+        MONTH_NAMES[f[MONTH] as usize],
+        year_to_string(f[YEAR]),
+        f[HOURS],
+        f[MINUTES],
+        f[SECONDS]
+    )
+}
+
+// https://tc39.github.io/ecma262/#sec-date.prototype.tostring
+pub fn to_string(t: f64) -> String {
+    if !t.is_finite() {
+        return "Invalid Date".to_string();
+    }
+    let (offset, zone_name) = local_time_zone(t);
+    let f = to_fields(t + offset);
+    let offset_min = (offset / MS_PER_MINUTE).abs();
+    format!(
+        "{} {} {:02} {} {:02}:{:02}:{:02} GMT{}{:02}{:02} ({})",
+        WEEK_DAY_NAMES[f[WEEK_DAY] as usize],
+        MONTH_NAMES[f[MONTH] as usize],
+        f[DATE],
+        year_to_string(f[YEAR]),
+        f[HOURS],
+        f[MINUTES],
+        f[SECONDS],
+        if offset < 0.0 { '-' } else { '+' },
+        (offset_min / 60.0).floor(),
+        offset_min % 60.0,
+        zone_name
+    )
+}
+
+#[test]
+fn fields() {
+    assert_eq!(to_fields(0.0), [1970.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 4.0]);
+    // 2000-02-29T23:59:59.999Z, a leap day
+    let t = 951868799999.0;
+    assert_eq!(
+        to_fields(t),
+        [2000.0, 1.0, 29.0, 23.0, 59.0, 59.0, 999.0, 2.0]
+    );
+    assert_eq!(from_fields(&to_fields(t)), t);
+    // Before the epoch
+    assert_eq!(
+        to_fields(-1.0),
+        [1969.0, 11.0, 31.0, 23.0, 59.0, 59.0, 999.0, 3.0]
+    );
+    // Out-of-range fields are carried over.
+    assert_eq!(
+        from_fields(&[2018.0, 12.0, 32.0, 0.0, 0.0, 0.0, 0.0]),
+        from_fields(&[2019.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0])
+    );
+    assert!(time_clip(8.64e15 + 1.0).is_nan());
+}
+
+#[test]
+fn parse_and_format() {
+    for (s, t) in &[
+        ("1970-01-01T00:00:00.000Z", 0.0),
+        ("2019-01-31", 1548892800000.0),
+        ("2019-01", 1546300800000.0),
+        ("2019-01-31T10:20:30.4+09:00", 1548897630400.0),
+        ("+275760-09-13T00:00:00.000Z", 8.64e15),
+        ("-000001-01-01T00:00:00Z", -62198755200000.0),
+        ("Thu, 31 Jan 2019 01:20:30 GMT", 1548897630000.0),
+        ("Thu Jan 31 2019 10:20:30 GMT+0900 (JST)", 1548897630000.0),
+        ("January 31, 2019 1:20:30 AM UTC", 1548897630000.0),
+        ("31 Jan 2019 20:20:30 EST", 1548984030000.0),
+        ("2019-01-31 01:20:30 +0000", 1548897630000.0),
+    ] {
+        assert_eq!(parse(s), *t, "{}", s);
+    }
+    for s in &[
+        "",
+        "2019-13-01",
+        "Jan 32 2019",
+        "2019-01-01T24:00:01Z",
+        "-000000-01-01T00:00:00Z",
+        "Foo 1 2019",
+    ] {
+        assert!(parse(s).is_nan(), "{}", s);
+    }
+
+    assert_eq!(to_iso_string(1548897630400.0), "2019-01-31T01:20:30.400Z");
+    assert_eq!(to_iso_string(8.64e15), "+275760-09-13T00:00:00.000Z");
+    assert_eq!(
+        to_iso_string(-62198755200000.0),
+        "-000001-01-01T00:00:00.000Z"
+    );
+    assert_eq!(
+        to_utc_string(1548897630400.0),
+        "Thu, 31 Jan 2019 01:20:30 GMT"
+    );
+    assert_eq!(to_utc_string(::std::f64::NAN), "Invalid Date");
+}
+
+#[test]
+fn local_time_round_trip() {
+    // Whatever the time zone is, UTC() is the inverse of LocalTime() except around transitions.
+    let t = 1548897630400.0;
+    assert_eq!(utc(local_time(t)), t);
+    assert_eq!(parse(&to_string(t)), (t / 1000.0).floor() * 1000.0);
+}
+
+#[test]
+fn dst_transitions() {
+    use std::env;
+    use std::process::Command;
+
+    // TZ applies to the whole process, so the test runs again in a process of its own.
+    let tz = "EST5EDT,M3.2.0,M11.1.0";
+    if env::var("TZ").ok().as_ref().map(|tz| tz.as_str()) != Some(tz) {
+        let status = Command::new(env::current_exe().unwrap())
+            .args(&["--exact", "date::dst_transitions"])
+            .env("TZ", tz)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        return;
+    }
+
+    // 2019-03-10 02:30 is skipped and taken as 03:30 EDT. 01:30 is still EST.
+    assert_eq!(utc(1552185000000.0), 1552203000000.0);
+    assert_eq!(utc(1552181400000.0), 1552199400000.0);
+    // 2019-11-03 01:30 comes twice and is taken as the earlier one in EDT. 02:30 is EST.
+    assert_eq!(utc(1572744600000.0), 1572759000000.0);
+    assert_eq!(utc(1572748200000.0), 1572766200000.0);
+}
+
+#[test]
+fn injected_clock() {
+    use builtin;
+    use std::rc::Rc;
+    use vm::{CallObject, Value, VM};
+    use vm_codegen::VMCodeGen;
+
+    let mut vm = VM::new(VMCodeGen::new().global_varmap);
+    vm.clock = Rc::new(|| 1548897630400.5);
+    unsafe {
+        builtin::date_now(CallObject::new(Value::undefined()), vec![], &mut vm).unwrap();
+    }
+    assert_eq!(vm.state.stack.pop(), Some(Value::number(1548897630400.0)));
+}
@@ -0,0 +1,583 @@
+// An interface to run JS code from Rust programs, which hides the steps to parse, compile and run
+// code in a VM:
+//
+//     let mut engine = Engine::new();
+//     engine.set_global("n", 10.0);
+//     let sum: f64 = engine.eval("var s = 0; for (var i = 1; i <= n; i++) s += i; s")?.try_into()?;
+//
+// Each engine has a realm of its own (see the realm module), so engines never share objects. The
+// GC doesn't know the values held by Rust code. A value returned by the engine, or converted by
+// value_from(), is kept alive until the next run ends, so store it in a global variable (see
+// set_global) to keep it longer. Converting arrays and objects into Rust values reads them, so
+// get() is unsafe for values that may have been collected since. Values of other engines are
+// rejected.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::hash::BuildHasher;
+
+use builtin;
+use extract_anony_func::AnonymousFunctionExtractor;
+use gc::{self, Heap};
+use host::{HostArgs, JsError};
+use js_string::JsString;
+use parser::Parser;
+use realm;
+use vm::{self, ArrayValue, PropertyMap, RuntimeError, Value, ValueBase, VM};
+use vm_codegen::VMCodeGen;
+
+pub struct Engine {
+    vm: VM,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    Syntax {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    Exception(Value), // A value thrown and not caught, including the errors the VM raised
+    Conversion(String), // A value couldn't be converted into the Rust type
+    Terminated(String), // An execution limit was hit (see VM::set_fuel etc.)
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            vm: VM::new(VMCodeGen::new().global_varmap),
+        }
+    }
+
+    /// The VM that runs the code, e.g. to register host functions.
+    pub fn vm(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    /// Runs 'source' as a script and returns its completion value (the value of the last
+    /// expression statement, like the REPL shows). The variables and functions declared in it
+    /// become global ones.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        use parser::Error::*;
+
+        let mut parser = Parser::new(source.to_string());
+        let mut node = match parser.parse_all() {
+            Ok(node) => node,
+            Err(err) => {
+                let (pos, message) = match err {
+                    Expect(pos, _, msg)
+                    | UnexpectedEOF(pos, _, msg)
+                    | UnexpectedToken(pos, _, msg) => (pos, msg),
+                    UnsupportedFeature(pos) => (pos, "unsupported feature".to_string()),
+                    NormalEOF => (source.len(), "unexpected end of input".to_string()),
+                };
+                let (line, column) = parser.lexer.get_line_and_column(pos);
+                return Err(Error::Syntax {
+                    message: message,
+                    line: line,
+                    column: column,
+                });
+            }
+        };
+        AnonymousFunctionExtractor::new().run_toplevel(&mut node);
+
+        realm::enter(&self.vm.realm);
+        let global = self.vm.state.scope[0];
+        let result =
+            unsafe { builtin::run_in_scope(&mut self.vm, &parser, &node, global, "anonymous") };
+        self.finish(result)
+    }
+
+    /// Calls 'function' with 'args' and undefined as 'this'. Returns Error::Conversion if any of
+    /// them is a value of another engine.
+    pub fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Value, Error> {
+        if !is_in_heap(&self.vm.realm.heap, function)
+            || !args.iter().all(|arg| is_in_heap(&self.vm.realm.heap, arg))
+        {
+            return Err(foreign_value());
+        }
+        realm::enter(&self.vm.realm);
+        let result = unsafe {
+            builtin::call_callback(&mut self.vm, function, Value::undefined(), &args)
+                .map(|val| self.vm.state.stack.push(val))
+        };
+        self.finish(result)
+    }
+
+    /// The value of the global variable 'name', if it's defined.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        unsafe { (*self.vm.state.scope[0]).get_value(&name.to_string()).ok() }
+    }
+
+    /// Sets the global variable 'name' to 'val'. Panics if 'val' is a value of another engine.
+    pub fn set_global<V: IntoValue>(&mut self, name: &str, val: V) {
+        let val = val.into_value(&mut self.vm);
+        unsafe { (*self.vm.state.scope[0]).set_value(name.to_string(), val) }
+    }
+
+    /// Converts 'val' into a JS value allocated in the realm of the engine, e.g. to pass it to
+    /// call(). Like the values returned, it's kept alive until the next run ends.
+    pub fn value_from<V: IntoValue>(&mut self, val: V) -> Value {
+        let val = val.into_value(&mut self.vm);
+        self.vm.state.stack.push(val.clone());
+        val
+    }
+
+    /// Converts 'val', a value of the engine, into a Rust value. Returns Error::Conversion if it
+    /// is of another type or of another engine.
+    ///
+    /// Unsafe since the arrays and objects 'val' refers to are read. They must be alive: returned
+    /// by the engine since the last run, or kept in a global variable.
+    pub unsafe fn get<T: FromValue>(&self, val: &Value) -> Result<T, Error> {
+        if !is_in_heap(&self.vm.realm.heap, val) {
+            return Err(foreign_value());
+        }
+        T::from_value(val)
+    }
+
+    /// Defines the global function 'name' that calls 'f' (see the host module).
+    pub fn register_global_function<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(&mut VM, HostArgs) -> Result<Value, JsError> + 'static,
+    {
+        self.vm.register_global_function(name, f)
+    }
+
+    // Takes the value a run left on the stack, or the exception that stopped it. The VM is reset
+    // in both cases so that it can run code again, but the value is left on the stack so that
+    // the GC doesn't collect it until the next run ends.
+    fn finish(&mut self, result: Result<(), RuntimeError>) -> Result<Value, Error> {
+        let (val, thrown) = match result {
+            Ok(()) => (self.vm.state.stack.pop().unwrap(), false),
+            Err(RuntimeError::Terminated(msg)) => {
+                self.vm.unwind();
+                return Err(Error::Terminated(msg));
+            }
+            Err(err) => (self.vm.exception_value(err), true),
+        };
+        self.vm.unwind();
+        self.vm.state.stack.push(val.clone());
+        if thrown {
+            Err(Error::Exception(val))
+        } else {
+            Ok(val)
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Syntax {
+                message,
+                line,
+                column,
+            } => write!(f, "syntax error({}:{}): {}", line, column, message),
+            Error::Exception(val) => write!(f, "{}", vm::uncaught_exception_message(val)),
+            Error::Conversion(msg) => write!(f, "conversion error: {}", msg),
+            Error::Terminated(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+// Lets host functions propagate the errors of the conversions with '?'.
+impl From<Error> for JsError {
+    fn from(err: Error) -> JsError {
+        match err {
+            Error::Exception(val) => JsError::Throw(val),
+            Error::Terminated(msg) => JsError::Terminated(msg),
+            err => JsError::TypeError(err.to_string()),
+        }
+    }
+}
+
+// Whether the object 'val' refers to, if any, is allocated in 'heap'. A collected object is not,
+// unless another one has been allocated at its address since.
+fn is_in_heap(heap: &Heap, val: &Value) -> bool {
+    match val.val {
+        ValueBase::Function(box (_, _, obj, _)) | ValueBase::Object(obj) => heap.contains(obj),
+        ValueBase::BuiltinFunction(box (_, obj, _)) => obj.is_null() || heap.contains(obj),
+        ValueBase::Array(ary) => heap.contains(ary),
+        ValueBase::Collection(collection) => heap.contains(collection),
+        ValueBase::Proxy(proxy) => heap.contains(proxy),
+        ValueBase::ArrayBuffer(buffer) => heap.contains(buffer),
+        ValueBase::TypedArray(typed_array) => heap.contains(typed_array),
+        _ => true,
+    }
+}
+
+fn foreign_value() -> Error {
+    Error::Conversion("the value is of another engine".to_string())
+}
+
+// Conversions from Rust values into JS values. Those not allocating objects are From<T>, and all
+// of them are IntoValue, which allocates in the realm of a VM.
+
+/// Rust values that can be converted into JS values (see Engine::value_from()).
+pub trait IntoValue {
+    /// Panics if the value is a JS value of another realm.
+    fn into_value(self, vm: &mut VM) -> Value;
+}
+
+impl IntoValue for Value {
+    fn into_value(self, vm: &mut VM) -> Value {
+        assert!(is_in_heap(&vm.realm.heap, &self), "{}", foreign_value());
+        self
+    }
+}
+
+macro_rules! into_value_from {
+    ($($t:ty),*) => {
+        $(
+            impl IntoValue for $t {
+                fn into_value(self, _: &mut VM) -> Value {
+                    self.into()
+                }
+            }
+        )*
+    };
+}
+
+into_value_from!(bool, f64, i32, u32, String, JsString);
+
+impl<'a> IntoValue for &'a str {
+    fn into_value(self, _: &mut VM) -> Value {
+        self.into()
+    }
+}
+
+/// None is converted into undefined.
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self, vm: &mut VM) -> Value {
+        self.map_or_else(Value::undefined, |val| val.into_value(vm))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self, vm: &mut VM) -> Value {
+        let elems = self.into_iter().map(|elem| elem.into_value(vm)).collect();
+        realm::enter(&vm.realm);
+        Value::array(gc::new(ArrayValue::new(elems)))
+    }
+}
+
+impl<T: IntoValue, S: BuildHasher> IntoValue for HashMap<String, T, S> {
+    fn into_value(self, vm: &mut VM) -> Value {
+        let mut obj = PropertyMap::default();
+        for (key, val) in self {
+            obj.insert(JsString::from(key).to_key(), val.into_value(vm));
+        }
+        realm::enter(&vm.realm);
+        Value::plain_object(obj)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::bool(b)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::number(n)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Value {
+        Value::number(n as f64)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Value {
+        Value::number(n as f64)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Value {
+        Value::string(JsString::from(s))
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::string(JsString::from(s))
+    }
+}
+
+impl From<JsString> for Value {
+    fn from(s: JsString) -> Value {
+        Value::string(s)
+    }
+}
+
+/// None is converted into undefined.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(val: Option<T>) -> Value {
+        val.map_or_else(Value::undefined, |val| val.into())
+    }
+}
+
+// Conversions from JS values into Rust values. Unlike the arguments of host functions (see
+// FromArgument), values are not coerced, so e.g. "1" can't be converted into f64. Those of
+// primitives are TryFrom<Value>, and all of them are FromValue, which reads arrays and objects.
+
+/// Rust types that JS values can be converted into (see Engine::get()).
+pub trait FromValue: Sized {
+    /// Unsafe since the arrays and objects 'val' refers to are read, which must be alive.
+    unsafe fn from_value(val: &Value) -> Result<Self, Error>;
+}
+
+macro_rules! from_value_try_from {
+    ($($t:ty),*) => {
+        $(
+            impl FromValue for $t {
+                unsafe fn from_value(val: &Value) -> Result<$t, Error> {
+                    <$t>::try_from(val.clone())
+                }
+            }
+        )*
+    };
+}
+
+from_value_try_from!(bool, f64, i32, u32, String, JsString);
+
+impl FromValue for Value {
+    unsafe fn from_value(val: &Value) -> Result<Value, Error> {
+        Ok(val.clone())
+    }
+}
+
+/// undefined and null are converted into None.
+impl<T: FromValue> FromValue for Option<T> {
+    unsafe fn from_value(val: &Value) -> Result<Option<T>, Error> {
+        match val.val {
+            ValueBase::Undefined | ValueBase::Null => Ok(None),
+            _ => Ok(Some(T::from_value(val)?)),
+        }
+    }
+}
+
+fn type_mismatch(expected: &str, val: &Value) -> Error {
+    let actual = match val.val {
+        ValueBase::Empty | ValueBase::Undefined => "undefined",
+        ValueBase::Null => "null",
+        ValueBase::Bool(_) => "a boolean",
+        ValueBase::Number(_) => "a number",
+        ValueBase::BigInt(_) => "a bigint",
+        ValueBase::String(_) => "a string",
+        ValueBase::Function(_) | ValueBase::BuiltinFunction(_) => "a function",
+        ValueBase::Array(_) => "an array",
+        _ => "an object",
+    };
+    Error::Conversion(format!("expected {} but got {}", expected, actual))
+}
+
+impl TryFrom<Value> for bool {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<bool, Error> {
+        match val.val {
+            ValueBase::Bool(b) => Ok(b),
+            _ => Err(type_mismatch("a boolean", &val)),
+        }
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<f64, Error> {
+        match val.val {
+            ValueBase::Number(n) => Ok(n),
+            _ => Err(type_mismatch("a number", &val)),
+        }
+    }
+}
+
+/// Only numbers that are integers in the range of i32 are converted.
+impl TryFrom<Value> for i32 {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<i32, Error> {
+        match val.val {
+            ValueBase::Number(n) if n as i32 as f64 == n => Ok(n as i32),
+            _ => Err(type_mismatch("an integer in the range of i32", &val)),
+        }
+    }
+}
+
+/// Only numbers that are integers in the range of u32 are converted.
+impl TryFrom<Value> for u32 {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<u32, Error> {
+        match val.val {
+            ValueBase::Number(n) if n as u32 as f64 == n => Ok(n as u32),
+            _ => Err(type_mismatch("an integer in the range of u32", &val)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<String, Error> {
+        match val.val {
+            ValueBase::String(ref s) => Ok(s.to_string()),
+            _ => Err(type_mismatch("a string", &val)),
+        }
+    }
+}
+
+impl TryFrom<Value> for JsString {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<JsString, Error> {
+        match val.val {
+            ValueBase::String(s) => Ok(s),
+            _ => Err(type_mismatch("a string", &val)),
+        }
+    }
+}
+
+/// undefined and null are converted into None.
+impl<T: TryFrom<Value, Error = Error>> TryFrom<Value> for Option<T> {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<Option<T>, Error> {
+        match val.val {
+            ValueBase::Undefined | ValueBase::Null => Ok(None),
+            _ => Ok(Some(T::try_from(val)?)),
+        }
+    }
+}
+
+/// Holes in the array are converted as undefined.
+impl<T: FromValue> FromValue for Vec<T> {
+    unsafe fn from_value(val: &Value) -> Result<Vec<T>, Error> {
+        let ary = match val.val {
+            ValueBase::Array(ary) => &*ary,
+            _ => return Err(type_mismatch("an array", val)),
+        };
+        ary.elems[..ary.length]
+            .iter()
+            .map(|elem| match elem.val {
+                ValueBase::Empty => T::from_value(&Value::undefined()),
+                ref elem => T::from_value(&Value::new(elem.clone())),
+            })
+            .collect()
+    }
+}
+
+/// The own enumerable properties of the object are converted.
+impl<T: FromValue, S: BuildHasher + Default> FromValue for HashMap<String, T, S> {
+    unsafe fn from_value(val: &Value) -> Result<HashMap<String, T, S>, Error> {
+        match val.val {
+            ValueBase::Object(_) => {}
+            _ => return Err(type_mismatch("an object", val)),
+        }
+        let mut map = HashMap::default();
+        for key in val.own_property_keys() {
+            match val.get_own_property(key.as_str()) {
+                Some(ref prop) if prop.enumerable => {
+                    let prop = T::from_value(&Value::new(prop.val.clone()))?;
+                    map.insert(JsString::from_key(&key).to_string(), prop);
+                }
+                _ => {}
+            }
+        }
+        Ok(map)
+    }
+}
+
+#[test]
+fn engine() {
+    let mut engine = Engine::new();
+
+    engine.set_global("n", 10);
+    let sum = engine
+        .eval("var s = 0; for (var i = 1; i <= n; i++) s += i; s")
+        .unwrap();
+    assert_eq!(f64::try_from(sum), Ok(55.0));
+    assert_eq!(engine.get_global("i"), Some(Value::number(11.0)));
+    assert_eq!(engine.get_global("undeclared"), None);
+
+    engine
+        .eval("function join(a, b) { return a + '-' + b }")
+        .unwrap();
+    let join = engine.get_global("join").unwrap();
+    let joined = engine.call(&join, vec!["x".into(), 1.into()]).unwrap();
+    assert_eq!(String::try_from(joined), Ok("x-1".to_string()));
+
+    let mut scores = HashMap::new();
+    scores.insert("a".to_string(), vec![1, 2]);
+    scores.insert("b".to_string(), vec![]);
+    scores.insert("__proto__".to_string(), vec![3]);
+    engine.set_global("scores", scores.clone());
+    let scores_again = engine.eval("scores").unwrap();
+    assert_eq!(unsafe { engine.get(&scores_again) }, Ok(scores));
+    let lengths = engine.eval("[scores.a.length, scores.b.length]").unwrap();
+    assert_eq!(unsafe { engine.get::<Vec<u32>>(&lengths) }, Ok(vec![2, 0]));
+    // An own property named __proto__ doesn't replace the prototype.
+    let proto_is_object = engine
+        .eval("Object.getPrototypeOf(scores) === Object.prototype")
+        .unwrap();
+    assert_eq!(proto_is_object, Value::bool(true));
+    assert_eq!(
+        bool::try_from(Value::number(1.0)),
+        Err(Error::Conversion(
+            "expected a boolean but got a number".to_string()
+        ))
+    );
+
+    match engine.eval("var a = 1;\nvar b = ;") {
+        Err(Error::Syntax { line, .. }) => assert_eq!(line, 2),
+        otherwise => panic!("{:?}", otherwise),
+    }
+    match engine.eval("undeclared()") {
+        Err(err @ Error::Exception(_)) => {
+            assert!(err.to_string().starts_with("uncaught ReferenceError"))
+        }
+        otherwise => panic!("{:?}", otherwise),
+    }
+    match engine.eval("throw 'oops'") {
+        Err(Error::Exception(val)) => assert_eq!(String::try_from(val), Ok("oops".to_string())),
+        otherwise => panic!("{:?}", otherwise),
+    }
+    let fail = engine
+        .eval("var fail = function() { throw 1 }; fail")
+        .unwrap();
+    assert_eq!(
+        engine.call(&fail, vec![]),
+        Err(Error::Exception(Value::number(1.0)))
+    );
+
+    // The engine can still run code after errors.
+    assert_eq!(engine.eval("s + 1"), Ok(Value::number(56.0)));
+
+    // Values are allocated in the realm of the engine, and those of other engines are rejected.
+    let mut other = Engine::new();
+    let concat = other
+        .eval("function concat(a) { return a.join('') } concat")
+        .unwrap();
+    let words = other.value_from(vec!["a", "b"]);
+    let expected = vec!["a".to_string(), "b".to_string()];
+    assert_eq!(unsafe { other.get::<Vec<String>>(&words) }, Ok(expected));
+    assert_eq!(
+        unsafe { engine.get::<Vec<String>>(&words) },
+        Err(foreign_value())
+    );
+    assert_eq!(
+        engine.call(&join, vec![words.clone()]),
+        Err(foreign_value())
+    );
+    assert_eq!(other.call(&concat, vec![words]), Ok(Value::from("ab")));
+}
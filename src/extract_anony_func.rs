@@ -38,8 +38,8 @@ impl AnonymousFunctionExtractor {
                     self.run(node)
                 }
             }
-            NodeBase::FunctionDecl(_, _, _) => {
-                if let NodeBase::FunctionDecl(ref name, ref params, ref mut body) =
+            NodeBase::FunctionDecl(_, _, _, _) => {
+                if let NodeBase::FunctionDecl(ref name, ref params, ref mut body, ref source) =
                     node.clone().base
                 {
                     // TODO: Need refinement
@@ -64,6 +64,7 @@ impl AnonymousFunctionExtractor {
                                 name_mangled.clone(),
                                 params.clone(),
                                 Box::new(Node::new(NodeBase::StatementList(body.clone()), 0)),
+                                source.clone(),
                             ),
                             0,
                         ));
@@ -76,14 +77,16 @@ impl AnonymousFunctionExtractor {
                             ))),
                         );
                     } else {
-                        if let NodeBase::FunctionDecl(_, _, ref mut body_) = node.base {
+                        if let NodeBase::FunctionDecl(_, _, ref mut body_, _) = node.base {
                             *body_ = Box::new(Node::new(NodeBase::StatementList(body.clone()), 0));
                         }
                     }
                 }
             }
-            NodeBase::FunctionExpr(_, _, _) => {
-                if let NodeBase::FunctionExpr(mut name, mut params, mut body) = node.clone().base {
+            NodeBase::FunctionExpr(_, _, _, _) => {
+                if let NodeBase::FunctionExpr(mut name, mut params, mut body, source) =
+                    node.clone().base
+                {
                     let mut name_ = match name {
                         Some(name) => {
                             let new_name = format!("anonymous.{}.{}", name, random::<u32>());
@@ -107,6 +110,7 @@ impl AnonymousFunctionExtractor {
                             name_.clone(),
                             params,
                             Box::new(Node::new(NodeBase::StatementList(body), 0)),
+                            source,
                         ),
                         0,
                     ));
@@ -120,8 +124,9 @@ impl AnonymousFunctionExtractor {
                 }
             }
             NodeBase::New(ref mut expr) => self.run(expr),
-            NodeBase::VarDecl(_, ref mut init) => {
+            NodeBase::VarDecl(ref name, ref mut init) => {
                 if let &mut Some(ref mut init) = init {
+                    name_anonymous_function(init, name);
                     self.run(init)
                 }
             }
@@ -130,6 +135,12 @@ impl AnonymousFunctionExtractor {
                     self.run(&mut **val)
                 }
             }
+            NodeBase::Throw(ref mut val) => self.run(&mut **val),
+            NodeBase::Try(ref mut block, _, ref mut catch, ref mut finally) => {
+                self.run(&mut *block);
+                self.run(&mut *catch);
+                self.run(&mut *finally);
+            }
             NodeBase::Member(ref mut parent, _) => {
                 self.run(&mut *parent);
             }
@@ -152,7 +163,10 @@ impl AnonymousFunctionExtractor {
                 self.run(&mut *step);
                 self.run(&mut *body);
             }
-            NodeBase::Assign(_, ref mut src) => {
+            NodeBase::Assign(ref dst, ref mut src) => {
+                if let NodeBase::Identifier(ref name) = dst.base {
+                    name_anonymous_function(&mut *src, name);
+                }
                 self.run(&mut *src);
             }
             NodeBase::UnaryOp(ref mut expr, _) => {
@@ -187,7 +201,13 @@ impl AnonymousFunctionExtractor {
                                 ),
                             );
                         }
-                        &mut PropertyDefinition::Property(_, ref mut node) => self.run(node),
+                        &mut PropertyDefinition::Property(ref name, ref mut node) => {
+                            // '__proto__: value' sets the prototype instead of defining a property.
+                            if name != "__proto__" {
+                                name_anonymous_function(node, name);
+                            }
+                            self.run(node)
+                        }
                     }
                 }
             }
@@ -200,3 +220,14 @@ impl AnonymousFunctionExtractor {
         }
     }
 }
+
+// Names an anonymous function expression assigned to the variable or property 'name' after it.
+// https://tc39.github.io/ecma262/#sec-variable-statement-runtime-semantics-evaluation
+// https://tc39.github.io/ecma262/#sec-object-initializer
+fn name_anonymous_function(node: &mut Node, name: &String) {
+    if let NodeBase::FunctionExpr(ref mut func_name, _, _, _) = node.base {
+        if func_name.is_none() {
+            *func_name = Some(name.clone());
+        }
+    }
+}
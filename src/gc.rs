@@ -1,15 +1,69 @@
+use collection::Collection;
+use proxy::ProxyValue;
+use realm;
 use rustc_hash::{FxHashMap, FxHashSet};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::hash::{Hash, Hasher};
 use std::mem;
-use std::sync::atomic::{self, AtomicUsize};
-use vm::{ArrayValue, CallObject, VMState, Value, ValueBase};
+use typed_array::{ArrayBuffer, TypedArray};
+use vm::{
+    ArrayValue, CallObject, PropertyMap, RuntimeError, VMState, Value, ValueBase, ARRAY_BUFFER_OBJ,
+    ARRAY_OBJ, BIGINT_OBJ, COLLECTION_ITERATOR_PROTOTYPE, COLLECTION_OBJS, DATA_VIEW_OBJ, DATE_OBJ,
+    ERROR_OBJS, FUNCTION_OBJ, NUMBER_OBJ, OBJECT_OBJ, STRING_OBJ, TYPED_ARRAY_OBJS,
+};
 
-static ALLOCATED_MEM_SIZE_BYTE: AtomicUsize = AtomicUsize::new(0);
+// The objects allocated in a realm. They are all freed when the realm is dropped.
+pub struct Heap {
+    mem: RefCell<FxHashMap<GcPtr, usize>>, // With the sizes last measured (see Gc::size())
+    weak_collections: RefCell<Vec<*mut Collection>>, // WeakMaps and WeakSets alive, whose entries are checked after marking
+    allocated_mem_size_byte: Cell<usize>,
+    limit: Cell<Option<usize>>, // The maximum of allocated_mem_size_byte, or None if unlimited
+    threshold: Cell<usize>,     // The size over which check_limit() must be called
+    grown: Cell<usize>,         // The part of allocated_mem_size_byte charged by grow()
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap {
+            mem: RefCell::new(FxHashMap::default()),
+            weak_collections: RefCell::new(vec![]),
+            allocated_mem_size_byte: Cell::new(0),
+            limit: Cell::new(None),
+            threshold: Cell::new(usize::MAX),
+            grown: Cell::new(0),
+        }
+    }
+
+    /// The size of the objects allocated, in bytes.
+    pub fn allocated_size(&self) -> usize {
+        self.allocated_mem_size_byte.get()
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit.get()
+    }
 
-thread_local!(pub static GC_MEM: RefCell<FxHashSet<GcPtr>> = {
-    RefCell::new(FxHashSet::default())
-});
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.limit.set(limit);
+        self.threshold.set(limit.unwrap_or(usize::MAX));
+    }
+
+    /// Whether the object at 'ptr' was allocated in this heap and is alive.
+    pub fn contains<X: Gc + 'static>(&self, ptr: *mut X) -> bool {
+        self.mem.borrow().contains_key(&GcPtr(ptr))
+    }
+
+    #[inline]
+    pub fn is_over_threshold(&self) -> bool {
+        self.allocated_mem_size_byte.get() > self.threshold.get()
+    }
+}
+
+impl Drop for Heap {
+    fn drop(&mut self) {
+        free(self, &FxHashSet::default());
+    }
+}
 
 #[derive(Clone, Debug, Eq)]
 pub struct GcPtr(*mut Gc);
@@ -33,6 +87,9 @@ impl Hash for GcPtr {
 pub trait Gc {
     fn free(&self);
     fn trace(&self, &mut FxHashSet<GcPtr>);
+    // The memory used by the object including the buffers it owns, roughly. The values in them
+    // are counted as objects of their own.
+    fn size(&self) -> usize;
 }
 
 impl Gc for Value {
@@ -47,6 +104,7 @@ impl Gc for Value {
             | ValueBase::Undefined
             | ValueBase::Bool(_)
             | ValueBase::Number(_)
+            | ValueBase::BigInt(_)
             | ValueBase::String(_) => {}
             ValueBase::Function(box (_, _, ref obj, ref c)) => {
                 not_marked_then(*obj, marked, |obj, marked| unsafe {
@@ -54,8 +112,14 @@ impl Gc for Value {
                 });
                 c.trace(marked);
             }
-            // Never trace _xxx
-            ValueBase::BuiltinFunction(box (_, _x, ref c)) => c.trace(marked),
+            ValueBase::BuiltinFunction(box (_, ref obj, ref c)) => {
+                if !obj.is_null() {
+                    not_marked_then(*obj, marked, |obj, marked| unsafe {
+                        (*obj).trace(marked);
+                    });
+                }
+                c.trace(marked);
+            }
             ValueBase::Object(ref obj) => {
                 not_marked_then(*obj, marked, |obj, marked| unsafe {
                     (*obj).trace(marked);
@@ -67,11 +131,33 @@ impl Gc for Value {
                 });
             }
             ValueBase::Arguments => {}
+            ValueBase::Collection(ref c) => {
+                not_marked_then(*c, marked, |c, marked| unsafe {
+                    (*c).trace(marked);
+                });
+            }
+            ValueBase::Proxy(ref p) => {
+                not_marked_then(*p, marked, |p, marked| unsafe {
+                    (*p).trace(marked);
+                });
+            }
+            ValueBase::ArrayBuffer(ref b) => {
+                not_marked_then(*b, marked, |_, _| {});
+            }
+            ValueBase::TypedArray(ref t) => {
+                not_marked_then(*t, marked, |t, marked| unsafe {
+                    (*t).trace(marked);
+                });
+            }
         }
     }
+
+    fn size(&self) -> usize {
+        mem::size_of_val(self)
+    }
 }
 
-impl Gc for FxHashMap<String, Value> {
+impl Gc for PropertyMap {
     fn free(&self) {
         mem::drop(self);
     }
@@ -81,6 +167,10 @@ impl Gc for FxHashMap<String, Value> {
             val.trace(marked);
         }
     }
+
+    fn size(&self) -> usize {
+        mem::size_of_val(self) + self.buffer_size() + self.values().map(string_size).sum::<usize>()
+    }
 }
 
 impl Gc for CallObject {
@@ -104,6 +194,12 @@ impl Gc for CallObject {
             self.this.trace(marked);
         }
     }
+
+    fn size(&self) -> usize {
+        mem::size_of_val(self)
+            + self.params.capacity() * mem::size_of::<(String, bool)>()
+            + self.arg_rest_vals.capacity() * mem::size_of::<Value>()
+    }
 }
 
 impl Gc for ArrayValue {
@@ -119,35 +215,169 @@ impl Gc for ArrayValue {
             val.trace(marked)
         }
     }
+
+    fn size(&self) -> usize {
+        mem::size_of_val(self)
+            + self.elems.capacity() * mem::size_of::<Value>()
+            + self.obj.buffer_size()
+            + self.elems.iter().map(string_size).sum::<usize>()
+            + self.obj.values().map(string_size).sum::<usize>()
+    }
+}
+
+impl Gc for Collection {
+    fn free(&self) {
+        mem::drop(self);
+    }
+
+    // The entries of weak collections are traced by trace_weak_collections().
+    fn trace(&self, marked: &mut FxHashSet<GcPtr>) {
+        if self.kind.is_weak() {
+            return;
+        }
+        for (key, val) in self.iter() {
+            key.trace(marked);
+            val.trace(marked);
+        }
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of_val(self) + self.entries.capacity() * mem::size_of::<Option<(Value, Value)>>()
+    }
+}
+
+impl Gc for ProxyValue {
+    fn free(&self) {
+        mem::drop(self);
+    }
+
+    fn trace(&self, marked: &mut FxHashSet<GcPtr>) {
+        self.target.trace(marked);
+        self.handler.trace(marked);
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of_val(self)
+    }
+}
+
+impl Gc for ArrayBuffer {
+    fn free(&self) {
+        mem::drop(self);
+    }
+
+    fn trace(&self, _marked: &mut FxHashSet<GcPtr>) {}
+
+    fn size(&self) -> usize {
+        mem::size_of_val(self) + self.bytes.capacity()
+    }
+}
+
+impl Gc for TypedArray {
+    fn free(&self) {
+        mem::drop(self);
+    }
+
+    // The bytes are kept alive through the ArrayBuffer object.
+    fn trace(&self, marked: &mut FxHashSet<GcPtr>) {
+        self.buffer.trace(marked);
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of_val(self)
+    }
+}
+
+// Strings are not objects of their own, and are counted with the objects holding them.
+fn string_size(val: &Value) -> usize {
+    match val.val {
+        ValueBase::String(ref s) => s.len() * mem::size_of::<u16>(),
+        _ => 0,
+    }
 }
 
 pub fn new<X: Gc + 'static>(data: X) -> *mut X {
-    let data_size = mem::size_of_val(&data);
+    let data_size = data.size();
     let ptr = Box::into_raw(Box::new(data));
-    ALLOCATED_MEM_SIZE_BYTE.fetch_add(data_size, atomic::Ordering::SeqCst);
-    GC_MEM.with(|m| m.borrow_mut().insert(GcPtr(ptr)));
+    let heap = &realm::current().heap;
+    heap.allocated_mem_size_byte
+        .set(heap.allocated_mem_size_byte.get() + data_size);
+    heap.mem.borrow_mut().insert(GcPtr(ptr), data_size);
     ptr
 }
 
-// pub fn gc_new_and_free<X: Gc + 'static>(data: X, vm_state: &VMState) -> *mut X {
-//     let mut marked = FxHashSet::default();
-//     trace(&vm_state, &mut marked);
-//     free(&marked);
-//
-//     let ptr = Box::into_raw(Box::new(data));
-//     GC_MEM.with(|m| m.borrow_mut().insert(GcPtr(ptr)));
-//     ptr
-// }
+pub fn new_collection(collection: Collection) -> *mut Collection {
+    let is_weak = collection.kind.is_weak();
+    let ptr = new(collection);
+    if is_weak {
+        realm::current()
+            .heap
+            .weak_collections
+            .borrow_mut()
+            .push(ptr);
+    }
+    ptr
+}
+
+// Charges 'size' bytes by which the buffers of an object have grown, or of a new string, so that
+// the VM raises the error for the heap limit before running the next instruction (see
+// check_limit()). They are measured again at the next collection. Returns a RangeError at once
+// if 'size' alone is over the limit, since allocating that much may abort the process.
+pub fn grow(size: usize) -> Result<(), RuntimeError> {
+    let heap = &realm::current().heap;
+    if heap.limit.get().map_or(false, |limit| size > limit) {
+        return Err(out_of_memory());
+    }
+    heap.allocated_mem_size_byte
+        .set(heap.allocated_mem_size_byte.get() + size);
+    heap.grown.set(heap.grown.get() + size);
+    Ok(())
+}
 
 pub fn mark_and_sweep(vm_state: &VMState) {
-    fn over16kb_allocated() -> bool {
-        ALLOCATED_MEM_SIZE_BYTE.load(atomic::Ordering::SeqCst) > 16 * 1024
+    let realm = realm::current();
+    let heap = &realm.heap;
+
+    if heap.allocated_mem_size_byte.get() > 16 * 1024 {
+        collect(vm_state, heap);
     }
+}
 
-    if over16kb_allocated() {
-        let mut marked = FxHashSet::default();
-        trace(&vm_state, &mut marked);
-        free(&marked);
+// Called when the heap has grown over the threshold (see Heap::is_over_threshold()). Collects
+// garbage, and returns a RangeError if the heap is still over its limit.
+//
+// The code handling the error may allocate an eighth of the limit more before the error is
+// raised again, so that it can run and drop the objects it no longer needs.
+pub fn check_limit(vm_state: &VMState) -> Result<(), RuntimeError> {
+    let realm = realm::current();
+    let heap = &realm.heap;
+
+    collect(vm_state, heap);
+    match heap.limit.get() {
+        Some(limit) if heap.allocated_mem_size_byte.get() > limit => {
+            heap.threshold.set(limit + limit / 8);
+            Err(out_of_memory())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn out_of_memory() -> RuntimeError {
+    RuntimeError::Range("range error: out of memory".to_string())
+}
+
+fn collect(vm_state: &VMState, heap: &Heap) {
+    let mut marked = FxHashSet::default();
+    trace(&vm_state, &mut marked);
+    trace_weak_collections(heap, &mut marked);
+    free(heap, &marked);
+    heap.allocated_mem_size_byte
+        .set(heap.allocated_mem_size_byte.get() - heap.grown.replace(0));
+
+    if let Some(limit) = heap.limit.get() {
+        if heap.allocated_mem_size_byte.get() <= limit {
+            heap.threshold.set(limit);
+        }
     }
 }
 
@@ -160,21 +390,96 @@ fn trace(vm_state: &VMState, marked: &mut FxHashSet<GcPtr>) {
             (*scope).trace(marked)
         });
     }
+    // Built-in objects shared by all the values must survive even if nothing refers to them now.
+    ARRAY_OBJ.with(|x| x.trace(marked));
+    OBJECT_OBJ.with(|x| x.trace(marked));
+    STRING_OBJ.with(|x| x.trace(marked));
+    NUMBER_OBJ.with(|x| x.trace(marked));
+    BIGINT_OBJ.with(|x| x.trace(marked));
+    ERROR_OBJS.with(|x| {
+        for error in x {
+            error.trace(marked)
+        }
+    });
+    COLLECTION_OBJS.with(|x| {
+        for collection in x {
+            collection.trace(marked)
+        }
+    });
+    DATE_OBJ.with(|x| x.trace(marked));
+    ARRAY_BUFFER_OBJ.with(|x| x.trace(marked));
+    TYPED_ARRAY_OBJS.with(|x| {
+        for typed_array in x {
+            typed_array.trace(marked)
+        }
+    });
+    DATA_VIEW_OBJ.with(|x| x.trace(marked));
+    FUNCTION_OBJ.with(|x| x.trace(marked));
+    COLLECTION_ITERATOR_PROTOTYPE
+        .with(|x| not_marked_then(*x, marked, |x, marked| unsafe { (*x).trace(marked) }));
+}
+
+// The object a weak collection refers to through 'key'. Weak collections only have such keys.
+fn weak_key_ptr(key: &Value) -> Option<GcPtr> {
+    match key.val {
+        ValueBase::Function(box (_, _, obj, _)) | ValueBase::Object(obj) => Some(GcPtr(obj)),
+        ValueBase::BuiltinFunction(box (_, obj, _)) if !obj.is_null() => Some(GcPtr(obj)),
+        ValueBase::Array(a) => Some(GcPtr(a)),
+        ValueBase::Proxy(p) => Some(GcPtr(p)),
+        _ => None,
+    }
 }
 
-fn free(marked: &FxHashSet<GcPtr>) {
-    GC_MEM.with(|mem| {
-        mem.borrow_mut().retain(|p| {
-            let is_marked = marked.contains(p);
-            if !is_marked {
-                unsafe {
-                    (*p.0).free();
-                    let released_size = mem::size_of_val(&*Box::from_raw(p.0));
-                    ALLOCATED_MEM_SIZE_BYTE.fetch_sub(released_size, atomic::Ordering::SeqCst);
+// A value in a weak collection is reachable only while its key is, so values are marked as
+// their keys turn out to be reachable, until nothing changes (i.e. keys are ephemerons).
+// Then the entries whose keys are unreachable are removed so that they never refer to freed
+// objects.
+fn trace_weak_collections(heap: &Heap, marked: &mut FxHashSet<GcPtr>) {
+    let mut collections = heap.weak_collections.borrow_mut();
+    collections.retain(|c| marked.contains(&GcPtr(*c)));
+
+    let is_live_key = |key: &Value, marked: &FxHashSet<GcPtr>| match weak_key_ptr(key) {
+        Some(ptr) => marked.contains(&ptr),
+        None => true,
+    };
+
+    loop {
+        let marked_len = marked.len();
+        for c in collections.iter() {
+            for (key, val) in unsafe { (**c).iter() } {
+                if is_live_key(key, marked) {
+                    val.trace(marked);
                 }
             }
-            is_marked
-        });
+        }
+        if marked.len() == marked_len {
+            break;
+        }
+    }
+
+    for c in collections.iter() {
+        unsafe { (**c).retain(|key| is_live_key(key, marked)) };
+    }
+}
+
+// Frees the objects not marked, and measures the sizes of the others again since they may have
+// grown or shrunk since they were allocated.
+fn free(heap: &Heap, marked: &FxHashSet<GcPtr>) {
+    heap.mem.borrow_mut().retain(|p, size| {
+        let is_marked = marked.contains(p);
+        let old_size = *size;
+        *size = unsafe {
+            if is_marked {
+                (*p.0).size()
+            } else {
+                (*p.0).free();
+                mem::drop(Box::from_raw(p.0));
+                0
+            }
+        };
+        heap.allocated_mem_size_byte
+            .set(heap.allocated_mem_size_byte.get() - old_size + *size);
+        is_marked
     });
 }
 
@@ -186,3 +491,62 @@ where
         f(p, marked);
     }
 }
+
+#[test]
+fn heap_limit() {
+    use engine::{Engine, Error};
+    use js_string::JsString;
+
+    let mut engine = Engine::new();
+    engine.vm().set_heap_limit(Some(1024 * 1024));
+
+    let source = "
+        var message, list = [];
+        try {
+            while (true) list.push([1, 2, 3]);
+        } catch (e) {
+            list = null;
+            message = e.message;
+        }
+        message";
+    assert_eq!(
+        engine.eval(source),
+        Ok(Value::string(JsString::from("out of memory")))
+    );
+    // The arrays dropped by the handler are collected.
+    let source = "var a = []; for (var i = 0; i < 1000; i++) a.push([i]); a.length";
+    assert_eq!(engine.eval(source), Ok(Value::number(1000.0)));
+
+    // The buffers of arrays count too.
+    match engine.eval("var list = []; while (true) list.push(new Array(10000))") {
+        Err(Error::Exception(_)) => {}
+        otherwise => panic!("{:?}", otherwise),
+    }
+    // The error is raised again when the handlers keep allocating.
+    engine.vm().set_fuel(Some(100_000_000));
+    match engine.eval("while (true) { try { while (true) list.push([]) } catch (e) {} }") {
+        Err(Error::Exception(_)) => {}
+        otherwise => panic!("{:?}", otherwise),
+    }
+    // Running code fails until the embedder drops some objects.
+    assert!(engine.eval("[1, 2].length").is_err());
+    engine.set_global("list", Value::null());
+    assert_eq!(engine.eval("[1, 2].length"), Ok(Value::number(2.0)));
+
+    // The growth of existing arrays and strings counts too.
+    let err = engine
+        .eval("var a = []; while (true) a.push(0)")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "uncaught RangeError: out of memory");
+    engine.set_global("a", Value::null());
+    // Growing by more than the limit at once fails before allocating.
+    let source = "
+        var names = [], s = 'x';
+        try { while (true) s += s } catch (e) { s = null; names.push(e.name) }
+        try { [].length = 4294967295 } catch (e) { names.push(e.message) }
+        names.join()";
+    assert_eq!(
+        engine.eval(source),
+        Ok(Value::string(JsString::from("RangeError,out of memory")))
+    );
+}
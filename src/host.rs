@@ -0,0 +1,368 @@
+// Functions written in Rust by the program embedding the VM ("host functions").
+//
+// A host function is a closure that is called with the arguments converted by FromArgument and
+// returns the value of the call or a JsError thrown as a JS exception:
+//
+//     vm.register_global_function("add", |_vm, args| {
+//         let (a, b): (f64, f64) = (args.get(0)?, args.get(1)?);
+//         Ok(Value::number(a + b))
+//     });
+//
+// Host functions are BuiltinFunction values whose ids follow the ones of the builtin functions,
+// so JS code cannot tell them from builtin functions. The closures are not traced by the GC, so
+// Values captured by them may be collected unless they are also reachable from JS.
+
+use std::rc::Rc;
+
+use builtin;
+use js_string::JsString;
+use realm;
+use vm::{obj_find_val, CallObject, RuntimeError, Value, ValueBase, VM};
+
+pub type HostFunction = Fn(&mut VM, HostArgs) -> Result<Value, JsError>;
+
+/// An exception thrown by a host function.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsError {
+    Error(String),
+    TypeError(String),
+    RangeError(String),
+    ReferenceError(String),
+    SyntaxError(String),
+    Throw(Value),       // Throws the value as it is
+    Terminated(String), // Stops running code like the execution limits do. JS code can't catch it
+}
+
+impl JsError {
+    pub fn error<S: Into<String>>(msg: S) -> JsError {
+        JsError::Error(msg.into())
+    }
+
+    pub fn type_error<S: Into<String>>(msg: S) -> JsError {
+        JsError::TypeError(msg.into())
+    }
+
+    pub fn range_error<S: Into<String>>(msg: S) -> JsError {
+        JsError::RangeError(msg.into())
+    }
+
+    pub fn reference_error<S: Into<String>>(msg: S) -> JsError {
+        JsError::ReferenceError(msg.into())
+    }
+
+    pub fn syntax_error<S: Into<String>>(msg: S) -> JsError {
+        JsError::SyntaxError(msg.into())
+    }
+
+    pub fn throw(val: Value) -> JsError {
+        JsError::Throw(val)
+    }
+
+    // Creates the error to be thrown in the VM. Plain errors become Error objects right away
+    // since RuntimeError has no variant for them.
+    fn into_runtime_error(self, vm: &VM) -> RuntimeError {
+        match self {
+            JsError::Error(msg) => RuntimeError::Exception(builtin::create_error_object(
+                vm,
+                builtin::ERROR_NEW,
+                Some(JsString::from(msg)),
+            )),
+            JsError::TypeError(msg) => RuntimeError::Type(msg),
+            JsError::RangeError(msg) => RuntimeError::Range(msg),
+            JsError::ReferenceError(msg) => RuntimeError::Reference(msg),
+            JsError::SyntaxError(msg) => RuntimeError::Syntax(msg),
+            JsError::Throw(val) => RuntimeError::Exception(val),
+            JsError::Terminated(msg) => RuntimeError::Terminated(msg),
+        }
+    }
+}
+
+// Lets host functions propagate the errors of the VM functions they call with '?'.
+impl From<RuntimeError> for JsError {
+    fn from(err: RuntimeError) -> JsError {
+        match err {
+            RuntimeError::Unknown => JsError::Error("unknown error occurred".to_string()),
+            RuntimeError::Unimplemented => JsError::Error("unimplemented feature".to_string()),
+            RuntimeError::Type(msg) => JsError::TypeError(msg),
+            RuntimeError::Range(msg) => JsError::RangeError(msg),
+            RuntimeError::Reference(msg) => JsError::ReferenceError(msg),
+            RuntimeError::Syntax(msg) => JsError::SyntaxError(msg),
+            RuntimeError::Exception(val) => JsError::Throw(val),
+            RuntimeError::Terminated(msg) => JsError::Terminated(msg),
+        }
+    }
+}
+
+/// The 'this' value and the arguments of a call to a host function.
+pub struct HostArgs {
+    this: Value,
+    args: Vec<Value>,
+}
+
+impl HostArgs {
+    pub fn this(&self) -> &Value {
+        &self.this
+    }
+
+    /// The number of the arguments actually passed.
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// The n-th argument, or undefined if it's not passed.
+    pub fn value(&self, n: usize) -> Value {
+        self.args.get(n).cloned().unwrap_or_else(Value::undefined)
+    }
+
+    /// Converts the n-th argument into T, e.g. args.get::<f64>(0).
+    pub fn get<T: FromArgument>(&self, n: usize) -> Result<T, JsError> {
+        T::from_argument(&self.value(n))
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.args
+    }
+}
+
+/// Types that arguments of host functions can be converted into. The conversions are the
+/// implicit ones of JS (e.g. ToNumber for f64), so they only fail where JS throws a TypeError.
+pub trait FromArgument: Sized {
+    fn from_argument(val: &Value) -> Result<Self, JsError>;
+}
+
+impl FromArgument for Value {
+    fn from_argument(val: &Value) -> Result<Value, JsError> {
+        Ok(val.clone())
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-toboolean
+impl FromArgument for bool {
+    fn from_argument(val: &Value) -> Result<bool, JsError> {
+        Ok(val.val.to_boolean())
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-tonumber
+impl FromArgument for f64 {
+    fn from_argument(val: &Value) -> Result<f64, JsError> {
+        match val.val {
+            ValueBase::BigInt(_) => Err(JsError::type_error(
+                "type error: cannot convert a BigInt value to a number",
+            )),
+            ref val => Ok(val.to_number()),
+        }
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-toint32
+impl FromArgument for i32 {
+    fn from_argument(val: &Value) -> Result<i32, JsError> {
+        Ok(u32::from_argument(val)? as i32)
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-touint32
+impl FromArgument for u32 {
+    fn from_argument(val: &Value) -> Result<u32, JsError> {
+        f64::from_argument(val)?;
+        Ok(val.val.to_uint32() as u32)
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-tostring
+impl FromArgument for String {
+    fn from_argument(val: &Value) -> Result<String, JsError> {
+        Ok(JsString::from_argument(val)?.to_string())
+    }
+}
+
+impl FromArgument for JsString {
+    fn from_argument(val: &Value) -> Result<JsString, JsError> {
+        match val.val {
+            ValueBase::Undefined
+            | ValueBase::Null
+            | ValueBase::Bool(_)
+            | ValueBase::Number(_)
+            | ValueBase::BigInt(_)
+            | ValueBase::String(_)
+            | ValueBase::Array(_)
+            | ValueBase::Object(_)
+            | ValueBase::Proxy(_) => Ok(val.to_js_string()),
+            // Without the source text that Function.prototype.toString() gives, which needs the VM.
+            ValueBase::Function(box (_, _, obj, _))
+            | ValueBase::BuiltinFunction(box (_, obj, _)) => {
+                let name = match obj_find_val(unsafe { &*obj }, "name").val {
+                    ValueBase::String(name) => name.to_string(),
+                    _ => String::new(),
+                };
+                Ok(JsString::from(format!(
+                    "function {}() {{ [native code] }}",
+                    name
+                )))
+            }
+            // The internal values, which are never passed to functions. (Symbols would throw here.)
+            _ => Err(JsError::type_error(
+                "type error: cannot convert the value to a string",
+            )),
+        }
+    }
+}
+
+/// None if the argument is undefined (or not passed).
+impl<T: FromArgument> FromArgument for Option<T> {
+    fn from_argument(val: &Value) -> Result<Option<T>, JsError> {
+        match val.val {
+            ValueBase::Undefined => Ok(None),
+            _ => Ok(Some(T::from_argument(val)?)),
+        }
+    }
+}
+
+impl VM {
+    /// Creates a function value that calls 'f'.
+    pub fn new_host_function<F>(&mut self, f: F) -> Value
+    where
+        F: Fn(&mut VM, HostArgs) -> Result<Value, JsError> + 'static,
+    {
+        realm::enter(&self.realm);
+        let id = self.builtin_functions.len() + self.host_functions.len();
+        self.host_functions.push(Rc::new(f));
+        Value::builtin_function(id, CallObject::new(Value::undefined()))
+    }
+
+    /// Defines the global function 'name' that calls 'f'.
+    pub fn register_global_function<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(&mut VM, HostArgs) -> Result<Value, JsError> + 'static,
+    {
+        let func = self.new_host_function(f);
+        let global_vals = self.state.scope[0];
+        unsafe { (*global_vals).set_value(name.to_string(), func) };
+    }
+
+    /// Defines the method 'name' that calls 'f' on 'target', like the methods of builtin objects
+    /// (writable, configurable and not enumerable).
+    pub fn register_method<F>(&mut self, target: &Value, name: &str, f: F) -> Result<(), JsError>
+    where
+        F: Fn(&mut VM, HostArgs) -> Result<Value, JsError> + 'static,
+    {
+        let mut func = self.new_host_function(f);
+        func.enumerable = false;
+        match target.val {
+            ValueBase::Object(map)
+            | ValueBase::Function(box (_, _, map, _))
+            | ValueBase::BuiltinFunction(box (_, map, _)) => {
+                unsafe { (*map).insert(name.to_string(), func) };
+                Ok(())
+            }
+            ValueBase::Array(ary) => {
+                unsafe { (*ary).obj.insert(name.to_string(), func) };
+                Ok(())
+            }
+            _ => Err(JsError::type_error(
+                "type error: methods can only be registered on objects",
+            )),
+        }
+    }
+
+    /// Calls the builtin or host function 'id' with the 'this' value in 'callobj', leaving the
+    /// result on the stack.
+    pub unsafe fn call_builtin_function(
+        &mut self,
+        id: usize,
+        callobj: CallObject,
+        args: Vec<Value>,
+    ) -> Result<(), RuntimeError> {
+        if id < self.builtin_functions.len() {
+            return self.builtin_functions[id](callobj, args, self);
+        }
+
+        let f = self.host_functions[id - self.builtin_functions.len()].clone();
+        let args = HostArgs {
+            this: *callobj.this,
+            args: args,
+        };
+        match f(self, args) {
+            Ok(val) => {
+                self.state.stack.push(val);
+                Ok(())
+            }
+            Err(err) => Err(err.into_runtime_error(self)),
+        }
+    }
+}
+
+#[test]
+fn host_functions() {
+    use extract_anony_func::AnonymousFunctionExtractor;
+    use parser::Parser;
+    use std::cell::Cell;
+    use vm::PropertyMap;
+    use vm_codegen::VMCodeGen;
+
+    let mut node = Parser::new(
+        "
+        var sum = add(1, '2')
+        counter.next(); counter.next()
+        var count = counter.next()
+        var missing = add()
+        var message
+        try { fail('oops') } catch (e) { message = e.message }
+        var thrown
+        try { fail(5) } catch (e) { thrown = e }
+        var shown = [show(function f(a) {}), show(Math.max), show({})].join()
+        "
+        .to_string(),
+    )
+    .parse_all()
+    .unwrap();
+    AnonymousFunctionExtractor::new().run_toplevel(&mut node);
+    let mut vm_codegen = VMCodeGen::new();
+    let mut iseq = vec![];
+    vm_codegen.compile(&node, &mut iseq, false);
+
+    let mut vm = VM::new(vm_codegen.global_varmap);
+    vm.const_table = vm_codegen.bytecode_gen.const_table;
+
+    vm.register_global_function("add", |_, args| {
+        let a: Option<f64> = args.get(0)?;
+        let b: f64 = args.get(1)?;
+        Ok(Value::number(a.unwrap_or(0.0) + b))
+    });
+    let count = Rc::new(Cell::new(0));
+    let counter = Value::plain_object(PropertyMap::default());
+    vm.register_method(&counter, "next", move |_, _| {
+        count.set(count.get() + 1);
+        Ok(Value::number(count.get() as f64))
+    })
+    .unwrap();
+    unsafe { (*vm.state.scope[0]).set_value("counter".to_string(), counter) };
+    vm.register_global_function("fail", |_, args| match args.value(0).val {
+        ValueBase::String(_) => Err(JsError::type_error(args.get::<String>(0)?)),
+        _ => Err(JsError::throw(args.value(0))),
+    });
+    vm.register_global_function("show", |_, args| {
+        Ok(Value::string(args.get::<JsString>(0)?))
+    });
+
+    vm.run(iseq).unwrap();
+
+    let global = |name: &str| unsafe { (*vm.state.scope[0]).get_value(&name.to_string()).unwrap() };
+    assert_eq!(global("sum").val, ValueBase::Number(3.0));
+    assert_eq!(global("count").val, ValueBase::Number(3.0));
+    assert!(global("missing").val.to_number().is_nan());
+    assert_eq!(
+        global("message").val,
+        ValueBase::String(JsString::from("oops"))
+    );
+    assert_eq!(global("thrown").val, ValueBase::Number(5.0));
+    assert_eq!(
+        global("shown").to_string(),
+        "function f() { [native code] },function max() { [native code] },[object Object]"
+    );
+}
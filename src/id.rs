@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use realm;
 
 pub type Id = usize;
 
@@ -31,13 +31,9 @@ impl IdGen {
     }
 }
 
-thread_local!(pub static ID_GEN: RefCell<IdGen> = {
-    RefCell::new(IdGen::new())
-});
-
-// unique id must be greater than 0.
+// unique id must be greater than 0. Ids are unique in the current realm.
 pub fn get_unique_id() -> usize {
-    ID_GEN.with(|idgen| idgen.borrow_mut().gen_id() + 1) 
+    realm::current().id_gen.borrow_mut().gen_id() + 1
 }
 
 #[test]
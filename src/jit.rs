@@ -1,10 +1,13 @@
 use builtin;
 use bytecode_gen::{ByteCode, VMInst};
 use id::Id;
+use js_string::JsString;
+use limits::Limits;
+use realm;
 use vm;
 use vm::{CallObject, FuncId};
 
-use rand::{random, thread_rng, RngCore};
+use rand::random;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 
@@ -40,6 +43,19 @@ impl CastIntoLLVMType for ValueType {
     }
 }
 
+// A variable a loop reads or writes, passed to the compiled loop by pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoopVariable {
+    Name(usize),  // Accessed by name, the index of which in const_table.string is given
+    Local(usize), // In the slot of the running frame
+}
+
+// The key in 'env' of the local variable in 'slot', which never clashes with the names of JS
+// variables.
+fn local_slot_name(slot: usize) -> String {
+    format!("%{}", slot)
+}
+
 fn get_value_type(val: &vm::Value) -> Option<ValueType> {
     match val.val {
         vm::ValueBase::Bool(_) => Some(ValueType::Bool),
@@ -94,7 +110,7 @@ pub struct JITInfo {
 pub struct LoopInfo {
     raw_func: Option<fn(*mut f64) -> i32>,
     llvm_func: Option<LLVMValueRef>,
-    local_vars: Vec<(usize, ValueType)>, // the (ids, types) of local variables used in this loop
+    local_vars: Vec<(LoopVariable, ValueType)>, // the variables used in this loop and their types
     jit_info: JITInfo,
 }
 
@@ -149,6 +165,7 @@ pub struct TracingJit {
     count: FxHashMap<UniquePosition, usize>,
     cur_func: Option<LLVMValueRef>,
     builtin_funcs: FxHashMap<usize, LLVMValueRef>,
+    limits: *const Limits, // Of the VM, which the code being generated checks at back edges
     context: LLVMContextRef,
     module: LLVMModuleRef,
     builder: LLVMBuilderRef,
@@ -157,8 +174,6 @@ pub struct TracingJit {
 
 impl TracingJit {
     pub unsafe fn new() -> TracingJit {
-        MATH_RAND_SEED = thread_rng().next_u64();
-
         llvm::target::LLVM_InitializeNativeTarget();
         llvm::target::LLVM_InitializeNativeAsmPrinter();
         llvm::target::LLVM_InitializeNativeAsmParser();
@@ -174,7 +189,6 @@ impl TracingJit {
         llvm::transforms::scalar::LLVMAddGVNPass(pm);
         llvm::transforms::scalar::LLVMAddInstructionCombiningPass(pm);
         llvm::transforms::scalar::LLVMAddPromoteMemoryToRegisterPass(pm);
-        llvm::transforms::scalar::LLVMAddTailCallEliminationPass(pm);
         llvm::transforms::scalar::LLVMAddJumpThreadingPass(pm);
 
         TracingJit {
@@ -187,6 +201,7 @@ impl TracingJit {
             builder: LLVMCreateBuilderInContext(context),
             pass_manager: pm,
             cur_func: None,
+            limits: ptr::null(),
             builtin_funcs: {
                 let mut hmap = FxHashMap::default();
 
@@ -295,6 +310,48 @@ impl TracingJit {
                 );
                 hmap.insert(BUILTIN_MATH_RANDOM, f_math_random);
 
+                let f_check_limits = LLVMAddFunction(
+                    module,
+                    CString::new("jit_check_limits").unwrap().as_ptr(),
+                    LLVMFunctionType(
+                        LLVMInt32TypeInContext(context),
+                        vec![LLVMPointerType(LLVMInt8TypeInContext(context), 0)]
+                            .as_mut_slice()
+                            .as_mut_ptr(),
+                        1,
+                        0,
+                    ),
+                );
+                hmap.insert(BUILTIN_CHECK_LIMITS, f_check_limits);
+
+                let f_check_stack = LLVMAddFunction(
+                    module,
+                    CString::new("jit_check_stack").unwrap().as_ptr(),
+                    LLVMFunctionType(
+                        LLVMInt32TypeInContext(context),
+                        vec![LLVMPointerType(LLVMInt8TypeInContext(context), 0)]
+                            .as_mut_slice()
+                            .as_mut_ptr(),
+                        1,
+                        0,
+                    ),
+                );
+                hmap.insert(BUILTIN_CHECK_STACK, f_check_stack);
+
+                let f_leave_call = LLVMAddFunction(
+                    module,
+                    CString::new("jit_leave_call").unwrap().as_ptr(),
+                    LLVMFunctionType(
+                        LLVMVoidTypeInContext(context),
+                        vec![LLVMPointerType(LLVMInt8TypeInContext(context), 0)]
+                            .as_mut_slice()
+                            .as_mut_ptr(),
+                        1,
+                        0,
+                    ),
+                );
+                hmap.insert(BUILTIN_LEAVE_CALL, f_leave_call);
+
                 hmap
             },
         }
@@ -302,7 +359,15 @@ impl TracingJit {
 }
 
 unsafe fn cur_bb_has_no_terminator(builder: LLVMBuilderRef) -> bool {
-    LLVMIsATerminatorInst(LLVMGetLastInstruction(LLVMGetInsertBlock(builder))) == ptr::null_mut()
+    bb_has_no_terminator(LLVMGetInsertBlock(builder))
+}
+
+// Blocks may be left empty, e.g. when compiling a loop fails halfway. LLVMIsATerminatorInst() used
+// to return null for a null instruction, but newer LLVM dereferences it (builds against LLVM 14
+// crashed here), so an empty block is checked first.
+unsafe fn bb_has_no_terminator(bb: LLVMBasicBlockRef) -> bool {
+    let last = LLVMGetLastInstruction(bb);
+    last == ptr::null_mut() || LLVMIsATerminatorInst(last) == ptr::null_mut()
 }
 
 impl TracingJit {
@@ -312,6 +377,7 @@ impl TracingJit {
         iseq: &ByteCode,
         scope: &CallObject,
         const_table: &vm::ConstantTable,
+        limits: &Limits,
         argc: usize,
     ) -> Option<fn()> {
         if !self.func_is_called_enough_times(id, 0) {
@@ -334,6 +400,7 @@ impl TracingJit {
         }
 
         let name = format!("func-{}", random::<u32>());
+        self.limits = limits;
 
         // If gen_code fails, it means the function can't be JIT-compiled and should never be
         // compiled. (cannot_jit = true)
@@ -407,6 +474,21 @@ impl TracingJit {
                 *self.builtin_funcs.get(&BUILTIN_MATH_RANDOM).unwrap(),
                 math_random as *mut libc::c_void,
             );
+            llvm::execution_engine::LLVMAddGlobalMapping(
+                ee,
+                *self.builtin_funcs.get(&BUILTIN_CHECK_LIMITS).unwrap(),
+                jit_check_limits as *mut libc::c_void,
+            );
+            llvm::execution_engine::LLVMAddGlobalMapping(
+                ee,
+                *self.builtin_funcs.get(&BUILTIN_CHECK_STACK).unwrap(),
+                jit_check_stack as *mut libc::c_void,
+            );
+            llvm::execution_engine::LLVMAddGlobalMapping(
+                ee,
+                *self.builtin_funcs.get(&BUILTIN_LEAVE_CALL).unwrap(),
+                jit_leave_call as *mut libc::c_void,
+            );
         }
         let f_raw = llvm::execution_engine::LLVMGetFunctionAddress(
             ee,
@@ -463,14 +545,23 @@ impl TracingJit {
         let mut env = FxHashMap::default();
         self.cur_func = Some(func);
 
+        // The parameters are in the first slots.
         for i in 0..argc {
+            match scope.params.get(i) {
+                Some(&(_, false)) => {}
+                _ => continue,
+            }
             LLVMBuildStore(
                 self.builder,
                 LLVMGetParam(func, i as u32),
-                self.declare_local_var(scope.get_parameter_nth_name(i).unwrap(), &mut env),
+                self.declare_local_var(local_slot_name(i), &mut env),
             );
         }
 
+        self.gen_check_limits(BUILTIN_CHECK_STACK, 0, true);
+        // Recursive functions may run for long without a loop.
+        self.gen_check_limits(BUILTIN_CHECK_LIMITS, 0, true);
+
         let mut compilation_failed = false;
         if let Err(_) = self.gen_body(
             iseq,
@@ -487,11 +578,25 @@ impl TracingJit {
 
         let mut iter_bb = LLVMGetFirstBasicBlock(func);
         while iter_bb != ptr::null_mut() {
-            if LLVMIsATerminatorInst(LLVMGetLastInstruction(iter_bb)) == ptr::null_mut() {
+            if bb_has_no_terminator(iter_bb) {
                 let terminator_builder = LLVMCreateBuilderInContext(self.context);
                 LLVMPositionBuilderAtEnd(terminator_builder, iter_bb);
                 LLVMBuildRet(terminator_builder, LLVMConstNull(func_ret_ty));
             }
+            // Every return ends the call counted at the entry (see Limits::check_stack_jit()).
+            // The call in between also keeps calls in tail position from becoming jumps.
+            let ret = LLVMGetLastInstruction(iter_bb);
+            if LLVMGetInstructionOpcode(ret) == llvm::LLVMOpcode::LLVMRet {
+                let leave_builder = LLVMCreateBuilderInContext(self.context);
+                LLVMPositionBuilderBefore(leave_builder, ret);
+                LLVMBuildCall(
+                    leave_builder,
+                    *self.builtin_funcs.get(&BUILTIN_LEAVE_CALL).unwrap(),
+                    vec![self.limits_ptr()].as_mut_ptr(),
+                    1,
+                    CString::new("").unwrap().as_ptr(),
+                );
+            }
             iter_bb = LLVMGetNextBasicBlock(iter_bb);
         }
 
@@ -522,6 +627,7 @@ impl TracingJit {
         iseq: &ByteCode,
         const_table: &vm::ConstantTable,
         vm_state: &mut vm::VMState,
+        limits: &Limits,
         bgn: usize,
         end: usize,
     ) -> Option<isize> {
@@ -551,6 +657,7 @@ impl TracingJit {
         }
 
         let name = format!("loop-{}", random::<u32>());
+        self.limits = limits;
 
         // If gen_code fails, it means the function can't be JIT-compiled and should never be
         // compiled. (cannot_jit = true)
@@ -627,6 +734,21 @@ impl TracingJit {
                 *self.builtin_funcs.get(&BUILTIN_MATH_RANDOM).unwrap(),
                 math_random as *mut libc::c_void,
             );
+            llvm::execution_engine::LLVMAddGlobalMapping(
+                ee,
+                *self.builtin_funcs.get(&BUILTIN_CHECK_LIMITS).unwrap(),
+                jit_check_limits as *mut libc::c_void,
+            );
+            llvm::execution_engine::LLVMAddGlobalMapping(
+                ee,
+                *self.builtin_funcs.get(&BUILTIN_CHECK_STACK).unwrap(),
+                jit_check_stack as *mut libc::c_void,
+            );
+            llvm::execution_engine::LLVMAddGlobalMapping(
+                ee,
+                *self.builtin_funcs.get(&BUILTIN_LEAVE_CALL).unwrap(),
+                jit_leave_call as *mut libc::c_void,
+            );
         }
 
         let raw_func =
@@ -655,7 +777,7 @@ impl TracingJit {
         const_table: &vm::ConstantTable,
         bgn: usize,
         end: usize,
-    ) -> Result<(LLVMValueRef, Vec<(usize, ValueType)>), ()> {
+    ) -> Result<(LLVMValueRef, Vec<(LoopVariable, ValueType)>), ()> {
         let local_vars = self.collect_local_variables(vm_state, iseq, const_table, bgn, end)?;
 
         let func_ret_ty = LLVMInt32TypeInContext(self.context);
@@ -686,8 +808,12 @@ impl TracingJit {
 
         let arg_0 = LLVMGetParam(func, 0);
         for i in 0..local_vars.len() {
+            let name = match local_vars[i].0 {
+                LoopVariable::Name(id) => const_table.string[id].clone(),
+                LoopVariable::Local(slot) => local_slot_name(slot),
+            };
             env.insert(
-                const_table.string[local_vars[i].0].clone(),
+                name,
                 LLVMBuildPointerCast(
                     self.builder,
                     LLVMBuildLoad(
@@ -728,7 +854,7 @@ impl TracingJit {
 
         let mut iter_bb = LLVMGetFirstBasicBlock(func);
         while iter_bb != ptr::null_mut() {
-            if LLVMIsATerminatorInst(LLVMGetLastInstruction(iter_bb)) == ptr::null_mut() {
+            if bb_has_no_terminator(iter_bb) {
                 let terminator_builder = LLVMCreateBuilderInContext(self.context);
                 LLVMPositionBuilderAtEnd(terminator_builder, iter_bb);
                 LLVMBuildRet(
@@ -795,9 +921,10 @@ impl TracingJit {
         const_table: &vm::ConstantTable,
         mut pc: usize,
         end: usize,
-    ) -> Result<Vec<(usize, ValueType)>, ()> {
+    ) -> Result<Vec<(LoopVariable, ValueType)>, ()> {
         let mut local_vars = FxHashSet::default();
         let local_scope = &**vm_state.scope.last().unwrap();
+        let sp = vm_state.frames.last().unwrap().sp;
 
         while pc < end {
             let inst_size = try_opt!(VMInst::get_inst_size(iseq[pc]));
@@ -812,7 +939,14 @@ impl TracingJit {
                         } else {
                             continue;
                         };
-                        local_vars.insert((id, ty));
+                        local_vars.insert((LoopVariable::Name(id), ty));
+                    }
+                }
+                VMInst::GET_LOCAL | VMInst::SET_LOCAL => {
+                    pc += 1;
+                    get_int32!(iseq, pc, slot, usize);
+                    if let Some(ty) = get_value_type(&vm_state.stack[sp + slot]) {
+                        local_vars.insert((LoopVariable::Local(slot), ty));
                     }
                 }
                 _ => pc += inst_size,
@@ -967,6 +1101,10 @@ impl TracingJit {
             Some(())
         }
 
+        unsafe fn is_number(val: LLVMValueRef) -> bool {
+            LLVMGetTypeKind(LLVMTypeOf(val)) == llvm::LLVMTypeKind::LLVMDoubleTypeKind
+        }
+
         unsafe fn val_to_bool(self_: &TracingJit, val: LLVMValueRef) -> LLVMValueRef {
             match LLVMGetTypeKind(LLVMTypeOf(val)) {
                 llvm::LLVMTypeKind::LLVMDoubleTypeKind | llvm::LLVMTypeKind::LLVMFloatTypeKind => {
@@ -1053,10 +1191,15 @@ impl TracingJit {
                     LLVMPositionBuilderAtEnd(self.builder, bb_then);
                 }
                 VMInst::JMP => {
+                    let jmp_pc = pc;
                     pc += 1;
                     get_int32!(iseq, pc, dst, i32);
-                    let bb = label_retrieve(try_opt!(labels.get(&((pc as i32 + dst) as usize))));
+                    let dst = (pc as i32 + dst) as usize;
+                    let bb = label_retrieve(try_opt!(labels.get(&dst)));
                     if cur_bb_has_no_terminator(self.builder) {
+                        if dst <= jmp_pc {
+                            self.gen_check_limits(BUILTIN_CHECK_LIMITS, dst, is_func_jit);
+                        }
                         LLVMBuildBr(self.builder, bb);
                     }
                 }
@@ -1151,6 +1294,11 @@ impl TracingJit {
                     pc += 1;
                     let rhs = try_stack!(stack.pop());
                     let lhs = try_stack!(stack.pop());
+                    // Concatenating strings is left to the interpreter. LLVMBuildFAdd() of
+                    // string pointers makes a broken function that aborts the process.
+                    if !is_number(lhs) || !is_number(rhs) {
+                        return Err(());
+                    }
                     stack.push((
                         LLVMBuildFAdd(
                             self.builder,
@@ -1564,11 +1712,44 @@ impl TracingJit {
                         None,
                     ));
                 }
-                VMInst::GET_NAME => {
+                VMInst::GET_LOCAL => {
+                    pc += 1;
+                    get_int32!(iseq, pc, slot, usize);
+                    let var = *try_opt!(env.get(&local_slot_name(slot)));
+                    stack.push((
+                        LLVMBuildLoad(self.builder, var, CString::new("").unwrap().as_ptr()),
+                        None,
+                    ));
+                }
+                VMInst::SET_LOCAL => {
                     pc += 1;
+                    get_int32!(iseq, pc, slot, usize);
+                    // In loops, only the variables passed from the VM are written back.
+                    let dst = if is_func_jit {
+                        self.declare_local_var(local_slot_name(slot), env)
+                    } else {
+                        *try_opt!(env.get(&local_slot_name(slot)))
+                    };
+                    let src = try_stack!(stack.pop());
+                    LLVMBuildStore(self.builder, src, dst);
+                }
+                VMInst::GET_NAME | VMInst::GET_UPVAR => {
+                    let inst = iseq[pc];
+                    pc += 1;
+                    // Variables captured from outer functions are only read to call them.
+                    let (scope, in_env) = if inst == VMInst::GET_UPVAR {
+                        get_int32!(iseq, pc, depth, usize);
+                        let mut scope = scope;
+                        for _ in 0..depth {
+                            scope = &*CallObject::function_scope(*try_opt!(scope.parent.as_ref()));
+                        }
+                        (scope, false)
+                    } else {
+                        (scope, true)
+                    };
                     get_int32!(iseq, pc, id, usize);
                     let name = &const_table.string[id];
-                    match env.get(name) {
+                    match env.get(name).filter(|_| in_env) {
                         Some(val) => {
                             stack.push((
                                 LLVMBuildLoad(
@@ -1579,7 +1760,7 @@ impl TracingJit {
                                 None,
                             ));
                         }
-                        None => match scope.get_value(name).unwrap().val {
+                        None => match try_opt!(scope.get_value(name).ok()).val {
                             vm::ValueBase::Function(box (id, _, _, _)) if id == func_id => {
                                 stack.push((func, None));
                             }
@@ -1703,12 +1884,14 @@ impl TracingJit {
                             },
                             None,
                         )),
+                        // The compiled code refers to the string by address, so give it a copy
+                        // that never moves nor gets freed.
                         vm::ValueBase::String(ref s) => stack.push((
                             LLVMBuildIntToPtr(
                                 self.builder,
                                 LLVMConstInt(
                                     LLVMInt64TypeInContext(self.context),
-                                    s.as_ptr() as u64,
+                                    Box::into_raw(Box::new(s.clone())) as u64,
                                     0,
                                 ),
                                 LLVMPointerType(LLVMInt8TypeInContext(self.context), 0),
@@ -1802,12 +1985,57 @@ impl TracingJit {
         Ok(())
     }
 
+    // Makes the code return if 'check' (jit_check_limits or jit_check_stack) tells it to stop, e.g.
+    // at the entry of a function or a back edge of a loop. Loops return the pc to resume from, and
+    // functions a dummy value. In either case, the interpreter raises the error before running the
+    // next instruction.
+    unsafe fn gen_check_limits(&mut self, check: usize, resume_pc: usize, is_func_jit: bool) {
+        let func = self.cur_func.unwrap();
+        let limits = self.limits_ptr();
+        let stop = LLVMBuildCall(
+            self.builder,
+            *self.builtin_funcs.get(&check).unwrap(),
+            vec![limits].as_mut_ptr(),
+            1,
+            CString::new("").unwrap().as_ptr(),
+        );
+        let stop = LLVMBuildICmp(
+            self.builder,
+            llvm::LLVMIntPredicate::LLVMIntNE,
+            stop,
+            LLVMConstInt(LLVMInt32TypeInContext(self.context), 0, 0),
+            CString::new("stop").unwrap().as_ptr(),
+        );
+
+        let bb_stop = LLVMAppendBasicBlock(func, CString::new("").unwrap().as_ptr());
+        let bb_continue = LLVMAppendBasicBlock(func, CString::new("").unwrap().as_ptr());
+        LLVMBuildCondBr(self.builder, stop, bb_stop, bb_continue);
+
+        LLVMPositionBuilderAtEnd(self.builder, bb_stop);
+        let ret_val = if is_func_jit {
+            LLVMConstNull(LLVMGetReturnType(LLVMGetElementType(LLVMTypeOf(func))))
+        } else {
+            LLVMConstInt(LLVMInt32TypeInContext(self.context), resume_pc as u64, 0)
+        };
+        LLVMBuildRet(self.builder, ret_val);
+
+        LLVMPositionBuilderAtEnd(self.builder, bb_continue);
+    }
+
     pub fn record_function_return_type(&mut self, func_id: FuncId, val: &vm::Value) {
         if let Some(ty) = get_value_type(val) {
             self.function_return_types.insert(func_id, ty);
         }
     }
 
+    // The limits of the VM the code is compiled for, passed to jit_check_limits etc.
+    unsafe fn limits_ptr(&self) -> LLVMValueRef {
+        LLVMConstIntToPtr(
+            LLVMConstInt(LLVMInt64TypeInContext(self.context), self.limits as u64, 0),
+            LLVMPointerType(LLVMInt8TypeInContext(self.context), 0),
+        )
+    }
+
     pub unsafe fn run_llvm_func(&mut self, pc: usize, f: fn(), args: &Vec<vm::Value>) -> vm::Value {
         let mut llvm_args = vec![];
         for arg in args {
@@ -1863,14 +2091,18 @@ pub unsafe fn run_loop_llvm_func(
     f: fn(*mut f64) -> i32,
     vm_state: &mut vm::VMState,
     const_table: &vm::ConstantTable,
-    local_vars: &Vec<(usize, ValueType)>,
+    local_vars: &Vec<(LoopVariable, ValueType)>,
 ) -> Option<isize> {
     let scope = *vm_state.scope.last().unwrap();
+    let sp = vm_state.frames.last().unwrap().sp;
     let mut args_of_local_vars = vec![];
 
-    for (id, _) in local_vars {
-        let name = &const_table.string[*id];
-        args_of_local_vars.push(match (*scope).get_value(name).unwrap().val {
+    for (var, _) in local_vars {
+        let val = match *var {
+            LoopVariable::Name(id) => (*scope).get_value(&const_table.string[id]).unwrap(),
+            LoopVariable::Local(slot) => vm_state.stack[sp + slot].clone(),
+        };
+        args_of_local_vars.push(match val.val {
             vm::ValueBase::Number(f) => Box::into_raw(Box::new(f)) as *mut libc::c_void,
             vm::ValueBase::Bool(b) => Box::into_raw(Box::new(b)) as *mut libc::c_void,
             _ => return None,
@@ -1883,16 +2115,18 @@ pub unsafe fn run_loop_llvm_func(
     );
     // println!("after:  farg[{:?}] local[{:?}]", args_of_arg_vars, args_of_local_vars);
 
-    for (i, (id, ty)) in local_vars.iter().enumerate() {
-        let name = const_table.string[*id].clone();
-        (*scope).set_value_if_exist(
-            name,
-            match ty {
-                ValueType::Number => vm::Value::number(*(args_of_local_vars[i] as *mut f64)),
-                ValueType::Bool => vm::Value::bool(*(args_of_local_vars[i] as *mut bool)),
-                _ => unimplemented!(),
-            },
-        );
+    for (i, (var, ty)) in local_vars.iter().enumerate() {
+        let val = match ty {
+            ValueType::Number => vm::Value::number(*(args_of_local_vars[i] as *mut f64)),
+            ValueType::Bool => vm::Value::bool(*(args_of_local_vars[i] as *mut bool)),
+            _ => unimplemented!(),
+        };
+        match *var {
+            LoopVariable::Name(id) => {
+                (*scope).set_value_if_exist(const_table.string[id].clone(), val)
+            }
+            LoopVariable::Local(slot) => vm_state.stack[sp + slot] = val,
+        }
         Box::from_raw(args_of_local_vars[i]);
     }
 
@@ -1926,11 +2160,15 @@ const BUILTIN_PROCESS_STDOUT_WRITE: usize = 4;
 const BUILTIN_MATH_POW: usize = 5;
 const BUILTIN_MATH_FLOOR: usize = 6;
 const BUILTIN_MATH_RANDOM: usize = 7;
+const BUILTIN_CHECK_LIMITS: usize = 8;
+const BUILTIN_CHECK_STACK: usize = 9;
+const BUILTIN_LEAVE_CALL: usize = 10;
 
 #[no_mangle]
-pub extern "C" fn console_log_string(s: vm::RawStringPtr) {
+pub extern "C" fn console_log_string(s: *const JsString) {
     unsafe {
-        libc::printf(b"%s \0".as_ptr() as vm::RawStringPtr, s);
+        (*s).print();
+        libc::printf(b" \0".as_ptr() as vm::RawStringPtr);
     }
 }
 
@@ -1948,7 +2186,8 @@ pub extern "C" fn console_log_bool(b: bool) {
 #[no_mangle]
 pub extern "C" fn console_log_f64(n: f64) {
     unsafe {
-        libc::printf(b"%.15g \0".as_ptr() as vm::RawStringPtr, n);
+        builtin::debug_print(&vm::Value::number(n), false);
+        libc::printf(b" \0".as_ptr() as vm::RawStringPtr);
     }
 }
 
@@ -1960,9 +2199,9 @@ pub extern "C" fn console_log_newline() {
 }
 
 #[no_mangle]
-pub extern "C" fn process_stdout_write(s: vm::RawStringPtr) {
+pub extern "C" fn process_stdout_write(s: *const JsString) {
     unsafe {
-        libc::printf(b"%s\0".as_ptr() as vm::RawStringPtr, s);
+        (*s).print();
     }
 }
 
@@ -1971,19 +2210,40 @@ pub extern "C" fn math_floor(n: f64) -> f64 {
     n.floor()
 }
 
-// TODO: Find a better way for rand gen. (rand::random is slow)
-static mut MATH_RAND_SEED: u64 = 0xf6d582196d588cac;
 #[no_mangle]
 pub extern "C" fn math_random() -> f64 {
-    unsafe {
-        MATH_RAND_SEED = MATH_RAND_SEED ^ (MATH_RAND_SEED << 13);
-        MATH_RAND_SEED = MATH_RAND_SEED ^ (MATH_RAND_SEED >> 17);
-        MATH_RAND_SEED = MATH_RAND_SEED ^ (MATH_RAND_SEED << 5);
-        (MATH_RAND_SEED as f64) / ::std::u64::MAX as f64
-    }
+    realm::current().random()
+}
+
+// Returns non-zero if the code must stop (see Limits::tick_jit()).
+#[no_mangle]
+pub extern "C" fn jit_check_limits(limits: *const Limits) -> i32 {
+    unsafe { (*limits).tick_jit() as i32 }
+}
+
+// Returns non-zero if the function must return (see Limits::check_stack_jit()).
+#[no_mangle]
+pub extern "C" fn jit_check_stack(limits: *const Limits) -> i32 {
+    unsafe { (*limits).check_stack_jit() as i32 }
+}
+
+#[no_mangle]
+pub extern "C" fn jit_leave_call(limits: *const Limits) {
+    unsafe { (*limits).leave_jit_call() }
 }
 
 #[no_mangle]
 pub extern "C" fn math_pow(x: f64, y: f64) -> f64 {
-    x.powf(y)
+    vm::js_pow(x, y)
+}
+
+#[test]
+fn string_concatenation() {
+    use engine::Engine;
+    use vm::Value;
+
+    // Loops that add strings are left to the interpreter.
+    let mut engine = Engine::new();
+    let source = "var s = 'x'; for (var i = 0; i < 20; i++) s += s; s.length";
+    assert_eq!(engine.eval(source), Ok(Value::number(1048576.0)));
 }
@@ -0,0 +1,232 @@
+use libc;
+use std::char;
+use std::fmt;
+
+// See JsString::to_key().
+const KEY_ESCAPE: char = '\u{1b}';
+
+// The property name of an own data property called '__proto__', which must not be confused with
+// the internal '__proto__' property holding the prototype.
+pub const PROTO_KEY: &str = "\u{1b}__proto__";
+
+// Keys of the hidden properties holding the internal slots of objects start with this. to_key()
+// never makes such keys, so scripts can neither read nor forge internal slots.
+pub const INTERNAL_SLOT_PREFIX: &str = "\u{1b}[";
+
+// A JavaScript string value: a sequence of UTF-16 code units.
+// Unlike Rust strings, it can contain NUL and unpaired surrogates, and 'length' and indexing
+// are in code units, as the spec requires.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JsString(Vec<u16>);
+
+impl JsString {
+    pub fn new() -> JsString {
+        JsString(vec![])
+    }
+
+    pub fn from_units(units: Vec<u16>) -> JsString {
+        JsString(units)
+    }
+
+    pub fn units(&self) -> &[u16] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Returns the code unit at the index 'n'.
+    pub fn at(&self, n: usize) -> Option<u16> {
+        self.0.get(n).cloned()
+    }
+
+    // Returns the code point starting at the index 'n'. A surrogate pair is combined into one
+    // code point, and an unpaired surrogate is returned as it is.
+    pub fn code_point_at(&self, n: usize) -> Option<u32> {
+        let first = self.at(n)? as u32;
+        Some(match self.at(n + 1) {
+            Some(second)
+                if 0xd800 <= first && first <= 0xdbff && 0xdc00 <= second && second <= 0xdfff =>
+            {
+                (first - 0xd800) * 0x400 + (second as u32 - 0xdc00) + 0x10000
+            }
+            _ => first,
+        })
+    }
+
+    pub fn substring(&self, start: usize, end: usize) -> JsString {
+        JsString(self.0[start..end].to_vec())
+    }
+
+    pub fn concat(&self, other: &JsString) -> JsString {
+        let mut units = self.0.clone();
+        units.extend_from_slice(&other.0);
+        JsString(units)
+    }
+
+    // Returns the index of the first occurrence of 'search' at or after 'from'.
+    pub fn find(&self, search: &JsString, from: usize) -> Option<usize> {
+        if search.len() > self.len() {
+            return None;
+        }
+        (from..self.len() - search.len() + 1).find(|&i| self.0[i..].starts_with(&search.0))
+    }
+
+    // Returns the index of the last occurrence of 'search' at or before 'from'.
+    pub fn rfind(&self, search: &JsString, from: usize) -> Option<usize> {
+        if search.len() > self.len() {
+            return None;
+        }
+        (0..from.min(self.len() - search.len()) + 1)
+            .rev()
+            .find(|&i| self.0[i..].starts_with(&search.0))
+    }
+
+    pub fn starts_with(&self, search: &JsString) -> bool {
+        self.0.starts_with(&search.0)
+    }
+
+    pub fn ends_with(&self, search: &JsString) -> bool {
+        self.0.ends_with(&search.0)
+    }
+
+    pub fn eq_str(&self, s: &str) -> bool {
+        self.0.iter().cloned().eq(s.encode_utf16())
+    }
+
+    // Converts the string into a property name. Property names are Rust strings, which cannot
+    // hold unpaired surrogates, so each of them is encoded as KEY_ESCAPE followed by
+    // U+E000 + (surrogate - 0xD800), and KEY_ESCAPE itself is doubled. Different strings thus
+    // never become the same property name, and from_key() gets the original string back.
+    // "__proto__" becomes PROTO_KEY.
+    pub fn to_key(&self) -> String {
+        if self.eq_str("__proto__") {
+            return PROTO_KEY.to_string();
+        }
+        let mut key = String::with_capacity(self.len());
+        for c in char::decode_utf16(self.0.iter().cloned()) {
+            match c {
+                Ok(KEY_ESCAPE) => {
+                    key.push(KEY_ESCAPE);
+                    key.push(KEY_ESCAPE);
+                }
+                Ok(c) => key.push(c),
+                Err(e) => {
+                    let surrogate = e.unpaired_surrogate() as u32;
+                    key.push(KEY_ESCAPE);
+                    key.push(char::from_u32(0xe000 + surrogate - 0xd800).unwrap());
+                }
+            }
+        }
+        key
+    }
+
+    pub fn from_key(key: &str) -> JsString {
+        if key == PROTO_KEY {
+            return JsString::from("__proto__");
+        }
+        let mut units = vec![];
+        let mut chars = key.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                KEY_ESCAPE => match chars.next() {
+                    Some(KEY_ESCAPE) | None => units.push(KEY_ESCAPE as u16),
+                    Some(c) => units.push((c as u32 - 0xe000 + 0xd800) as u16),
+                },
+                c => {
+                    let mut buf = [0; 2];
+                    units.extend_from_slice(c.encode_utf16(&mut buf));
+                }
+            }
+        }
+        JsString(units)
+    }
+
+    // Writes the string to stdout through libc (like printf does) so that the output is not
+    // reordered with what the other builtins and the JIT-compiled code print.
+    pub unsafe fn print(&self) {
+        for byte in self.to_string().bytes() {
+            libc::putchar(byte as libc::c_int);
+        }
+    }
+}
+
+impl<'a> From<&'a str> for JsString {
+    fn from(s: &'a str) -> JsString {
+        JsString(s.encode_utf16().collect())
+    }
+}
+
+impl From<String> for JsString {
+    fn from(s: String) -> JsString {
+        JsString::from(s.as_str())
+    }
+}
+
+// Unpaired surrogates are replaced with U+FFFD.
+impl fmt::Display for JsString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from_utf16_lossy(&self.0))
+    }
+}
+
+impl fmt::Debug for JsString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", String::from_utf16_lossy(&self.0))
+    }
+}
+
+#[test]
+fn utf16_semantics() {
+    let s = JsString::from("a\u{1f600}b");
+    assert_eq!(s.len(), 4);
+    assert_eq!(s.at(1), Some(0xd83d));
+    assert_eq!(s.at(2), Some(0xde00));
+    assert_eq!(s.code_point_at(1), Some(0x1f600));
+    assert_eq!(s.code_point_at(2), Some(0xde00));
+    assert_eq!(s.at(4), None);
+    assert_eq!(s.substring(1, 2).to_string(), "\u{fffd}");
+    assert_eq!(s.substring(1, 3).to_string(), "\u{1f600}");
+}
+
+#[test]
+fn nul_and_comparison() {
+    let s = JsString::from("a\u{0}b");
+    assert_eq!(s.len(), 3);
+    assert!(s.eq_str("a\u{0}b"));
+    assert!(JsString::from("a") < JsString::from("b"));
+    // Strings are compared by code units, so U+FF61 sorts after the surrogates of U+1F600.
+    assert!(JsString::from("\u{1f600}") < JsString::from("\u{ff61}"));
+}
+
+#[test]
+fn find() {
+    let s = JsString::from("abcabc");
+    let bc = JsString::from("bc");
+    assert_eq!(s.find(&bc, 0), Some(1));
+    assert_eq!(s.find(&bc, 2), Some(4));
+    assert_eq!(s.find(&bc, 5), None);
+    assert_eq!(s.rfind(&bc, 6), Some(4));
+    assert_eq!(s.rfind(&bc, 3), Some(1));
+    assert_eq!(s.find(&JsString::new(), 6), Some(6));
+}
+
+#[test]
+fn property_keys() {
+    let lone_high = JsString::from_units(vec![0x61, 0xd800]);
+    let lone_low = JsString::from_units(vec![0x61, 0xdc00]);
+    let escape = JsString::from("a\u{1b}\u{e000}");
+    let keys = [lone_high.to_key(), lone_low.to_key(), escape.to_key()];
+    assert!(keys[0] != keys[1] && keys[0] != keys[2] && keys[1] != keys[2]);
+    assert_eq!(JsString::from_key(&keys[0]), lone_high);
+    assert_eq!(JsString::from_key(&keys[1]), lone_low);
+    assert_eq!(JsString::from_key(&keys[2]), escape);
+    assert_eq!(JsString::from("a\u{1f600}").to_key(), "a\u{1f600}");
+    assert_eq!(JsString::from("__proto__").to_key(), PROTO_KEY);
+    assert_eq!(JsString::from_key(PROTO_KEY), JsString::from("__proto__"));
+}
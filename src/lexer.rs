@@ -1,11 +1,10 @@
+use bigint::BigInt;
+use js_string::JsString;
 use parser::Error;
 use token::{convert_reserved_keyword, Kind, Symbol, Token};
 
 use std::collections::VecDeque;
 
-use encoding::all::UTF_16BE;
-use encoding::{DecoderTrap, Encoding};
-
 #[derive(Clone, Debug)]
 pub struct Lexer {
     pub code: String,
@@ -139,48 +138,79 @@ impl Lexer {
     pub fn read_number(&mut self) -> Result<Token, Error> {
         let pos = self.pos;
         self.pos_line_list.push((pos, self.line));
+        let is_hex = self.starts_with("0x") || self.starts_with("0X");
         let mut is_float = false;
         let mut last = ' ';
         let num = self.skip_while(|c| {
-            let is_f = "eE".contains(last) && "+-0123456789".contains(c);
+            let is_f = !is_hex && "eE".contains(last) && "+-0123456789".contains(c);
             let is_end_of_num = !c.is_alphanumeric() && c != '.' && !is_f;
             is_float = is_float || is_f || c == '.';
             last = c;
             !is_end_of_num
         })?;
 
+        let invalid_literal =
+            |msg: &str| Error::UnexpectedToken(pos, ErrorMsgKind::Normal, msg.to_string());
+
+        if is_float {
+            if num.ends_with('n') {
+                return Err(invalid_literal("BigInt literals must be integers"));
+            }
+            return match num.parse() {
+                Ok(n) => Ok(Token::new_number(n, pos)),
+                Err(_) => Err(invalid_literal("invalid number literal")),
+            };
+        }
+
+        // A BigInt literal has the suffix 'n'.
+        let is_bigint = num.ends_with('n');
+        let literal = if is_bigint {
+            &num[..num.len() - 1]
+        } else {
+            num.as_str()
+        };
+
         macro_rules! verify {
             ( $( $valid_chars:pat ),* ) => {
-                num.chars().all(|c| match c.to_ascii_lowercase() {
+                literal.chars().all(|c| match c.to_ascii_lowercase() {
                     $( $valid_chars )|* => true,
                     _ => false,
                 })
             }
         }
 
-        let num: f64 = if is_float {
-            num.parse().unwrap()
-        } else if num.len() > 2
-            && num.chars().nth(1).unwrap().to_ascii_lowercase() == 'x'
+        // Parsed as a BigInt first so that the conversion to a Number is rounded only once.
+        let n = if literal.len() >= 2
+            && literal.chars().nth(1).unwrap().to_ascii_lowercase() == 'x'
             && verify!('0'...'9', 'a'...'f', 'x')
         {
-            self.read_hex_num(&num[2..]) as f64
-        } else if num.len() > 2
-            && num.chars().nth(1).unwrap().to_ascii_lowercase() == 'b'
+            BigInt::parse(&literal[2..], 16)
+        } else if literal.len() >= 2
+            && literal.chars().nth(1).unwrap().to_ascii_lowercase() == 'b'
             && verify!('0'...'1', 'b')
         {
-            self.read_bin_num(&num[2..]) as f64
-        } else if num.chars().nth(0).unwrap() == '0' && verify!('0'...'7') {
-            self.read_oct_num(&num[1..]) as f64
-        } else if (num.len() > 2 && num.chars().nth(1).unwrap().to_ascii_lowercase() == 'o')
+            BigInt::parse(&literal[2..], 2)
+        } else if literal.len() > 1 && literal.starts_with('0') && verify!('0'...'7') {
+            if is_bigint {
+                return Err(invalid_literal("legacy octal literals can't be BigInt"));
+            }
+            BigInt::parse(&literal[1..], 8)
+        } else if (literal.len() >= 2
+            && literal.chars().nth(1).unwrap().to_ascii_lowercase() == 'o')
             && verify!('0'...'7', 'o')
         {
-            self.read_oct_num(&num[2..]) as f64
+            BigInt::parse(&literal[2..], 8)
         } else {
-            self.read_dec_num(num.as_str()) as f64
-        };
+            let digits: String = literal.chars().filter(|c| c.is_digit(10)).collect();
+            BigInt::parse(digits.as_str(), 10)
+        }
+        .ok_or_else(|| invalid_literal("invalid number literal"))?;
 
-        Ok(Token::new_number(num, pos))
+        if is_bigint {
+            Ok(Token::new_bigint(n, pos))
+        } else {
+            Ok(Token::new_number(n.to_f64(), pos))
+        }
     }
 
     fn read_hex_num(&mut self, num_literal: &str) -> i64 {
@@ -191,32 +221,6 @@ impl Lexer {
                 _ => n,
             })
     }
-
-    fn read_dec_num(&mut self, num_literal: &str) -> i64 {
-        num_literal
-            .chars()
-            .fold(0, |n, c| match c.to_ascii_lowercase() {
-                '0'...'9' => n * 10 + c.to_digit(10).unwrap() as i64,
-                _ => n,
-            })
-    }
-
-    fn read_oct_num(&mut self, num_literal: &str) -> i64 {
-        num_literal
-            .chars()
-            .fold(0, |n, c| match c.to_ascii_lowercase() {
-                '0'...'7' => n * 8 + c.to_digit(8).unwrap() as i64,
-                _ => n,
-            })
-    }
-    fn read_bin_num(&mut self, num_literal: &str) -> i64 {
-        num_literal
-            .chars()
-            .fold(0, |n, c| match c.to_ascii_lowercase() {
-                '0' | '1' => n * 2 + c.to_digit(2).unwrap() as i64,
-                _ => n,
-            })
-    }
 }
 
 impl Lexer {
@@ -224,69 +228,82 @@ impl Lexer {
         let pos = self.pos;
         self.pos_line_list.push((pos, self.line));
         let quote = self.skip_char()?;
-        // TODO: support escape sequence
-        let mut s = "".to_string();
+        // Collected as UTF-16 code units, so escapes like "\uD83D" can express lone surrogates
+        let mut s = vec![];
         loop {
             match self.skip_char()? {
                 q if q == quote => break,
-                '\\' => for c in self.read_escaped_char()? {
-                    s.push(c)
-                },
-                c => s.push(c),
+                '\\' => s.extend(self.read_escaped_char()?),
+                c => {
+                    let mut buf = [0u16; 2];
+                    s.extend_from_slice(c.encode_utf16(&mut buf))
+                }
             }
         }
-        Ok(Token::new_string(s, pos))
+        Ok(Token::new_string(JsString::from_units(s), pos))
     }
 
-    fn read_escaped_char(&mut self) -> Result<Vec<char>, Error> {
+    fn read_escaped_char(&mut self) -> Result<Vec<u16>, Error> {
         let c = self.skip_char()?;
         Ok(match c {
-            '\'' | '"' | '?' | '\\' => vec![c],
-            'a' => vec!['\x07'],
-            'b' => vec!['\x08'],
-            'f' => vec!['\x0c'],
-            'n' => vec!['\x0a'],
-            'r' => vec!['\x0d'],
-            't' => vec!['\x09'],
-            'v' => vec!['\x0b'],
-            'x' => {
-                let hex = self.skip_while(|c| c.is_alphanumeric())?;
-                vec![self.read_hex_num(hex.as_str()) as u8 as char]
-            }
+            'a' => vec![0x07],
+            'b' => vec![0x08],
+            'f' => vec![0x0c],
+            'n' => vec![0x0a],
+            'r' => vec![0x0d],
+            't' => vec![0x09],
+            'v' => vec![0x0b],
+            'x' => vec![self.read_hex_escape(2)? as u16],
             'u' => {
-                let mut u8s = vec![];
-                loop {
-                    let hex = self.skip_while(|c| c.is_alphanumeric())?;
-                    let mut i = 0;
-                    while i < hex.len() {
-                        u8s.push(
-                            self.read_hex_num(&hex[i..i + if i + 2 > hex.len() { 1 } else { 2 }])
-                                as u8,
-                        );
-                        i += 2;
-                    }
-                    if u8s.len() % 2 != 0 {
-                        // TODO: Support \u{X..X}
-                        unimplemented!("unsupported escape sequence");
-                    }
-                    let save_pos = self.pos;
-                    // TODO: Error handling
-                    if self.skip_char()? == '\\' && self.skip_char()? == 'u' {
-                        continue;
-                    } else {
-                        self.pos = save_pos;
-                        break;
-                    }
+                if !self.skip_char_if_any('{')? {
+                    return Ok(vec![self.read_hex_escape(4)? as u16]);
+                }
+                let pos = self.pos;
+                let hex = self.skip_while(|c| c != '}')?;
+                let code_point = self.read_hex_num(hex.as_str());
+                if hex.is_empty()
+                    || !hex.chars().all(|c| c.is_digit(16))
+                    || code_point > 0x10ffff
+                    || !self.skip_char_if_any('}')?
+                {
+                    return Err(Error::UnexpectedToken(
+                        pos,
+                        ErrorMsgKind::Normal,
+                        "invalid Unicode escape sequence".to_string(),
+                    ));
+                }
+                if code_point < 0x10000 {
+                    vec![code_point as u16]
+                } else {
+                    let code_point = code_point - 0x10000;
+                    vec![
+                        0xd800 + (code_point >> 10) as u16,
+                        0xdc00 + (code_point & 0x3ff) as u16,
+                    ]
                 }
-                UTF_16BE
-                    .decode(u8s.as_slice(), DecoderTrap::Strict)
-                    .unwrap()
-                    .chars()
-                    .collect::<Vec<char>>()
             }
-            _ => vec![c],
+            c => {
+                let mut buf = [0u16; 2];
+                c.encode_utf16(&mut buf).to_vec()
+            }
         })
     }
+
+    fn read_hex_escape(&mut self, digits: usize) -> Result<i64, Error> {
+        let pos = self.pos;
+        let mut hex = "".to_string();
+        while hex.len() < digits && !self.eof() && self.next_char()?.is_digit(16) {
+            hex.push(self.skip_char()?);
+        }
+        if hex.len() < digits {
+            return Err(Error::UnexpectedToken(
+                pos,
+                ErrorMsgKind::Normal,
+                "invalid hexadecimal escape sequence".to_string(),
+            ));
+        }
+        Ok(self.read_hex_num(hex.as_str()))
+    }
 }
 
 impl Lexer {
@@ -575,6 +592,26 @@ impl Lexer {
     }
 }
 
+impl Lexer {
+    /// Returns the line and the column (both 1-origin) of the token at 'pos'. 'pos' may point to
+    /// the whitespaces before the token or to the middle of it.
+    pub fn get_line_and_column(&self, mut pos: usize) -> (usize, usize) {
+        let code = self.code.as_bytes();
+        while pos < code.len() && (code[pos] as char).is_whitespace() {
+            pos += 1;
+        }
+        let (tok_pos, line) = self
+            .pos_line_list
+            .iter()
+            .take_while(|(p, _)| *p <= pos)
+            .last()
+            .cloned()
+            .unwrap_or((0, 1));
+        let line_start = self.code[..tok_pos].rfind('\n').map_or(0, |p| p + 1);
+        (line, self.code[line_start..tok_pos].chars().count() + 1)
+    }
+}
+
 #[test]
 fn number() {
     let mut lexer = Lexer::new("1 2 0x34 056 7.89 0b10 5e3 5e+3 5e-3 0999 0O123".to_string());
@@ -589,6 +626,29 @@ fn number() {
     assert_eq!(lexer.next().unwrap().kind, Kind::Number(5e-3));
     assert_eq!(lexer.next().unwrap().kind, Kind::Number(999.0));
     assert_eq!(lexer.next().unwrap().kind, Kind::Number(0o123 as f64));
+
+    let mut lexer =
+        Lexer::new("9007199254740993 0x20000000000001 0n 0x1fn 12345678901234567890n".to_string());
+    assert_eq!(lexer.next().unwrap().kind, Kind::Number(9007199254740992.0));
+    assert_eq!(lexer.next().unwrap().kind, Kind::Number(9007199254740992.0));
+    for n in &["0", "31", "12345678901234567890"] {
+        assert_eq!(
+            lexer.next().unwrap().kind,
+            Kind::BigInt(BigInt::from_string(n).unwrap())
+        );
+    }
+    assert!(Lexer::new("017n".to_string()).next().is_err());
+
+    // Non-integers can't have the BigInt suffix, and malformed literals are syntax errors.
+    let mut lexer = Lexer::new("0x1e3 0x1En".to_string());
+    assert_eq!(lexer.next().unwrap().kind, Kind::Number(0x1e3 as f64));
+    assert_eq!(
+        lexer.next().unwrap().kind,
+        Kind::BigInt(BigInt::from_string("30").unwrap())
+    );
+    for source in &["1.5n", "1e3n", "1.5.3", "1e+", "0xn"] {
+        assert!(Lexer::new(source.to_string()).next().is_err(), "{}", source);
+    }
 }
 
 #[test]
@@ -607,8 +667,14 @@ fn identifier() {
 #[test]
 fn string() {
     let mut lexer = Lexer::new("'aaa' \"bbb\"".to_string());
-    assert_eq!(lexer.next().unwrap().kind, Kind::String("aaa".to_string()));
-    assert_eq!(lexer.next().unwrap().kind, Kind::String("bbb".to_string()));
+    assert_eq!(
+        lexer.next().unwrap().kind,
+        Kind::String(JsString::from("aaa"))
+    );
+    assert_eq!(
+        lexer.next().unwrap().kind,
+        Kind::String(JsString::from("bbb"))
+    );
 }
 
 #[test]
@@ -771,8 +837,27 @@ fn escape_seq() {
     );
     assert_eq!(
         lexer.next().unwrap().kind,
-        Kind::String("\' \" \\ \x07 \x08 \x0c \n \r \t \x0b \x12 𩸽".to_string())
+        Kind::String(JsString::from(
+            "\' \" \\ \x07 \x08 \x0c \n \r \t \x0b \x12 𩸽"
+        ))
+    );
+}
+
+#[test]
+fn unicode_escape_seq() {
+    let mut lexer = Lexer::new("\"\\uD83D\" \"\\u{1F600}\\u{41}\"".to_string());
+    assert_eq!(
+        lexer.next().unwrap().kind,
+        Kind::String(JsString::from_units(vec![0xd83d]))
+    );
+    assert_eq!(
+        lexer.next().unwrap().kind,
+        Kind::String(JsString::from_units(vec![0xd83d, 0xde00, 0x41]))
     );
+    for code in &["\"\\u12\"", "\"\\xg0\"", "\"\\u{110000}\"", "\"\\u{41\""] {
+        let mut lexer = Lexer::new(code.to_string());
+        assert!(lexer.next().is_err());
+    }
 }
 
 #[test]
@@ -798,3 +883,15 @@ fn comment() {
         Kind::Identifier("y".to_string())
     );
 }
+
+#[test]
+fn line_and_column() {
+    let mut lexer = Lexer::new("var a\n  foo( b)".to_string());
+    while lexer.next().is_ok() {}
+    assert_eq!(lexer.get_line_and_column(0), (1, 1));
+    assert_eq!(lexer.get_line_and_column(4), (1, 5));
+    assert_eq!(lexer.get_line_and_column(5), (2, 3));
+    assert_eq!(lexer.get_line_and_column(10), (2, 3));
+    assert_eq!(lexer.get_line_and_column(11), (2, 6));
+    assert_eq!(lexer.get_line_and_column(12), (2, 8));
+}
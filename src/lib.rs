@@ -2,21 +2,34 @@
 #![feature(if_while_or_patterns)]
 #![feature(repeat_generic_slice)]
 
+pub mod bigint;
 pub mod builtin;
 pub mod bytecode_gen;
+pub mod collection;
+pub mod date;
+pub mod engine;
 pub mod extract_anony_func;
 pub mod gc;
+pub mod host;
 pub mod id;
 pub mod jit;
+pub mod js_string;
 pub mod lexer;
+pub mod limits;
 pub mod node;
 pub mod parser;
+pub mod proxy;
+#[macro_use]
+pub mod realm;
+pub mod scope_analysis;
 pub mod token;
+pub mod typed_array;
 pub mod vm;
 pub mod vm_codegen;
 
+pub use engine::Engine;
+
 extern crate ansi_term;
-extern crate encoding;
 extern crate libc;
 extern crate llvm_sys as llvm;
 extern crate nix;
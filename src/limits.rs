@@ -0,0 +1,484 @@
+// Limits on how long a VM runs code: the number of instructions (fuel), a deadline, and an
+// interrupt that other threads can request. When one is hit, the VM stops with
+// RuntimeError::Terminated, which JS code can't catch.
+//
+// The interpreter checks the limits before every instruction, and JIT-compiled code at the back
+// edges of loops. Such code only tells whether to stop (see jit_check_limits()), and the
+// interpreter raises the error right after returning from it.
+//
+// The number of calls running and the native stack used by them are limited too, but hitting
+// those limits throws a RangeError like other JS engines do. Calls of JS functions only recurse in
+// Rust when builtin functions call back into JS, or in JIT-compiled code.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vm::RuntimeError;
+
+// The deadline and interrupts are checked once in this many instructions, since getting the time
+// is much slower than running an instruction.
+const CHECK_INTERVAL: u32 = 1024;
+
+// The stack left unused by calls, for the Rust code that runs between the checks and raises the
+// error.
+const STACK_MARGIN: usize = 256 * 1024;
+
+// The stack calls may use when the size of the stack of the thread is unknown.
+const DEFAULT_STACK_SIZE: usize = 1024 * 1024;
+
+// The number of calls that may run at once by default, about as many as other JS engines allow.
+const DEFAULT_MAX_CALL_DEPTH: usize = 10000;
+
+#[derive(Debug)]
+pub struct Limits {
+    fuel: Cell<Option<u64>>, // The number of instructions left to run, or None if unlimited
+    deadline: Cell<Option<Instant>>,
+    interrupted: Arc<AtomicBool>,
+    ticks: Cell<u32>,
+    stack_limit: Cell<usize>, // The address of the stack below which calls throw a RangeError
+    thread_stack_limit: usize, // The stack_limit that lets calls use as much stack as possible
+    stack_overflowed: Cell<bool>, // Set by JIT-compiled code, which can't raise the error itself
+    max_call_depth: Cell<Option<usize>>,
+    jit_call_depth: Cell<usize>, // The calls running, counted by JIT-compiled functions too
+}
+
+/// Makes the VM it was got from stop running code, from any thread.
+#[derive(Clone, Debug)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Limits {
+    pub fn new() -> Limits {
+        let stack_limit = default_stack_limit();
+        Limits {
+            fuel: Cell::new(None),
+            deadline: Cell::new(None),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            ticks: Cell::new(0),
+            stack_limit: Cell::new(stack_limit),
+            thread_stack_limit: stack_limit,
+            stack_overflowed: Cell::new(false),
+            max_call_depth: Cell::new(Some(DEFAULT_MAX_CALL_DEPTH)),
+            jit_call_depth: Cell::new(0),
+        }
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel.get()
+    }
+
+    pub fn set_fuel(&self, fuel: Option<u64>) {
+        self.fuel.set(fuel)
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline.get()
+    }
+
+    pub fn set_deadline(&self, deadline: Option<Instant>) {
+        self.deadline.set(deadline)
+    }
+
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.deadline.set(Some(Instant::now() + timeout))
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            interrupted: self.interrupted.clone(),
+        }
+    }
+
+    // Lets calls use 'size' bytes of the stack below the caller of this method, or as much of the
+    // stack of the thread as possible if None.
+    pub fn set_max_stack_size(&self, size: Option<usize>) {
+        let limit = match size {
+            Some(size) => stack_pointer()
+                .saturating_sub(size)
+                .max(self.thread_stack_limit),
+            None => self.thread_stack_limit,
+        };
+        self.stack_limit.set(limit)
+    }
+
+    pub fn set_max_call_depth(&self, depth: Option<usize>) {
+        self.max_call_depth.set(depth)
+    }
+
+    // Called before pushing a frame when 'depth' frames are on the call stack.
+    #[inline]
+    pub fn check_call_depth(&self, depth: usize) -> Result<(), RuntimeError> {
+        match self.max_call_depth.get() {
+            Some(max) if depth >= max => Err(stack_overflow_error()),
+            _ => Ok(()),
+        }
+    }
+
+    // Called before running a nested interpreter loop.
+    #[inline]
+    pub fn check_stack(&self) -> Result<(), RuntimeError> {
+        if stack_pointer() < self.stack_limit.get() {
+            return Err(stack_overflow_error());
+        }
+        Ok(())
+    }
+
+    // Called before running JIT-compiled code when 'depth' frames are on the call stack. The calls
+    // the code makes are counted from there (see check_stack_jit()).
+    pub fn enter_jit(&self, depth: usize) {
+        self.jit_call_depth.set(depth)
+    }
+
+    // Like check_stack() and check_call_depth(), but for JIT-compiled functions, which call it at
+    // their entry and leave_jit_call() before returning. Returns true if the function must return
+    // at once, which it does while the error is left for check_jit_stack_overflow().
+    pub fn check_stack_jit(&self) -> bool {
+        let depth = self.jit_call_depth.get();
+        self.jit_call_depth.set(depth + 1);
+        if stack_pointer() < self.stack_limit.get() || self.check_call_depth(depth).is_err() {
+            self.stack_overflowed.set(true);
+        }
+        self.stack_overflowed.get()
+    }
+
+    pub fn leave_jit_call(&self) {
+        self.jit_call_depth.set(self.jit_call_depth.get() - 1)
+    }
+
+    // Called after running JIT-compiled code, to raise the error it left.
+    pub fn check_jit_stack_overflow(&self) -> Result<(), RuntimeError> {
+        if self.stack_overflowed.replace(false) {
+            return Err(stack_overflow_error());
+        }
+        Ok(())
+    }
+
+    // Consumes the fuel for an instruction, and checks the other limits now and then.
+    #[inline]
+    pub fn tick(&self) -> Result<(), RuntimeError> {
+        if let Some(fuel) = self.fuel.get() {
+            if fuel == 0 {
+                return Err(RuntimeError::Terminated(
+                    "terminated: out of fuel".to_string(),
+                ));
+            }
+            self.fuel.set(Some(fuel - 1));
+        }
+
+        let ticks = self.ticks.get().wrapping_add(1);
+        self.ticks.set(ticks);
+        if ticks % CHECK_INTERVAL != 0 {
+            return Ok(());
+        }
+
+        // A request to interrupt is forgotten once it stops the VM, so that the VM can run code
+        // again.
+        if self.interrupted.swap(false, Ordering::SeqCst) {
+            return Err(RuntimeError::Terminated(
+                "terminated: interrupted".to_string(),
+            ));
+        }
+        match self.deadline.get() {
+            Some(deadline) if Instant::now() >= deadline => Err(RuntimeError::Terminated(
+                "terminated: timed out".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Like tick(), but for JIT-compiled code, which can't raise errors. Returns true if the code
+    // must stop. The limit that was hit is left for the next tick() to report.
+    pub fn tick_jit(&self) -> bool {
+        // The loops in functions that returned early due to a stack overflow stop too.
+        if self.stack_overflowed.get() {
+            return true;
+        }
+
+        if let Some(fuel) = self.fuel.get() {
+            if fuel == 0 {
+                return true;
+            }
+            self.fuel.set(Some(fuel - 1));
+        }
+
+        let ticks = self.ticks.get().wrapping_add(1);
+        self.ticks.set(ticks);
+        if ticks % CHECK_INTERVAL != 0 {
+            return false;
+        }
+
+        let deadline_passed = self
+            .deadline
+            .get()
+            .map_or(false, |deadline| Instant::now() >= deadline);
+        if self.interrupted.load(Ordering::SeqCst) || deadline_passed {
+            // Makes the next tick() check the deadline and interrupts.
+            self.ticks.set(CHECK_INTERVAL - 1);
+            return true;
+        }
+        false
+    }
+}
+
+fn stack_overflow_error() -> RuntimeError {
+    RuntimeError::Range("range error: Maximum call stack size exceeded".to_string())
+}
+
+// Roughly the address of the top of the stack.
+#[inline(always)]
+fn stack_pointer() -> usize {
+    let top = 0u8;
+    &top as *const u8 as usize
+}
+
+fn default_stack_limit() -> usize {
+    match thread_stack_bottom() {
+        Some(bottom) => bottom + STACK_MARGIN,
+        None => stack_pointer().saturating_sub(DEFAULT_STACK_SIZE),
+    }
+}
+
+// The lowest address of the stack of the current thread.
+#[cfg(target_os = "linux")]
+fn thread_stack_bottom() -> Option<usize> {
+    use libc;
+    use std::{mem, ptr};
+
+    unsafe {
+        let mut attr: libc::pthread_attr_t = mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let mut addr = ptr::null_mut();
+        let mut size = 0;
+        let result = libc::pthread_attr_getstack(&attr, &mut addr, &mut size);
+        libc::pthread_attr_destroy(&mut attr);
+        if result != 0 {
+            return None;
+        }
+        Some(addr as usize)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_stack_bottom() -> Option<usize> {
+    None
+}
+
+#[test]
+fn limits() {
+    let limits = Limits::new();
+    for _ in 0..10 * CHECK_INTERVAL {
+        assert_eq!(limits.tick(), Ok(()));
+    }
+
+    limits.set_fuel(Some(3));
+    assert!(limits.tick().is_ok() && !limits.tick_jit() && limits.tick().is_ok());
+    assert!(limits.tick_jit());
+    assert!(limits.tick().is_err());
+    limits.set_fuel(None);
+
+    limits.interrupt_handle().interrupt();
+    let stopped = (0..CHECK_INTERVAL).position(|_| limits.tick_jit()).unwrap();
+    assert!(stopped < CHECK_INTERVAL as usize);
+    assert_eq!(
+        limits.tick(),
+        Err(RuntimeError::Terminated(
+            "terminated: interrupted".to_string()
+        ))
+    );
+    // The interrupt is handled only once.
+    for _ in 0..10 * CHECK_INTERVAL {
+        assert_eq!(limits.tick(), Ok(()));
+    }
+
+    limits.set_timeout(Duration::from_millis(0));
+    let stopped = (0..CHECK_INTERVAL).position(|_| limits.tick().is_err());
+    assert!(stopped.is_some());
+}
+
+#[test]
+fn terminate_scripts() {
+    use engine::{Engine, Error};
+    use std::sync::mpsc;
+    use std::thread;
+    use vm::Value;
+
+    let mut engine = Engine::new();
+
+    // Terminations can't be caught, and the engine can run code again after them.
+    engine.vm().set_fuel(Some(10000));
+    match engine.eval("var caught = false; try { while (true) {} } catch (e) { caught = true }") {
+        Err(Error::Terminated(_)) => {}
+        otherwise => panic!("{:?}", otherwise),
+    }
+    assert_eq!(engine.get_global("caught"), Some(Value::bool(false)));
+    assert_eq!(engine.vm().fuel(), Some(0));
+    engine.vm().set_fuel(None);
+    assert_eq!(engine.eval("1 + 2"), Ok(Value::number(3.0)));
+
+    // The inner loop is JIT-compiled after it has run several times, and runs forever then. The
+    // script calls ready() just before it, which sets the limit to hit.
+    let source = "
+        for (var j = 0; j < 10; j++) {
+            if (j == 8) ready();
+            for (var i = 0; i < (j < 8 ? 10 : 1 / 0); i++) {}
+        }";
+    engine.register_global_function("ready", |vm, _| {
+        vm.set_timeout(Duration::from_millis(100));
+        Ok(Value::undefined())
+    });
+    match engine.eval(source) {
+        Err(Error::Terminated(msg)) => assert_eq!(msg, "terminated: timed out"),
+        otherwise => panic!("{:?}", otherwise),
+    }
+    assert_eq!(engine.get_global("j"), Some(Value::number(8.0)));
+    engine.vm().set_deadline(None);
+
+    engine.register_global_function("ready", |vm, _| {
+        vm.set_fuel(Some(1000));
+        Ok(Value::undefined())
+    });
+    match engine.eval(source) {
+        Err(Error::Terminated(msg)) => assert_eq!(msg, "terminated: out of fuel"),
+        otherwise => panic!("{:?}", otherwise),
+    }
+    assert_eq!(engine.get_global("j"), Some(Value::number(8.0)));
+    engine.vm().set_fuel(None);
+
+    // Another thread interrupts the script once it has called ready().
+    let (sender, receiver) = mpsc::channel();
+    engine.register_global_function("ready", move |_, _| {
+        sender.send(()).unwrap();
+        Ok(Value::undefined())
+    });
+    let handle = engine.vm().interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        receiver.recv().unwrap();
+        handle.interrupt();
+    });
+    match engine.eval(source) {
+        Err(Error::Terminated(msg)) => assert_eq!(msg, "terminated: interrupted"),
+        otherwise => panic!("{:?}", otherwise),
+    }
+    interrupter.join().unwrap();
+    assert_eq!(engine.get_global("j"), Some(Value::number(8.0)));
+
+    // 'fib' is JIT-compiled after it has been called several times, and has no loops to check
+    // the limits at.
+    let source = "
+        function fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2) }
+        for (var k = 0; k < 10; k++) { fib(10) }
+        ready();
+        fib(100)";
+    engine.register_global_function("ready", |vm, _| {
+        vm.interrupt_handle().interrupt();
+        Ok(Value::undefined())
+    });
+    match engine.eval(source) {
+        Err(Error::Terminated(msg)) => assert_eq!(msg, "terminated: interrupted"),
+        otherwise => panic!("{:?}", otherwise),
+    }
+    assert_eq!(engine.get_global("k"), Some(Value::number(10.0)));
+}
+
+#[test]
+fn stack_overflow() {
+    use engine::Engine;
+    use js_string::JsString;
+    use vm::Value;
+
+    let mut engine = Engine::new();
+    let source = "
+        var depth = 0, message;
+        function f() { depth++; f() }
+        try { f() } catch (e) { message = e.message }
+        message";
+    assert_eq!(
+        engine.eval(source),
+        Ok(Value::string(JsString::from(
+            "Maximum call stack size exceeded"
+        )))
+    );
+    let depth = engine.get_global("depth").unwrap().val.to_number();
+    assert!(depth > 1000.0);
+
+    engine.vm().set_max_call_depth(Some(100));
+    engine.eval(source).unwrap();
+    assert!(engine.get_global("depth").unwrap().val.to_number() < 100.0);
+    // So are the calls through Function.prototype.call() and apply().
+    let source = "
+        depth = 0;
+        function h() { depth++; h.call(null); h.apply(null, []) }
+        try { h() } catch (e) { message = e.message }
+        message";
+    assert_eq!(
+        engine.eval(source),
+        Ok(Value::string(JsString::from(
+            "Maximum call stack size exceeded"
+        )))
+    );
+    assert!(engine.get_global("depth").unwrap().val.to_number() < 100.0);
+    // So are the calls JIT-compiled functions make. 'r' and 'g' are compiled after they have
+    // been called several times, and the tail call in 'g' must not become a loop. The timeout
+    // stops the test if it does.
+    let source = "
+        message = undefined;
+        function r(n) { return n == 0 ? 0 : r(n - 1) + 1 }
+        for (var k = 0; k < 10; k++) r(10);
+        try { r(5000) } catch (e) { message = e.message }
+        message";
+    assert_eq!(
+        engine.eval(source),
+        Ok(Value::string(JsString::from(
+            "Maximum call stack size exceeded"
+        )))
+    );
+    assert_eq!(engine.eval("r(50)"), Ok(Value::number(50.0)));
+    engine.vm().set_max_call_depth(Some(DEFAULT_MAX_CALL_DEPTH));
+    engine.vm().set_timeout(Duration::from_secs(10));
+    let source = "
+        message = undefined;
+        function g(n) { return g(n + 1) }
+        try { g(0) } catch (e) { message = e.message }
+        message";
+    assert_eq!(
+        engine.eval(source),
+        Ok(Value::string(JsString::from(
+            "Maximum call stack size exceeded"
+        )))
+    );
+    engine.vm().set_deadline(None);
+
+    // Calls through other builtin functions recurse in Rust, and are limited by the native stack.
+    let source = "
+        depth = 0;
+        function g() { depth++; [0].map(g) }
+        try { g() } catch (e) { message = e.message }
+        message";
+    assert_eq!(
+        engine.eval(source),
+        Ok(Value::string(JsString::from(
+            "Maximum call stack size exceeded"
+        )))
+    );
+    let depth = engine.get_global("depth").unwrap().val.to_number();
+
+    engine.vm().set_max_stack_size(Some(64 * 1024));
+    engine.eval(source).unwrap();
+    let limited_depth = engine.get_global("depth").unwrap().val.to_number();
+    assert!(0.0 < limited_depth && limited_depth < depth);
+
+    engine.vm().set_max_stack_size(None);
+    engine.eval(source).unwrap();
+    assert_eq!(engine.get_global("depth").unwrap().val.to_number(), depth);
+}
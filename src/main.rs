@@ -41,6 +41,12 @@ fn main() {
                 .help("Show useful information for debugging")
                 .long("debug"),
         )
+        .arg(
+            Arg::with_name("seed")
+                .help("Seed Math.random() to make runs reproducible")
+                .long("seed")
+                .takes_value(true),
+        )
         .arg(Arg::with_name("file").help("Input file name").index(1));
     let app_matches = app.clone().get_matches();
 
@@ -52,9 +58,21 @@ fn main() {
         }
     };
 
+    let seed = match app_matches.value_of("seed").map(|seed| seed.parse::<u64>()) {
+        Some(Ok(seed)) => Some(seed),
+        Some(Err(_)) => {
+            eprintln!(
+                "{}: The seed must be an unsigned integer",
+                Colour::Red.bold().paint("error"),
+            );
+            return;
+        }
+        None => None,
+    };
+
     // Normally run the given code
     if !app_matches.is_present("debug") {
-        run(file_name);
+        run(file_name, seed);
         return;
     }
 
@@ -128,6 +146,7 @@ fn repl() {
     let mut vm = vm::VM::new(vm_codegen.global_varmap);
 
     let mut rl = rustyline::Editor::<()>::new();
+    let mut input_count = 0;
 
     loop {
         let line = match rl.readline("> ") {
@@ -153,6 +172,9 @@ fn repl() {
             Err(_) => break,
         };
 
+        input_count += 1;
+        let input_name = format!("REPL{}", input_count);
+
         let mut parser = parser::Parser::new(line);
 
         let mut node = match parser.parse_all() {
@@ -173,19 +195,15 @@ fn repl() {
         extract_anony_func::AnonymousFunctionExtractor::new().run_toplevel(&mut node);
 
         let mut iseq = vec![];
+        vm_codegen.set_source(input_name.as_str(), &parser.lexer);
         vm_codegen.compile(&node, &mut iseq, true);
 
         vm.const_table = vm_codegen.bytecode_gen.const_table.clone();
         vm.state.pc = 0;
 
         if let Err(e) = vm.run(iseq) {
-            match e {
-                RuntimeError::Unknown => vm::runtime_error("unknown error occurred"),
-                RuntimeError::Unimplemented => vm::runtime_error("unimplemented feature"),
-                RuntimeError::Reference(msg) | RuntimeError::Type(msg) => {
-                    vm::runtime_error(msg.as_str())
-                }
-            }
+            show_runtime_error(&vm, e, input_name.as_str(), &parser.lexer);
+            vm.unwind();
             continue;
         }
 
@@ -202,7 +220,7 @@ fn repl() {
     }
 }
 
-fn run(file_name: &str) {
+fn run(file_name: &str, seed: Option<u64>) {
     match fork() {
         Ok(ForkResult::Parent { child, .. }) => {
             match waitpid(child, None) {
@@ -274,21 +292,69 @@ fn run(file_name: &str) {
 
             let mut vm_codegen = vm_codegen::VMCodeGen::new();
             let mut iseq = vec![];
+            vm_codegen.set_source(file_name, &parser.lexer);
             vm_codegen.compile(&node, &mut iseq, false);
 
             let mut vm = vm::VM::new(vm_codegen.global_varmap);
             vm.const_table = vm_codegen.bytecode_gen.const_table;
+            if let Some(seed) = seed {
+                vm.seed_random(seed);
+            }
 
             if let Err(e) = vm.run(iseq) {
-                match e {
-                    RuntimeError::Unknown => vm::runtime_error("unknown error occurred"),
-                    RuntimeError::Unimplemented => vm::runtime_error("unimplemented feature"),
-                    RuntimeError::Reference(msg) | RuntimeError::Type(msg) => {
-                        vm::runtime_error(msg.as_str())
-                    }
-                }
+                show_runtime_error(&vm, e, file_name, &parser.lexer);
             }
         }
         Err(e) => panic!("Rapidus Internal Error: fork failed: {:?}", e),
     }
 }
+
+// Shows the error with the source location where it occurred and the backtrace of the function
+// calls left in 'vm'. 'lexer' is the one used to parse 'file_name'.
+fn show_runtime_error(vm: &vm::VM, err: RuntimeError, file_name: &str, lexer: &lexer::Lexer) {
+    let msg = match err {
+        RuntimeError::Unknown => "unknown error occurred".to_string(),
+        RuntimeError::Unimplemented => "unimplemented feature".to_string(),
+        RuntimeError::Reference(msg)
+        | RuntimeError::Type(msg)
+        | RuntimeError::Range(msg)
+        | RuntimeError::Syntax(msg)
+        | RuntimeError::Terminated(msg) => msg,
+        RuntimeError::Exception(val) => vm::uncaught_exception_message(&val),
+    };
+
+    let backtrace = vm.backtrace();
+
+    match backtrace
+        .iter()
+        .filter_map(|(_, location)| location.clone())
+        .next()
+    {
+        Some(location) => {
+            eprintln!(
+                "{}({}): {}",
+                Colour::Red.bold().paint("runtime error"),
+                location,
+                msg
+            );
+            if location.file == file_name {
+                if let Some(line) = lexer.code.lines().nth(location.line - 1) {
+                    let caret = line
+                        .chars()
+                        .take(location.column - 1)
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect::<String>();
+                    eprintln!("{}\n{}^", line, caret);
+                }
+            }
+        }
+        None => vm::runtime_error(msg.as_str()),
+    }
+
+    for (name, location) in backtrace {
+        match location {
+            Some(location) => eprintln!("    at {} ({})", name, location),
+            None => eprintln!("    at {}", name),
+        }
+    }
+}
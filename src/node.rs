@@ -1,3 +1,6 @@
+use bigint::BigInt;
+use js_string::JsString;
+
 // TODO: Support all features: https://tc39.github.io/ecma262/#prod-FormalParameter
 #[derive(Clone, Debug, PartialEq)]
 pub struct FormalParameter {
@@ -34,8 +37,8 @@ pub struct Node {
 #[derive(Clone, Debug, PartialEq)]
 pub enum NodeBase {
     StatementList(Vec<Node>),
-    FunctionDecl(String, FormalParameters, Box<Node>), // name, params, body
-    FunctionExpr(Option<String>, FormalParameters, Box<Node>), // Name, params, body
+    FunctionDecl(String, FormalParameters, Box<Node>, String), // name, params, body, source text
+    FunctionExpr(Option<String>, FormalParameters, Box<Node>, String), // Name, params, body, source text
     VarDecl(String, Option<Box<Node>>),
     Member(Box<Node>, String),
     Index(Box<Node>, Box<Node>),
@@ -49,6 +52,8 @@ pub enum NodeBase {
     BinaryOp(Box<Node>, Box<Node>, BinOp),
     TernaryOp(Box<Node>, Box<Node>, Box<Node>),
     Return(Option<Box<Node>>),
+    Throw(Box<Node>),
+    Try(Box<Node>, Option<String>, Box<Node>, Box<Node>), // Block, CatchParam, Catch, Finally
     Break,
     Continue,
     Array(Vec<Node>),
@@ -57,9 +62,11 @@ pub enum NodeBase {
     This,
     Arguments,
     Undefined,
-    String(String),
+    Null,
+    String(JsString),
     Boolean(bool),
     Number(f64),
+    BigInt(BigInt),
     Nope,
     SetCurCallObj(String),
 }
@@ -78,8 +85,8 @@ impl NodeBase {
         // TODO: Support If, UnaryOp, TernaryOp.
         match self {
             // NodeBase::StatementList(Vec<Node>),
-            // NodeBase::FunctionDecl(String, FormalParameters, Box<Node>), // name, params, body
-            // NodeBase::FunctionExpr(Option<String>, FormalParameters, Box<Node>), // Name, params, body
+            // NodeBase::FunctionDecl(String, FormalParameters, Box<Node>, String), // name, params, body, source text
+            // NodeBase::FunctionExpr(Option<String>, FormalParameters, Box<Node>, String), // Name, params, body, source text
             // NodeBase::VarDecl(String, Option<Box<Node>>),
             // NodeBase::Member(Box<Node>, String),
             // NodeBase::Index(Box<Node>, Box<Node>),
@@ -127,7 +134,7 @@ impl NodeBase {
                         _ => None,
                     },
                     (NodeBase::String(l), NodeBase::String(r)) => match op {
-                        BinOp::Add => Some(NodeBase::String(l + r.as_str())),
+                        BinOp::Add => Some(NodeBase::String(l.concat(&r))),
                         _ => None,
                     },
                     _ => None,
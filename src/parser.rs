@@ -1,7 +1,7 @@
 use lexer;
 use lexer::ErrorMsgKind;
 use node::{BinOp, FormalParameter, FormalParameters, Node, NodeBase, PropertyDefinition, UnaryOp};
-use token::{Keyword, Kind, Symbol, Token};
+use token::{keyword_to_str, Keyword, Kind, Symbol, Token};
 
 use ansi_term::Colour;
 
@@ -199,6 +199,8 @@ impl Parser {
             Kind::Keyword(Keyword::Return) => self.read_return_statement(),
             Kind::Keyword(Keyword::Break) => self.read_break_statement(),
             Kind::Keyword(Keyword::Continue) => self.read_continue_statement(),
+            Kind::Keyword(Keyword::Throw) => self.read_throw_statement(),
+            Kind::Keyword(Keyword::Try) => self.read_try_statement(),
             Kind::Symbol(Symbol::OpeningBrace) => self.read_block_statement(),
             _ => {
                 self.lexer.unget(&tok);
@@ -386,6 +388,87 @@ impl Parser {
     }
 }
 
+impl Parser {
+    /// https://tc39.github.io/ecma262/#prod-ThrowStatement
+    fn read_throw_statement(&mut self) -> Result<Node, Error> {
+        let pos = self.lexer.pos - "throw".len();
+
+        // no LineTerminator here
+        if self.lexer.skip(Kind::LineTerminator) {
+            return Err(Error::UnexpectedToken(
+                pos,
+                ErrorMsgKind::Normal,
+                "illegal newline after throw".to_string(),
+            ));
+        }
+
+        let expr = self.read_expression()?;
+
+        Ok(Node::new(NodeBase::Throw(Box::new(expr)), pos))
+    }
+
+    /// https://tc39.github.io/ecma262/#prod-TryStatement
+    fn read_try_statement(&mut self) -> Result<Node, Error> {
+        let pos = self.lexer.pos - "try".len();
+
+        expect!(self, Kind::Symbol(Symbol::OpeningBrace), "expect '{'");
+        let block = self.read_block_statement()?;
+
+        let (param, catch) = if self
+            .lexer
+            .skip_except_lineterminator(Kind::Keyword(Keyword::Catch))
+        {
+            let param = if self
+                .lexer
+                .skip_except_lineterminator(Kind::Symbol(Symbol::OpeningParen))
+            {
+                let tok = self.lexer.next_except_lineterminator()?;
+                let name = match tok.kind {
+                    Kind::Identifier(name) => name,
+                    _ => {
+                        return Err(Error::Expect(
+                            tok.pos,
+                            ErrorMsgKind::Normal,
+                            "expect identifier".to_string(),
+                        ))
+                    }
+                };
+                expect!(self, Kind::Symbol(Symbol::ClosingParen), "expect ')'");
+                Some(name)
+            } else {
+                None
+            };
+            expect!(self, Kind::Symbol(Symbol::OpeningBrace), "expect '{'");
+            (param, self.read_block_statement()?)
+        } else {
+            (None, Node::new(NodeBase::Nope, 0))
+        };
+
+        let finally = if self
+            .lexer
+            .skip_except_lineterminator(Kind::Keyword(Keyword::Finally))
+        {
+            expect!(self, Kind::Symbol(Symbol::OpeningBrace), "expect '{'");
+            self.read_block_statement()?
+        } else {
+            Node::new(NodeBase::Nope, 0)
+        };
+
+        if catch.base == NodeBase::Nope && finally.base == NodeBase::Nope {
+            return Err(Error::Expect(
+                self.lexer.pos,
+                ErrorMsgKind::Normal,
+                "expect 'catch' or 'finally'".to_string(),
+            ));
+        }
+
+        Ok(Node::new(
+            NodeBase::Try(Box::new(block), param, Box::new(catch), Box::new(finally)),
+            pos,
+        ))
+    }
+}
+
 macro_rules! expression { ( $name:ident, $lower:ident, [ $( $op:path ),* ] ) => {
     fn $name (&mut self) -> Result<Node, Error> {
         let mut lhs = self. $lower ()?;
@@ -724,6 +807,16 @@ impl Parser {
                         Kind::Identifier(name) => {
                             lhs = Node::new(NodeBase::Member(Box::new(lhs), name), pos)
                         }
+                        // Reserved words are allowed as property names, like 'map.delete'.
+                        Kind::Keyword(keyword) => {
+                            lhs = Node::new(
+                                NodeBase::Member(
+                                    Box::new(lhs),
+                                    keyword_to_str(&keyword).to_string(),
+                                ),
+                                pos,
+                            )
+                        }
                         _ => {
                             return Err(Error::Expect(
                                 pos_,
@@ -813,7 +906,7 @@ impl Parser {
         match tok.kind {
             Kind::Keyword(Keyword::This) => Ok(Node::new(NodeBase::This, tok.pos)),
             Kind::Keyword(Keyword::Arguments) => Ok(Node::new(NodeBase::Arguments, tok.pos)),
-            Kind::Keyword(Keyword::Function) => self.read_function_expression(),
+            Kind::Keyword(Keyword::Function) => self.read_function_expression(tok.pos),
             Kind::Symbol(Symbol::OpeningParen) => {
                 let x = self.read_expression();
                 expect!(self, Kind::Symbol(Symbol::ClosingParen), "expect ')'");
@@ -830,9 +923,11 @@ impl Parser {
             Kind::Identifier(ref i) if i == "undefined" => {
                 Ok(Node::new(NodeBase::Undefined, tok.pos))
             }
+            Kind::Identifier(ref i) if i == "null" => Ok(Node::new(NodeBase::Null, tok.pos)),
             Kind::Identifier(ident) => Ok(Node::new(NodeBase::Identifier(ident), tok.pos)),
             Kind::String(s) => Ok(Node::new(NodeBase::String(s), tok.pos)),
             Kind::Number(num) => Ok(Node::new(NodeBase::Number(num), tok.pos)),
+            Kind::BigInt(n) => Ok(Node::new(NodeBase::BigInt(n), tok.pos)),
             Kind::LineTerminator => self.read_primary_expression(),
             _ => {
                 return Err(Error::UnexpectedToken(
@@ -845,7 +940,8 @@ impl Parser {
     }
 
     /// https://tc39.github.io/ecma262/#prod-FunctionDeclaration
-    fn read_function_expression(&mut self) -> Result<Node, Error> {
+    /// 'start' is the position of the keyword 'function'.
+    fn read_function_expression(&mut self, start: usize) -> Result<Node, Error> {
         token_start_pos!(pos, self.lexer);
         let name = if let Kind::Identifier(name) = self.lexer.peek()?.kind {
             self.lexer.next()?;
@@ -861,9 +957,10 @@ impl Parser {
         expect!(self, Kind::Symbol(Symbol::OpeningBrace), "expect '{'");
 
         let body = self.read_statement_list(true)?;
+        let source = self.lexer.code[start..self.lexer.pos].to_string();
 
         Ok(Node::new(
-            NodeBase::FunctionExpr(name, params, Box::new(body)),
+            NodeBase::FunctionExpr(name, params, Box::new(body), source),
             pos,
         ))
     }
@@ -917,7 +1014,8 @@ impl Parser {
             match kind {
                 Kind::Identifier(name) => name,
                 Kind::Number(n) => format!("{}", n),
-                Kind::String(s) => s,
+                Kind::BigInt(n) => n.to_string(),
+                Kind::String(s) => s.to_key(),
                 _ => unimplemented!(),
             }
         }
@@ -967,13 +1065,14 @@ impl Parser {
     fn read_declaration(&mut self) -> Result<Node, Error> {
         let tok = self.lexer.next()?;
         match tok.kind {
-            Kind::Keyword(Keyword::Function) => self.read_function_declaration(),
+            Kind::Keyword(Keyword::Function) => self.read_function_declaration(tok.pos),
             _ => unreachable!(),
         }
     }
 
     /// https://tc39.github.io/ecma262/#prod-FunctionDeclaration
-    fn read_function_declaration(&mut self) -> Result<Node, Error> {
+    /// 'start' is the position of the keyword 'function'.
+    fn read_function_declaration(&mut self, start: usize) -> Result<Node, Error> {
         token_start_pos!(pos, self.lexer);
         let name = if let Kind::Identifier(name) = self.lexer.next()?.kind {
             name
@@ -992,28 +1091,40 @@ impl Parser {
         expect!(self, Kind::Symbol(Symbol::OpeningBrace), "expect '{'");
 
         let body = self.read_statement_list(true)?;
+        let source = self.lexer.code[start..self.lexer.pos].to_string();
 
         Ok(Node::new(
-            NodeBase::FunctionDecl(name, params, Box::new(body)),
+            NodeBase::FunctionDecl(name, params, Box::new(body), source),
             pos,
         ))
     }
 
     fn read_formal_parameters(&mut self) -> Result<FormalParameters, Error> {
-        if self.lexer.skip(Kind::Symbol(Symbol::ClosingParen)) {
+        if self
+            .lexer
+            .skip_except_lineterminator(Kind::Symbol(Symbol::ClosingParen))
+        {
             return Ok(vec![]);
         }
 
         let mut params = vec![];
 
         loop {
-            params.push(if self.lexer.skip(Kind::Symbol(Symbol::Rest)) {
-                self.read_function_rest_parameter()?
-            } else {
-                self.read_formal_parameter()?
-            });
+            params.push(
+                if self
+                    .lexer
+                    .skip_except_lineterminator(Kind::Symbol(Symbol::Rest))
+                {
+                    self.read_function_rest_parameter()?
+                } else {
+                    self.read_formal_parameter()?
+                },
+            );
 
-            if self.lexer.skip(Kind::Symbol(Symbol::ClosingParen)) {
+            if self
+                .lexer
+                .skip_except_lineterminator(Kind::Symbol(Symbol::ClosingParen))
+            {
                 break;
             }
 
@@ -1026,7 +1137,7 @@ impl Parser {
     // TODO: Support all features: https://tc39.github.io/ecma262/#prod-FormalParameter
     fn read_formal_parameter(&mut self) -> Result<FormalParameter, Error> {
         token_start_pos!(pos, self.lexer);
-        let name = if let Kind::Identifier(name) = self.lexer.next()?.kind {
+        let name = if let Kind::Identifier(name) = self.lexer.next_except_lineterminator()?.kind {
             name
         } else {
             return Err(Error::Expect(
@@ -1042,7 +1153,7 @@ impl Parser {
     fn read_function_rest_parameter(&mut self) -> Result<FormalParameter, Error> {
         token_start_pos!(pos, self.lexer);
         Ok(FormalParameter::new(
-            if let Kind::Identifier(name) = self.lexer.next()?.kind {
+            if let Kind::Identifier(name) = self.lexer.next_except_lineterminator()?.kind {
                 name
             } else {
                 return Err(Error::Expect(
@@ -1092,7 +1203,10 @@ fn string() {
     assert_eq!(
         parser.parse_all().unwrap(),
         Node::new(
-            NodeBase::StatementList(vec![Node::new(NodeBase::String("aaa".to_string()), 0)]),
+            NodeBase::StatementList(vec![Node::new(
+                NodeBase::String(::js_string::JsString::from("aaa")),
+                0
+            )]),
             0
         )
     );
@@ -1110,6 +1224,18 @@ fn boolean() {
     );
 }
 
+#[test]
+fn null() {
+    let mut parser = Parser::new("null".to_string());
+    assert_eq!(
+        parser.parse_all().unwrap(),
+        Node::new(
+            NodeBase::StatementList(vec![Node::new(NodeBase::Null, 0)]),
+            0
+        )
+    );
+}
+
 #[test]
 fn identifier() {
     let mut parser = Parser::new("variable".to_string());
@@ -1625,6 +1751,16 @@ fn member() {
                 7,
             ),
         ),
+        (
+            "m.delete",
+            Node::new(
+                NodeBase::Member(
+                    Box::new(Node::new(NodeBase::Identifier("m".to_string()), 0)),
+                    "delete".to_string(),
+                ),
+                1,
+            ),
+        ),
     ].iter()
     {
         let mut parser = Parser::new(input.to_string());
@@ -1743,6 +1879,78 @@ fn return_() {
     }
 }
 
+#[test]
+fn throw_() {
+    let mut parser = Parser::new("throw 1".to_string());
+    assert_eq!(
+        parser.parse_all().unwrap(),
+        Node::new(
+            NodeBase::StatementList(vec![Node::new(
+                NodeBase::Throw(Box::new(Node::new(NodeBase::Number(1.0), 6))),
+                0,
+            )]),
+            0
+        )
+    );
+}
+
+#[test]
+fn try_() {
+    let mut parser = Parser::new("try{a}catch(e){b}finally{c}".to_string());
+    assert_eq!(
+        parser.parse_all().unwrap(),
+        Node::new(
+            NodeBase::StatementList(vec![Node::new(
+                NodeBase::Try(
+                    Box::new(Node::new(
+                        NodeBase::StatementList(vec![Node::new(
+                            NodeBase::Identifier("a".to_string()),
+                            4,
+                        )]),
+                        4,
+                    )),
+                    Some("e".to_string()),
+                    Box::new(Node::new(
+                        NodeBase::StatementList(vec![Node::new(
+                            NodeBase::Identifier("b".to_string()),
+                            15,
+                        )]),
+                        15,
+                    )),
+                    Box::new(Node::new(
+                        NodeBase::StatementList(vec![Node::new(
+                            NodeBase::Identifier("c".to_string()),
+                            25,
+                        )]),
+                        25,
+                    )),
+                ),
+                0,
+            )]),
+            0
+        )
+    );
+
+    let mut parser = Parser::new("try{}finally{}".to_string());
+    assert_eq!(
+        parser.parse_all().unwrap(),
+        Node::new(
+            NodeBase::StatementList(vec![Node::new(
+                NodeBase::Try(
+                    Box::new(Node::new(NodeBase::StatementList(vec![]), 4)),
+                    None,
+                    Box::new(Node::new(NodeBase::Nope, 0)),
+                    Box::new(Node::new(NodeBase::StatementList(vec![]), 13)),
+                ),
+                0,
+            )]),
+            0
+        )
+    );
+
+    assert!(Parser::new("try{}".to_string()).parse_all().is_err());
+}
+
 #[test]
 fn if_() {
     use node::BinOp;
@@ -1848,6 +2056,7 @@ fn function_decl() {
                     "f".to_string(),
                     vec![],
                     Box::new(Node::new(NodeBase::StatementList(vec![]), 14)),
+                    "function f() { }".to_string(),
                 ),
                 8,
             ),
@@ -1875,6 +2084,7 @@ fn function_decl() {
                         )]),
                         18,
                     )),
+                    "function f(x, y) { return x + y }".to_string(),
                 ),
                 8,
             ),
@@ -1913,6 +2123,8 @@ fn asi1() {
                         ]),
                         24,
                     )),
+                    "function f() \n         {\n             return \n             {};\n         }"
+                        .to_string(),
                 ),
                 8,
             )]),
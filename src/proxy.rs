@@ -0,0 +1,730 @@
+use builtin::{call_callback, is_callable, to_property_descriptor};
+use gc;
+use js_string::JsString;
+use vm::{construct_object, ArrayValue, PropertyDescriptor, RuntimeError, Value, ValueBase, VM};
+
+// https://tc39.github.io/ecma262/#sec-proxy-object-internal-methods-and-internal-slots
+// The operations on a proxy call the traps of its handler, or are forwarded to its target when the
+// handler doesn't have them. A revoked proxy has null as its target and handler.
+// The results of the traps are checked against the non-configurable properties and the
+// extensibility of the target, like the invariants of the internal methods require.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyValue {
+    pub target: Value,
+    pub handler: Value,
+}
+
+impl ProxyValue {
+    pub fn new(target: Value, handler: Value) -> ProxyValue {
+        ProxyValue {
+            target: target,
+            handler: handler,
+        }
+    }
+
+    pub fn revoke(&mut self) {
+        self.target = Value::null();
+        self.handler = Value::null();
+    }
+
+    // Returns the trap named 'name', or None if the handler doesn't have it.
+    fn trap(&self, name: &str) -> Result<Option<Value>, RuntimeError> {
+        if self.handler.val == ValueBase::Null {
+            return Err(RuntimeError::Type(format!(
+                "type error: cannot perform '{}' on a proxy that has been revoked",
+                name
+            )));
+        }
+        let trap = self
+            .handler
+            .get_property(ValueBase::String(JsString::from(name)), None);
+        match trap.val {
+            ValueBase::Undefined | ValueBase::Null => Ok(None),
+            _ if is_callable(&trap) => Ok(Some(trap)),
+            _ => Err(RuntimeError::Type(format!(
+                "type error: proxy trap '{}' is not a function",
+                name
+            ))),
+        }
+    }
+
+    unsafe fn call_trap(
+        &self,
+        self_: &mut VM,
+        trap: &Value,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        call_callback(self_, trap, self.handler.clone(), &args)
+    }
+}
+
+fn property_key(property: &ValueBase) -> Value {
+    Value::string(property.to_js_string())
+}
+
+fn falsish(trap: &str, property: &ValueBase) -> RuntimeError {
+    RuntimeError::Type(format!(
+        "type error: '{}' on proxy: trap returned falsish for property '{}'",
+        trap,
+        property.to_string()
+    ))
+}
+
+fn violated(trap: &str, msg: String) -> RuntimeError {
+    RuntimeError::Type(format!("type error: '{}' on proxy: {}", trap, msg))
+}
+
+// Returns true if the property 'current' of the target may be reported as 'desc' describes.
+// https://tc39.github.io/ecma262/#sec-iscompatiblepropertydescriptor
+fn is_compatible_descriptor(desc: &PropertyDescriptor, current: &Value) -> bool {
+    if current.configurable {
+        return true;
+    }
+    if desc.configurable == Some(true)
+        || desc
+            .enumerable
+            .map_or(false, |enumerable| enumerable != current.enumerable)
+    {
+        return false;
+    }
+    current.writable
+        || desc.writable != Some(true)
+            && desc
+                .value
+                .as_ref()
+                .map_or(true, |val| val.same_value(&current.val))
+}
+
+// Methods got from an object are bound to it (see Value::get_property), so the ones got through a
+// proxy are bound to the proxy. Builtin methods, which access the internal data of the target
+// directly, stay bound to the target unless they are called on a function proxy like call().
+fn bind_this(val: Value, this: &Value) -> Value {
+    match val.val {
+        ValueBase::Function(box (id, iseq, obj, mut callobj)) => {
+            *callobj.this = this.clone();
+            Value::new(ValueBase::Function(Box::new((id, iseq, obj, callobj))))
+        }
+        ValueBase::BuiltinFunction(box (id, obj, mut callobj)) if is_callable(this) => {
+            *callobj.this = this.clone();
+            Value::new(ValueBase::BuiltinFunction(Box::new((id, obj, callobj))))
+        }
+        val => Value::new(val),
+    }
+}
+
+// The functions below perform the operations on any value, going through the traps of proxies.
+// 'receiver' is the object the property was looked up on first, i.e. the outermost proxy.
+
+pub unsafe fn get_property(
+    self_: &mut VM,
+    obj: &Value,
+    property: ValueBase,
+    receiver: &Value,
+) -> Result<Value, RuntimeError> {
+    let proxy = match obj.val {
+        ValueBase::Proxy(p) => (*p).clone(),
+        _ => return Ok(obj.get_property(property, None)),
+    };
+    let val = match proxy.trap("get")? {
+        Some(trap) => {
+            let val = proxy.call_trap(
+                self_,
+                &trap,
+                vec![
+                    proxy.target.clone(),
+                    property_key(&property),
+                    receiver.clone(),
+                ],
+            )?;
+            match proxy.target.get_own_property(&property.to_property_key()) {
+                Some(ref prop)
+                    if !prop.configurable && !prop.writable && !val.val.same_value(&prop.val) =>
+                {
+                    return Err(violated(
+                        "get",
+                        format!(
+                            "non-configurable, read only property '{}' must report its value",
+                            property.to_string()
+                        ),
+                    ))
+                }
+                _ => val,
+            }
+        }
+        None => get_property(self_, &proxy.target, property, receiver)?,
+    };
+    Ok(bind_this(val, receiver))
+}
+
+pub unsafe fn set_property(
+    self_: &mut VM,
+    obj: &Value,
+    property: ValueBase,
+    val: Value,
+    receiver: &Value,
+    strict: bool,
+) -> Result<(), RuntimeError> {
+    let proxy = match obj.val {
+        ValueBase::Proxy(p) => (*p).clone(),
+        _ => return obj.set_property(property, val, None, strict),
+    };
+    match proxy.trap("set")? {
+        Some(trap) => {
+            let ok = proxy
+                .call_trap(
+                    self_,
+                    &trap,
+                    vec![
+                        proxy.target.clone(),
+                        property_key(&property),
+                        val.clone(),
+                        receiver.clone(),
+                    ],
+                )?
+                .val
+                .to_boolean();
+            if !ok {
+                return if strict {
+                    Err(falsish("set", &property))
+                } else {
+                    Ok(())
+                };
+            }
+            match proxy.target.get_own_property(&property.to_property_key()) {
+                Some(ref prop)
+                    if !prop.configurable && !prop.writable && !val.val.same_value(&prop.val) =>
+                {
+                    Err(violated(
+                        "set",
+                        format!(
+                            "trap returned truish for non-configurable, read only property '{}'",
+                            property.to_string()
+                        ),
+                    ))
+                }
+                _ => Ok(()),
+            }
+        }
+        None => set_property(self_, &proxy.target, property, val, receiver, strict),
+    }
+}
+
+// Returns whether the property is deleted (or not found).
+pub unsafe fn delete_property(
+    self_: &mut VM,
+    obj: &Value,
+    property: ValueBase,
+    strict: bool,
+) -> Result<bool, RuntimeError> {
+    let proxy = match obj.val {
+        ValueBase::Proxy(p) => (*p).clone(),
+        _ => return obj.delete_property(property, strict),
+    };
+    match proxy.trap("deleteProperty")? {
+        Some(trap) => {
+            let deleted = proxy
+                .call_trap(
+                    self_,
+                    &trap,
+                    vec![proxy.target.clone(), property_key(&property)],
+                )?
+                .val
+                .to_boolean();
+            if !deleted {
+                return if strict {
+                    Err(falsish("deleteProperty", &property))
+                } else {
+                    Ok(false)
+                };
+            }
+            match proxy.target.get_own_property(&property.to_property_key()) {
+                Some(ref prop) if !prop.configurable || !proxy.target.is_extensible() => {
+                    Err(violated(
+                        "deleteProperty",
+                        format!(
+                            "trap returned truish for property '{}' the target can't delete",
+                            property.to_string()
+                        ),
+                    ))
+                }
+                _ => Ok(true),
+            }
+        }
+        None => delete_property(self_, &proxy.target, property, strict),
+    }
+}
+
+pub unsafe fn has_property(self_: &mut VM, obj: &Value, key: &str) -> Result<bool, RuntimeError> {
+    let proxy = match obj.val {
+        ValueBase::Proxy(p) => (*p).clone(),
+        _ => return Ok(obj.has_property(key)),
+    };
+    match proxy.trap("has")? {
+        Some(trap) => {
+            let has = proxy
+                .call_trap(
+                    self_,
+                    &trap,
+                    vec![proxy.target.clone(), Value::string(JsString::from_key(key))],
+                )?
+                .val
+                .to_boolean();
+            match proxy.target.get_own_property(key) {
+                Some(ref prop) if !has && (!prop.configurable || !proxy.target.is_extensible()) => {
+                    Err(violated(
+                        "has",
+                        format!(
+                            "property '{}' of the target can't be reported as non-existent",
+                            JsString::from_key(key).to_string()
+                        ),
+                    ))
+                }
+                _ => Ok(has),
+            }
+        }
+        None => has_property(self_, &proxy.target, key),
+    }
+}
+
+// Returns the own property named 'key' with its attributes, or None if there isn't one.
+pub unsafe fn get_own_property(
+    self_: &mut VM,
+    obj: &Value,
+    key: &str,
+) -> Result<Option<Value>, RuntimeError> {
+    let proxy = match obj.val {
+        ValueBase::Proxy(p) => (*p).clone(),
+        _ => return Ok(obj.get_own_property(key)),
+    };
+    let trap = match proxy.trap("getOwnPropertyDescriptor")? {
+        Some(trap) => trap,
+        None => return get_own_property(self_, &proxy.target, key),
+    };
+    let result = proxy.call_trap(
+        self_,
+        &trap,
+        vec![proxy.target.clone(), Value::string(JsString::from_key(key))],
+    )?;
+    let current = proxy.target.get_own_property(key);
+    let extensible = proxy.target.is_extensible();
+    let name = JsString::from_key(key).to_string();
+
+    if result.val == ValueBase::Undefined {
+        return match current {
+            Some(ref current) if !current.configurable || !extensible => Err(violated(
+                "getOwnPropertyDescriptor",
+                format!(
+                    "property '{}' of the target can't be reported as non-existent",
+                    name
+                ),
+            )),
+            _ => Ok(None),
+        };
+    }
+    if !result.is_object() {
+        return Err(violated(
+            "getOwnPropertyDescriptor",
+            format!(
+                "trap returned neither object nor undefined for property '{}'",
+                name
+            ),
+        ));
+    }
+
+    let desc = to_property_descriptor(&result)?;
+    let prop = Value {
+        val: desc.value.clone().unwrap_or(ValueBase::Undefined),
+        writable: desc.writable.unwrap_or(false),
+        enumerable: desc.enumerable.unwrap_or(false),
+        configurable: desc.configurable.unwrap_or(false),
+    };
+    let compatible = match current {
+        None => extensible && prop.configurable,
+        Some(ref current) => {
+            is_compatible_descriptor(&desc, current)
+                && (prop.configurable || !current.configurable)
+                && (prop.configurable || prop.writable || !current.writable)
+        }
+    };
+    if !compatible {
+        return Err(violated(
+            "getOwnPropertyDescriptor",
+            format!(
+                "trap reported a descriptor incompatible with the target for property '{}'",
+                name
+            ),
+        ));
+    }
+    Ok(Some(prop))
+}
+
+// Returns the names of own properties, including non-enumerable ones.
+pub unsafe fn own_property_keys(self_: &mut VM, obj: &Value) -> Result<Vec<String>, RuntimeError> {
+    let proxy = match obj.val {
+        ValueBase::Proxy(p) => (*p).clone(),
+        _ => return Ok(obj.own_property_keys()),
+    };
+    let trap = match proxy.trap("ownKeys")? {
+        Some(trap) => trap,
+        None => return own_property_keys(self_, &proxy.target),
+    };
+    let keys = proxy.call_trap(self_, &trap, vec![proxy.target.clone()])?;
+    let ary = match keys.val {
+        ValueBase::Array(ary) => &*ary,
+        _ => {
+            return Err(RuntimeError::Type(
+                "type error: 'ownKeys' on proxy: trap result is not an array".to_string(),
+            ))
+        }
+    };
+    let mut names = vec![];
+    for key in &ary.elems[0..ary.length] {
+        match key.val {
+            ValueBase::String(ref s) if names.contains(&s.to_key()) => {
+                return Err(RuntimeError::Type(format!(
+                    "type error: 'ownKeys' on proxy: trap returned duplicate entries '{}'",
+                    s.to_string()
+                )))
+            }
+            ValueBase::String(ref s) => names.push(s.to_key()),
+            _ => {
+                return Err(RuntimeError::Type(format!(
+                    "type error: 'ownKeys' on proxy: {} is not a valid property name",
+                    key.to_string()
+                )))
+            }
+        }
+    }
+
+    // The non-configurable keys of the target must be reported, and so must all of its keys and
+    // nothing else if it's not extensible.
+    let extensible = proxy.target.is_extensible();
+    let target_keys = proxy.target.own_property_keys();
+    for key in &target_keys {
+        let configurable = proxy
+            .target
+            .get_own_property(key)
+            .map_or(true, |prop| prop.configurable);
+        if (!configurable || !extensible) && !names.contains(key) {
+            return Err(violated(
+                "ownKeys",
+                format!(
+                    "trap result did not include '{}'",
+                    JsString::from_key(key).to_string()
+                ),
+            ));
+        }
+    }
+    if !extensible {
+        if let Some(key) = names.iter().find(|key| !target_keys.contains(key)) {
+            return Err(violated(
+                "ownKeys",
+                format!(
+                    "trap returned extra key '{}' for a non-extensible target",
+                    JsString::from_key(key).to_string()
+                ),
+            ));
+        }
+    }
+    Ok(names)
+}
+
+// Returns the [[Prototype]] of the object, or null if there is none.
+pub unsafe fn get_prototype(self_: &mut VM, obj: &Value) -> Result<Value, RuntimeError> {
+    let proxy = match obj.val {
+        ValueBase::Proxy(p) => (*p).clone(),
+        _ => return Ok(obj.get_prototype()),
+    };
+    match proxy.trap("getPrototypeOf")? {
+        Some(trap) => {
+            let proto = proxy.call_trap(self_, &trap, vec![proxy.target.clone()])?;
+            if proto.val != ValueBase::Null && !proto.is_object() {
+                return Err(RuntimeError::Type(
+                    "type error: 'getPrototypeOf' on proxy: trap returned neither object nor null"
+                        .to_string(),
+                ));
+            }
+            if !proxy.target.is_extensible()
+                && !proto.val.same_value(&proxy.target.get_prototype().val)
+            {
+                return Err(violated(
+                    "getPrototypeOf",
+                    "trap didn't return the prototype of the non-extensible target".to_string(),
+                ));
+            }
+            Ok(proto)
+        }
+        None => get_prototype(self_, &proxy.target),
+    }
+}
+
+// Defines the property as the descriptor object 'desc' describes. Returns false if a trap
+// refuses it, while errors on ordinary objects are thrown.
+pub unsafe fn define_own_property(
+    self_: &mut VM,
+    obj: &Value,
+    key: String,
+    desc: &Value,
+) -> Result<bool, RuntimeError> {
+    let proxy = match obj.val {
+        ValueBase::Proxy(p) => (*p).clone(),
+        _ => {
+            obj.define_own_property(key, to_property_descriptor(desc)?)?;
+            return Ok(true);
+        }
+    };
+    match proxy.trap("defineProperty")? {
+        Some(trap) => {
+            let desc_ = to_property_descriptor(desc)?;
+            let defined = proxy
+                .call_trap(
+                    self_,
+                    &trap,
+                    vec![
+                        proxy.target.clone(),
+                        Value::string(JsString::from_key(&key)),
+                        desc.clone(),
+                    ],
+                )?
+                .val
+                .to_boolean();
+            if !defined {
+                return Ok(false);
+            }
+            let setting_config_false = desc_.configurable == Some(false);
+            let compatible = match proxy.target.get_own_property(&key) {
+                None => proxy.target.is_extensible() && !setting_config_false,
+                Some(ref current) => {
+                    is_compatible_descriptor(&desc_, current)
+                        && !(setting_config_false && current.configurable)
+                        && !(!current.configurable
+                            && current.writable
+                            && desc_.writable == Some(false))
+                }
+            };
+            if !compatible {
+                return Err(violated(
+                    "defineProperty",
+                    format!(
+                        "trap returned truish for property '{}' the target can't have",
+                        JsString::from_key(&key).to_string()
+                    ),
+                ));
+            }
+            Ok(true)
+        }
+        None => define_own_property(self_, &proxy.target, key, desc),
+    }
+}
+
+// [[Call]] of a proxy. Returns what the trap or the target returned.
+pub unsafe fn call(
+    self_: &mut VM,
+    p: *mut ProxyValue,
+    this: Value,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let proxy = (*p).clone();
+    let trap = proxy.trap("apply")?;
+    if !is_callable(&proxy.target) {
+        return Err(RuntimeError::Type(
+            "type error: proxy is not a function".to_string(),
+        ));
+    }
+    match trap {
+        Some(trap) => {
+            let args = Value::array(gc::new(ArrayValue::new(args)));
+            proxy.call_trap(self_, &trap, vec![proxy.target.clone(), this, args])
+        }
+        None => call_callback(self_, &proxy.target, this, &args),
+    }
+}
+
+// [[Construct]] of a proxy. 'new_target' is the proxy 'new' is applied to.
+pub unsafe fn construct(
+    self_: &mut VM,
+    p: *mut ProxyValue,
+    new_target: &Value,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let proxy = (*p).clone();
+    let trap = proxy.trap("construct")?;
+    if !is_callable(&proxy.target) {
+        return Err(RuntimeError::Type(
+            "type error: proxy is not a constructor".to_string(),
+        ));
+    }
+    match trap {
+        Some(trap) => {
+            let args = Value::array(gc::new(ArrayValue::new(args)));
+            let obj = proxy.call_trap(
+                self_,
+                &trap,
+                vec![proxy.target.clone(), args, new_target.clone()],
+            )?;
+            if !obj.is_object() {
+                return Err(RuntimeError::Type(
+                    "type error: 'construct' on proxy: trap returned non-object".to_string(),
+                ));
+            }
+            Ok(obj)
+        }
+        None => {
+            construct_object(self_, proxy.target.clone(), args)?;
+            Ok(self_.state.stack.pop().unwrap())
+        }
+    }
+}
+
+#[test]
+fn traps() {
+    use vm::ScriptRunner;
+
+    let mut runner = ScriptRunner::new();
+    let source = "
+        var log = [], target = {a: 1, b: 2};
+        var p = new Proxy(target, {
+            get: function(t, key) { log.push('get ' + key); return key === 'x' ? 'trap' : t[key] },
+            set: function(t, key, value) {
+                log.push('set ' + key);
+                if (key === 'readonly') return false;
+                t[key] = value * 10;
+                return true;
+            },
+            has: function(t, key) { return key === 'magic' || Reflect.has(t, key) },
+            deleteProperty: function(t, key) {
+                log.push('delete ' + key);
+                return key !== 'b' && delete t[key];
+            },
+            ownKeys: function(t) { return Reflect.ownKeys(t).concat(['extra']) },
+            getPrototypeOf: function(t) { return Array.prototype },
+            defineProperty: function(t, key, desc) {
+                log.push('define ' + key);
+                return Reflect.defineProperty(t, key, desc);
+            }
+        });
+        var a = p.a, x = p.x;
+        p.c = 3;
+        var deletedA = delete p.a, deletedB = delete p.b;
+        Object.defineProperty(p, 'd', {value: 4, enumerable: true});
+        var setFalse = Reflect.set(p, 'readonly', 1);
+        var strictError = (function() {
+            'use strict';
+            try { p.readonly = 1 } catch (e) { return e.name }
+        })();
+        [a, x, target.c, deletedA, deletedB, target.a, Reflect.has(p, 'magic'),
+         Reflect.has(p, 'zzz'), Reflect.ownKeys(p).join(),
+         Object.getPrototypeOf(p) === Array.prototype, target.d, setFalse, strictError,
+         log.join()].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "1;trap;30;true;false;;true;false;b,c,d,extra;true;4;false;TypeError;\
+         get a,get x,set c,delete a,delete b,define d,set readonly,set readonly"
+    );
+
+    let source = "
+        function sum(a, b) { return a + b }
+        function Point(x, y) { this.x = x; this.y = y }
+        var calls = 0, P, made, q;
+        var traced = new Proxy(sum, {
+            apply: function(t, thisArg, args) { calls++; return t.apply(thisArg, args) * 2 }
+        });
+        var results = [traced(1, 2), traced.call(null, 3, 4), traced.apply(null, [5, 6])].join();
+        P = new Proxy(Point, {
+            construct: function(t, args, newTarget) {
+                return {made: args.length, by: newTarget === P};
+            }
+        });
+        made = new P(1, 2);
+        q = new (new Proxy(Point, {}))(3, 4);
+        [results, calls, made.made, made.by, q.x, q.y, Object.getPrototypeOf(q) === Point.prototype,
+         [1, 2].map(new Proxy(function(x) { return x + 1 }, {})).join()].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "6,14,22;3;2;true;3;4;true;2,3");
+
+    // Proxies without traps forward everything to their targets.
+    let source = "
+        var r = Proxy.revocable({a: 1}, {}), beforeRevoke = r.proxy.a, errors = [];
+        r.revoke();
+        try { r.proxy.a } catch (e) { errors.push(e.name) }
+        try { Proxy({}, {}) } catch (e) { errors.push(e.name) }
+        try { new Proxy(1, {}) } catch (e) { errors.push(e.name) }
+        try {
+            new (new Proxy(Point, {construct: function() { return 1 }}))();
+        } catch (e) { errors.push(e.name) }
+        var plain = new Proxy([1, 2, 3], {});
+        plain.push(4);
+        [beforeRevoke, errors.join(), plain.length, Array.isArray(plain),
+         JSON.stringify(plain)].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "1;TypeError,TypeError,TypeError,TypeError;4;true;[1,2,3,4]"
+    );
+
+    // Traps can't misreport the non-configurable properties of the target.
+    let source = "
+        var target = {}, errors = [];
+        Object.defineProperty(target, 'x', {value: 1});
+        var p = new Proxy(target, {
+            get: function() { return 2 },
+            has: function() { return false },
+            defineProperty: function() { return true },
+            getOwnPropertyDescriptor: function() { return undefined }
+        });
+        try { p.x } catch (e) { errors.push('get ' + e.name) }
+        try { Reflect.has(p, 'x') } catch (e) { errors.push('has ' + e.name) }
+        try {
+            Object.defineProperty(p, 'y', {value: 1, configurable: false})
+        } catch (e) { errors.push('defineProperty ' + e.name) }
+        try {
+            Object.getOwnPropertyDescriptor(p, 'x')
+        } catch (e) { errors.push('getOwnPropertyDescriptor ' + e.name) }
+        var q = new Proxy({a: 1}, {
+            get: function() { return 2 },
+            has: function() { return false },
+            getOwnPropertyDescriptor: function() { return {value: 5, configurable: true} }
+        });
+        [errors.join(), q.a, Reflect.has(q, 'a'), Object.getOwnPropertyDescriptor(q, 'a').value,
+         Reflect.defineProperty(p, 'x', {value: 1})].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "get TypeError,has TypeError,defineProperty TypeError,getOwnPropertyDescriptor TypeError;\
+         2;false;5;true"
+    );
+
+    // Nor can they report changes the target can't have.
+    let source = "
+        var target = {a: 1}, errors = [];
+        Object.defineProperty(target, 'x', {value: 1});
+        var p = new Proxy(target, {
+            set: function() { return true },
+            deleteProperty: function() { return true },
+            ownKeys: function() { return ['a'] }
+        });
+        try { p.x = 2 } catch (e) { errors.push('set ' + e.name) }
+        try { delete p.x } catch (e) { errors.push('deleteProperty ' + e.name) }
+        try { Reflect.ownKeys(p) } catch (e) { errors.push('ownKeys ' + e.name) }
+        var frozen = Object.preventExtensions({a: 1}), keys = ['a'];
+        var q = new Proxy(frozen, {
+            ownKeys: function() { return keys },
+            deleteProperty: function() { return true },
+            getPrototypeOf: function() { return Array.prototype }
+        });
+        var before = Reflect.ownKeys(q).join();
+        keys = [];
+        try { Reflect.ownKeys(q) } catch (e) { errors.push('ownKeys ' + e.name) }
+        keys = ['a', 'b'];
+        try { Reflect.ownKeys(q) } catch (e) { errors.push('ownKeys ' + e.name) }
+        try { delete q.a } catch (e) { errors.push('deleteProperty ' + e.name) }
+        try { Object.getPrototypeOf(q) } catch (e) { errors.push('getPrototypeOf ' + e.name) }
+        [errors.join(), p.y = 3, delete p.a, Reflect.set(p, 'x', 1), before].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "set TypeError,deleteProperty TypeError,ownKeys TypeError,ownKeys TypeError,\
+         ownKeys TypeError,deleteProperty TypeError,getPrototypeOf TypeError;3;true;true;a"
+    );
+}
@@ -0,0 +1,212 @@
+// A realm has everything that VMs must not share with other ones: the heap of the GC, the
+// builtin objects (intrinsics) like Array.prototype, the id generator of functions and the
+// generator of Math.random().
+//
+// Code that allocates objects or uses intrinsics works on the current realm of the thread, which
+// a VM enters whenever it runs code (see enter()). Each code generator made by VMCodeGen::new()
+// has a realm of its own, where it allocates the global object and the functions of the scripts it
+// compiles before a VM runs them, and the VM made for the global object joins it (see VM::new()).
+// VM::with_realm() makes a VM that shares the realm of another. Threads start with a realm of their
+// own for the objects made outside VMs.
+
+use rand::random;
+use rustc_hash::FxHashMap;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+use gc::Heap;
+use id::IdGen;
+
+pub struct Realm {
+    pub heap: Heap,
+    pub id_gen: RefCell<IdGen>,
+    intrinsics: RefCell<FxHashMap<usize, Box<Any>>>, // Keyed by the address of the RealmLocal
+    random_state: Cell<u64>, // The state of the xorshift generator behind Math.random(). Never zero
+}
+
+thread_local!(static CURRENT_REALM: RefCell<Option<Rc<Realm>>> = RefCell::new(None));
+
+impl Realm {
+    pub fn new() -> Rc<Realm> {
+        let realm = Realm {
+            heap: Heap::new(),
+            id_gen: RefCell::new(IdGen::new()),
+            intrinsics: RefCell::new(FxHashMap::default()),
+            random_state: Cell::new(1),
+        };
+        realm.seed_random(random::<u64>());
+        Rc::new(realm)
+    }
+
+    // Resets the generator so that Math.random() gives the same sequence for the same seed.
+    pub fn seed_random(&self, seed: u64) {
+        // splitmix64 spreads the bits of nearby seeds.
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        self.random_state
+            .set(if z == 0 { 0xf6d582196d588cac } else { z });
+    }
+
+    pub fn random(&self) -> f64 {
+        let mut state = self.random_state.get();
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        self.random_state.set(state);
+        // The top 53 bits make a number in [0, 1) with every value equally likely.
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl fmt::Debug for Realm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Realm({} bytes)", self.heap.allocated_size())
+    }
+}
+
+/// Makes 'realm' the current realm of the thread.
+pub fn enter(realm: &Rc<Realm>) {
+    CURRENT_REALM.with(|current| {
+        let mut current = current.borrow_mut();
+        if !current.as_ref().map_or(false, |cur| Rc::ptr_eq(cur, realm)) {
+            *current = Some(realm.clone());
+        }
+    })
+}
+
+/// The current realm of the thread, which is created if the thread has never had one.
+pub fn current() -> Rc<Realm> {
+    CURRENT_REALM.with(|current| {
+        current
+            .borrow_mut()
+            .get_or_insert_with(|| Realm::new())
+            .clone()
+    })
+}
+
+/// Like thread_local!, but each realm has its own value, which is created on first access.
+macro_rules! realm_local {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr);* $(;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::realm::RealmLocal<$t> = $crate::realm::RealmLocal::new({
+                fn init() -> $t {
+                    $init
+                }
+                init
+            });
+        )*
+    };
+}
+
+pub struct RealmLocal<T> {
+    init: fn() -> T,
+}
+
+impl<T: 'static> RealmLocal<T> {
+    pub const fn new(init: fn() -> T) -> RealmLocal<T> {
+        RealmLocal { init: init }
+    }
+
+    pub fn with<R, F: FnOnce(&T) -> R>(&'static self, f: F) -> R {
+        let realm = current();
+        let key = self as *const RealmLocal<T> as usize;
+
+        let found = realm
+            .intrinsics
+            .borrow()
+            .get(&key)
+            .map(|val| &**val as *const Any);
+        let val = match found {
+            Some(val) => val,
+            None => {
+                // The initializer may use other intrinsics, so the map must not be borrowed here.
+                let val: Box<Any> = Box::new((self.init)());
+                let ptr = &*val as *const Any;
+                realm.intrinsics.borrow_mut().insert(key, val);
+                ptr
+            }
+        };
+
+        // The values are boxed and never removed while the realm is alive.
+        f(unsafe { (*val).downcast_ref::<T>().unwrap() })
+    }
+}
+
+#[test]
+fn isolated_realms() {
+    use engine::Engine;
+    use std::thread;
+    use vm::{ScriptRunner, Value};
+
+    let mut a = Engine::new();
+    let mut b = Engine::new();
+    a.eval("Array.prototype.answer = 42").unwrap();
+    assert_eq!(a.eval("[].answer"), Ok(Value::number(42.0)));
+    assert_eq!(b.eval("[].answer"), Ok(Value::undefined()));
+
+    // Each realm has its own generator, so interleaved calls don't disturb the sequences.
+    a.vm().seed_random(7);
+    b.vm().seed_random(7);
+    let first = a.eval("Math.random()").unwrap();
+    assert_eq!(b.eval("Math.random()"), Ok(first));
+    let second = b.eval("Math.random()").unwrap();
+    assert_eq!(a.eval("Math.random()"), Ok(second));
+
+    // So do VMs made without an engine, even in the same thread.
+    let mut c = ScriptRunner::new();
+    let mut d = ScriptRunner::new();
+    c.run("Object.prototype.answer = 42").unwrap();
+    assert_eq!(c.run("({}).answer"), Ok(Value::number(42.0)));
+    assert_eq!(d.run("({}).answer"), Ok(Value::undefined()));
+    assert!(!Rc::ptr_eq(&c.vm().realm, &d.vm().realm));
+    c.vm().seed_random(7);
+    d.vm().seed_random(7);
+    let first = c.run("Math.random()").unwrap();
+    assert_eq!(d.run("Math.random()"), Ok(first));
+
+    let threads = (0..4)
+        .map(|i| {
+            thread::spawn(move || {
+                let mut engine = Engine::new();
+                engine.set_global("n", i);
+                let source = "
+                    var a = 'x'.repeat(1000).split('').map(function(_, i) { return [i * n] });
+                    a.length";
+                engine.eval(source).unwrap().val.to_number()
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), 1000.0);
+    }
+}
+
+#[test]
+fn compile_before_vm() {
+    use extract_anony_func::AnonymousFunctionExtractor;
+    use parser::Parser;
+    use vm::{Value, VM};
+    use vm_codegen::VMCodeGen;
+
+    // main.rs compiles a script before making the VM to run it, so the functions declared in the
+    // script are allocated before the VM exists.
+    let source = "
+        function P() {}
+        P.prototype.m = function() { return 42 };
+        var answer = (new P()).m()";
+    let mut node = Parser::new(source.to_string()).parse_all().unwrap();
+    AnonymousFunctionExtractor::new().run_toplevel(&mut node);
+    let mut vm_codegen = VMCodeGen::new();
+    let mut iseq = vec![];
+    vm_codegen.compile(&node, &mut iseq, false);
+    let mut vm = VM::new(vm_codegen.global_varmap);
+    vm.const_table = vm_codegen.bytecode_gen.const_table;
+    vm.run(iseq).unwrap();
+    let answer = unsafe { (*vm.state.scope[0]).get_value(&"answer".to_string()) };
+    assert_eq!(answer, Ok(Value::number(42.0)));
+}
@@ -0,0 +1,363 @@
+use node::{Node, NodeBase, PropertyDefinition};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use std::mem;
+
+/// How the code of a function accesses a variable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Binding {
+    /// A slot of the frame of the function. (GET_LOCAL, SET_LOCAL)
+    Local(usize),
+    /// A variable of the enclosing function 'depth' scopes out, captured in its environment.
+    /// (GET_UPVAR)
+    Upvar(usize),
+    /// Looked up by name through the scope chain, e.g. a global variable, a variable of the
+    /// function captured by inner functions or one that eval() may declare. (GET_NAME, SET_NAME)
+    Name,
+}
+
+#[derive(Clone, Debug)]
+pub struct FunctionScope {
+    pub parent: Option<String>, // The function this one is nested in, if any
+    pub locals: FxHashMap<String, usize>, // The variables in slots, from the parameters
+    pub locals_len: usize,
+    pub captured_params: Vec<(usize, String)>, // The parameters moved to the environment on entry
+    pub catch_params: Vec<String>, // The parameters of the catch blocks the function is created in
+    params: Vec<String>,
+    declared: Vec<String>,
+    referenced: FxHashSet<String>,
+    captured: FxHashSet<String>,
+    has_eval: bool, // Contains a direct eval, or a nested function does
+    uses_arguments: bool,
+}
+
+impl FunctionScope {
+    fn new(params: Vec<String>) -> FunctionScope {
+        FunctionScope {
+            parent: None,
+            locals: FxHashMap::default(),
+            locals_len: params.len(),
+            captured_params: vec![],
+            catch_params: vec![],
+            params: params,
+            declared: vec![],
+            referenced: FxHashSet::default(),
+            captured: FxHashSet::default(),
+            has_eval: false,
+            uses_arguments: false,
+        }
+    }
+
+    fn declares(&self, name: &String) -> bool {
+        self.params.contains(name) || self.declared.contains(name)
+    }
+}
+
+/// Classifies the variables of the functions flattened by AnonymousFunctionExtractor into local,
+/// captured and global ones. Functions nested in another are found by the SetCurCallObj nodes
+/// that create them.
+#[derive(Clone, Debug)]
+pub struct ScopeAnalyzer {
+    pub functions: FxHashMap<String, FunctionScope>,
+    parents: Vec<(String, Option<String>, Vec<String>)>, // (function, its creator, catch_params)
+    catch_params: Vec<String>, // The parameters of the catch blocks being analyzed
+}
+
+impl ScopeAnalyzer {
+    pub fn new() -> ScopeAnalyzer {
+        ScopeAnalyzer {
+            functions: FxHashMap::default(),
+            parents: vec![],
+            catch_params: vec![],
+        }
+    }
+
+    pub fn run_toplevel(&mut self, node: &Node) {
+        let mut names = vec![];
+        self.run(node, &None, &mut names);
+
+        for (name, parent, catch_params) in self.parents.drain(..).collect::<Vec<_>>() {
+            if let Some(func) = self.functions.get_mut(&name) {
+                func.parent = parent;
+                func.catch_params = catch_params;
+            }
+        }
+
+        // eval() in a function may declare variables in it and read those of the functions it's
+        // nested in, by name.
+        for name in &names {
+            if self.functions[name].has_eval {
+                let mut parent = self.functions[name].parent.clone();
+                while let Some(name) = parent {
+                    let func = self.functions.get_mut(&name).unwrap();
+                    func.has_eval = true;
+                    parent = func.parent.clone();
+                }
+            }
+        }
+
+        for name in &names {
+            let referenced = self.functions[name].referenced.clone();
+            for var in referenced.iter() {
+                if self.functions[name].declares(var) {
+                    continue;
+                }
+                let mut catch_params = self.functions[name].catch_params.clone();
+                let mut parent = self.functions[name].parent.clone();
+                while let Some(name) = parent {
+                    if catch_params.contains(var) {
+                        break;
+                    }
+                    let func = self.functions.get_mut(&name).unwrap();
+                    if func.declares(var) {
+                        func.captured.insert(var.clone());
+                        break;
+                    }
+                    catch_params = func.catch_params.clone();
+                    parent = func.parent.clone();
+                }
+            }
+        }
+
+        for name in &names {
+            let func = self.functions.get_mut(name).unwrap();
+            // 'arguments' reads the parameters by name.
+            let params_captured = func.has_eval || func.uses_arguments;
+            for (i, param) in func.params.clone().into_iter().enumerate() {
+                if params_captured || func.captured.contains(&param) {
+                    func.captured_params.push((i, param));
+                } else {
+                    func.locals.insert(param, i);
+                }
+            }
+            if func.has_eval {
+                continue;
+            }
+            for var in func.declared.clone() {
+                if !func.captured.contains(&var) && !func.params.contains(&var) {
+                    func.locals.insert(var, func.locals_len);
+                    func.locals_len += 1;
+                }
+            }
+        }
+    }
+
+    /// Returns how the code of 'function', or the top level code if None, accesses 'name'.
+    pub fn resolve(&self, function: &Option<String>, name: &String) -> Binding {
+        let func = match function.as_ref().and_then(|name| self.functions.get(name)) {
+            Some(func) => func,
+            None => return Binding::Name,
+        };
+        if let Some(slot) = func.locals.get(name) {
+            return Binding::Local(*slot);
+        }
+        if func.declares(name) {
+            return Binding::Name;
+        }
+
+        let mut depth = 0;
+        let mut func = func;
+        loop {
+            // The parameter of a catch block the function is created in is in a scope of its own.
+            if func.has_eval || func.catch_params.contains(name) {
+                return Binding::Name;
+            }
+            func = match func.parent.as_ref() {
+                Some(parent) => &self.functions[parent],
+                None => return Binding::Name,
+            };
+            depth += 1;
+            if func.declares(name) {
+                return Binding::Upvar(depth);
+            }
+        }
+    }
+
+    /// Returns the number of slots of 'function'.
+    pub fn locals_len(&self, function: &str) -> usize {
+        self.functions
+            .get(function)
+            .map_or(0, |func| func.locals_len)
+    }
+
+    // Collects the variables 'function' declares and references in 'node', and the names of the
+    // functions found into 'names'.
+    fn run(&mut self, node: &Node, function: &Option<String>, names: &mut Vec<String>) {
+        match node.base {
+            NodeBase::FunctionDecl(ref name, ref params, ref body, _) => {
+                let params = params.iter().map(|param| param.name.clone()).collect();
+                self.functions
+                    .insert(name.clone(), FunctionScope::new(params));
+                names.push(name.clone());
+                let catch_params = mem::replace(&mut self.catch_params, vec![]);
+                self.run(&*body, &Some(name.clone()), names);
+                self.catch_params = catch_params;
+            }
+            NodeBase::SetCurCallObj(ref name) => {
+                self.parents
+                    .push((name.clone(), function.clone(), self.catch_params.clone()));
+            }
+            NodeBase::VarDecl(ref name, ref init) => {
+                self.declare(function, name);
+                if let &Some(ref init) = init {
+                    self.run(&*init, function, names);
+                }
+            }
+            NodeBase::Try(ref block, ref param, ref catch, ref finally) => {
+                self.run(&*block, function, names);
+                if let &Some(ref param) = param {
+                    self.catch_params.push(param.clone());
+                    self.run(&*catch, function, names);
+                    self.catch_params.pop();
+                } else {
+                    self.run(&*catch, function, names);
+                }
+                self.run(&*finally, function, names);
+            }
+            NodeBase::Identifier(ref name) => {
+                if let Some(func) = function.as_ref().map(|name| self.current(name)) {
+                    func.referenced.insert(name.clone());
+                }
+            }
+            NodeBase::Arguments => {
+                if let Some(func) = function.as_ref().map(|name| self.current(name)) {
+                    func.uses_arguments = true;
+                }
+            }
+            NodeBase::Call(ref callee, ref args) => {
+                match callee.base {
+                    NodeBase::Identifier(ref name) if name == "eval" => {
+                        if let Some(func) = function.as_ref().map(|name| self.current(name)) {
+                            func.has_eval = true;
+                        }
+                    }
+                    _ => {}
+                }
+                self.run(&*callee, function, names);
+                for arg in args {
+                    self.run(arg, function, names);
+                }
+            }
+            NodeBase::StatementList(ref nodes) | NodeBase::Array(ref nodes) => {
+                for node in nodes {
+                    self.run(node, function, names);
+                }
+            }
+            NodeBase::Object(ref properties) => {
+                for property in properties {
+                    if let &PropertyDefinition::Property(_, ref node) = property {
+                        self.run(node, function, names);
+                    }
+                }
+            }
+            NodeBase::Member(ref node, _)
+            | NodeBase::New(ref node)
+            | NodeBase::UnaryOp(ref node, _)
+            | NodeBase::Throw(ref node)
+            | NodeBase::Return(Some(ref node)) => self.run(&*node, function, names),
+            NodeBase::Index(ref lhs, ref rhs)
+            | NodeBase::While(ref lhs, ref rhs)
+            | NodeBase::Assign(ref lhs, ref rhs)
+            | NodeBase::BinaryOp(ref lhs, ref rhs, _) => {
+                self.run(&*lhs, function, names);
+                self.run(&*rhs, function, names);
+            }
+            NodeBase::If(ref cond, ref then, ref else_)
+            | NodeBase::TernaryOp(ref cond, ref then, ref else_) => {
+                self.run(&*cond, function, names);
+                self.run(&*then, function, names);
+                self.run(&*else_, function, names);
+            }
+            NodeBase::For(ref init, ref cond, ref step, ref body) => {
+                self.run(&*init, function, names);
+                self.run(&*cond, function, names);
+                self.run(&*step, function, names);
+                self.run(&*body, function, names);
+            }
+            _ => {}
+        }
+    }
+
+    fn current(&mut self, function: &String) -> &mut FunctionScope {
+        self.functions.get_mut(function).unwrap()
+    }
+
+    fn declare(&mut self, function: &Option<String>, name: &String) {
+        if let Some(func) = function.as_ref().map(|name| self.current(name)) {
+            if !func.declared.contains(name) {
+                func.declared.push(name.clone());
+            }
+        }
+    }
+}
+
+#[test]
+fn scope_analysis() {
+    use engine::Engine;
+    use extract_anony_func::AnonymousFunctionExtractor;
+    use parser::Parser;
+    use vm::Value;
+
+    let source = "
+        function outer(a, b) {
+            var local = a, shared = b;
+            function inner(c) { return shared + b + c + global }
+            return inner(local);
+        }
+        function dynamic(x) { var y = 1; return function() { return eval('x + y') } }
+        function args(p) { var q = p; return arguments[0] + q }";
+    let mut node = Parser::new(source.to_string()).parse_all().unwrap();
+    AnonymousFunctionExtractor::new().run_toplevel(&mut node);
+    let mut analyzer = ScopeAnalyzer::new();
+    analyzer.run_toplevel(&node);
+
+    let find = |parent: &str| {
+        analyzer
+            .functions
+            .iter()
+            .find(|(_, func)| func.parent == Some(parent.to_string()))
+            .map(|(name, _)| Some(name.clone()))
+            .unwrap()
+    };
+    let resolve =
+        |function: &Option<String>, name: &str| analyzer.resolve(function, &name.to_string());
+
+    let outer = Some("outer".to_string());
+    assert_eq!(resolve(&outer, "a"), Binding::Local(0));
+    assert_eq!(resolve(&outer, "b"), Binding::Name);
+    assert_eq!(resolve(&outer, "local"), Binding::Local(2));
+    assert_eq!(resolve(&outer, "shared"), Binding::Name);
+    assert_eq!(resolve(&outer, "inner"), Binding::Local(3));
+    assert_eq!(analyzer.locals_len("outer"), 4);
+    let inner = find("outer");
+    assert_eq!(resolve(&inner, "c"), Binding::Local(0));
+    assert_eq!(resolve(&inner, "shared"), Binding::Upvar(1));
+    assert_eq!(resolve(&inner, "b"), Binding::Upvar(1));
+    assert_eq!(resolve(&inner, "global"), Binding::Name);
+
+    let dynamic = Some("dynamic".to_string());
+    assert_eq!(resolve(&dynamic, "x"), Binding::Name);
+    assert_eq!(resolve(&dynamic, "y"), Binding::Name);
+    assert_eq!(resolve(&find("dynamic"), "y"), Binding::Name);
+
+    let args = Some("args".to_string());
+    assert_eq!(resolve(&args, "p"), Binding::Name);
+    assert_eq!(resolve(&args, "q"), Binding::Local(1));
+    assert_eq!(resolve(&None, "outer"), Binding::Name);
+
+    let mut engine = Engine::new();
+    engine.eval(source).unwrap();
+    let source = "
+        var global = 100;
+        function counter() { var n = 0; return function() { n += 1; return n } }
+        function nested(x) { return function() { return function() { return x } } }
+        function Point(x) { var doubled = x * 2; this.x = doubled }
+        function late() { var before = v; var v = 1; return before === undefined }
+        var count = counter(), point = new Point(3);
+        count();
+        [count(), outer(1, 2), dynamic(3)(), args(4), nested(5)()(), point.x, late()]";
+    let val = engine.eval(source).unwrap();
+    assert_eq!(val.to_string(), "2,105,4,8,5,6,true");
+    assert_eq!(engine.eval("global"), Ok(Value::number(100.0)));
+}
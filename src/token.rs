@@ -1,3 +1,5 @@
+use bigint::BigInt;
+use js_string::JsString;
 use node::BinOp;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -11,7 +13,8 @@ pub enum Kind {
     Keyword(Keyword),
     Identifier(String),
     Number(f64),
-    String(String),
+    BigInt(BigInt),
+    String(JsString),
     Symbol(Symbol),
     LineTerminator,
 }
@@ -113,6 +116,13 @@ impl Token {
         }
     }
 
+    pub fn new_bigint(n: BigInt, pos: usize) -> Token {
+        Token {
+            kind: Kind::BigInt(n),
+            pos: pos,
+        }
+    }
+
     pub fn new_identifier(ident: String, pos: usize) -> Token {
         Token {
             kind: Kind::Identifier(ident),
@@ -127,7 +137,7 @@ impl Token {
         }
     }
 
-    pub fn new_string(s: String, pos: usize) -> Token {
+    pub fn new_string(s: JsString, pos: usize) -> Token {
         Token {
             kind: Kind::String(s),
             pos: pos,
@@ -223,3 +233,37 @@ pub fn convert_reserved_keyword(keyword: &str) -> Option<Keyword> {
         _ => None,
     }
 }
+
+// The inverse of convert_reserved_keyword().
+pub fn keyword_to_str(keyword: &Keyword) -> &'static str {
+    match keyword {
+        Keyword::Abstract => "abstract",
+        Keyword::Arguments => "arguments",
+        Keyword::Break => "break",
+        Keyword::Case => "case",
+        Keyword::Catch => "catch",
+        Keyword::Continue => "continue",
+        Keyword::Debugger => "debugger",
+        Keyword::Default => "default",
+        Keyword::Delete => "delete",
+        Keyword::Do => "do",
+        Keyword::Else => "else",
+        Keyword::Finally => "finally",
+        Keyword::For => "for",
+        Keyword::Function => "function",
+        Keyword::If => "if",
+        Keyword::In => "in",
+        Keyword::Instanceof => "instanceof",
+        Keyword::New => "new",
+        Keyword::Return => "return",
+        Keyword::Switch => "switch",
+        Keyword::This => "this",
+        Keyword::Throw => "throw",
+        Keyword::Try => "try",
+        Keyword::Typeof => "typeof",
+        Keyword::Var => "var",
+        Keyword::Void => "void",
+        Keyword::While => "while",
+        Keyword::With => "with",
+    }
+}
@@ -0,0 +1,229 @@
+use vm::Value;
+
+// https://tc39.github.io/ecma262/#table-49
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TypedArrayKind {
+    Int8,
+    Uint8,
+    Uint8Clamped,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl TypedArrayKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TypedArrayKind::Int8 => "Int8Array",
+            TypedArrayKind::Uint8 => "Uint8Array",
+            TypedArrayKind::Uint8Clamped => "Uint8ClampedArray",
+            TypedArrayKind::Int16 => "Int16Array",
+            TypedArrayKind::Uint16 => "Uint16Array",
+            TypedArrayKind::Int32 => "Int32Array",
+            TypedArrayKind::Uint32 => "Uint32Array",
+            TypedArrayKind::Float32 => "Float32Array",
+            TypedArrayKind::Float64 => "Float64Array",
+        }
+    }
+
+    pub fn element_size(&self) -> usize {
+        match self {
+            TypedArrayKind::Int8 | TypedArrayKind::Uint8 | TypedArrayKind::Uint8Clamped => 1,
+            TypedArrayKind::Int16 | TypedArrayKind::Uint16 => 2,
+            TypedArrayKind::Int32 | TypedArrayKind::Uint32 | TypedArrayKind::Float32 => 4,
+            TypedArrayKind::Float64 => 8,
+        }
+    }
+
+    // https://tc39.github.io/ecma262/#sec-rawbytestonumber
+    pub fn from_bytes(&self, bytes: &[u8], little_endian: bool) -> f64 {
+        let size = self.element_size();
+        let bits = (0..size).fold(0u64, |bits, i| {
+            let byte = if little_endian {
+                bytes[size - 1 - i]
+            } else {
+                bytes[i]
+            };
+            bits << 8 | byte as u64
+        });
+        match self {
+            TypedArrayKind::Int8 => bits as u8 as i8 as f64,
+            TypedArrayKind::Uint8 | TypedArrayKind::Uint8Clamped => bits as u8 as f64,
+            TypedArrayKind::Int16 => bits as u16 as i16 as f64,
+            TypedArrayKind::Uint16 => bits as u16 as f64,
+            TypedArrayKind::Int32 => bits as u32 as i32 as f64,
+            TypedArrayKind::Uint32 => bits as u32 as f64,
+            TypedArrayKind::Float32 => f32::from_bits(bits as u32) as f64,
+            TypedArrayKind::Float64 => f64::from_bits(bits),
+        }
+    }
+
+    // https://tc39.github.io/ecma262/#sec-numbertorawbytes
+    // Integers wrap around like ToInt8() and friends, except Uint8Clamped which saturates.
+    pub fn to_bytes(&self, n: f64, bytes: &mut [u8], little_endian: bool) {
+        let bits = match self {
+            TypedArrayKind::Uint8Clamped => clamp_to_uint8(n) as u64,
+            TypedArrayKind::Float32 => (n as f32).to_bits() as u64,
+            TypedArrayKind::Float64 => n.to_bits(),
+            _ => to_uint32(n) as u64,
+        };
+        let size = self.element_size();
+        for i in 0..size {
+            let byte = (bits >> (8 * i)) as u8;
+            if little_endian {
+                bytes[i] = byte;
+            } else {
+                bytes[size - 1 - i] = byte;
+            }
+        }
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-touint32
+fn to_uint32(n: f64) -> u32 {
+    if !n.is_finite() {
+        return 0;
+    }
+    let n = n.trunc() % 4294967296.0;
+    if n < 0.0 {
+        (n + 4294967296.0) as u32
+    } else {
+        n as u32
+    }
+}
+
+// https://tc39.github.io/ecma262/#sec-touint8clamp
+fn clamp_to_uint8(n: f64) -> u8 {
+    if n.is_nan() || n <= 0.0 {
+        return 0;
+    }
+    if n >= 255.0 {
+        return 255;
+    }
+    let f = n.floor();
+    if f + 0.5 < n || (f + 0.5 == n && f % 2.0 == 1.0) {
+        f as u8 + 1
+    } else {
+        f as u8
+    }
+}
+
+// The bytes of an ArrayBuffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArrayBuffer {
+    pub bytes: Vec<u8>,
+}
+
+// A view of an ArrayBuffer, i.e. a typed array or a DataView. The length of a DataView is counted
+// in bytes with 'kind' being Uint8.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypedArray {
+    pub kind: TypedArrayKind,
+    pub buffer: Value, // The ArrayBuffer object
+    pub data: *mut ArrayBuffer,
+    pub byte_offset: usize,
+    pub length: usize,
+}
+
+impl TypedArray {
+    pub fn byte_length(&self) -> usize {
+        self.length * self.kind.element_size()
+    }
+
+    // Returns the n-th element, or None if it's out of range.
+    pub unsafe fn get(&self, n: usize) -> Option<f64> {
+        if n >= self.length {
+            return None;
+        }
+        self.get_value(
+            n * self.kind.element_size(),
+            self.kind,
+            cfg!(target_endian = "little"),
+        )
+    }
+
+    // Sets the n-th element. Elements out of range are ignored.
+    pub unsafe fn set(&self, n: usize, val: f64) {
+        if n < self.length {
+            self.set_value(
+                n * self.kind.element_size(),
+                self.kind,
+                val,
+                cfg!(target_endian = "little"),
+            );
+        }
+    }
+
+    pub unsafe fn to_vec(&self) -> Vec<f64> {
+        (0..self.length).filter_map(|i| self.get(i)).collect()
+    }
+
+    // https://tc39.github.io/ecma262/#sec-getviewvalue
+    // Reads a 'kind' value at the byte index 'index'. Returns None if it's out of range.
+    pub unsafe fn get_value(
+        &self,
+        index: usize,
+        kind: TypedArrayKind,
+        little_endian: bool,
+    ) -> Option<f64> {
+        if index + kind.element_size() > self.byte_length() {
+            return None;
+        }
+        let start = self.byte_offset + index;
+        let bytes = &(*self.data).bytes;
+        Some(kind.from_bytes(&bytes[start..start + kind.element_size()], little_endian))
+    }
+
+    // https://tc39.github.io/ecma262/#sec-setviewvalue
+    // Writes 'val' as a 'kind' value at the byte index 'index'. Returns false if it's out of range.
+    pub unsafe fn set_value(
+        &self,
+        index: usize,
+        kind: TypedArrayKind,
+        val: f64,
+        little_endian: bool,
+    ) -> bool {
+        if index + kind.element_size() > self.byte_length() {
+            return false;
+        }
+        let start = self.byte_offset + index;
+        let bytes = &mut (*self.data).bytes;
+        kind.to_bytes(
+            val,
+            &mut bytes[start..start + kind.element_size()],
+            little_endian,
+        );
+        true
+    }
+}
+
+#[test]
+fn element_conversion() {
+    let mut bytes = [0u8; 8];
+    for &(kind, n, expected) in &[
+        (TypedArrayKind::Int8, 200.0, -56.0),
+        (TypedArrayKind::Uint8, -1.0, 255.0),
+        (TypedArrayKind::Uint8, 256.5, 0.0),
+        (TypedArrayKind::Uint8Clamped, 300.0, 255.0),
+        (TypedArrayKind::Uint8Clamped, 2.5, 2.0),
+        (TypedArrayKind::Uint8Clamped, 3.5, 4.0),
+        (TypedArrayKind::Int16, 40000.0, -25536.0),
+        (TypedArrayKind::Uint32, -1.0, 4294967295.0),
+        (TypedArrayKind::Int32, ::std::f64::NAN, 0.0),
+        (TypedArrayKind::Float32, 0.1, 0.10000000149011612),
+        (TypedArrayKind::Float64, -0.1, -0.1),
+    ] {
+        for &little_endian in &[true, false] {
+            kind.to_bytes(n, &mut bytes, little_endian);
+            assert_eq!(kind.from_bytes(&bytes, little_endian), expected);
+        }
+    }
+
+    TypedArrayKind::Uint16.to_bytes(0x1234 as f64, &mut bytes, false);
+    assert_eq!(&bytes[0..2], &[0x12, 0x34]);
+    TypedArrayKind::Uint16.to_bytes(0x1234 as f64, &mut bytes, true);
+    assert_eq!(&bytes[0..2], &[0x34, 0x12]);
+}
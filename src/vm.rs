@@ -1,16 +1,30 @@
 use rustc_hash::FxHashMap;
-use std::collections::hash_map::Entry;
-use std::ffi::CString;
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::Hash;
+use std::mem;
+use std::rc::Rc;
 
 use ansi_term::Colour;
 use libc;
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
 // use cpuprofiler::PROFILER;
 
+use bigint::BigInt;
 use builtin;
 use bytecode_gen::{ByteCode, VMInst};
+use collection::{Collection, CollectionKind};
+use date;
 use gc;
+use host::HostFunction;
 use id::Id;
 use jit::TracingJit;
+use js_string::{JsString, INTERNAL_SLOT_PREFIX, PROTO_KEY};
+use limits::{InterruptHandle, Limits};
+use proxy::{self, ProxyValue};
+use realm::{self, Realm};
+use typed_array::{ArrayBuffer, TypedArray, TypedArrayKind};
 
 pub type RawStringPtr = *mut libc::c_char;
 
@@ -20,18 +34,30 @@ pub type FuncId = Id;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct CallObject {
-    pub vals: *mut FxHashMap<String, Value>,
+    pub vals: *mut PropertyMap,
     pub params: Vec<(String, bool)>, // (name, rest param?)
     pub arg_rest_vals: Vec<Value>,
     pub this: Box<Value>,
     pub parent: Option<CallObjectRef>,
+    pub strict: bool,  // true if the code running in this scope is strict mode code
+    pub locals: usize, // The number of local variable slots of the function, from the parameters
+    pub block: bool,   // The scope of a catch block, which holds only the catch parameter
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ArrayValue {
     pub elems: Vec<Value>,
     pub length: usize,
-    pub obj: FxHashMap<String, Value>,
+    pub length_writable: bool, // The 'writable' attribute of 'length'
+    pub obj: PropertyMap,
+}
+
+// Own properties of an object. Unlike FxHashMap, keeps the order in which they were added.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyMap {
+    entries: Vec<(String, Value)>,
+    index: FxHashMap<String, usize>,
+    pub extensible: bool, // false after Object.preventExtensions() (or freeze, seal)
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -49,12 +75,17 @@ pub enum ValueBase {
     Undefined,
     Bool(bool),
     Number(f64),
-    String(CString),
-    Function(Box<(FuncId, ByteCode, *mut FxHashMap<String, Value>, CallObject)>),
-    BuiltinFunction(Box<(usize, *mut FxHashMap<String, Value>, CallObject)>), // id(==0:unknown)
-    Object(*mut FxHashMap<String, Value>), // Object(FxHashMap<String, Value>),
+    BigInt(BigInt),
+    String(JsString),
+    Function(Box<(FuncId, Rc<ByteCode>, *mut PropertyMap, CallObject)>),
+    BuiltinFunction(Box<(usize, *mut PropertyMap, CallObject)>), // id(==0:unknown)
+    Object(*mut PropertyMap),
     Array(*mut ArrayValue),
     Arguments,
+    Collection(*mut Collection), // Only held by hidden properties like COLLECTION_DATA
+    Proxy(*mut ProxyValue),
+    ArrayBuffer(*mut ArrayBuffer), // Only held by hidden properties like ARRAY_BUFFER_DATA
+    TypedArray(*mut TypedArray),   // Only held by hidden properties like TYPED_ARRAY_DATA
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -62,13 +93,18 @@ pub enum RuntimeError {
     Unknown,
     Type(String),
     Reference(String),
+    Range(String),
+    Syntax(String),
     Unimplemented,
+    Exception(Value), // A value thrown by JS code, or an internal error converted to an Error object
+    Terminated(String), // Execution was stopped by a limit (see the limits module). Can't be caught
 }
 
 #[derive(Debug, Clone)]
 pub struct ConstantTable {
     pub value: Vec<Value>,
     pub string: Vec<String>,
+    pub debug_info: FxHashMap<FuncId, DebugInfo>,
 }
 
 impl ConstantTable {
@@ -76,45 +112,126 @@ impl ConstantTable {
         ConstantTable {
             value: vec![],
             string: vec![],
+            debug_info: FxHashMap::default(),
         }
     }
 }
 
+// The source position of the instructions in [start, end) of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourcePosition {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugInfo {
+    pub name: String,
+    pub file: String,
+    pub positions: Vec<SourcePosition>, // Inner nodes come first
+    pub source: String,                 // The source text returned by Function.prototype.toString
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl DebugInfo {
+    pub fn find_position(&self, pc: usize) -> Option<&SourcePosition> {
+        self.positions
+            .iter()
+            .find(|pos| pos.start <= pc && pc < pos.end)
+    }
+}
+
+// Where to resume when an exception is thrown in a try block.
+#[derive(Debug, Clone)]
+pub struct TryFrame {
+    pub handler_pc: isize,
+    pub sp: usize,
+    pub scope_len: usize,
+    pub frames_len: usize,
+}
+
+// A call of a JS function running in the interpreter, or top level code. Calls don't recurse in
+// Rust: CALL and CONSTRUCT push a frame that the same interpreter loop goes on running, and RETURN
+// pops it.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub iseq: Rc<ByteCode>,
+    pub func_id: FuncId,
+    pub return_pc: isize, // Where the caller resumes
+    pub sp: usize,        // The length of the stack when called
+    pub scope_len: usize, // The length of the scope stack before the scope of the callee was pushed
+    pub kind: FrameKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameKind {
+    Call,
+    // Called by 'new'. The object created is returned unless the function returns an object.
+    Construct(*mut PropertyMap),
+    // The main code or code run by eval(), which ends with END instead of RETURN.
+    TopLevel,
+}
+
 pub struct VM {
     pub jit: TracingJit,
     pub state: VMState,
     pub const_table: ConstantTable,
-    pub cur_func_id: FuncId, // id == 0: main
-    pub op_table: [fn(&mut VM, &ByteCode) -> Result<(), RuntimeError>; 51],
-    pub builtin_functions: Vec<unsafe fn(CallObject, Vec<Value>, &mut VM)>,
+    pub op_table: [fn(&mut VM, &ByteCode) -> Result<(), RuntimeError>; 63],
+    pub builtin_functions: Vec<builtin::BuiltinFunction>,
+    pub host_functions: Vec<Rc<HostFunction>>, // Called with the ids after the builtin functions
+    pub realm: Rc<Realm>,                      // Shared with the VMs made by VM::with_realm()
+    // JIT-compiled code refers to the limits, so they must not be replaced after running code.
+    limits: Rc<Limits>,
+    pub clock: Rc<Fn() -> f64>, // The current time in milliseconds since the epoch. Replaceable for tests
 }
 
 pub struct VMState {
     pub stack: Vec<Value>,
     pub scope: Vec<CallObjectRef>,
-    pub pc: isize,
-    pub history: Vec<(usize, isize)>, // sp, return_pc
+    pub pc: isize,          // The pc of the running frame
+    pub frames: Vec<Frame>, // The call stack. The bottom frame runs the main code
+    pub try_stack: Vec<TryFrame>,
 }
 
 impl CallObject {
     pub fn new(this: Value) -> CallObject {
         CallObject {
-            vals: gc::new(FxHashMap::default()),
+            vals: gc::new(PropertyMap::default()),
             params: vec![],
             arg_rest_vals: vec![],
             this: Box::new(this),
             parent: None,
+            strict: false,
+            locals: 0,
+            block: false,
         }
     }
 
     pub fn new_global() -> CallObjectRef {
-        let vals = gc::new(FxHashMap::default());
+        let vals = gc::new(PropertyMap::default());
         let callobj = gc::new(CallObject {
             vals: vals.clone(),
             params: vec![],
             arg_rest_vals: vec![],
             this: Box::new(Value::new(ValueBase::Undefined)),
             parent: None,
+            strict: false,
+            locals: 0,
+            block: false,
         });
         unsafe {
             *(*callobj).this = Value::new(ValueBase::Object(vals));
@@ -122,24 +239,81 @@ impl CallObject {
         callobj
     }
 
+    // A scope for a catch block in 'parent', in which 'param' is 'val'.
+    pub unsafe fn new_catch(parent: CallObjectRef, param: String, val: Value) -> CallObjectRef {
+        let mut callobj = CallObject::new((*(*parent).this).clone());
+        (*callobj.vals).insert(param, val);
+        callobj.parent = Some(parent);
+        callobj.strict = (*parent).strict;
+        callobj.block = true;
+        gc::new(callobj)
+    }
+
+    /// The scope of the function or script 'scope' is in, which is 'scope' itself unless it's the
+    /// scope of a catch block. Variables are declared there, and 'arguments' refers to it.
+    pub unsafe fn function_scope(mut scope: CallObjectRef) -> CallObjectRef {
+        while (*scope).block {
+            scope = (*scope).parent.unwrap();
+        }
+        scope
+    }
+
     pub fn set_value(&mut self, name: String, val: Value) {
         unsafe {
-            (*self.vals).insert(name, val);
+            match (*self.vals).get_mut(&name) {
+                Some(v) => {
+                    if v.writable {
+                        v.val = val.val
+                    }
+                    return;
+                }
+                None => {}
+            }
+            (*self.vals).insert(name, Value::new(val.val));
         }
     }
 
     pub fn set_value_if_exist(&mut self, name: String, val: Value) {
+        // Never fails in non-strict mode.
+        let _ = self.assign_value(name, val, false);
+    }
+
+    /// Assigns ``val`` to the nearest binding of ``name``. Assignments to read-only bindings are
+    /// silently ignored in non-strict mode and raise a TypeError in strict mode, and strict mode
+    /// code cannot create a global variable by assigning to an undeclared name.
+    pub fn assign_value(
+        &mut self,
+        name: String,
+        val: Value,
+        strict: bool,
+    ) -> Result<(), RuntimeError> {
         unsafe {
-            match (*self.vals).entry(name.clone()) {
-                Entry::Occupied(ref mut v) => *v.get_mut() = val,
-                Entry::Vacant(v) => {
-                    match self.parent {
-                        Some(ref parent) => return (**parent).set_value_if_exist(name, val),
-                        None => v.insert(val),
-                    };
+            match (*self.vals).get_mut(&name) {
+                Some(v) => {
+                    if v.writable {
+                        v.val = val.val;
+                    } else if strict {
+                        return Err(RuntimeError::Type(format!(
+                            "type error: assignment to read only variable '{}'",
+                            name
+                        )));
+                    }
+                    return Ok(());
                 }
+                None => {}
             }
+            match self.parent {
+                Some(ref parent) => return (**parent).assign_value(name, val, strict),
+                None if strict => {
+                    return Err(RuntimeError::Reference(format!(
+                        "reference error: '{}' is not defined",
+                        name
+                    )))
+                }
+                None => (*self.vals).insert(name, Value::new(val.val)),
+            };
         }
+        Ok(())
     }
 
     pub fn get_value(&self, name: &String) -> Result<Value, RuntimeError> {
@@ -196,9 +370,9 @@ impl CallObject {
     }
 }
 
-thread_local!(
+realm_local!(
     pub static ARRAY_PROTOTYPE: *mut ArrayValue = {
-        let mut prototype = FxHashMap::default();
+        let mut prototype = PropertyMap::default();
 
         prototype.insert(
             "push".to_string(),
@@ -224,11 +398,46 @@ thread_local!(
             ),
         );
 
+        for (name, id) in &[
+            ("forEach", builtin::ARRAY_FOR_EACH),
+            ("filter", builtin::ARRAY_FILTER),
+            ("reduce", builtin::ARRAY_REDUCE),
+            ("reduceRight", builtin::ARRAY_REDUCE_RIGHT),
+            ("some", builtin::ARRAY_SOME),
+            ("every", builtin::ARRAY_EVERY),
+            ("find", builtin::ARRAY_FIND),
+            ("findIndex", builtin::ARRAY_FIND_INDEX),
+            ("indexOf", builtin::ARRAY_INDEX_OF),
+            ("lastIndexOf", builtin::ARRAY_LAST_INDEX_OF),
+            ("includes", builtin::ARRAY_INCLUDES),
+            ("slice", builtin::ARRAY_SLICE),
+            ("splice", builtin::ARRAY_SPLICE),
+            ("concat", builtin::ARRAY_CONCAT),
+            ("join", builtin::ARRAY_JOIN),
+            ("reverse", builtin::ARRAY_REVERSE),
+            ("shift", builtin::ARRAY_SHIFT),
+            ("unshift", builtin::ARRAY_UNSHIFT),
+            ("sort", builtin::ARRAY_SORT),
+            ("fill", builtin::ARRAY_FILL),
+            ("toString", builtin::ARRAY_TO_STRING),
+        ] {
+            prototype.insert(
+                name.to_string(),
+                Value::builtin_function(*id, CallObject::new(Value::undefined())),
+            );
+        }
+
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
+
         // https://www.ecma-international.org/ecma-262/7.0/#sec-properties-of-the-array-prototype-object
         // TODO: precise implementation
         gc::new(ArrayValue {
             elems: vec![],
             length: 0,
+            length_writable: true,
             obj: prototype
         })
     };
@@ -239,13 +448,17 @@ thread_local!(
             builtin::ARRAY_NEW,
             CallObject::new(Value::undefined()),
             {
-                let obj = FxHashMap::default();
-                // TODO: Add:
-                //          - Array.from()
-                //          - Array.isArray()
-                //          - Array.observe()
-                //          - Array.of()
-                //          etc...
+                let mut obj = PropertyMap::default();
+                for (name, id) in &[
+                    ("from", builtin::ARRAY_FROM),
+                    ("isArray", builtin::ARRAY_IS_ARRAY),
+                    ("of", builtin::ARRAY_OF),
+                ] {
+                    obj.insert(
+                        name.to_string(),
+                        Value::builtin_function(*id, CallObject::new(Value::undefined())),
+                    );
+                }
                 obj
             },
             Value::array(ArrayValue::prototype()),
@@ -253,287 +466,2166 @@ thread_local!(
 
         unsafe {(*prototype).obj.insert("constructor".to_string(), array.clone()); }
         array
-    }
-);
+    };
 
-impl ArrayValue {
-    pub fn new(arr: Vec<Value>) -> ArrayValue {
-        let len = arr.len();
-        ArrayValue {
-            elems: arr,
-            length: len,
-            obj: {
-                let mut hm = FxHashMap::default();
-                hm.insert("__proto__".to_string(), Value::array(Self::prototype()));
-                hm
+    // https://www.ecma-international.org/ecma-262/7.0/#sec-properties-of-the-string-prototype-object
+    pub static STRING_PROTOTYPE: *mut PropertyMap = {
+        let mut prototype = PropertyMap::default();
+
+        for (name, id) in &[
+            ("charAt", builtin::STRING_PROTOTYPE_CHAR_AT),
+            ("charCodeAt", builtin::STRING_PROTOTYPE_CHAR_CODE_AT),
+            ("codePointAt", builtin::STRING_PROTOTYPE_CODE_POINT_AT),
+            ("indexOf", builtin::STRING_PROTOTYPE_INDEX_OF),
+            ("lastIndexOf", builtin::STRING_PROTOTYPE_LAST_INDEX_OF),
+            ("slice", builtin::STRING_PROTOTYPE_SLICE),
+            ("substring", builtin::STRING_PROTOTYPE_SUBSTRING),
+            ("substr", builtin::STRING_PROTOTYPE_SUBSTR),
+            ("split", builtin::STRING_PROTOTYPE_SPLIT),
+            ("toUpperCase", builtin::STRING_PROTOTYPE_TO_UPPER_CASE),
+            ("toLowerCase", builtin::STRING_PROTOTYPE_TO_LOWER_CASE),
+            ("trim", builtin::STRING_PROTOTYPE_TRIM),
+            ("trimStart", builtin::STRING_PROTOTYPE_TRIM_START),
+            ("trimLeft", builtin::STRING_PROTOTYPE_TRIM_START),
+            ("trimEnd", builtin::STRING_PROTOTYPE_TRIM_END),
+            ("trimRight", builtin::STRING_PROTOTYPE_TRIM_END),
+            ("padStart", builtin::STRING_PROTOTYPE_PAD_START),
+            ("padEnd", builtin::STRING_PROTOTYPE_PAD_END),
+            ("repeat", builtin::STRING_PROTOTYPE_REPEAT),
+            ("startsWith", builtin::STRING_PROTOTYPE_STARTS_WITH),
+            ("endsWith", builtin::STRING_PROTOTYPE_ENDS_WITH),
+            ("includes", builtin::STRING_PROTOTYPE_INCLUDES),
+            ("concat", builtin::STRING_PROTOTYPE_CONCAT),
+            ("toString", builtin::STRING_PROTOTYPE_TO_STRING),
+            ("valueOf", builtin::STRING_PROTOTYPE_TO_STRING),
+        ] {
+            prototype.insert(
+                name.to_string(),
+                Value::builtin_function(*id, CallObject::new(Value::undefined())),
+            );
+        }
+
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
+
+        gc::new(prototype)
+    };
+
+    pub static STRING_OBJ: Value = {
+        let prototype = STRING_PROTOTYPE.with(|x| x.clone());
+        let string = Value::builtin_function_with_obj_and_prototype(
+            builtin::STRING_NEW,
+            CallObject::new(Value::undefined()),
+            {
+                let mut obj = PropertyMap::default();
+                obj.insert(
+                    "fromCharCode".to_string(),
+                    Value::builtin_function(
+                        builtin::STRING_FROM_CHAR_CODE,
+                        CallObject::new(Value::undefined()),
+                    ),
+                );
+                obj
             },
+            Value::object(prototype),
+        );
+
+        unsafe { (*prototype).insert("constructor".to_string(), string.clone()); }
+        string
+    };
+
+    // https://www.ecma-international.org/ecma-262/7.0/#sec-properties-of-the-number-prototype-object
+    pub static NUMBER_PROTOTYPE: *mut PropertyMap = {
+        let mut prototype = PropertyMap::default();
+
+        for (name, id) in &[
+            ("toString", builtin::NUMBER_PROTOTYPE_TO_STRING),
+            ("toFixed", builtin::NUMBER_PROTOTYPE_TO_FIXED),
+            ("toPrecision", builtin::NUMBER_PROTOTYPE_TO_PRECISION),
+            ("toExponential", builtin::NUMBER_PROTOTYPE_TO_EXPONENTIAL),
+            ("valueOf", builtin::NUMBER_PROTOTYPE_VALUE_OF),
+        ] {
+            prototype.insert(
+                name.to_string(),
+                Value::builtin_function(*id, CallObject::new(Value::undefined())),
+            );
         }
-    }
 
-    pub fn prototype() -> *mut ArrayValue {
-        ARRAY_PROTOTYPE.with(|x| x.clone())
-    }
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
 
-    pub fn to_string(&self) -> String {
-        self.elems[0..self.length]
-            .iter()
-            .fold("".to_string(), |acc, val| {
-                acc + val.to_string().as_str() + ","
-            })
-            .trim_right_matches(",")
-            .to_string()
-    }
+        gc::new(prototype)
+    };
 
-    pub fn push(&mut self, val: Value) {
-        self.elems.push(val);
-        self.length += 1;
-    }
-}
+    pub static NUMBER_OBJ: Value = {
+        let prototype = NUMBER_PROTOTYPE.with(|x| x.clone());
+        let number = Value::builtin_function_with_obj_and_prototype(
+            builtin::NUMBER_NEW,
+            CallObject::new(Value::undefined()),
+            {
+                let mut obj = PropertyMap::default();
+                for (name, id) in &[
+                    ("isInteger", builtin::NUMBER_IS_INTEGER),
+                    ("isSafeInteger", builtin::NUMBER_IS_SAFE_INTEGER),
+                    ("isFinite", builtin::NUMBER_IS_FINITE),
+                    ("isNaN", builtin::NUMBER_IS_NAN),
+                    ("parseFloat", builtin::PARSE_FLOAT),
+                    ("parseInt", builtin::PARSE_INT),
+                ] {
+                    obj.insert(
+                        name.to_string(),
+                        Value::builtin_function(*id, CallObject::new(Value::undefined())),
+                    );
+                }
+                for (name, n) in &[
+                    ("EPSILON", ::std::f64::EPSILON),
+                    ("MAX_SAFE_INTEGER", 9007199254740991.0),
+                    ("MIN_SAFE_INTEGER", -9007199254740991.0),
+                    ("MAX_VALUE", ::std::f64::MAX),
+                    ("MIN_VALUE", 5e-324),
+                    ("NaN", ::std::f64::NAN),
+                    ("POSITIVE_INFINITY", ::std::f64::INFINITY),
+                    ("NEGATIVE_INFINITY", ::std::f64::NEG_INFINITY),
+                ] {
+                    obj.insert(
+                        name.to_string(),
+                        Value {
+                            val: ValueBase::Number(*n),
+                            writable: false,
+                            enumerable: false,
+                            configurable: false,
+                        },
+                    );
+                }
+                obj
+            },
+            Value::object(prototype),
+        );
 
-impl Value {
-    pub fn new(val: ValueBase) -> Value {
-        Value {
-            val: val,
-            writable: true,
-            enumerable: true,
-            configurable: true,
+        unsafe { (*prototype).insert("constructor".to_string(), number.clone()); }
+        number
+    };
+
+    // https://tc39.github.io/ecma262/#sec-properties-of-the-function-prototype-object
+    // The methods are created by hand since Value::builtin_function() refers to this prototype.
+    // Its '__proto__' is set when FUNCTION_OBJ is created.
+    pub static FUNCTION_PROTOTYPE: *mut PropertyMap = {
+        let prototype = gc::new(PropertyMap::default());
+
+        for (name, id) in &[
+            ("apply", builtin::FUNCTION_PROTOTYPE_APPLY),
+            ("call", builtin::FUNCTION_PROTOTYPE_CALL),
+            ("bind", builtin::FUNCTION_PROTOTYPE_BIND),
+            ("toString", builtin::FUNCTION_PROTOTYPE_TO_STRING),
+        ] {
+            let mut obj = PropertyMap::default();
+            obj.insert("__proto__".to_string(), Value::object(prototype));
+            insert_builtin_name_and_length(&mut obj, *id);
+            unsafe {
+                (*prototype).insert(
+                    name.to_string(),
+                    Value {
+                        enumerable: false,
+                        ..Value::new(ValueBase::BuiltinFunction(Box::new((
+                            *id,
+                            gc::new(obj),
+                            CallObject::new(Value::undefined()),
+                        ))))
+                    },
+                );
+            }
         }
-    }
 
-    pub fn to_string(&self) -> String {
-        self.val.to_string()
-    }
+        prototype
+    };
 
-    pub fn empty() -> Value {
-        Value::new(ValueBase::Empty)
-    }
+    pub static FUNCTION_OBJ: Value = {
+        let prototype = FUNCTION_PROTOTYPE.with(|x| x.clone());
+        let function = Value::builtin_function_with_obj_and_prototype(
+            builtin::FUNCTION_NEW,
+            CallObject::new(Value::undefined()),
+            PropertyMap::default(),
+            Value::object(prototype),
+        );
 
-    pub fn null() -> Value {
-        Value::new(ValueBase::Null)
-    }
+        unsafe {
+            (*prototype).insert(
+                "__proto__".to_string(),
+                Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+            );
+            (*prototype).insert(
+                "constructor".to_string(),
+                Value { enumerable: false, ..function.clone() },
+            );
+        }
+        function
+    };
 
-    pub fn undefined() -> Value {
-        Value::new(ValueBase::Undefined)
-    }
+    // https://www.ecma-international.org/ecma-262/7.0/#sec-properties-of-the-object-prototype-object
+    pub static OBJECT_PROTOTYPE: *mut PropertyMap = {
+        let mut prototype = PropertyMap::default();
+
+        for (name, id) in &[
+            ("hasOwnProperty", builtin::OBJECT_PROTOTYPE_HAS_OWN_PROPERTY),
+            ("isPrototypeOf", builtin::OBJECT_PROTOTYPE_IS_PROTOTYPE_OF),
+            ("propertyIsEnumerable", builtin::OBJECT_PROTOTYPE_PROPERTY_IS_ENUMERABLE),
+            ("toString", builtin::OBJECT_PROTOTYPE_TO_STRING),
+            ("valueOf", builtin::OBJECT_PROTOTYPE_VALUE_OF),
+        ] {
+            prototype.insert(
+                name.to_string(),
+                Value::builtin_function(*id, CallObject::new(Value::undefined())),
+            );
+        }
 
-    pub fn bool(b: bool) -> Value {
-        Value::new(ValueBase::Bool(b))
-    }
+        gc::new(prototype)
+    };
 
-    pub fn number(n: f64) -> Value {
-        Value::new(ValueBase::Number(n))
-    }
+    pub static OBJECT_OBJ: Value = {
+        let prototype = OBJECT_PROTOTYPE.with(|x| x.clone());
+        let object = Value::builtin_function_with_obj_and_prototype(
+            builtin::OBJECT_NEW,
+            CallObject::new(Value::undefined()),
+            {
+                let mut obj = PropertyMap::default();
+                for (name, id) in &[
+                    ("keys", builtin::OBJECT_KEYS),
+                    ("values", builtin::OBJECT_VALUES),
+                    ("entries", builtin::OBJECT_ENTRIES),
+                    ("assign", builtin::OBJECT_ASSIGN),
+                    ("create", builtin::OBJECT_CREATE),
+                    ("getPrototypeOf", builtin::OBJECT_GET_PROTOTYPE_OF),
+                    ("setPrototypeOf", builtin::OBJECT_SET_PROTOTYPE_OF),
+                    ("getOwnPropertyNames", builtin::OBJECT_GET_OWN_PROPERTY_NAMES),
+                    ("is", builtin::OBJECT_IS),
+                    ("defineProperty", builtin::OBJECT_DEFINE_PROPERTY),
+                    ("defineProperties", builtin::OBJECT_DEFINE_PROPERTIES),
+                    ("getOwnPropertyDescriptor", builtin::OBJECT_GET_OWN_PROPERTY_DESCRIPTOR),
+                    ("freeze", builtin::OBJECT_FREEZE),
+                    ("seal", builtin::OBJECT_SEAL),
+                    ("preventExtensions", builtin::OBJECT_PREVENT_EXTENSIONS),
+                    ("isFrozen", builtin::OBJECT_IS_FROZEN),
+                    ("isSealed", builtin::OBJECT_IS_SEALED),
+                    ("isExtensible", builtin::OBJECT_IS_EXTENSIBLE),
+                ] {
+                    obj.insert(
+                        name.to_string(),
+                        Value::builtin_function(*id, CallObject::new(Value::undefined())),
+                    );
+                }
+                obj
+            },
+            Value::object(prototype),
+        );
 
-    pub fn string(s: CString) -> Value {
-        Value::new(ValueBase::String(s))
-    }
+        unsafe { (*prototype).insert("constructor".to_string(), object.clone()); }
+        object
+    };
 
-    pub fn function(
-        id: FuncId,
-        iseq: ByteCode,
-        obj: *mut FxHashMap<String, Value>,
-        callobj: CallObject,
-    ) -> Value {
-        Value::new(ValueBase::Function(Box::new((id, iseq, obj, callobj))))
-    }
+    // https://tc39.github.io/ecma262/#sec-properties-of-the-error-prototype-object
+    pub static ERROR_PROTOTYPE: *mut PropertyMap = {
+        let mut prototype = PropertyMap::default();
 
-    pub fn builtin_function(pc: usize, callobj: CallObject) -> Value {
-        Value::builtin_function_with_obj_and_prototype(
-            pc,
-            callobj,
-            FxHashMap::default(),
-            Value::new(ValueBase::Object(gc::new(FxHashMap::default()))),
-        )
-    }
+        prototype.insert(
+            "toString".to_string(),
+            Value::builtin_function(
+                builtin::ERROR_PROTOTYPE_TO_STRING,
+                CallObject::new(Value::undefined()),
+            ),
+        );
 
-    pub fn builtin_function_with_obj_and_prototype(
-        pc: usize,
-        callobj: CallObject,
-        mut obj: FxHashMap<String, Value>,
-        prototype: Value,
-    ) -> Value {
-        obj.insert("prototype".to_string(), prototype);
-        obj.insert(
+        prototype.insert(
             "__proto__".to_string(),
-            Value::new(ValueBase::Object(gc::new({
-                let mut hm = FxHashMap::default();
-                hm.insert(
-                    "apply".to_string(),
-                    Value::new(ValueBase::BuiltinFunction(Box::new((
-                        builtin::FUNCTION_PROTOTYPE_APPLY,
-                        ::std::ptr::null_mut(),
-                        CallObject::new(Value::undefined()),
-                    )))),
-                );
-                hm.insert(
-                    "call".to_string(),
-                    Value::new(ValueBase::BuiltinFunction(Box::new((
-                        builtin::FUNCTION_PROTOTYPE_CALL,
-                        ::std::ptr::null_mut(),
-                        CallObject::new(Value::undefined()),
-                    )))),
-                );
-                hm
-            }))),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
         );
 
-        let obj_ = obj.clone();
+        gc::new(prototype)
+    };
+
+    // Error and the NativeError constructors, in the order of ERROR_NAMES.
+    pub static ERROR_OBJS: Vec<Value> = {
+        ERROR_NAMES.iter().enumerate().map(|(i, name)| {
+            let prototype = if i == 0 {
+                ERROR_PROTOTYPE.with(|x| x.clone())
+            } else {
+                let mut prototype = PropertyMap::default();
+                prototype.insert(
+                    "__proto__".to_string(),
+                    Value::object(ERROR_PROTOTYPE.with(|x| x.clone())),
+                );
+                gc::new(prototype)
+            };
+
+            let error = Value::builtin_function_with_obj_and_prototype(
+                builtin::ERROR_NEW + i,
+                CallObject::new(Value::undefined()),
+                PropertyMap::default(),
+                Value::object(prototype),
+            );
 
-        if let ValueBase::Object(ref mut obj2) = obj.get_mut("__proto__").unwrap().val {
             unsafe {
-                for name in ["apply", "call"].iter() {
-                    if let ValueBase::BuiltinFunction(box (_, ref mut obj3, _)) =
-                        (**obj2).get_mut(*name).unwrap().val
-                    {
-                        *obj3 = gc::new(obj_.clone());
-                    }
+                for (key, val) in vec![
+                    ("constructor", error.clone()),
+                    ("name", Value::string(JsString::from(*name))),
+                    ("message", Value::string(JsString::new())),
+                ] {
+                    (*prototype).insert(key.to_string(), Value { enumerable: false, ..val });
                 }
             }
-        }
+            error
+        }).collect()
+    };
 
-        Value::new(ValueBase::BuiltinFunction(Box::new((
-            pc,
-            gc::new(obj),
-            callobj,
-        ))))
-    }
+    // https://tc39.github.io/ecma262/#sec-%mapiteratorprototype%-object
+    // Shared by the iterators of Map and Set.
+    pub static COLLECTION_ITERATOR_PROTOTYPE: *mut PropertyMap = {
+        let mut prototype = PropertyMap::default();
 
-    pub fn object(obj: *mut FxHashMap<String, Value>) -> Value {
-        Value::new(ValueBase::Object(obj))
-    }
+        prototype.insert(
+            "next".to_string(),
+            Value {
+                enumerable: false,
+                ..Value::builtin_function(
+                    builtin::COLLECTION_ITERATOR_NEXT,
+                    CallObject::new(Value::undefined()),
+                )
+            },
+        );
 
-    pub fn array(ary: *mut ArrayValue) -> Value {
-        Value::new(ValueBase::Array(ary))
-    }
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
 
-    pub fn arguments() -> Value {
-        Value::new(ValueBase::Arguments)
-    }
+        gc::new(prototype)
+    };
 
-    pub fn get_property(&self, property: ValueBase, callobjref: Option<&CallObjectRef>) -> Value {
-        let property_of_simple = |obj: &FxHashMap<String, Value>| -> Value {
-            match obj_find_val(obj, property.to_string().as_str()).val {
-                ValueBase::Function(box (id, iseq, map2, mut callobj)) => {
-                    Value::new(ValueBase::Function(Box::new((id, iseq, map2, {
-                        *callobj.this = self.clone();
-                        callobj
-                    }))))
-                }
-                ValueBase::BuiltinFunction(box (id, obj, mut callobj)) => {
-                    Value::new(ValueBase::BuiltinFunction(Box::new((id, obj, {
-                        *callobj.this = self.clone();
-                        callobj
-                    }))))
-                }
-                val => Value::new(val),
+    // Map, Set, WeakMap and WeakSet constructors, in the order of COLLECTION_KINDS.
+    pub static COLLECTION_OBJS: Vec<Value> = {
+        COLLECTION_KINDS.iter().enumerate().map(|(i, kind)| {
+            let mut prototype = PropertyMap::default();
+            let methods: &[(&str, usize)] = match kind {
+                CollectionKind::Map => &[
+                    ("get", builtin::COLLECTION_PROTOTYPE_GET),
+                    ("set", builtin::COLLECTION_PROTOTYPE_SET),
+                    ("has", builtin::COLLECTION_PROTOTYPE_HAS),
+                    ("delete", builtin::COLLECTION_PROTOTYPE_DELETE),
+                    ("clear", builtin::COLLECTION_PROTOTYPE_CLEAR),
+                    ("forEach", builtin::COLLECTION_PROTOTYPE_FOR_EACH),
+                    ("keys", builtin::COLLECTION_PROTOTYPE_KEYS),
+                    ("values", builtin::COLLECTION_PROTOTYPE_VALUES),
+                    ("entries", builtin::COLLECTION_PROTOTYPE_ENTRIES),
+                ],
+                CollectionKind::Set => &[
+                    ("add", builtin::COLLECTION_PROTOTYPE_ADD),
+                    ("has", builtin::COLLECTION_PROTOTYPE_HAS),
+                    ("delete", builtin::COLLECTION_PROTOTYPE_DELETE),
+                    ("clear", builtin::COLLECTION_PROTOTYPE_CLEAR),
+                    ("forEach", builtin::COLLECTION_PROTOTYPE_FOR_EACH),
+                    ("values", builtin::COLLECTION_PROTOTYPE_VALUES),
+                    ("entries", builtin::COLLECTION_PROTOTYPE_ENTRIES),
+                ],
+                CollectionKind::WeakMap => &[
+                    ("get", builtin::COLLECTION_PROTOTYPE_GET),
+                    ("set", builtin::COLLECTION_PROTOTYPE_SET),
+                    ("has", builtin::COLLECTION_PROTOTYPE_HAS),
+                    ("delete", builtin::COLLECTION_PROTOTYPE_DELETE),
+                ],
+                CollectionKind::WeakSet => &[
+                    ("add", builtin::COLLECTION_PROTOTYPE_ADD),
+                    ("has", builtin::COLLECTION_PROTOTYPE_HAS),
+                    ("delete", builtin::COLLECTION_PROTOTYPE_DELETE),
+                ],
+            };
+            for (name, id) in methods {
+                prototype.insert(
+                    name.to_string(),
+                    Value {
+                        enumerable: false,
+                        ..Value::builtin_function(*id, CallObject::new(Value::undefined()))
+                    },
+                );
             }
-        };
-        let property_of_string = |s: &CString| -> Value {
-            match property {
-                // Character at the index 'n'
-                ValueBase::Number(n) if is_integer(n) => Value::string(
-                    CString::new(
-                        s.to_str()
-                            .unwrap()
-                            .chars()
-                            .nth(n as usize)
-                            .unwrap()
-                            .to_string(),
-                    ).unwrap(),
-                ),
-                // Length of string. TODO: Is this implementation correct?
-                ValueBase::String(ref member) if member.to_str().unwrap() == "length" => {
-                    Value::number(
-                        s.to_str()
-                            .unwrap()
-                            .chars()
-                            .fold(0, |x, c| x + c.len_utf16()) as f64,
-                    )
-                }
-                // TODO: Support all features.
-                _ => Value::undefined(),
+            // Set.prototype.keys is the same function object as Set.prototype.values.
+            if *kind == CollectionKind::Set {
+                let values = prototype.get("values").unwrap().clone();
+                prototype.insert("keys".to_string(), values);
             }
-        };
-        let property_of_object =
-            |properties: &FxHashMap<String, Value>| -> Value { property_of_simple(properties) };
-        let property_of_array = |ary: &ArrayValue| -> Value {
-            let get_by_idx = |n: usize| -> Value {
+            prototype.insert(
+                "__proto__".to_string(),
+                Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+            );
+            let prototype = gc::new(prototype);
+
+            let collection = Value::builtin_function_with_obj_and_prototype(
+                builtin::MAP_NEW + i,
+                CallObject::new(Value::undefined()),
+                PropertyMap::default(),
+                Value::object(prototype),
+            );
+
+            unsafe {
+                (*prototype).insert(
+                    "constructor".to_string(),
+                    Value { enumerable: false, ..collection.clone() },
+                );
+            }
+            collection
+        }).collect()
+    };
+
+    // https://tc39.github.io/ecma262/#sec-properties-of-the-date-prototype-object
+    pub static DATE_PROTOTYPE: *mut PropertyMap = {
+        let mut prototype = PropertyMap::default();
+
+        for (name, id) in &[
+            ("getTime", builtin::DATE_PROTOTYPE_GET_TIME),
+            ("valueOf", builtin::DATE_PROTOTYPE_GET_TIME),
+            ("setTime", builtin::DATE_PROTOTYPE_SET_TIME),
+            ("getTimezoneOffset", builtin::DATE_PROTOTYPE_GET_TIMEZONE_OFFSET),
+            ("toISOString", builtin::DATE_PROTOTYPE_TO_ISO_STRING),
+            ("toString", builtin::DATE_PROTOTYPE_TO_STRING),
+            ("toUTCString", builtin::DATE_PROTOTYPE_TO_UTC_STRING),
+            ("toJSON", builtin::DATE_PROTOTYPE_TO_JSON),
+            ("getFullYear", builtin::DATE_PROTOTYPE_GET_FULL_YEAR),
+            ("getMonth", builtin::DATE_PROTOTYPE_GET_MONTH),
+            ("getDate", builtin::DATE_PROTOTYPE_GET_DATE),
+            ("getDay", builtin::DATE_PROTOTYPE_GET_DAY),
+            ("getHours", builtin::DATE_PROTOTYPE_GET_HOURS),
+            ("getMinutes", builtin::DATE_PROTOTYPE_GET_MINUTES),
+            ("getSeconds", builtin::DATE_PROTOTYPE_GET_SECONDS),
+            ("getMilliseconds", builtin::DATE_PROTOTYPE_GET_MILLISECONDS),
+            ("getUTCFullYear", builtin::DATE_PROTOTYPE_GET_UTC_FULL_YEAR),
+            ("getUTCMonth", builtin::DATE_PROTOTYPE_GET_UTC_MONTH),
+            ("getUTCDate", builtin::DATE_PROTOTYPE_GET_UTC_DATE),
+            ("getUTCDay", builtin::DATE_PROTOTYPE_GET_UTC_DAY),
+            ("getUTCHours", builtin::DATE_PROTOTYPE_GET_UTC_HOURS),
+            ("getUTCMinutes", builtin::DATE_PROTOTYPE_GET_UTC_MINUTES),
+            ("getUTCSeconds", builtin::DATE_PROTOTYPE_GET_UTC_SECONDS),
+            ("getUTCMilliseconds", builtin::DATE_PROTOTYPE_GET_UTC_MILLISECONDS),
+            ("setFullYear", builtin::DATE_PROTOTYPE_SET_FULL_YEAR),
+            ("setMonth", builtin::DATE_PROTOTYPE_SET_MONTH),
+            ("setDate", builtin::DATE_PROTOTYPE_SET_DATE),
+            ("setHours", builtin::DATE_PROTOTYPE_SET_HOURS),
+            ("setMinutes", builtin::DATE_PROTOTYPE_SET_MINUTES),
+            ("setSeconds", builtin::DATE_PROTOTYPE_SET_SECONDS),
+            ("setMilliseconds", builtin::DATE_PROTOTYPE_SET_MILLISECONDS),
+            ("setUTCFullYear", builtin::DATE_PROTOTYPE_SET_UTC_FULL_YEAR),
+            ("setUTCMonth", builtin::DATE_PROTOTYPE_SET_UTC_MONTH),
+            ("setUTCDate", builtin::DATE_PROTOTYPE_SET_UTC_DATE),
+            ("setUTCHours", builtin::DATE_PROTOTYPE_SET_UTC_HOURS),
+            ("setUTCMinutes", builtin::DATE_PROTOTYPE_SET_UTC_MINUTES),
+            ("setUTCSeconds", builtin::DATE_PROTOTYPE_SET_UTC_SECONDS),
+            ("setUTCMilliseconds", builtin::DATE_PROTOTYPE_SET_UTC_MILLISECONDS),
+        ] {
+            prototype.insert(
+                name.to_string(),
+                Value {
+                    enumerable: false,
+                    ..Value::builtin_function(*id, CallObject::new(Value::undefined()))
+                },
+            );
+        }
+
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
+
+        gc::new(prototype)
+    };
+
+    pub static DATE_OBJ: Value = {
+        let prototype = DATE_PROTOTYPE.with(|x| x.clone());
+        let date = Value::builtin_function_with_obj_and_prototype(
+            builtin::DATE_NEW,
+            CallObject::new(Value::undefined()),
+            {
+                let mut obj = PropertyMap::default();
+                for (name, id) in &[
+                    ("now", builtin::DATE_NOW),
+                    ("parse", builtin::DATE_PARSE),
+                    ("UTC", builtin::DATE_UTC),
+                ] {
+                    obj.insert(
+                        name.to_string(),
+                        Value::builtin_function(*id, CallObject::new(Value::undefined())),
+                    );
+                }
+                obj
+            },
+            Value::object(prototype),
+        );
+
+        unsafe {
+            (*prototype).insert(
+                "constructor".to_string(),
+                Value { enumerable: false, ..date.clone() },
+            );
+        }
+        date
+    };
+
+    // https://tc39.github.io/ecma262/#sec-arraybuffer-constructor
+    pub static ARRAY_BUFFER_OBJ: Value = {
+        let mut prototype = PropertyMap::default();
+        prototype.insert(
+            "slice".to_string(),
+            Value {
+                enumerable: false,
+                ..Value::builtin_function(
+                    builtin::ARRAY_BUFFER_PROTOTYPE_SLICE,
+                    CallObject::new(Value::undefined()),
+                )
+            },
+        );
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
+        let prototype = gc::new(prototype);
+
+        let array_buffer = Value::builtin_function_with_obj_and_prototype(
+            builtin::ARRAY_BUFFER_NEW,
+            CallObject::new(Value::undefined()),
+            {
+                let mut obj = PropertyMap::default();
+                obj.insert(
+                    "isView".to_string(),
+                    Value::builtin_function(
+                        builtin::ARRAY_BUFFER_IS_VIEW,
+                        CallObject::new(Value::undefined()),
+                    ),
+                );
+                obj
+            },
+            Value::object(prototype),
+        );
+
+        unsafe {
+            (*prototype).insert(
+                "constructor".to_string(),
+                Value { enumerable: false, ..array_buffer.clone() },
+            );
+        }
+        array_buffer
+    };
+
+    // https://tc39.github.io/ecma262/#sec-properties-of-the-%typedarrayprototype%-object
+    // Shared by the prototypes of all the typed array constructors.
+    pub static TYPED_ARRAY_PROTOTYPE: *mut PropertyMap = {
+        let mut prototype = PropertyMap::default();
+
+        for (name, id) in &[
+            ("subarray", builtin::TYPED_ARRAY_PROTOTYPE_SUBARRAY),
+            ("set", builtin::TYPED_ARRAY_PROTOTYPE_SET),
+            ("slice", builtin::TYPED_ARRAY_PROTOTYPE_SLICE),
+            ("fill", builtin::TYPED_ARRAY_PROTOTYPE_FILL),
+            ("join", builtin::TYPED_ARRAY_PROTOTYPE_JOIN),
+            ("toString", builtin::TYPED_ARRAY_PROTOTYPE_JOIN),
+        ] {
+            prototype.insert(
+                name.to_string(),
+                Value {
+                    enumerable: false,
+                    ..Value::builtin_function(*id, CallObject::new(Value::undefined()))
+                },
+            );
+        }
+
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
+
+        gc::new(prototype)
+    };
+
+    // Int8Array, Uint8Array and the other typed array constructors, in the order of
+    // TYPED_ARRAY_KINDS.
+    pub static TYPED_ARRAY_OBJS: Vec<Value> = {
+        TYPED_ARRAY_KINDS.iter().enumerate().map(|(i, kind)| {
+            let bytes_per_element = Value {
+                writable: false,
+                enumerable: false,
+                configurable: false,
+                ..Value::number(kind.element_size() as f64)
+            };
+
+            let mut prototype = PropertyMap::default();
+            prototype.insert("BYTES_PER_ELEMENT".to_string(), bytes_per_element.clone());
+            prototype.insert(
+                "__proto__".to_string(),
+                Value::object(TYPED_ARRAY_PROTOTYPE.with(|x| x.clone())),
+            );
+            let prototype = gc::new(prototype);
+
+            let typed_array = Value::builtin_function_with_obj_and_prototype(
+                builtin::INT8_ARRAY_NEW + i,
+                CallObject::new(Value::undefined()),
+                {
+                    let mut obj = PropertyMap::default();
+                    obj.insert("BYTES_PER_ELEMENT".to_string(), bytes_per_element);
+                    obj
+                },
+                Value::object(prototype),
+            );
+
+            unsafe {
+                (*prototype).insert(
+                    "constructor".to_string(),
+                    Value { enumerable: false, ..typed_array.clone() },
+                );
+            }
+            typed_array
+        }).collect()
+    };
+
+    // https://tc39.github.io/ecma262/#sec-dataview-constructor
+    pub static DATA_VIEW_OBJ: Value = {
+        let mut prototype = PropertyMap::default();
+
+        for (name, id) in &[
+            ("getInt8", builtin::DATA_VIEW_PROTOTYPE_GET_INT8),
+            ("getUint8", builtin::DATA_VIEW_PROTOTYPE_GET_UINT8),
+            ("getInt16", builtin::DATA_VIEW_PROTOTYPE_GET_INT16),
+            ("getUint16", builtin::DATA_VIEW_PROTOTYPE_GET_UINT16),
+            ("getInt32", builtin::DATA_VIEW_PROTOTYPE_GET_INT32),
+            ("getUint32", builtin::DATA_VIEW_PROTOTYPE_GET_UINT32),
+            ("getFloat32", builtin::DATA_VIEW_PROTOTYPE_GET_FLOAT32),
+            ("getFloat64", builtin::DATA_VIEW_PROTOTYPE_GET_FLOAT64),
+            ("setInt8", builtin::DATA_VIEW_PROTOTYPE_SET_INT8),
+            ("setUint8", builtin::DATA_VIEW_PROTOTYPE_SET_UINT8),
+            ("setInt16", builtin::DATA_VIEW_PROTOTYPE_SET_INT16),
+            ("setUint16", builtin::DATA_VIEW_PROTOTYPE_SET_UINT16),
+            ("setInt32", builtin::DATA_VIEW_PROTOTYPE_SET_INT32),
+            ("setUint32", builtin::DATA_VIEW_PROTOTYPE_SET_UINT32),
+            ("setFloat32", builtin::DATA_VIEW_PROTOTYPE_SET_FLOAT32),
+            ("setFloat64", builtin::DATA_VIEW_PROTOTYPE_SET_FLOAT64),
+        ] {
+            prototype.insert(
+                name.to_string(),
+                Value {
+                    enumerable: false,
+                    ..Value::builtin_function(*id, CallObject::new(Value::undefined()))
+                },
+            );
+        }
+
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
+        let prototype = gc::new(prototype);
+
+        let data_view = Value::builtin_function_with_obj_and_prototype(
+            builtin::DATA_VIEW_NEW,
+            CallObject::new(Value::undefined()),
+            PropertyMap::default(),
+            Value::object(prototype),
+        );
+
+        unsafe {
+            (*prototype).insert(
+                "constructor".to_string(),
+                Value { enumerable: false, ..data_view.clone() },
+            );
+        }
+        data_view
+    };
+
+    // https://tc39.github.io/proposal-bigint/#sec-properties-of-the-bigint-prototype-object
+    pub static BIGINT_PROTOTYPE: *mut PropertyMap = {
+        let mut prototype = PropertyMap::default();
+
+        for (name, id) in &[
+            ("toString", builtin::BIGINT_PROTOTYPE_TO_STRING),
+            ("valueOf", builtin::BIGINT_PROTOTYPE_VALUE_OF),
+        ] {
+            prototype.insert(
+                name.to_string(),
+                Value {
+                    enumerable: false,
+                    ..Value::builtin_function(*id, CallObject::new(Value::undefined()))
+                },
+            );
+        }
+
+        prototype.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
+
+        gc::new(prototype)
+    };
+
+    pub static BIGINT_OBJ: Value = {
+        let prototype = BIGINT_PROTOTYPE.with(|x| x.clone());
+        let bigint = Value::builtin_function_with_obj_and_prototype(
+            builtin::BIGINT,
+            CallObject::new(Value::undefined()),
+            {
+                let mut obj = PropertyMap::default();
+                for (name, id) in &[
+                    ("asIntN", builtin::BIGINT_AS_INT_N),
+                    ("asUintN", builtin::BIGINT_AS_UINT_N),
+                ] {
+                    obj.insert(
+                        name.to_string(),
+                        Value {
+                            enumerable: false,
+                            ..Value::builtin_function(*id, CallObject::new(Value::undefined()))
+                        },
+                    );
+                }
+                obj
+            },
+            Value::object(prototype),
+        );
+
+        unsafe {
+            (*prototype).insert(
+                "constructor".to_string(),
+                Value { enumerable: false, ..bigint.clone() },
+            );
+        }
+        bigint
+    }
+);
+
+pub const TYPED_ARRAY_KINDS: [TypedArrayKind; 9] = [
+    TypedArrayKind::Int8,
+    TypedArrayKind::Uint8,
+    TypedArrayKind::Uint8Clamped,
+    TypedArrayKind::Int16,
+    TypedArrayKind::Uint16,
+    TypedArrayKind::Int32,
+    TypedArrayKind::Uint32,
+    TypedArrayKind::Float32,
+    TypedArrayKind::Float64,
+];
+
+pub const COLLECTION_KINDS: [CollectionKind; 4] = [
+    CollectionKind::Map,
+    CollectionKind::Set,
+    CollectionKind::WeakMap,
+    CollectionKind::WeakSet,
+];
+
+pub const ERROR_NAMES: [&str; 7] = [
+    "Error",
+    "TypeError",
+    "ReferenceError",
+    "RangeError",
+    "SyntaxError",
+    "EvalError",
+    "URIError",
+];
+
+impl ArrayValue {
+    pub fn new(arr: Vec<Value>) -> ArrayValue {
+        let len = arr.len();
+        ArrayValue {
+            elems: arr,
+            length: len,
+            length_writable: true,
+            obj: {
+                let mut hm = PropertyMap::default();
+                hm.insert("__proto__".to_string(), Value::array(Self::prototype()));
+                hm
+            },
+        }
+    }
+
+    pub fn prototype() -> *mut ArrayValue {
+        ARRAY_PROTOTYPE.with(|x| x.clone())
+    }
+
+    pub fn to_string(&self) -> String {
+        self.join(",")
+    }
+
+    // Array.prototype.join(). Holes, undefined and null are converted to empty strings.
+    pub fn join(&self, separator: &str) -> String {
+        self.elems[0..self.length]
+            .iter()
+            .map(|val| match val.val {
+                ValueBase::Empty | ValueBase::Undefined | ValueBase::Null => "".to_string(),
+                ref val => val.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(separator)
+    }
+
+    pub fn push(&mut self, val: Value) {
+        self.elems.push(val);
+        self.length += 1;
+    }
+
+    // Makes room for 'len' elements, charging the heap for the growth of the buffer.
+    pub fn reserve_elems(&mut self, len: usize) -> Result<(), RuntimeError> {
+        let capacity = self.elems.capacity();
+        if len > capacity {
+            let new_capacity = len.max(capacity * 2);
+            gc::grow((new_capacity - capacity).saturating_mul(mem::size_of::<Value>()))?;
+            self.elems.reserve_exact(new_capacity - self.elems.len());
+        }
+        Ok(())
+    }
+}
+
+// Defines the 'name' and 'length' properties of the builtin function 'id'. Host functions have
+// neither since they are not in the registry.
+fn insert_builtin_name_and_length(obj: &mut PropertyMap, id: usize) {
+    if let Some((name, length)) = builtin::builtin_name_and_length(id) {
+        for (key, val) in vec![
+            ("name", Value::string(JsString::from(name))),
+            ("length", Value::number(length as f64)),
+        ] {
+            obj.insert(
+                key.to_string(),
+                Value {
+                    writable: false,
+                    enumerable: false,
+                    ..val
+                },
+            );
+        }
+    }
+}
+
+impl Value {
+    pub fn new(val: ValueBase) -> Value {
+        Value {
+            val: val,
+            writable: true,
+            enumerable: true,
+            configurable: true,
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        self.val.to_string()
+    }
+
+    pub fn to_js_string(&self) -> JsString {
+        self.val.to_js_string()
+    }
+
+    pub fn empty() -> Value {
+        Value::new(ValueBase::Empty)
+    }
+
+    pub fn null() -> Value {
+        Value::new(ValueBase::Null)
+    }
+
+    pub fn undefined() -> Value {
+        Value::new(ValueBase::Undefined)
+    }
+
+    pub fn bool(b: bool) -> Value {
+        Value::new(ValueBase::Bool(b))
+    }
+
+    pub fn number(n: f64) -> Value {
+        Value::new(ValueBase::Number(n))
+    }
+
+    pub fn string(s: JsString) -> Value {
+        Value::new(ValueBase::String(s))
+    }
+
+    pub fn bigint(n: BigInt) -> Value {
+        Value::new(ValueBase::BigInt(n))
+    }
+
+    pub fn function(
+        id: FuncId,
+        iseq: Rc<ByteCode>,
+        obj: *mut PropertyMap,
+        callobj: CallObject,
+    ) -> Value {
+        Value::new(ValueBase::Function(Box::new((id, iseq, obj, callobj))))
+    }
+
+    pub fn builtin_function(pc: usize, callobj: CallObject) -> Value {
+        Value::builtin_function_with_obj_and_prototype(
+            pc,
+            callobj,
+            PropertyMap::default(),
+            Value::new(ValueBase::Object(gc::new(PropertyMap::default()))),
+        )
+    }
+
+    pub fn builtin_function_with_obj_and_prototype(
+        pc: usize,
+        callobj: CallObject,
+        mut obj: PropertyMap,
+        prototype: Value,
+    ) -> Value {
+        obj.insert("prototype".to_string(), prototype);
+        obj.insert(
+            "__proto__".to_string(),
+            Value::object(FUNCTION_PROTOTYPE.with(|x| x.clone())),
+        );
+        insert_builtin_name_and_length(&mut obj, pc);
+
+        Value::new(ValueBase::BuiltinFunction(Box::new((
+            pc,
+            gc::new(obj),
+            callobj,
+        ))))
+    }
+
+    pub fn object(obj: *mut PropertyMap) -> Value {
+        Value::new(ValueBase::Object(obj))
+    }
+
+    // Creates an ordinary object whose prototype is Object.prototype.
+    pub fn plain_object(mut map: PropertyMap) -> Value {
+        map.insert(
+            "__proto__".to_string(),
+            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+        );
+        Value::object(gc::new(map))
+    }
+
+    pub fn array(ary: *mut ArrayValue) -> Value {
+        Value::new(ValueBase::Array(ary))
+    }
+
+    pub fn arguments() -> Value {
+        Value::new(ValueBase::Arguments)
+    }
+
+    pub fn get_property(&self, property: ValueBase, callobjref: Option<&CallObjectRef>) -> Value {
+        let property_of_simple = |obj: &PropertyMap| -> Value {
+            match obj_find_val(obj, property.to_property_key().as_str()).val {
+                ValueBase::Function(box (id, iseq, map2, mut callobj)) => {
+                    Value::new(ValueBase::Function(Box::new((id, iseq, map2, {
+                        *callobj.this = self.clone();
+                        callobj
+                    }))))
+                }
+                ValueBase::BuiltinFunction(box (id, obj, mut callobj)) => {
+                    Value::new(ValueBase::BuiltinFunction(Box::new((id, obj, {
+                        *callobj.this = self.clone();
+                        callobj
+                    }))))
+                }
+                val => Value::new(val),
+            }
+        };
+        let property_of_string = |s: &JsString| -> Value {
+            match property {
+                // Code unit at the index 'n'
+                ValueBase::Number(n) if is_integer(n) && n >= 0.0 => match s.at(n as usize) {
+                    Some(unit) => Value::string(JsString::from_units(vec![unit])),
+                    None => Value::undefined(),
+                },
+                // Length of string in UTF-16 code units
+                ValueBase::String(ref member) if member.eq_str("length") => {
+                    Value::number(s.len() as f64)
+                }
+                // Other properties are looked up as if the string were boxed in a String object.
+                _ => property_of_simple(unsafe { &*STRING_PROTOTYPE.with(|x| x.clone()) }),
+            }
+        };
+        let property_of_object = |properties: &PropertyMap| -> Value {
+            // TODO: 'size' of Map and Set should be a getter on the prototype.
+            if let ValueBase::String(ref s) = property {
+                if s.eq_str("size") {
+                    if let Some(Value {
+                        val: ValueBase::Collection(collection),
+                        ..
+                    }) = properties.get(COLLECTION_DATA)
+                    {
+                        if !unsafe { (**collection).kind.is_weak() } {
+                            return Value::number(unsafe { (**collection).size() } as f64);
+                        }
+                    }
+                }
+            }
+            if let Some(val) = unsafe { buffer_property(properties, &property) } {
+                return val;
+            }
+            property_of_simple(properties)
+        };
+        let property_of_array = |ary: &ArrayValue| -> Value {
+            let get_by_idx = |n: usize| -> Value {
                 let arr = &ary.elems;
 
-                if n >= ary.length {
-                    return Value::undefined();
+                if n >= ary.length {
+                    return Value::undefined();
+                }
+
+                match arr[n] {
+                    Value {
+                        val: ValueBase::Empty,
+                        ..
+                    } => Value::undefined(),
+                    ref other => other.clone(),
+                }
+            };
+
+            match property {
+                // Index
+                ValueBase::Number(n) if is_integer(n) && n >= 0.0 => get_by_idx(n as usize),
+                ValueBase::String(ref s) if s.eq_str("length") => Value::number(ary.length as f64),
+                ValueBase::String(ref s) => {
+                    // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-array-exotic-objects
+                    let num = property.to_uint32();
+                    if s.eq_str(Value::number(num).to_string().as_str()) {
+                        get_by_idx(num as usize)
+                    } else {
+                        property_of_simple(&ary.obj)
+                    }
+                }
+                _ => property_of_simple(&ary.obj),
+            }
+        };
+        let property_of_arguments = || -> Value {
+            unsafe {
+                match property {
+                    // Index
+                    ValueBase::Number(n) if is_integer(n) && n >= 0.0 => callobjref
+                        .and_then(|co| Some((**co).get_arguments_nth_value(n as usize).unwrap()))
+                        .unwrap_or_else(|| Value::undefined()),
+                    ValueBase::String(ref s) if s.eq_str("length") => {
+                        let length = callobjref
+                            .and_then(|co| Some((**co).get_arguments_length()))
+                            .unwrap_or(0);
+                        Value::number(length as f64)
+                    }
+                    _ => Value::undefined(),
+                }
+            }
+        };
+
+        unsafe {
+            match self.val {
+                ValueBase::String(ref s) => property_of_string(s),
+                ValueBase::Number(_) => property_of_simple(&*NUMBER_PROTOTYPE.with(|x| x.clone())),
+                ValueBase::BigInt(_) => property_of_simple(&*BIGINT_PROTOTYPE.with(|x| x.clone())),
+                ValueBase::BuiltinFunction(box (_, ref obj, _))
+                | ValueBase::Function(box (_, _, ref obj, _))
+                | ValueBase::Object(ref obj) => property_of_object(&**obj),
+                ValueBase::Array(ref ary) => property_of_array(&**ary),
+                ValueBase::Arguments => property_of_arguments(),
+                // Traps are invoked by the VM (see proxy::get_property).
+                ValueBase::Proxy(p) => (*p).target.get_property(property, callobjref),
+                // TODO: Implement
+                _ => Value::undefined(),
+            }
+        }
+    }
+
+    pub fn set_number_if_possible(&mut self, n: f64) {
+        if let ValueBase::Number(ref mut n_) = self.val {
+            *n_ = n;
+        }
+    }
+}
+
+impl Default for PropertyMap {
+    fn default() -> Self {
+        PropertyMap {
+            entries: vec![],
+            index: FxHashMap::default(),
+            extensible: true,
+        }
+    }
+}
+
+impl PropertyMap {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> bool
+    where
+        String: Borrow<Q>,
+    {
+        self.index.contains_key(key)
+    }
+
+    pub fn get<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<&Value>
+    where
+        String: Borrow<Q>,
+    {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn get_mut<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<&mut Value>
+    where
+        String: Borrow<Q>,
+    {
+        match self.index.get(key) {
+            Some(&i) => Some(&mut self.entries[i].1),
+            None => None,
+        }
+    }
+
+    // Replacing the value of an existing property keeps its position.
+    pub fn insert(&mut self, key: String, val: Value) -> Option<Value> {
+        if let Some(&i) = self.index.get(&key) {
+            return Some(mem::replace(&mut self.entries[i].1, val));
+        }
+        self.index.insert(key.clone(), self.entries.len());
+        self.entries.push((key, val));
+        None
+    }
+
+    pub fn remove<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<Value>
+    where
+        String: Borrow<Q>,
+    {
+        let i = self.index.remove(key)?;
+        let (_, val) = self.entries.remove(i);
+        for &(ref key, _) in &self.entries[i..] {
+            *self.index.get_mut::<String>(key).unwrap() -= 1;
+        }
+        Some(val)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a String, &'a Value)> {
+        self.entries.iter().map(|&(ref key, ref val)| (key, val))
+    }
+
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (&'a String, &'a mut Value)> {
+        self.entries
+            .iter_mut()
+            .map(|&mut (ref key, ref mut val)| (key, val))
+    }
+
+    pub fn keys<'a>(&'a self) -> impl Iterator<Item = &'a String> {
+        self.entries.iter().map(|&(ref key, _)| key)
+    }
+
+    pub fn values<'a>(&'a self) -> impl Iterator<Item = &'a Value> {
+        self.entries.iter().map(|&(_, ref val)| val)
+    }
+
+    // The size of the buffers allocated for the entries and the index (see gc::Gc::size()).
+    pub fn buffer_size(&self) -> usize {
+        self.entries.capacity() * mem::size_of::<(String, Value)>()
+            + self.index.capacity() * mem::size_of::<(String, usize)>()
+    }
+}
+
+impl<'a> IntoIterator for &'a PropertyMap {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = ::std::iter::Map<
+        ::std::slice::Iter<'a, (String, Value)>,
+        fn(&'a (String, Value)) -> (&'a String, &'a Value),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        fn entry<'a>(&(ref key, ref val): &'a (String, Value)) -> (&'a String, &'a Value) {
+            (key, val)
+        }
+        self.entries.iter().map(entry)
+    }
+}
+
+// A property descriptor given to Object.defineProperty() and friends. Absent fields are None.
+// TODO: Accessor properties (get/set) are not supported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyDescriptor {
+    pub value: Option<ValueBase>,
+    pub writable: Option<bool>,
+    pub enumerable: Option<bool>,
+    pub configurable: Option<bool>,
+}
+
+// Hidden properties of Map, Set, WeakMap and WeakSet objects and of their iterators.
+pub const COLLECTION_DATA: &'static str = "\u{1b}[CollectionData]";
+pub const ITERATED_COLLECTION: &'static str = "\u{1b}[IteratedCollection]";
+pub const ITERATOR_NEXT_INDEX: &'static str = "\u{1b}[IteratorNextIndex]";
+pub const ITERATOR_KIND: &'static str = "\u{1b}[IteratorKind]";
+
+// Hidden property of Date objects holding the time value.
+pub const DATE_VALUE: &'static str = "\u{1b}[DateValue]";
+
+// Hidden properties of ArrayBuffer, typed array and DataView objects.
+pub const ARRAY_BUFFER_DATA: &'static str = "\u{1b}[ArrayBufferData]";
+pub const TYPED_ARRAY_DATA: &'static str = "\u{1b}[TypedArrayData]";
+pub const DATA_VIEW_DATA: &'static str = "\u{1b}[DataViewData]";
+
+pub fn is_internal_slot(name: &str) -> bool {
+    name.starts_with(INTERNAL_SLOT_PREFIX)
+}
+
+pub fn is_internal_property(name: &str) -> bool {
+    name == "__proto__" || is_internal_slot(name)
+}
+
+// Returns the elements of typed arrays and 'length', 'byteLength', 'byteOffset' and 'buffer' of
+// ArrayBuffer, typed array and DataView objects, which are computed from their hidden properties.
+// Elements out of range are undefined rather than looked up in the prototype chain.
+// TODO: The properties other than elements should be getters on the prototypes.
+unsafe fn buffer_property(obj: &PropertyMap, property: &ValueBase) -> Option<Value> {
+    let view_property = |view: &TypedArray, name: &JsString, is_typed_array: bool| {
+        if is_typed_array && name.eq_str("length") {
+            Some(Value::number(view.length as f64))
+        } else if name.eq_str("byteLength") {
+            Some(Value::number(view.byte_length() as f64))
+        } else if name.eq_str("byteOffset") {
+            Some(Value::number(view.byte_offset as f64))
+        } else if name.eq_str("buffer") {
+            Some(view.buffer.clone())
+        } else {
+            None
+        }
+    };
+
+    if let Some(Value {
+        val: ValueBase::TypedArray(typed_array),
+        ..
+    }) = obj.get(TYPED_ARRAY_DATA)
+    {
+        if let Some(n) = canonical_numeric_index(property) {
+            return Some(
+                match typed_array_index(&**typed_array, n).and_then(|n| (**typed_array).get(n)) {
+                    Some(n) => Value::number(n),
+                    None => Value::undefined(),
+                },
+            );
+        }
+        if let ValueBase::String(ref name) = property {
+            return view_property(&**typed_array, name, true);
+        }
+        return None;
+    }
+
+    let name = match property {
+        ValueBase::String(ref name)
+            if name.eq_str("byteLength") || name.eq_str("byteOffset") || name.eq_str("buffer") =>
+        {
+            name
+        }
+        _ => return None,
+    };
+    match (obj.get(DATA_VIEW_DATA), obj.get(ARRAY_BUFFER_DATA)) {
+        (
+            Some(Value {
+                val: ValueBase::TypedArray(view),
+                ..
+            }),
+            _,
+        ) => view_property(&**view, name, false),
+        (
+            _,
+            Some(Value {
+                val: ValueBase::ArrayBuffer(buffer),
+                ..
+            }),
+        ) if name.eq_str("byteLength") => Some(Value::number((**buffer).bytes.len() as f64)),
+        _ => None,
+    }
+}
+
+// Returns the typed array whose hidden property 'obj' has, if any.
+fn obj_typed_array(obj: &PropertyMap) -> Option<*mut TypedArray> {
+    match obj.get(TYPED_ARRAY_DATA) {
+        Some(Value {
+            val: ValueBase::TypedArray(typed_array),
+            ..
+        }) => Some(*typed_array),
+        _ => None,
+    }
+}
+
+// Returns the number 'property' is if it's a number or a canonical numeric string like "1.5" or
+// "-1" (but not "01"). Typed arrays take all of them as indices, valid or not. The number -0 is the
+// key "0", so only the string "-0" is an invalid index.
+// https://tc39.github.io/ecma262/#sec-canonicalnumericindexstring
+fn canonical_numeric_index(property: &ValueBase) -> Option<f64> {
+    match property {
+        ValueBase::Number(n) if *n == 0.0 => Some(0.0),
+        ValueBase::Number(n) => Some(*n),
+        ValueBase::String(ref s) if s.eq_str("-0") => Some(-0.0),
+        ValueBase::String(ref s) => {
+            let n = property.to_number();
+            if s.eq_str(Value::number(n).to_string().as_str()) {
+                Some(n)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// Returns 'n' as an index if it's a valid one of 'typed_array'.
+// https://tc39.github.io/ecma262/#sec-isvalidintegerindex
+fn typed_array_index(typed_array: &TypedArray, n: f64) -> Option<usize> {
+    if is_integer(n) && !n.is_sign_negative() && n < typed_array.length as f64 {
+        Some(n as usize)
+    } else {
+        None
+    }
+}
+
+fn reject(strict: bool, msg: String) -> Result<(), RuntimeError> {
+    if strict {
+        Err(RuntimeError::Type(format!("type error: {}", msg)))
+    } else {
+        Ok(())
+    }
+}
+
+// Converts the new value of 'length' of an array, which must be an integer in the uint32 range.
+// https://tc39.github.io/ecma262/#sec-arraysetlength
+fn array_length(val: &ValueBase) -> Result<usize, RuntimeError> {
+    let len = val.to_uint32();
+    if len != val.to_number() {
+        return Err(RuntimeError::Range(
+            "range error: invalid array length".to_string(),
+        ));
+    }
+    Ok(len as usize)
+}
+
+// Returns the index if 'key' is the canonical form of an array index (0 to 2^32 - 2).
+fn array_index_of_key(key: &str) -> Option<u32> {
+    if key.len() > 1 && key.starts_with('0') {
+        return None;
+    }
+    match key.parse::<u32>() {
+        Ok(n) if n != ::std::u32::MAX && key.bytes().all(|b| b.is_ascii_digit()) => Some(n),
+        _ => None,
+    }
+}
+
+fn array_index(property: &ValueBase) -> Option<usize> {
+    match property {
+        ValueBase::Number(n) if is_integer(*n) && *n >= 0.0 => Some(*n as usize),
+        // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-array-exotic-objects
+        ValueBase::String(ref s) => {
+            let num = property.to_uint32();
+            if s.eq_str(Value::number(num).to_string().as_str()) {
+                Some(num as usize)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// Returns false if a read-only property named 'key' is found in the prototype chain.
+fn obj_inherited_writable(obj: &PropertyMap, key: &str) -> bool {
+    let proto = match obj.get("__proto__") {
+        Some(proto) => proto,
+        None => return true,
+    };
+    unsafe {
+        let proto_obj = match proto.val {
+            ValueBase::Function(box (_, _, obj, _))
+            | ValueBase::BuiltinFunction(box (_, obj, _))
+            | ValueBase::Object(obj) => &*obj,
+            ValueBase::Array(aryval) => &(*aryval).obj,
+            _ => return true,
+        };
+        match proto_obj.get(key) {
+            Some(val) => val.writable,
+            None => obj_inherited_writable(proto_obj, key),
+        }
+    }
+}
+
+fn obj_set_property(
+    obj: &mut PropertyMap,
+    name: String,
+    val: Value,
+    strict: bool,
+) -> Result<(), RuntimeError> {
+    if let Some(prop) = obj.get_mut(&name) {
+        if !prop.writable {
+            return reject(
+                strict,
+                format!("cannot assign to read only property '{}' of object", name),
+            );
+        }
+        prop.val = val.val;
+        return Ok(());
+    }
+
+    // Like the setter of Object.prototype.__proto__, unless a data property shadows it.
+    if name == PROTO_KEY && obj_lookup(obj, PROTO_KEY).is_none() {
+        match val.val {
+            ValueBase::Null => {
+                obj.remove("__proto__");
+            }
+            _ if val.is_object() => {
+                obj.insert("__proto__".to_string(), Value::new(val.val));
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    if !obj.extensible {
+        return reject(
+            strict,
+            format!("cannot add property '{}', object is not extensible", name),
+        );
+    }
+
+    if !obj_inherited_writable(obj, name.as_str()) {
+        return reject(
+            strict,
+            format!("cannot assign to read only property '{}' of object", name),
+        );
+    }
+
+    obj.insert(name, Value::new(val.val));
+    Ok(())
+}
+
+fn obj_delete_property(
+    obj: &mut PropertyMap,
+    name: &str,
+    strict: bool,
+) -> Result<bool, RuntimeError> {
+    match obj.get(name).map(|prop| prop.configurable) {
+        None => Ok(true),
+        Some(true) => {
+            obj.remove(name);
+            Ok(true)
+        }
+        Some(false) => {
+            reject(
+                strict,
+                format!("cannot delete property '{}' of object", name),
+            )?;
+            Ok(false)
+        }
+    }
+}
+
+fn obj_define_property(
+    obj: &mut PropertyMap,
+    name: String,
+    desc: PropertyDescriptor,
+) -> Result<(), RuntimeError> {
+    let extensible = obj.extensible;
+    match obj.get_mut(&name) {
+        Some(prop) => apply_property_descriptor(prop, &name, desc),
+        None if !extensible => Err(RuntimeError::Type(format!(
+            "type error: cannot define property '{}', object is not extensible",
+            name
+        ))),
+        None => {
+            obj.insert(name, Value::from_property_descriptor(desc));
+            Ok(())
+        }
+    }
+}
+
+// Defines the element 'n' of 'typed_array', which has to be a valid index. Elements are always
+// writable, enumerable and configurable.
+// https://tc39.github.io/ecma262/#sec-integer-indexed-exotic-objects-defineownproperty-p-desc
+unsafe fn typed_array_define_element(
+    typed_array: &TypedArray,
+    n: f64,
+    name: &str,
+    desc: PropertyDescriptor,
+) -> Result<(), RuntimeError> {
+    let n = match typed_array_index(typed_array, n) {
+        Some(n) => n,
+        None => {
+            return Err(RuntimeError::Type(format!(
+                "type error: cannot define property '{}', invalid typed array index",
+                name
+            )))
+        }
+    };
+    if desc.writable == Some(false)
+        || desc.enumerable == Some(false)
+        || desc.configurable == Some(false)
+    {
+        return Err(RuntimeError::Type(format!(
+            "type error: cannot redefine property '{}' of a typed array",
+            name
+        )));
+    }
+    if let Some(val) = desc.value {
+        typed_array.set(n, val.try_to_number()?);
+    }
+    Ok(())
+}
+
+// https://tc39.github.io/ecma262/#sec-validateandapplypropertydescriptor
+fn apply_property_descriptor(
+    prop: &mut Value,
+    name: &str,
+    desc: PropertyDescriptor,
+) -> Result<(), RuntimeError> {
+    if !prop.configurable {
+        let same_value = |val: &ValueBase| match (val, &prop.val) {
+            (ValueBase::Number(x), ValueBase::Number(y)) => {
+                (x.is_nan() && y.is_nan())
+                    || (x == y && x.is_sign_negative() == y.is_sign_negative())
+            }
+            (x, y) => x == y,
+        };
+        if desc.configurable == Some(true)
+            || desc.enumerable.map_or(false, |e| e != prop.enumerable)
+            || (!prop.writable && desc.writable == Some(true))
+            || (!prop.writable && desc.value.as_ref().map_or(false, |v| !same_value(v)))
+        {
+            return Err(RuntimeError::Type(format!(
+                "type error: cannot redefine property: '{}'",
+                name
+            )));
+        }
+    }
+
+    if let Some(val) = desc.value {
+        prop.val = val;
+    }
+    if let Some(writable) = desc.writable {
+        prop.writable = writable;
+    }
+    if let Some(enumerable) = desc.enumerable {
+        prop.enumerable = enumerable;
+    }
+    if let Some(configurable) = desc.configurable {
+        prop.configurable = configurable;
+    }
+    Ok(())
+}
+
+impl ArrayValue {
+    pub fn is_extensible(&self) -> bool {
+        self.obj.extensible
+    }
+
+    fn set_by_idx(&mut self, n: usize, val: Value, strict: bool) -> Result<(), RuntimeError> {
+        if n < self.length && self.elems[n].val != ValueBase::Empty {
+            if !self.elems[n].writable {
+                return reject(
+                    strict,
+                    format!("cannot assign to read only property '{}' of object", n),
+                );
+            }
+            self.elems[n].val = val.val;
+            return Ok(());
+        }
+
+        if !self.is_extensible() {
+            return reject(
+                strict,
+                format!("cannot add property {}, object is not extensible", n),
+            );
+        }
+
+        if n >= self.length {
+            if !self.length_writable {
+                return reject(
+                    strict,
+                    "cannot assign to read only property 'length' of object".to_string(),
+                );
+            }
+            self.reserve_elems(n + 1)?;
+            self.length = n + 1;
+            while self.elems.len() < n + 1 {
+                self.elems.push(Value::empty());
+            }
+        }
+        self.elems[n] = Value::new(val.val);
+        Ok(())
+    }
+
+    fn set_length(&mut self, len: usize, strict: bool) -> Result<(), RuntimeError> {
+        if !self.length_writable {
+            return reject(
+                strict,
+                "cannot assign to read only property 'length' of object".to_string(),
+            );
+        }
+        if len < self.length && self.elems[len..self.length].iter().any(|v| !v.configurable) {
+            return reject(
+                strict,
+                "cannot delete non-configurable array element".to_string(),
+            );
+        }
+        self.reserve_elems(len)?;
+        self.length = len;
+        self.elems.resize(len, Value::empty());
+        Ok(())
+    }
+}
+
+impl Value {
+    pub fn from_property_descriptor(desc: PropertyDescriptor) -> Value {
+        Value {
+            val: desc.value.unwrap_or(ValueBase::Undefined),
+            writable: desc.writable.unwrap_or(false),
+            enumerable: desc.enumerable.unwrap_or(false),
+            configurable: desc.configurable.unwrap_or(false),
+        }
+    }
+
+    // TODO: Functions should be also set here (see get_property).
+    pub fn set_property(
+        &self,
+        property: ValueBase,
+        val: Value,
+        callobjref: Option<&CallObjectRef>,
+        strict: bool,
+    ) -> Result<(), RuntimeError> {
+        unsafe {
+            match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => {
+                    // Elements of typed arrays. Writes to invalid indices are ignored.
+                    if let Some(typed_array) = obj_typed_array(&*map) {
+                        if let Some(n) = canonical_numeric_index(&property) {
+                            if let Some(n) = typed_array_index(&*typed_array, n) {
+                                (*typed_array).set(n, val.val.try_to_number()?);
+                            }
+                            return Ok(());
+                        }
+                    }
+                    obj_set_property(&mut *map, property.to_property_key(), val, strict)
+                }
+                ValueBase::Array(ary) => {
+                    let ary = &mut *ary;
+                    match property {
+                        ValueBase::String(ref s) if s.eq_str("length") => {
+                            ary.set_length(array_length(&val.val)?, strict)
+                        }
+                        _ => match array_index(&property) {
+                            Some(n) => ary.set_by_idx(n, val, strict),
+                            None => obj_set_property(
+                                &mut ary.obj,
+                                property.to_property_key(),
+                                val,
+                                strict,
+                            ),
+                        },
+                    }
                 }
+                ValueBase::Arguments => {
+                    match property {
+                        // Index
+                        ValueBase::Number(n) if is_integer(n) => {
+                            if let Some(callobj) = callobjref {
+                                (**callobj).set_arguments_nth_value(n as usize, val);
+                            }
+                        }
+                        // TODO: 'length'
+                        _ => {}
+                    }
+                    Ok(())
+                }
+                ValueBase::String(_) if strict => reject(
+                    strict,
+                    format!(
+                        "cannot create property '{}' on string",
+                        property.to_property_key()
+                    ),
+                ),
+                ValueBase::Proxy(p) => (*p).target.set_property(property, val, callobjref, strict),
+                _ => Ok(()),
+            }
+        }
+    }
 
-                match arr[n] {
-                    Value {
-                        val: ValueBase::Empty,
-                        ..
-                    } => Value::undefined(),
-                    ref other => other.clone(),
+    // Returns whether the property is deleted (or not found).
+    pub fn delete_property(&self, property: ValueBase, strict: bool) -> Result<bool, RuntimeError> {
+        unsafe {
+            match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => {
+                    if let Some(typed_array) = obj_typed_array(&*map) {
+                        if let Some(n) = canonical_numeric_index(&property) {
+                            if typed_array_index(&*typed_array, n).is_none() {
+                                return Ok(true);
+                            }
+                            reject(
+                                strict,
+                                format!("cannot delete property '{}'", property.to_string()),
+                            )?;
+                            return Ok(false);
+                        }
+                    }
+                    obj_delete_property(&mut *map, property.to_property_key().as_str(), strict)
                 }
-            };
+                ValueBase::Array(ary) => {
+                    let ary = &mut *ary;
+                    match property {
+                        ValueBase::String(ref s) if s.eq_str("length") => {
+                            reject(strict, "cannot delete property 'length'".to_string())?;
+                            Ok(false)
+                        }
+                        _ => match array_index(&property) {
+                            Some(n) if n < ary.length => {
+                                if !ary.elems[n].configurable {
+                                    reject(strict, format!("cannot delete property '{}'", n))?;
+                                    return Ok(false);
+                                }
+                                ary.elems[n] = Value::empty();
+                                Ok(true)
+                            }
+                            Some(_) => Ok(true),
+                            None => obj_delete_property(
+                                &mut ary.obj,
+                                property.to_property_key().as_str(),
+                                strict,
+                            ),
+                        },
+                    }
+                }
+                ValueBase::Proxy(p) => (*p).target.delete_property(property, strict),
+                _ => Ok(true),
+            }
+        }
+    }
 
-            match property {
-                // Index
-                ValueBase::Number(n) if is_integer(n) && n >= 0.0 => get_by_idx(n as usize),
-                ValueBase::String(ref s) if s.to_str().unwrap() == "length" => {
-                    Value::number(ary.length as f64)
+    // Returns the own property named 'key' with its attributes.
+    pub fn get_own_property(&self, key: &str) -> Option<Value> {
+        if is_internal_property(key) {
+            return None;
+        }
+        unsafe {
+            match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => {
+                    if let Some(typed_array) = obj_typed_array(&*map) {
+                        let key_val = ValueBase::String(JsString::from_key(key));
+                        if let Some(n) = canonical_numeric_index(&key_val) {
+                            return typed_array_index(&*typed_array, n)
+                                .and_then(|n| (*typed_array).get(n))
+                                .map(|n| Value::number(n));
+                        }
+                    }
+                    (*map).get(key).cloned()
+                }
+                ValueBase::Array(ary) => {
+                    let ary = &*ary;
+                    if key == "length" {
+                        return Some(Value {
+                            val: ValueBase::Number(ary.length as f64),
+                            writable: ary.length_writable,
+                            enumerable: false,
+                            configurable: false,
+                        });
+                    }
+                    let key_val = ValueBase::String(JsString::from_key(key));
+                    match array_index(&key_val) {
+                        Some(n) if n < ary.length && ary.elems[n].val != ValueBase::Empty => {
+                            Some(ary.elems[n].clone())
+                        }
+                        Some(_) => None,
+                        None => ary.obj.get(key).cloned(),
+                    }
                 }
                 ValueBase::String(ref s) => {
-                    // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-array-exotic-objects
-                    let num = property.to_uint32();
-                    if Value::number(num).to_string() == s.to_str().unwrap() {
-                        get_by_idx(num as usize)
+                    let (val, enumerable) = if key == "length" {
+                        (ValueBase::Number(s.len() as f64), false)
                     } else {
-                        property_of_simple(&ary.obj)
+                        let key_val = ValueBase::String(JsString::from_key(key));
+                        let unit = array_index(&key_val).and_then(|n| s.at(n))?;
+                        (ValueBase::String(JsString::from_units(vec![unit])), true)
+                    };
+                    Some(Value {
+                        val: val,
+                        writable: false,
+                        enumerable: enumerable,
+                        configurable: false,
+                    })
+                }
+                ValueBase::Proxy(p) => (*p).target.get_own_property(key),
+                _ => None,
+            }
+        }
+    }
+
+    // Returns true if the property named 'key' is found in this object or its prototype chain.
+    pub fn has_property(&self, key: &str) -> bool {
+        if self.get_own_property(key).is_some() {
+            return true;
+        }
+        // Numeric keys of typed arrays are never looked up in the prototype chain.
+        if let ValueBase::Object(map) = self.val {
+            let key_val = ValueBase::String(JsString::from_key(key));
+            if unsafe { obj_typed_array(&*map) }.is_some()
+                && canonical_numeric_index(&key_val).is_some()
+            {
+                return false;
+            }
+        }
+        let proto = self.get_prototype();
+        match proto.val {
+            ValueBase::Object(_)
+            | ValueBase::Array(_)
+            | ValueBase::Function(_)
+            | ValueBase::BuiltinFunction(_)
+            | ValueBase::Proxy(_) => proto.has_property(key),
+            _ => false,
+        }
+    }
+
+    // Returns the names of own properties except internal ones, including non-enumerable ones.
+    // Like OrdinaryOwnPropertyKeys, array indices come first in ascending order and the other
+    // names follow in the order they were added.
+    // https://tc39.github.io/ecma262/#sec-ordinaryownpropertykeys
+    pub fn own_property_keys(&self) -> Vec<String> {
+        fn obj_keys(obj: &PropertyMap) -> Vec<String> {
+            let mut indices = vec![];
+            let mut keys = vec![];
+            for key in obj.keys() {
+                if is_internal_property(key.as_str()) {
+                    continue;
+                }
+                match array_index_of_key(key.as_str()) {
+                    Some(n) => indices.push((n, key.clone())),
+                    None => keys.push(key.clone()),
+                }
+            }
+            indices.sort_by_key(|&(n, _)| n);
+            indices
+                .into_iter()
+                .map(|(_, key)| key)
+                .chain(keys)
+                .collect()
+        }
+
+        unsafe {
+            match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => {
+                    let mut keys = match obj_typed_array(&*map) {
+                        Some(typed_array) => (0..(*typed_array).length)
+                            .map(|i| i.to_string())
+                            .collect::<Vec<String>>(),
+                        None => vec![],
+                    };
+                    keys.extend(obj_keys(&*map));
+                    keys
+                }
+                ValueBase::Array(ary) => {
+                    let ary = &*ary;
+                    let mut keys = vec![];
+                    for i in 0..ary.length {
+                        if ary.elems[i].val != ValueBase::Empty {
+                            keys.push(i.to_string());
+                        }
                     }
+                    keys.push("length".to_string());
+                    keys.extend(obj_keys(&ary.obj));
+                    keys
                 }
-                _ => property_of_simple(&ary.obj),
+                ValueBase::String(ref s) => {
+                    let mut keys = (0..s.len()).map(|i| i.to_string()).collect::<Vec<String>>();
+                    keys.push("length".to_string());
+                    keys
+                }
+                ValueBase::Proxy(p) => (*p).target.own_property_keys(),
+                _ => vec![],
             }
-        };
-        let property_of_arguments = || -> Value {
-            unsafe {
-                match property {
-                    // Index
-                    ValueBase::Number(n) if is_integer(n) && n >= 0.0 => callobjref
-                        .and_then(|co| Some((**co).get_arguments_nth_value(n as usize).unwrap()))
-                        .unwrap_or_else(|| Value::undefined()),
-                    ValueBase::String(ref s) if s.to_str().unwrap() == "length" => {
-                        let length = callobjref
-                            .and_then(|co| Some((**co).get_arguments_length()))
-                            .unwrap_or(0);
-                        Value::number(length as f64)
+        }
+    }
+
+    pub fn define_own_property(
+        &self,
+        key: String,
+        desc: PropertyDescriptor,
+    ) -> Result<(), RuntimeError> {
+        unsafe {
+            match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => {
+                    if let Some(typed_array) = obj_typed_array(&*map) {
+                        let key_val = ValueBase::String(JsString::from_key(key.as_str()));
+                        if let Some(n) = canonical_numeric_index(&key_val) {
+                            return typed_array_define_element(&*typed_array, n, &key, desc);
+                        }
                     }
-                    _ => Value::undefined(),
+                    obj_define_property(&mut *map, key, desc)
+                }
+                ValueBase::Array(ary) => {
+                    let ary = &mut *ary;
+                    let key_val = ValueBase::String(JsString::from_key(key.as_str()));
+                    if key == "length" {
+                        let mut desc = desc;
+                        let len = match desc.value {
+                            Some(ref val) => array_length(val)?,
+                            None => ary.length,
+                        };
+                        desc.value = Some(ValueBase::Number(len as f64));
+                        let mut prop = Value {
+                            val: ValueBase::Number(ary.length as f64),
+                            writable: ary.length_writable,
+                            enumerable: false,
+                            configurable: false,
+                        };
+                        apply_property_descriptor(&mut prop, "length", desc)?;
+                        if len != ary.length {
+                            ary.set_length(len, true)?;
+                        }
+                        ary.length_writable = prop.writable;
+                        return Ok(());
+                    }
+                    match array_index(&key_val) {
+                        Some(n) if n < ary.length && ary.elems[n].val != ValueBase::Empty => {
+                            apply_property_descriptor(&mut ary.elems[n], key.as_str(), desc)
+                        }
+                        Some(n) => {
+                            if !ary.is_extensible() {
+                                return Err(RuntimeError::Type(format!(
+                                    "type error: cannot define property '{}', object is not extensible",
+                                    key
+                                )));
+                            }
+                            if n >= ary.length {
+                                if !ary.length_writable {
+                                    return Err(RuntimeError::Type(format!(
+                                        "type error: cannot define property '{}', 'length' is read-only",
+                                        key
+                                    )));
+                                }
+                                ary.reserve_elems(n + 1)?;
+                                ary.length = n + 1;
+                                while ary.elems.len() < n + 1 {
+                                    ary.elems.push(Value::empty());
+                                }
+                            }
+                            ary.elems[n] = Value::from_property_descriptor(desc);
+                            Ok(())
+                        }
+                        None => obj_define_property(&mut ary.obj, key, desc),
+                    }
+                }
+                ValueBase::Proxy(p) => (*p).target.define_own_property(key, desc),
+                _ => Err(RuntimeError::Type(
+                    "type error: Object.defineProperty called on non-object".to_string(),
+                )),
+            }
+        }
+    }
+
+    pub fn is_extensible(&self) -> bool {
+        unsafe {
+            match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => (*map).extensible,
+                ValueBase::Array(ary) => (*ary).is_extensible(),
+                ValueBase::Proxy(p) => (*p).target.is_extensible(),
+                _ => false,
+            }
+        }
+    }
+
+    pub fn prevent_extensions(&self) {
+        unsafe {
+            match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => (*map).extensible = false,
+                ValueBase::Array(ary) => (*ary).obj.extensible = false,
+                ValueBase::Proxy(p) => (*p).target.prevent_extensions(),
+                _ => {}
+            }
+        }
+    }
+
+    // Makes every own property non-configurable (and read-only if 'freeze' is true) and prevents
+    // extensions. Used by Object.seal() and Object.freeze().
+    pub fn seal_or_freeze(&self, freeze: bool) {
+        fn obj_seal(obj: &mut PropertyMap, freeze: bool) {
+            for (key, prop) in obj.iter_mut() {
+                if is_internal_property(key.as_str()) {
+                    continue;
+                }
+                prop.configurable = false;
+                if freeze {
+                    prop.writable = false;
+                }
+            }
+        }
+
+        unsafe {
+            match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => obj_seal(&mut *map, freeze),
+                ValueBase::Array(ary) => {
+                    let ary = &mut *ary;
+                    for elem in &mut ary.elems {
+                        elem.configurable = false;
+                        if freeze {
+                            elem.writable = false;
+                        }
+                    }
+                    if freeze {
+                        ary.length_writable = false;
+                    }
+                    obj_seal(&mut ary.obj, freeze)
                 }
+                ValueBase::Proxy(p) => return (*p).target.seal_or_freeze(freeze),
+                _ => return,
+            }
+        }
+
+        self.prevent_extensions();
+    }
+
+    // Object.isSealed() and Object.isFrozen().
+    pub fn is_sealed_or_frozen(&self, frozen: bool) -> bool {
+        if self.is_extensible() {
+            return false;
+        }
+        self.own_property_keys().iter().all(|key| {
+            self.get_own_property(key.as_str()).map_or(true, |prop| {
+                !prop.configurable && (!frozen || !prop.writable)
+            })
+        })
+    }
+
+    // Returns the [[Prototype]] of this object, or null if there is none.
+    pub fn get_prototype(&self) -> Value {
+        unsafe {
+            let proto = match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => (*map).get("__proto__").cloned(),
+                ValueBase::Array(ary) => (*ary).obj.get("__proto__").cloned(),
+                ValueBase::Proxy(p) => return (*p).target.get_prototype(),
+                _ => None,
+            };
+            match proto {
+                Some(proto) if proto.is_object() => Value::new(proto.val),
+                _ => Value::null(),
             }
-        };
+        }
+    }
+
+    // Object.setPrototypeOf(). 'proto' must be an object or null.
+    pub fn set_prototype(&self, proto: Value) -> Result<(), RuntimeError> {
+        if let ValueBase::Proxy(p) = self.val {
+            return unsafe { (*p).target.set_prototype(proto) };
+        }
+        if self.get_prototype().val.same_value(&proto.val) {
+            return Ok(());
+        }
+        if !self.is_extensible() {
+            return Err(RuntimeError::Type(
+                "type error: cannot set prototype, object is not extensible".to_string(),
+            ));
+        }
+
+        let mut p = proto.clone();
+        while p.is_object() {
+            if p.val.same_value(&self.val) {
+                return Err(RuntimeError::Type(
+                    "type error: cyclic __proto__ value".to_string(),
+                ));
+            }
+            p = p.get_prototype();
+        }
 
         unsafe {
-            match self.val {
-                ValueBase::String(ref s) => property_of_string(s),
-                ValueBase::BuiltinFunction(box (_, ref obj, _))
-                | ValueBase::Function(box (_, _, ref obj, _))
-                | ValueBase::Object(ref obj) => property_of_object(&**obj),
-                ValueBase::Array(ref ary) => property_of_array(&**ary),
-                ValueBase::Arguments => property_of_arguments(),
-                // TODO: Implement
-                _ => Value::undefined(),
+            let map = match self.val {
+                ValueBase::Object(map)
+                | ValueBase::Function(box (_, _, map, _))
+                | ValueBase::BuiltinFunction(box (_, map, _)) => &mut *map,
+                ValueBase::Array(ary) => &mut (*ary).obj,
+                _ => return Ok(()),
+            };
+            if proto.is_object() {
+                map.insert("__proto__".to_string(), Value::new(proto.val));
+            } else {
+                map.remove("__proto__");
             }
         }
+
+        Ok(())
     }
 
-    pub fn set_number_if_possible(&mut self, n: f64) {
-        if let ValueBase::Number(ref mut n_) = self.val {
-            *n_ = n;
+    pub fn is_object(&self) -> bool {
+        match self.val {
+            ValueBase::Object(_)
+            | ValueBase::Array(_)
+            | ValueBase::Function(_)
+            | ValueBase::BuiltinFunction(_)
+            | ValueBase::Proxy(_) => true,
+            _ => false,
         }
     }
 }
 
 impl ValueBase {
+    // https://www.ecma-international.org/ecma-262/7.0/#sec-samevalue
+    pub fn same_value(&self, other: &ValueBase) -> bool {
+        match (self, other) {
+            (ValueBase::Number(l), ValueBase::Number(r)) => {
+                if l.is_nan() && r.is_nan() {
+                    return true;
+                }
+                l == r && l.is_sign_negative() == r.is_sign_negative()
+            }
+            (ValueBase::Function(box (_, _, l, _)), ValueBase::Function(box (_, _, r, _)))
+            | (
+                ValueBase::BuiltinFunction(box (_, l, _)),
+                ValueBase::BuiltinFunction(box (_, r, _)),
+            ) => l == r,
+            (ValueBase::Object(l), ValueBase::Object(r)) => l == r,
+            (ValueBase::Array(l), ValueBase::Array(r)) => l == r,
+            (ValueBase::Proxy(l), ValueBase::Proxy(r)) => l == r,
+            (ValueBase::Function(_), _)
+            | (ValueBase::BuiltinFunction(_), _)
+            | (ValueBase::Object(_), _)
+            | (ValueBase::Array(_), _)
+            | (ValueBase::Proxy(_), _) => false,
+            (l, r) => l == r,
+        }
+    }
+
+    // https://www.ecma-international.org/ecma-262/7.0/#sec-samevaluezero
+    pub fn same_value_zero(&self, other: &ValueBase) -> bool {
+        match (self, other) {
+            (ValueBase::Number(l), ValueBase::Number(r)) => l == r || (l.is_nan() && r.is_nan()),
+            (l, r) => l.same_value(r),
+        }
+    }
+
+    // https://www.ecma-international.org/ecma-262/7.0/#sec-strict-equality-comparison
+    pub fn strict_equals(&self, other: &ValueBase) -> bool {
+        match (self, other) {
+            (ValueBase::Number(l), ValueBase::Number(r)) => l == r,
+            (l, r) => l.same_value(r),
+        }
+    }
+
     pub fn to_string(&self) -> String {
         match self {
             ValueBase::Undefined => "undefined".to_string(),
+            ValueBase::Null => "null".to_string(),
             ValueBase::Bool(b) => {
                 if *b {
                     "true".to_string()
@@ -541,42 +2633,53 @@ impl ValueBase {
                     "false".to_string()
                 }
             }
-            ValueBase::Number(n) => {
-                if n.is_nan() {
-                    return "NaN".to_string();
-                }
-
-                if *n == 0.0 {
-                    return "0".to_string();
-                }
-
-                if n.is_infinite() {
-                    return "Infinity".to_string();
-                }
-
-                // TODO: Need a correct implementation!
-                //  ref. https://tc39.github.io/ecma262/#sec-tostring-applied-to-the-number-type
-                format!("{}", *n)
-            }
-            ValueBase::String(s) => s.clone().into_string().unwrap(),
+            ValueBase::Number(n) => number_to_string(*n),
+            ValueBase::BigInt(n) => n.to_string(),
+            ValueBase::String(s) => s.to_string(),
             ValueBase::Array(ary_val) => unsafe { (**ary_val).to_string() },
             ValueBase::Object(_) => "[object Object]".to_string(),
+            ValueBase::Proxy(p) => unsafe { (**p).target.to_string() },
             e => unimplemented!("{:?}", e),
         }
     }
 
-    // TODO: Need a correct implementation!
+    // Converts the value into a property name. Unlike to_string(), keeps unpaired surrogates.
+    pub fn to_property_key(&self) -> String {
+        match self {
+            ValueBase::String(s) => s.to_key(),
+            other => other.to_string(),
+        }
+    }
+
+    // Like to_string(), but keeps the code units of a string as they are.
+    pub fn to_js_string(&self) -> JsString {
+        match self {
+            ValueBase::String(s) => s.clone(),
+            other => JsString::from(other.to_string()),
+        }
+    }
+
     pub fn to_number(&self) -> f64 {
+        // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-tonumber-applied-to-the-string-type
         fn str_to_num(s: &str) -> f64 {
-            let all_ws = s.chars().all(|c| c.is_whitespace());
+            let s = s.trim_matches(is_js_whitespace);
 
-            if all_ws {
+            if s.len() == 0 {
                 return 0.0;
             }
 
-            match s.parse::<f64>() {
-                Ok(n) => n,
-                _ => ::std::f64::NAN,
+            let radix = match &s[..s.len().min(2)] {
+                "0x" | "0X" => 16,
+                "0o" | "0O" => 8,
+                "0b" | "0B" => 2,
+                _ => match parse_decimal_prefix(s) {
+                    Some((n, len)) if len == s.len() => return n,
+                    _ => return ::std::f64::NAN,
+                },
+            };
+            match BigInt::parse(&s[2..], radix) {
+                Some(n) => n.to_f64(),
+                None => ::std::f64::NAN,
             }
         }
 
@@ -595,16 +2698,30 @@ impl ValueBase {
         unsafe {
             match self {
                 ValueBase::Undefined => ::std::f64::NAN,
+                ValueBase::Null => 0.0,
                 ValueBase::Bool(false) => 0.0,
                 ValueBase::Bool(true) => 1.0,
                 ValueBase::Number(n) => *n,
-                ValueBase::String(s) => str_to_num(s.to_str().unwrap()),
+                ValueBase::BigInt(_) => ::std::f64::NAN,
+                ValueBase::String(s) => str_to_num(s.to_string().as_str()),
                 ValueBase::Array(ary) => ary_to_num(&**ary),
                 _ => ::std::f64::NAN,
             }
         }
     }
 
+    // https://tc39.github.io/ecma262/#sec-tonumber
+    // Unlike to_number(), which gives NaN for them, throws a TypeError for BigInts as the
+    // operations taking numbers do. Those converting BigInts too, like Number(), check them first.
+    pub fn try_to_number(&self) -> Result<f64, RuntimeError> {
+        match self {
+            ValueBase::BigInt(_) => Err(RuntimeError::Type(
+                "type error: cannot convert a BigInt value to a number".to_string(),
+            )),
+            _ => Ok(self.to_number()),
+        }
+    }
+
     pub fn to_uint32(&self) -> f64 {
         let num = self.to_number();
         let p2_32 = 4294967296i64;
@@ -617,7 +2734,8 @@ impl ValueBase {
             -num.abs().floor()
         } else {
             num.abs().floor()
-        } as i64 % p2_32) as f64;
+        } as i64
+            % p2_32) as f64;
 
         if int32bit < 0.0 {
             p2_32 as f64 + int32bit
@@ -633,47 +2751,61 @@ impl ValueBase {
             ValueBase::Bool(b) => *b,
             ValueBase::Number(n) if *n == 0.0 || n.is_nan() => false,
             ValueBase::Number(_) => true,
-            ValueBase::String(s) if s.to_str().unwrap().len() == 0 => false,
+            ValueBase::BigInt(n) => !n.is_zero(),
+            ValueBase::String(s) if s.is_empty() => false,
             ValueBase::String(_) => true,
             ValueBase::Array(_) => true,
             ValueBase::Object(_) => true,
+            ValueBase::Proxy(_) => true,
             _ => false,
         }
     }
 }
 
 // TODO: Implement correctly
-pub fn new_value_function(id: FuncId, iseq: ByteCode, callobj: CallObject) -> Value {
+pub fn new_value_function(
+    id: FuncId,
+    iseq: ByteCode,
+    callobj: CallObject,
+    name: String,
+    length: usize,
+) -> Value {
     let mut val = Value::new(ValueBase::Function(Box::new((
         id,
-        iseq,
+        Rc::new(iseq),
         gc::new({
-            let mut hm = FxHashMap::default();
+            let mut hm = PropertyMap::default();
             hm.insert(
                 "prototype".to_string(),
-                Value::new(ValueBase::Object(gc::new(FxHashMap::default()))),
+                Value {
+                    enumerable: false,
+                    ..Value::new(ValueBase::Object(gc::new({
+                        let mut hm = PropertyMap::default();
+                        hm.insert(
+                            "__proto__".to_string(),
+                            Value::object(OBJECT_PROTOTYPE.with(|x| x.clone())),
+                        );
+                        hm
+                    })))
+                },
             );
             hm.insert(
                 "__proto__".to_string(),
-                Value::new(ValueBase::Object(gc::new({
-                    let mut hm = FxHashMap::default();
-                    hm.insert(
-                        "apply".to_string(),
-                        Value::builtin_function(
-                            builtin::FUNCTION_PROTOTYPE_APPLY,
-                            CallObject::new(Value::undefined()),
-                        ),
-                    );
-                    hm.insert(
-                        "call".to_string(),
-                        Value::builtin_function(
-                            builtin::FUNCTION_PROTOTYPE_CALL,
-                            CallObject::new(Value::undefined()),
-                        ),
-                    );
-                    hm
-                }))),
+                Value::object(FUNCTION_PROTOTYPE.with(|x| x.clone())),
             );
+            for (key, val) in vec![
+                ("name", Value::string(JsString::from(name.as_str()))),
+                ("length", Value::number(length as f64)),
+            ] {
+                hm.insert(
+                    key.to_string(),
+                    Value {
+                        writable: false,
+                        enumerable: false,
+                        ..val
+                    },
+                );
+            }
             hm
         }),
         callobj,
@@ -681,7 +2813,6 @@ pub fn new_value_function(id: FuncId, iseq: ByteCode, callobj: CallObject) -> Va
 
     let v2 = val.clone();
     if let ValueBase::Function(box (_, _, ref mut obj, _)) = &mut val.val {
-        // TODO: Add constructor of this function itself (==Function). (not prototype.constructor)
         unsafe {
             if let ValueBase::Object(ref mut obj) = (**obj).get_mut("prototype").unwrap().val {
                 (**obj).insert("constructor".to_string(), v2);
@@ -691,318 +2822,499 @@ pub fn new_value_function(id: FuncId, iseq: ByteCode, callobj: CallObject) -> Va
     val
 }
 
-pub fn obj_find_val(obj: &FxHashMap<String, Value>, key: &str) -> Value {
+pub fn obj_find_val(obj: &PropertyMap, key: &str) -> Value {
+    match obj_lookup(obj, key) {
+        Some(val) => val,
+        // Like the getter of Object.prototype.__proto__, unless a data property shadows it.
+        None if key == PROTO_KEY => obj.get("__proto__").cloned().unwrap_or(Value::undefined()),
+        None => Value::undefined(),
+    }
+}
+
+// Finds the property named 'key' in the object or its prototype chain.
+fn obj_lookup(obj: &PropertyMap, key: &str) -> Option<Value> {
     match obj.get(key) {
-        Some(addr) => addr.clone(),
+        Some(addr) => Some(addr.clone()),
         None => match obj.get("__proto__") {
             Some(val) => match val.val {
                 ValueBase::Function(box (_, _, obj, _))
                 | ValueBase::BuiltinFunction(box (_, obj, _))
-                | ValueBase::Object(obj) => unsafe { obj_find_val(&*obj, key) },
-                ValueBase::Array(aryval) => unsafe { obj_find_val(&(*aryval).obj, key) },
-                _ => Value::undefined(),
+                | ValueBase::Object(obj) => unsafe { obj_lookup(&*obj, key) },
+                ValueBase::Array(aryval) => unsafe { obj_lookup(&(*aryval).obj, key) },
+                _ => None,
             },
-            _ => Value::undefined(),
+            _ => None,
         },
     }
 }
 
-#[inline]
-fn is_integer(f: f64) -> bool {
-    f - f.floor() == 0.0
+// WhiteSpace and LineTerminator in the spec.
+pub fn is_js_whitespace(c: char) -> bool {
+    c.is_whitespace() || c == '\u{feff}'
 }
 
-pub fn runtime_error(msg: &str) {
-    eprintln!("{}: {}", Colour::Red.bold().paint("runtime error"), msg,);
-}
+// Parses the longest prefix of 's' that is a StrDecimalLiteral, like "-1.5e3" or "Infinity".
+// Returns the number and the length of the prefix.
+pub fn parse_decimal_prefix(s: &str) -> Option<(f64, usize)> {
+    let bytes = s.as_bytes();
+    let mut pos = 0;
+    let negative = match bytes.first() {
+        Some(b'-') => {
+            pos += 1;
+            true
+        }
+        Some(b'+') => {
+            pos += 1;
+            false
+        }
+        _ => false,
+    };
+    let sign = if negative { -1.0 } else { 1.0 };
 
-impl VM {
-    pub fn new(global_vals: CallObjectRef) -> VM {
-        // TODO: Support for 'require' is not enough.
-        unsafe {
-            (*global_vals).set_value(
-                "require".to_string(),
-                Value::builtin_function(builtin::REQUIRE, CallObject::new(Value::undefined())),
-            );
+    if s[pos..].starts_with("Infinity") {
+        return Some((sign * ::std::f64::INFINITY, pos + "Infinity".len()));
+    }
 
-            let module_exports = Value::object(gc::new(FxHashMap::default()));
-            (*global_vals).set_value("module".to_string(), {
-                let mut map = FxHashMap::default();
-                map.insert("exports".to_string(), module_exports.clone());
-                Value::object(gc::new(map))
-            });
-            (*global_vals).set_value("exports".to_string(), module_exports);
-        }
+    let count_digits = |from: usize| {
+        bytes[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
 
-        unsafe {
-            (*global_vals).set_value("console".to_string(), {
-                let mut map = FxHashMap::default();
-                map.insert(
-                    "log".to_string(),
-                    Value::builtin_function(
-                        builtin::CONSOLE_LOG,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                Value::object(gc::new(map))
-            });
+    let int_part = &s[pos..pos + count_digits(pos)];
+    pos += int_part.len();
+    let mut frac_part = "";
+    if bytes.get(pos) == Some(&b'.') {
+        frac_part = &s[pos + 1..pos + 1 + count_digits(pos + 1)];
+        if int_part.len() > 0 || frac_part.len() > 0 {
+            pos += 1 + frac_part.len();
         }
+    }
+    if int_part.len() == 0 && frac_part.len() == 0 {
+        return None;
+    }
 
-        unsafe {
-            (*global_vals).set_value("process".to_string(), {
-                let mut map = FxHashMap::default();
-                map.insert("stdout".to_string(), {
-                    let mut map = FxHashMap::default();
-                    map.insert(
-                        "write".to_string(),
-                        Value::builtin_function(
-                            builtin::PROCESS_STDOUT_WRITE,
-                            CallObject::new(Value::undefined()),
-                        ),
-                    );
-                    Value::object(gc::new(map))
-                });
-                Value::object(gc::new(map))
-            });
+    let mut exp_part = "0";
+    if bytes.get(pos) == Some(&b'e') || bytes.get(pos) == Some(&b'E') {
+        let sign_len = match bytes.get(pos + 1) {
+            Some(b'+') | Some(b'-') => 1,
+            _ => 0,
+        };
+        let exp_len = count_digits(pos + 1 + sign_len);
+        if exp_len > 0 {
+            exp_part = &s[pos + 1..pos + 1 + sign_len + exp_len];
+            pos += 1 + sign_len + exp_len;
         }
+    }
 
-        unsafe {
-            (*global_vals).set_value("Array".to_string(), ARRAY_OBJ.with(|x| x.clone()));
-        }
+    // Rust's parser rounds correctly, but does not accept some forms JavaScript does, like "1.e3".
+    let normalized = format!(
+        "{}.{}e{}",
+        if int_part.len() == 0 { "0" } else { int_part },
+        if frac_part.len() == 0 { "0" } else { frac_part },
+        exp_part
+    );
+    normalized.parse::<f64>().ok().map(|n| (sign * n, pos))
+}
 
-        unsafe {
-            (*global_vals).set_value("Math".to_string(), {
-                let mut map = FxHashMap::default();
-                map.insert("PI".to_string(), Value::number(::std::f64::consts::PI));
-                map.insert(
-                    "floor".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_FLOOR,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "random".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_RANDOM,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "pow".to_string(),
-                    Value::builtin_function(builtin::MATH_POW, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "abs".to_string(),
-                    Value::builtin_function(builtin::MATH_ABS, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "acos".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_ACOS,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "acosh".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_ACOSH,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "asin".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_ASIN,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "asinh".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_ASINH,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "atan".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_ATAN,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "atanh".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_ATANH,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "atan2".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_ATAN2,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "cbrt".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_CBRT,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "ceil".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_CEIL,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "clz32".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_CLZ32,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "cos".to_string(),
-                    Value::builtin_function(builtin::MATH_COS, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "cosh".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_COSH,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "exp".to_string(),
-                    Value::builtin_function(builtin::MATH_EXP, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "expm1".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_EXPM1,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "fround".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_FROUND,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "hypot".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_HYPOT,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
+// Returns the shortest decimal digits that round-trip to 'n' (> 0), and the exponent 'e' such
+// that 'n' is d.ddd * 10^e.
+pub fn shortest_digits(n: f64) -> (String, i32) {
+    let repr = format!("{:e}", n);
+    let (mantissa, exp) = repr.split_at(repr.find('e').unwrap());
+    (mantissa.replace(".", ""), exp[1..].parse().unwrap())
+}
+
+// https://www.ecma-international.org/ecma-262/9.0/index.html#sec-tostring-applied-to-the-number-type
+pub fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_string();
+    }
+    if n == 0.0 {
+        return "0".to_string();
+    }
+    if n < 0.0 {
+        return format!("-{}", number_to_string(-n));
+    }
+    if n.is_infinite() {
+        return "Infinity".to_string();
+    }
+
+    let (digits, e) = shortest_digits(n);
+    let k = digits.len() as i32;
+    let n = e + 1;
+    if k <= n && n <= 21 {
+        digits + "0".repeat((n - k) as usize).as_str()
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        format!(
+            "{}{}e{}{}",
+            &digits[..1],
+            if k > 1 {
+                format!(".{}", &digits[1..])
+            } else {
+                "".to_string()
+            },
+            if e < 0 { "-" } else { "+" },
+            e.abs()
+        )
+    }
+}
+
+#[inline]
+// https://tc39.github.io/ecma262/#sec-applying-the-exp-operator
+pub fn js_pow(base: f64, exponent: f64) -> f64 {
+    if exponent.is_nan() || (base.abs() == 1.0 && exponent.is_infinite()) {
+        return ::std::f64::NAN;
+    }
+    base.powf(exponent)
+}
+
+// https://tc39.github.io/proposal-bigint/#sec-numeric-types
+fn cannot_mix_bigint() -> RuntimeError {
+    RuntimeError::Type(
+        "type error: cannot mix BigInt and other types, use explicit conversions".to_string(),
+    )
+}
+
+fn division_by_zero() -> RuntimeError {
+    RuntimeError::Range("range error: division by zero".to_string())
+}
+
+fn bigint_range_error(msg: &'static str) -> RuntimeError {
+    RuntimeError::Range(format!("range error: {}", msg))
+}
+
+// Values that the addition operator converts to strings.
+fn is_string_like(val: &ValueBase) -> bool {
+    match val {
+        ValueBase::String(_) | ValueBase::Object(_) | ValueBase::Array(_) => true,
+        _ => false,
+    }
+}
+
+// https://tc39.github.io/proposal-bigint/#sec-abstract-relational-comparison
+// Compares values at least one of which is a BigInt. Returns None if they are not comparable, e.g.
+// a string that is not an integer, or NaN.
+fn compare_bigint(lhs: &ValueBase, rhs: &ValueBase) -> Option<Ordering> {
+    fn to_bigint(val: &ValueBase) -> Option<BigInt> {
+        match val {
+            ValueBase::String(s) => {
+                BigInt::from_string(s.to_string().trim_matches(is_js_whitespace))
+            }
+            _ => None,
+        }
+    }
+
+    match (lhs, rhs) {
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => Some(l.cmp(r)),
+        (ValueBase::BigInt(l), ValueBase::String(_)) => to_bigint(rhs).map(|r| l.cmp(&r)),
+        (ValueBase::String(_), ValueBase::BigInt(r)) => to_bigint(lhs).map(|l| l.cmp(r)),
+        (ValueBase::BigInt(l), r) => l.cmp_f64(r.to_number()),
+        (l, ValueBase::BigInt(r)) => r.cmp_f64(l.to_number()).map(|o| o.reverse()),
+        _ => None,
+    }
+}
+
+fn is_integer(f: f64) -> bool {
+    f - f.floor() == 0.0
+}
+
+pub fn runtime_error(msg: &str) {
+    eprintln!("{}: {}", Colour::Red.bold().paint("runtime error"), msg,);
+}
+
+// Returns the message for an exception that no catch block has caught: the first line of the
+// stack trace for Error objects, or the thrown value itself.
+pub fn uncaught_exception_message(val: &Value) -> String {
+    if let ValueBase::Object(_) = val.val {
+        if let ValueBase::String(stack) = val
+            .get_property(ValueBase::String(JsString::from("stack")), None)
+            .val
+        {
+            let stack = stack.to_string();
+            return format!("uncaught {}", stack.lines().next().unwrap_or(""));
+        }
+    }
+    format!("uncaught {}", val.to_string())
+}
+
+impl VM {
+    /// Makes a VM in the current realm, which is the one of the code generator that made
+    /// 'global_vals' (see VMCodeGen::new()) unless another one has been entered since.
+    pub fn new(global_vals: CallObjectRef) -> VM {
+        VM::with_realm(global_vals, realm::current())
+    }
+
+    /// Makes a VM in 'realm', which it shares with the other VMs in it, e.g. for modules that
+    /// must see the same builtin objects. 'global_vals' must have been allocated in 'realm'.
+    pub fn with_realm(global_vals: CallObjectRef, realm: Rc<Realm>) -> VM {
+        assert!(
+            realm.heap.contains(global_vals),
+            "the global object belongs to another realm"
+        );
+        realm::enter(&realm);
+        // TODO: Support for 'require' is not enough.
+        unsafe {
+            (*global_vals).set_value(
+                "require".to_string(),
+                Value::builtin_function(builtin::REQUIRE, CallObject::new(Value::undefined())),
+            );
+
+            let module_exports = Value::object(gc::new(PropertyMap::default()));
+            (*global_vals).set_value("module".to_string(), {
+                let mut map = PropertyMap::default();
+                map.insert("exports".to_string(), module_exports.clone());
+                Value::object(gc::new(map))
+            });
+            (*global_vals).set_value("exports".to_string(), module_exports);
+        }
+
+        unsafe {
+            (*global_vals).set_value("console".to_string(), {
+                let mut map = PropertyMap::default();
                 map.insert(
                     "log".to_string(),
-                    Value::builtin_function(builtin::MATH_LOG, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "log1p".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_LOG1P,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "log10".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_LOG10,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "log2".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_LOG2,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "max".to_string(),
-                    Value::builtin_function(builtin::MATH_MAX, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "min".to_string(),
-                    Value::builtin_function(builtin::MATH_MIN, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "round".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_ROUND,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "sign".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_SIGN,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "sin".to_string(),
-                    Value::builtin_function(builtin::MATH_SIN, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "sinh".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_SINH,
-                        CallObject::new(Value::undefined()),
-                    ),
-                );
-                map.insert(
-                    "sqrt".to_string(),
                     Value::builtin_function(
-                        builtin::MATH_SQRT,
+                        builtin::CONSOLE_LOG,
                         CallObject::new(Value::undefined()),
                     ),
                 );
-                map.insert(
-                    "tan".to_string(),
-                    Value::builtin_function(builtin::MATH_TAN, CallObject::new(Value::undefined())),
-                );
-                map.insert(
-                    "tanh".to_string(),
+                Value::object(gc::new(map))
+            });
+        }
+
+        unsafe {
+            (*global_vals).set_value("process".to_string(), {
+                let mut map = PropertyMap::default();
+                map.insert("stdout".to_string(), {
+                    let mut map = PropertyMap::default();
+                    map.insert(
+                        "write".to_string(),
+                        Value::builtin_function(
+                            builtin::PROCESS_STDOUT_WRITE,
+                            CallObject::new(Value::undefined()),
+                        ),
+                    );
+                    Value::object(gc::new(map))
+                });
+                Value::object(gc::new(map))
+            });
+        }
+
+        unsafe {
+            (*global_vals).set_value("Array".to_string(), ARRAY_OBJ.with(|x| x.clone()));
+            (*global_vals).set_value("String".to_string(), STRING_OBJ.with(|x| x.clone()));
+            (*global_vals).set_value("Number".to_string(), NUMBER_OBJ.with(|x| x.clone()));
+            (*global_vals).set_value("BigInt".to_string(), BIGINT_OBJ.with(|x| x.clone()));
+            let errors = ERROR_OBJS.with(|x| x.clone());
+            for (name, error) in ERROR_NAMES.iter().zip(errors) {
+                (*global_vals).set_value(name.to_string(), error);
+            }
+            (*global_vals).set_value("Function".to_string(), FUNCTION_OBJ.with(|x| x.clone()));
+            (*global_vals).set_value("Date".to_string(), DATE_OBJ.with(|x| x.clone()));
+            let collections = COLLECTION_OBJS.with(|x| x.clone());
+            for (kind, collection) in COLLECTION_KINDS.iter().zip(collections) {
+                (*global_vals).set_value(kind.name().to_string(), collection);
+            }
+            (*global_vals).set_value(
+                "ArrayBuffer".to_string(),
+                ARRAY_BUFFER_OBJ.with(|x| x.clone()),
+            );
+            let typed_arrays = TYPED_ARRAY_OBJS.with(|x| x.clone());
+            for (kind, typed_array) in TYPED_ARRAY_KINDS.iter().zip(typed_arrays) {
+                (*global_vals).set_value(kind.name().to_string(), typed_array);
+            }
+            (*global_vals).set_value("DataView".to_string(), DATA_VIEW_OBJ.with(|x| x.clone()));
+        }
+
+        unsafe {
+            // https://tc39.github.io/ecma262/#sec-math-object
+            (*global_vals).set_value("Math".to_string(), {
+                let mut map = PropertyMap::default();
+                for (name, id) in &[
+                    ("abs", builtin::MATH_ABS),
+                    ("acos", builtin::MATH_ACOS),
+                    ("acosh", builtin::MATH_ACOSH),
+                    ("asin", builtin::MATH_ASIN),
+                    ("asinh", builtin::MATH_ASINH),
+                    ("atan", builtin::MATH_ATAN),
+                    ("atan2", builtin::MATH_ATAN2),
+                    ("atanh", builtin::MATH_ATANH),
+                    ("cbrt", builtin::MATH_CBRT),
+                    ("ceil", builtin::MATH_CEIL),
+                    ("clz32", builtin::MATH_CLZ32),
+                    ("cos", builtin::MATH_COS),
+                    ("cosh", builtin::MATH_COSH),
+                    ("exp", builtin::MATH_EXP),
+                    ("expm1", builtin::MATH_EXPM1),
+                    ("floor", builtin::MATH_FLOOR),
+                    ("fround", builtin::MATH_FROUND),
+                    ("hypot", builtin::MATH_HYPOT),
+                    ("imul", builtin::MATH_IMUL),
+                    ("log", builtin::MATH_LOG),
+                    ("log10", builtin::MATH_LOG10),
+                    ("log1p", builtin::MATH_LOG1P),
+                    ("log2", builtin::MATH_LOG2),
+                    ("max", builtin::MATH_MAX),
+                    ("min", builtin::MATH_MIN),
+                    ("pow", builtin::MATH_POW),
+                    ("random", builtin::MATH_RANDOM),
+                    ("round", builtin::MATH_ROUND),
+                    ("sign", builtin::MATH_SIGN),
+                    ("sin", builtin::MATH_SIN),
+                    ("sinh", builtin::MATH_SINH),
+                    ("sqrt", builtin::MATH_SQRT),
+                    ("tan", builtin::MATH_TAN),
+                    ("tanh", builtin::MATH_TANH),
+                    ("trunc", builtin::MATH_TRUNC),
+                ] {
+                    map.insert(
+                        name.to_string(),
+                        Value {
+                            enumerable: false,
+                            ..Value::builtin_function(*id, CallObject::new(Value::undefined()))
+                        },
+                    );
+                }
+                for (name, n) in &[
+                    ("E", ::std::f64::consts::E),
+                    ("LN10", ::std::f64::consts::LN_10),
+                    ("LN2", ::std::f64::consts::LN_2),
+                    ("LOG10E", ::std::f64::consts::LOG10_E),
+                    ("LOG2E", ::std::f64::consts::LOG2_E),
+                    ("PI", ::std::f64::consts::PI),
+                    ("SQRT1_2", ::std::f64::consts::FRAC_1_SQRT_2),
+                    ("SQRT2", ::std::f64::consts::SQRT_2),
+                ] {
+                    map.insert(
+                        name.to_string(),
+                        Value {
+                            val: ValueBase::Number(*n),
+                            writable: false,
+                            enumerable: false,
+                            configurable: false,
+                        },
+                    );
+                }
+                Value::plain_object(map)
+            });
+        }
+
+        unsafe {
+            (*global_vals).set_value("Object".to_string(), OBJECT_OBJ.with(|x| x.clone()));
+        }
+
+        unsafe {
+            (*global_vals).set_value("JSON".to_string(), {
+                let mut map = PropertyMap::default();
+                for (name, id) in &[
+                    ("parse", builtin::JSON_PARSE),
+                    ("stringify", builtin::JSON_STRINGIFY),
+                ] {
+                    map.insert(
+                        name.to_string(),
+                        Value::builtin_function(*id, CallObject::new(Value::undefined())),
+                    );
+                }
+                Value::plain_object(map)
+            });
+        }
+
+        // https://tc39.github.io/ecma262/#sec-proxy-constructor
+        // https://tc39.github.io/ecma262/#sec-reflect-object
+        unsafe {
+            (*global_vals).set_value("Proxy".to_string(), {
+                let mut obj = PropertyMap::default();
+                obj.insert(
+                    "revocable".to_string(),
                     Value::builtin_function(
-                        builtin::MATH_TANH,
+                        builtin::PROXY_REVOCABLE,
                         CallObject::new(Value::undefined()),
                     ),
                 );
-                map.insert(
-                    "trunc".to_string(),
-                    Value::builtin_function(
-                        builtin::MATH_TRUNC,
-                        CallObject::new(Value::undefined()),
+                Value::builtin_function_with_obj_and_prototype(
+                    builtin::PROXY_NEW,
+                    CallObject::new(Value::undefined()),
+                    obj,
+                    Value::undefined(),
+                )
+            });
+            (*global_vals).set_value("Reflect".to_string(), {
+                let mut map = PropertyMap::default();
+                for (name, id) in &[
+                    ("apply", builtin::REFLECT_APPLY),
+                    ("construct", builtin::REFLECT_CONSTRUCT),
+                    ("defineProperty", builtin::REFLECT_DEFINE_PROPERTY),
+                    ("deleteProperty", builtin::REFLECT_DELETE_PROPERTY),
+                    ("get", builtin::REFLECT_GET),
+                    (
+                        "getOwnPropertyDescriptor",
+                        builtin::REFLECT_GET_OWN_PROPERTY_DESCRIPTOR,
                     ),
-                );
-                Value::object(gc::new(map))
+                    ("getPrototypeOf", builtin::REFLECT_GET_PROTOTYPE_OF),
+                    ("has", builtin::REFLECT_HAS),
+                    ("isExtensible", builtin::REFLECT_IS_EXTENSIBLE),
+                    ("ownKeys", builtin::REFLECT_OWN_KEYS),
+                    ("preventExtensions", builtin::REFLECT_PREVENT_EXTENSIONS),
+                    ("set", builtin::REFLECT_SET),
+                    ("setPrototypeOf", builtin::REFLECT_SET_PROTOTYPE_OF),
+                ] {
+                    map.insert(
+                        name.to_string(),
+                        Value::builtin_function(*id, CallObject::new(Value::undefined())),
+                    );
+                }
+                Value::plain_object(map)
             });
         }
 
+        // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-value-properties-of-the-global-object
+        unsafe {
+            (*global_vals).set_value("NaN".to_string(), Value::number(::std::f64::NAN));
+            (*global_vals).set_value("Infinity".to_string(), Value::number(::std::f64::INFINITY));
+            for (name, id) in &[
+                ("isNaN", builtin::IS_NAN),
+                ("isFinite", builtin::IS_FINITE),
+                ("eval", builtin::EVAL),
+            ] {
+                (*global_vals).set_value(
+                    name.to_string(),
+                    Value::builtin_function(*id, CallObject::new(Value::undefined())),
+                );
+            }
+            // The very same functions as Number.parseInt and Number.parseFloat
+            let number = NUMBER_OBJ.with(|x| x.clone());
+            for name in &["parseInt", "parseFloat"] {
+                (*global_vals).set_value(name.to_string(), number.get_own_property(name).unwrap());
+            }
+        }
+
         VM {
             jit: unsafe { TracingJit::new() },
             state: VMState {
                 stack: { Vec::with_capacity(128) },
                 scope: vec![global_vals],
-                history: {
+                frames: {
                     let mut s = Vec::with_capacity(128);
-                    s.push((0, 0));
+                    s.push(Frame {
+                        iseq: Rc::new(vec![]),
+                        func_id: 0, // 0 is main
+                        return_pc: 0,
+                        sp: 0,
+                        scope_len: 0,
+                        kind: FrameKind::TopLevel,
+                    });
                     s
                 },
+                try_stack: vec![],
                 pc: 0isize,
             },
             const_table: ConstantTable::new(),
-            cur_func_id: 0, // 0 is main
             op_table: [
                 end,
                 create_context,
@@ -1055,58 +3367,31 @@ impl VM {
                 decl_var,
                 cond_op,
                 loop_start,
+                delete,
+                enter_try,
+                leave_try,
+                throw,
+                call_eval,
+                exp,
+                bitwise_not,
+                get_local,
+                set_local,
+                get_upvar,
+                enter_catch,
+                leave_scope,
             ],
-            builtin_functions: vec![
-                builtin::console_log,
-                builtin::process_stdout_write,
-                builtin::array_new,
-                builtin::array_push,
-                builtin::array_pop,
-                builtin::array_map,
-                builtin::math_floor,
-                builtin::math_random,
-                builtin::math_pow,
-                builtin::math_abs,
-                builtin::math_acos,
-                builtin::math_acosh,
-                builtin::math_asin,
-                builtin::math_asinh,
-                builtin::math_atan,
-                builtin::math_atanh,
-                builtin::math_atan2,
-                builtin::math_cbrt,
-                builtin::math_ceil,
-                builtin::math_clz32,
-                builtin::math_cos,
-                builtin::math_cosh,
-                builtin::math_exp,
-                builtin::math_expm1,
-                builtin::math_fround,
-                builtin::math_hypot,
-                builtin::math_log,
-                builtin::math_log1p,
-                builtin::math_log10,
-                builtin::math_log2,
-                builtin::math_max,
-                builtin::math_min,
-                builtin::math_round,
-                builtin::math_sign,
-                builtin::math_sin,
-                builtin::math_sinh,
-                builtin::math_sqrt,
-                builtin::math_tan,
-                builtin::math_tanh,
-                builtin::math_trunc,
-                builtin::function_prototype_apply,
-                builtin::function_prototype_call,
-                builtin::require,
-            ],
+            builtin_functions: builtin::builtin_functions(),
+            host_functions: vec![],
+            realm: realm,
+            limits: Rc::new(Limits::new()),
+            clock: Rc::new(date::system_clock),
         }
     }
 }
 
 impl VM {
     pub fn run(&mut self, iseq: ByteCode) -> Result<(), RuntimeError> {
+        realm::enter(&self.realm);
         // self.iseq = iseq;
         // Unlock the mutex and start the profiler
         // PROFILER
@@ -1115,25 +3400,263 @@ impl VM {
         //     .start("./my-prof.profile")
         //     .expect("Couldn't start");
 
-        self.do_run(&iseq)
+        // The main code runs in the bottom frame, which is never popped.
+        self.state.frames[0].iseq = Rc::new(iseq);
+        self.run_frames()
 
         // Unwrap the mutex and stop the profiler
         // PROFILER.lock().unwrap().stop().expect("Couldn't stop");
     }
 
-    pub fn do_run(&mut self, iseq: &ByteCode) -> Result<(), RuntimeError> {
-        // let id = self.cur_func_id;
+    // Makes Math.random() return the same sequence for the same seed, e.g. for reproducible tests.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.realm.seed_random(seed);
+    }
+
+    /// Stops running code after 'fuel' more instructions, or never if None. A loop compiled by
+    /// the JIT compiler uses one per iteration.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.limits.set_fuel(fuel)
+    }
+
+    /// The number of instructions left to run, or None if unlimited.
+    pub fn fuel(&self) -> Option<u64> {
+        self.limits.fuel()
+    }
+
+    /// Stops running code at 'deadline', or never if None.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.limits.set_deadline(deadline)
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.limits.set_timeout(timeout)
+    }
+
+    /// A handle to stop running code from another thread.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.limits.interrupt_handle()
+    }
+
+    /// Makes running code throw a RangeError when the objects allocated take more than 'limit'
+    /// bytes even after garbage is collected, or never if None. The limit is on the heap of the
+    /// realm, which the VMs created in the realm share.
+    pub fn set_heap_limit(&mut self, limit: Option<usize>) {
+        self.realm.heap.set_limit(limit)
+    }
+
+    pub fn heap_limit(&self) -> Option<usize> {
+        self.realm.heap.limit()
+    }
+
+    /// The size of the objects allocated in the realm, in bytes.
+    pub fn heap_size(&self) -> usize {
+        self.realm.heap.allocated_size()
+    }
+
+    /// Makes calls throw a RangeError once they use more than 'size' bytes of the native stack
+    /// below the caller of this method, or when the stack of the thread is about to run out if
+    /// None (the default).
+    pub fn set_max_stack_size(&mut self, size: Option<usize>) {
+        self.limits.set_max_stack_size(size)
+    }
+
+    /// Makes calls throw a RangeError when 'depth' calls are running, or never if None. The
+    /// default is 10000.
+    pub fn set_max_call_depth(&mut self, depth: Option<usize>) {
+        self.limits.set_max_call_depth(depth)
+    }
+
+    // Makes this VM use the same limits as 'vm', e.g. to run a module for it. Must be called
+    // before running code.
+    pub fn share_limits(&mut self, vm: &VM) {
+        self.limits = vm.limits.clone();
+    }
+
+    pub fn is_strict_mode(&self) -> bool {
+        unsafe { (**self.state.scope.last().unwrap()).strict }
+    }
+
+    // The scope of the running function, which 'arguments' refers to.
+    pub fn function_scope(&self) -> CallObjectRef {
+        unsafe { CallObject::function_scope(*self.state.scope.last().unwrap()) }
+    }
+
+    pub fn cur_func_id(&self) -> FuncId {
+        self.state.frames.last().unwrap().func_id
+    }
+
+    // Runs the frame on the top of the call stack until it returns, or reaches END if it's top
+    // level code. The functions it calls run in this loop too, so this only recurses when builtin
+    // functions call back into JS. The frames are left as they are on errors for backtraces,
+    // until the exception is caught or unwind() is called.
+    pub fn run_frames(&mut self) -> Result<(), RuntimeError> {
+        self.limits.check_stack()?;
+
+        let depth = self.state.frames.len();
+        let mut frames_len = depth;
+        let mut iseq = self.state.frames[depth - 1].iseq.clone();
         loop {
+            self.limits.tick()?;
             let code = iseq[self.state.pc as usize];
-            self.op_table[code as usize](self, iseq)?;
-            if code == VMInst::RETURN || code == VMInst::END {
+            let result = if self.realm.heap.is_over_threshold() {
+                gc::check_limit(&self.state)
+            } else {
+                Ok(())
+            };
+            if let Err(err) = result.and_then(|()| self.op_table[code as usize](self, &*iseq)) {
+                if let RuntimeError::Terminated(_) = err {
+                    return Err(err);
+                }
+                let exception = self.exception_value(err);
+                if !self.catch_exception(depth, exception.clone()) {
+                    return Err(RuntimeError::Exception(exception));
+                }
+            } else if code == VMInst::END
+                || (code == VMInst::RETURN && self.state.frames.len() < depth)
+            {
                 break;
             }
+
+            // A call, return or exception switched to another frame.
+            if self.state.frames.len() != frames_len {
+                frames_len = self.state.frames.len();
+                iseq = self.state.frames[frames_len - 1].iseq.clone();
+            }
             // println!("stack trace: {:?} - {}", self.stack, *pc);
         }
 
         Ok(())
     }
+
+    // Calls the function 'func_id' in 'scope' by pushing a frame, which the interpreter loop
+    // runs from the next instruction.
+    pub fn push_frame(
+        &mut self,
+        iseq: Rc<ByteCode>,
+        func_id: FuncId,
+        scope: CallObjectRef,
+        kind: FrameKind,
+    ) -> Result<(), RuntimeError> {
+        self.limits.check_call_depth(self.state.frames.len())?;
+
+        self.state.frames.push(Frame {
+            iseq: iseq,
+            func_id: func_id,
+            return_pc: self.state.pc,
+            sp: self.state.stack.len(),
+            scope_len: self.state.scope.len(),
+            kind: kind,
+        });
+        self.state.scope.push(scope);
+        self.state.pc = 0;
+        Ok(())
+    }
+
+    // Pops the running frame and resumes its caller. The stack is left to the caller.
+    pub fn pop_frame(&mut self) -> Frame {
+        let depth = self.state.frames.len();
+        // Discard the exception handlers of try blocks left by 'return'.
+        while self
+            .state
+            .try_stack
+            .last()
+            .map_or(false, |handler| handler.frames_len >= depth)
+        {
+            self.state.try_stack.pop();
+        }
+
+        let frame = self.state.frames.pop().unwrap();
+        self.state.scope.truncate(frame.scope_len);
+        self.state.pc = frame.return_pc;
+        frame
+    }
+
+    // Jumps to the innermost exception handler if it's in the frames run by the interpreter loop
+    // started at 'depth' (the length of frames), with the thrown value pushed. Returns false if
+    // there is no handler there.
+    fn catch_exception(&mut self, depth: usize, exception: Value) -> bool {
+        match self.state.try_stack.last() {
+            Some(handler) if handler.frames_len >= depth => {}
+            _ => return false,
+        }
+
+        let handler = self.state.try_stack.pop().unwrap();
+        self.state.stack.truncate(handler.sp);
+        self.state.scope.truncate(handler.scope_len);
+        self.state.frames.truncate(handler.frames_len);
+        self.state.pc = handler.handler_pc;
+        self.state.stack.push(exception);
+        true
+    }
+
+    // Converts an internal error into the Error object that JS code can catch.
+    pub fn exception_value(&mut self, err: RuntimeError) -> Value {
+        let (kind, msg) = match err {
+            RuntimeError::Exception(val) => return val,
+            RuntimeError::Unknown => (builtin::ERROR_NEW, "unknown error occurred".to_string()),
+            RuntimeError::Unimplemented => {
+                (builtin::ERROR_NEW, "unimplemented feature".to_string())
+            }
+            RuntimeError::Type(msg) => (builtin::TYPE_ERROR_NEW, msg),
+            RuntimeError::Reference(msg) => (builtin::REFERENCE_ERROR_NEW, msg),
+            RuntimeError::Range(msg) => (builtin::RANGE_ERROR_NEW, msg),
+            RuntimeError::Syntax(msg) => (builtin::SYNTAX_ERROR_NEW, msg),
+            RuntimeError::Terminated(msg) => (builtin::ERROR_NEW, msg),
+        };
+        // Messages are like "type error(pc:12): 'a' is not a function but called"
+        let msg = match msg.find(": ") {
+            Some(i) if msg[..i].contains("error") => msg[i + 2..].to_string(),
+            _ => msg,
+        };
+        builtin::create_error_object(self, kind, Some(JsString::from(msg)))
+    }
+
+    // Returns the function calls running now, from the innermost one, with the source locations
+    // of the instructions being executed in them.
+    pub fn backtrace(&self) -> Vec<(String, Option<SourceLocation>)> {
+        let mut calls = vec![];
+        let mut pc = self.state.pc;
+        for frame in self.state.frames.iter().rev() {
+            calls.push((frame.func_id, pc - 1));
+            pc = frame.return_pc;
+        }
+
+        let mut backtrace = vec![];
+        for (func_id, pc) in calls {
+            if let Some(info) = self.const_table.debug_info.get(&func_id) {
+                backtrace.push((
+                    info.name.clone(),
+                    info.find_position(pc as usize).map(|pos| SourceLocation {
+                        file: info.file.clone(),
+                        line: pos.line,
+                        column: pos.column,
+                    }),
+                ));
+            }
+        }
+        backtrace
+    }
+
+    // Returns the lines of a stack trace, from the innermost function call.
+    pub fn stack_trace(&self) -> String {
+        self.backtrace()
+            .iter()
+            .map(|(name, location)| match location {
+                Some(location) => format!("    at {} ({})", name, location),
+                None => format!("    at {}", name),
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Drops the function calls left by an uncaught exception so that the VM can run code again.
+    pub fn unwind(&mut self) {
+        self.state.stack.clear();
+        self.state.scope.truncate(1);
+        self.state.frames.truncate(1);
+        self.state.try_stack.clear();
+    }
 }
 
 macro_rules! get_int8 {
@@ -1167,11 +3690,47 @@ fn construct(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
     get_int32!(self_, iseq, argc, usize);
 
     let callee = self_.state.stack.pop().unwrap();
+    let mut args = vec![];
+    for _ in 0..argc {
+        args.push(self_.state.stack.pop().unwrap());
+    }
+
+    enter_constructor(self_, callee, args)?;
+    Ok(())
+}
+
+// Calls 'callee' as a constructor with 'args' like 'new' does, and pushes the object created.
+pub fn construct_object(
+    self_: &mut VM,
+    callee: Value,
+    args: Vec<Value>,
+) -> Result<(), RuntimeError> {
+    if enter_constructor(self_, callee, args)? {
+        self_.run_frames()?;
+    }
+    Ok(())
+}
+
+// Calls 'callee' as a constructor. Returns true if it's a JS function, for which a frame is
+// pushed and the object is pushed when it returns. Other constructors run at once.
+fn enter_constructor(
+    self_: &mut VM,
+    mut callee: Value,
+    mut args: Vec<Value>,
+) -> Result<bool, RuntimeError> {
+    // 'new' on a bound function constructs its target with the bound arguments prepended.
+    while let ValueBase::BuiltinFunction(box (builtin::BOUND_FUNCTION_CALL, _, ref callobj)) =
+        callee.val.clone()
+    {
+        let (target, _, bound_args) = unsafe { builtin::bound_function_parts(callobj) };
+        args = bound_args.into_iter().chain(args).collect();
+        callee = target;
+    }
 
     match callee.val.clone() {
         ValueBase::BuiltinFunction(box (id, obj, mut callobj)) => {
             let new_this = {
-                let mut map = FxHashMap::default();
+                let mut map = PropertyMap::default();
                 map.insert("__proto__".to_string(), unsafe {
                     (*obj)
                         .get("prototype")
@@ -1180,123 +3739,76 @@ fn construct(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
                 });
                 gc::new(map)
             };
-            let mut args = vec![];
-
-            for _ in 0..argc {
-                args.push(self_.state.stack.pop().unwrap());
-            }
 
             *callobj.this = Value::object(new_this);
 
-            unsafe { self_.builtin_functions[id](callobj, args, self_) };
+            unsafe { self_.call_builtin_function(id, callobj, args)? };
         }
         ValueBase::Function(box (id, iseq, obj, mut callobj)) => {
             let new_this = {
-                let mut map = FxHashMap::default();
+                let mut map = PropertyMap::default();
                 map.insert("__proto__".to_string(), unsafe {
                     (*obj)
                         .get("prototype")
-                        .unwrap_or(&Value::undefined())
-                        .clone()
-                });
-                gc::new(map)
-            };
-
-            callobj.vals = gc::new(FxHashMap::default());
-
-            // similar code is used some times. should make it a function.
-            let mut args = vec![];
-            let mut rest_args = vec![];
-            let mut rest_param_name = None;
-            for _ in 0..argc {
-                args.push(self_.state.stack.pop().unwrap());
-            }
-            for (i, arg) in args.iter().enumerate() {
-                if let Some(name) = callobj.get_parameter_nth_name(i) {
-                    // When rest parameter
-                    if callobj.params[i].1 {
-                        rest_param_name = Some(name);
-                        rest_args.push(arg.clone());
-                    } else {
-                        callobj.set_value(name, arg.clone());
-                    }
-                } else {
-                    rest_args.push(arg.clone());
-                }
-            }
-            if let Some(rest_param_name) = rest_param_name {
-                callobj.set_value(
-                    rest_param_name,
-                    Value::array(gc::new(ArrayValue::new(rest_args))),
-                );
-            } else {
-                for arg in rest_args {
-                    callobj.arg_rest_vals.push(arg.clone());
-                }
-            }
+                        .unwrap_or(&Value::undefined())
+                        .clone()
+                });
+                gc::new(map)
+            };
+
+            callobj.vals = gc::new(PropertyMap::default());
+            let locals = bind_arguments(&mut callobj, &args);
 
             *callobj.this = Value::object(new_this);
-            self_.state.scope.push(gc::new(callobj));
-            self_
-                .state
-                .history
-                .push((self_.state.stack.len(), self_.state.pc));
-            self_.state.pc = 0;
-            let save_id = self_.cur_func_id;
-            self_.cur_func_id = id;
-
-            self_.do_run(&iseq)?;
-
-            self_.cur_func_id = save_id;
-            self_.state.scope.pop();
-
-            match self_.state.stack.last_mut().unwrap() {
-                &mut Value {
-                    val: ValueBase::Object(_),
-                    ..
-                }
-                | &mut Value {
-                    val: ValueBase::Array(_),
-                    ..
-                }
-                | &mut Value {
-                    val: ValueBase::Function(box (_, _, _, _)),
-                    ..
-                }
-                | &mut Value {
-                    val: ValueBase::BuiltinFunction(box (_, _, _)),
-                    ..
-                } => {}
-                others => *others = Value::object(new_this),
-            };
+            self_.push_frame(iseq, id, gc::new(callobj), FrameKind::Construct(new_this))?;
+            self_.state.stack.extend(locals);
+            return Ok(true);
+        }
+        ValueBase::Proxy(p) => {
+            let obj = unsafe { proxy::construct(self_, p, &callee, args)? };
+            self_.state.stack.push(obj);
         }
         c => {
             return Err(RuntimeError::Type(format!(
-                "type error(pc:{}): '{:?}' is not a constructor",
-                self_.state.pc, c
+                "type error: '{:?}' is not a constructor",
+                c
             )));
         }
     };
 
-    Ok(())
+    Ok(false)
 }
 
 fn create_object(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1; // create_object
     get_int32!(self_, iseq, len, usize);
 
-    let mut map = FxHashMap::default();
-    for _ in 0..len {
-        let name = if let ValueBase::String(name) = self_.state.stack.pop().unwrap().val {
-            name.into_string().unwrap()
+    // Properties are added in the order they appear in the literal: (value, name) pairs.
+    let at = self_.state.stack.len() - len * 2;
+    let mut map = PropertyMap::default();
+    let mut proto = None;
+    for pair in self_.state.stack.split_off(at).chunks(2) {
+        let name = if let ValueBase::String(ref name) = pair[1].val {
+            name.to_key()
         } else {
             unreachable!()
         };
-        let val = self_.state.stack.pop().unwrap();
-        map.insert(name, val.clone());
+        // '__proto__: value' sets the prototype instead of adding a property.
+        if name == PROTO_KEY {
+            proto = Some(pair[0].clone());
+        } else {
+            map.insert(name, pair[0].clone());
+        }
     }
 
-    self_.state.stack.push(Value::object(gc::new(map)));
+    let obj = Value::plain_object(map);
+    match proto {
+        Some(ref proto) if proto.is_object() || proto.val == ValueBase::Null => {
+            obj.set_prototype(proto.clone())?
+        }
+        _ => {}
+    }
+    self_.state.stack.push(obj);
 
     gc::mark_and_sweep(&self_.state);
 
@@ -1385,7 +3897,7 @@ fn lnot(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
 fn posi(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1; // posi
     let expr = self_.state.stack.last_mut().unwrap();
-    expr.val = ValueBase::Number(expr.val.to_number());
+    expr.val = ValueBase::Number(expr.val.try_to_number()?);
     Ok(())
 }
 
@@ -1394,6 +3906,7 @@ fn neg(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     let expr = self_.state.stack.last_mut().unwrap();
     match &mut expr.val {
         &mut ValueBase::Number(ref mut n) => *n = -*n,
+        &mut ValueBase::BigInt(ref mut n) => *n = n.neg(),
         _ => return Err(RuntimeError::Unimplemented),
     };
     Ok(())
@@ -1403,15 +3916,25 @@ fn add(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1; // $name
     let rhs = self_.state.stack.pop().unwrap();
     let lhs = self_.state.stack.pop().unwrap();
+    let (lhs, rhs) = unsafe {
+        (
+            builtin::to_primitive(self_, &lhs, "default")?,
+            builtin::to_primitive(self_, &rhs, "default")?,
+        )
+    };
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::number(l + r),
-        (ValueBase::Bool(false), ValueBase::Number(x))
-        | (ValueBase::Number(x), ValueBase::Bool(false)) => Value::number(x),
-        (ValueBase::Bool(true), ValueBase::Number(x))
-        | (ValueBase::Number(x), ValueBase::Bool(true)) => Value::number(x + 1.0),
-        // TODO: We need the correct implementation.
-        (ValueBase::Undefined, _) | (_, ValueBase::Undefined) => Value::number(::std::f64::NAN),
-        (l, r) => Value::string(CString::new(l.to_string() + r.to_string().as_str()).unwrap()),
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => Value::bigint(l.add(&r)),
+        // https://tc39.github.io/ecma262/#sec-addition-operator-plus-runtime-semantics-evaluation
+        // Concatenates if either is a string, otherwise adds the numbers.
+        (ref l, ref r) if is_string_like(l) || is_string_like(r) => {
+            let (l, r) = (l.to_js_string(), r.to_js_string());
+            gc::grow((l.len() + r.len()) * mem::size_of::<u16>())?;
+            Value::string(l.concat(&r))
+        }
+        // A BigInt can only be concatenated with strings (and objects converted to strings).
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
+        (l, r) => Value::number(l.to_number() + r.to_number()),
     });
     Ok(())
 }
@@ -1422,6 +3945,8 @@ fn sub(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     let lhs = self_.state.stack.pop().unwrap();
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::number(l - r),
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => Value::bigint(l.sub(&r)),
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1433,8 +3958,14 @@ fn mul(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     let lhs = self_.state.stack.pop().unwrap();
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::number(l * r),
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => Value::bigint(l.mul(&r)),
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         (ValueBase::String(l), ValueBase::Number(r)) => {
-            Value::string(CString::new(l.to_str().unwrap().repeat(r as usize)).unwrap())
+            let mut units = vec![];
+            for _ in 0..r as usize {
+                units.extend_from_slice(l.units());
+            }
+            Value::string(JsString::from_units(units))
         }
         _ => return Err(RuntimeError::Unimplemented),
     });
@@ -1447,6 +3978,11 @@ fn div(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     let lhs = self_.state.stack.pop().unwrap();
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::number(l / r),
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => match l.div_rem(&r) {
+            Some((quotient, _)) => Value::bigint(quotient),
+            None => return Err(division_by_zero()),
+        },
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1458,6 +3994,11 @@ fn rem(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     let lhs = self_.state.stack.pop().unwrap();
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::number((l as i64 % r as i64) as f64),
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => match l.div_rem(&r) {
+            Some((_, remainder)) => Value::bigint(remainder),
+            None => return Err(division_by_zero()),
+        },
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1470,6 +4011,10 @@ fn lt(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::bool(l < r),
         (ValueBase::String(l), ValueBase::String(r)) => Value::bool(l < r),
+        (l @ ValueBase::BigInt(_), r) | (l, r @ ValueBase::BigInt(_)) => {
+            let ordering = compare_bigint(&l, &r);
+            Value::bool(ordering == Some(Ordering::Less))
+        }
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1482,6 +4027,10 @@ fn gt(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::bool(l > r),
         (ValueBase::String(l), ValueBase::String(r)) => Value::bool(l > r),
+        (l @ ValueBase::BigInt(_), r) | (l, r @ ValueBase::BigInt(_)) => {
+            let ordering = compare_bigint(&l, &r);
+            Value::bool(ordering == Some(Ordering::Greater))
+        }
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1494,6 +4043,10 @@ fn le(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::bool(l <= r),
         (ValueBase::String(l), ValueBase::String(r)) => Value::bool(l <= r),
+        (l @ ValueBase::BigInt(_), r) | (l, r @ ValueBase::BigInt(_)) => {
+            let ordering = compare_bigint(&l, &r);
+            Value::bool(ordering == Some(Ordering::Less) || ordering == Some(Ordering::Equal))
+        }
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1506,6 +4059,10 @@ fn ge(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::bool(l >= r),
         (ValueBase::String(l), ValueBase::String(r)) => Value::bool(l >= r),
+        (l @ ValueBase::BigInt(_), r) | (l, r @ ValueBase::BigInt(_)) => {
+            let ordering = compare_bigint(&l, &r);
+            Value::bool(ordering == Some(Ordering::Greater) || ordering == Some(Ordering::Equal))
+        }
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1519,6 +4076,9 @@ fn eq(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::bool(l == r),
         (ValueBase::String(l), ValueBase::String(r)) => Value::bool(l == r),
+        (l @ ValueBase::BigInt(_), r) | (l, r @ ValueBase::BigInt(_)) => {
+            Value::bool(compare_bigint(&l, &r) == Some(Ordering::Equal))
+        }
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1532,34 +4092,33 @@ fn ne(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.stack.push(match (lhs.val, rhs.val) {
         (ValueBase::Number(l), ValueBase::Number(r)) => Value::bool(l != r),
         (ValueBase::String(l), ValueBase::String(r)) => Value::bool(l != r),
+        (l @ ValueBase::BigInt(_), r) | (l, r @ ValueBase::BigInt(_)) => {
+            Value::bool(compare_bigint(&l, &r) != Some(Ordering::Equal))
+        }
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
 }
 
-// TODO: Need more precise implemention
 fn seq(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1; // $name
     let rhs = self_.state.stack.pop().unwrap();
     let lhs = self_.state.stack.pop().unwrap();
-    self_.state.stack.push(match (lhs.val, rhs.val) {
-        (ValueBase::Number(l), ValueBase::Number(r)) => Value::bool(l == r),
-        (ValueBase::String(l), ValueBase::String(r)) => Value::bool(l == r),
-        _ => return Err(RuntimeError::Unimplemented),
-    });
+    self_
+        .state
+        .stack
+        .push(Value::bool(lhs.val.strict_equals(&rhs.val)));
     Ok(())
 }
 
-// TODO: Need more precise implemention
 fn sne(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1; // $name
     let rhs = self_.state.stack.pop().unwrap();
     let lhs = self_.state.stack.pop().unwrap();
-    self_.state.stack.push(match (lhs.val, rhs.val) {
-        (ValueBase::Number(l), ValueBase::Number(r)) => Value::bool(l != r),
-        (ValueBase::String(l), ValueBase::String(r)) => Value::bool(l != r),
-        _ => return Err(RuntimeError::Unimplemented),
-    });
+    self_
+        .state
+        .stack
+        .push(Value::bool(!lhs.val.strict_equals(&rhs.val)));
     Ok(())
 }
 
@@ -1571,6 +4130,8 @@ fn and(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
         (ValueBase::Number(l), ValueBase::Number(r)) => {
             Value::number(((l as i64 as i32) & (r as i64 as i32)) as f64)
         }
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => Value::bigint(l.bitand(&r)),
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1584,6 +4145,8 @@ fn or(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
         (ValueBase::Number(l), ValueBase::Number(r)) => {
             Value::number(((l as i64 as i32) | (r as i64 as i32)) as f64)
         }
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => Value::bigint(l.bitor(&r)),
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1597,6 +4160,8 @@ fn xor(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
         (ValueBase::Number(l), ValueBase::Number(r)) => {
             Value::number(((l as i64 as i32) ^ (r as i64 as i32)) as f64)
         }
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => Value::bigint(l.bitxor(&r)),
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1610,6 +4175,10 @@ fn shl(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
         (ValueBase::Number(l), ValueBase::Number(r)) => {
             Value::number(((l as i64 as i32) << (r as i64 as i32)) as f64)
         }
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => {
+            Value::bigint(l.shl(&r).map_err(bigint_range_error)?)
+        }
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1623,6 +4192,10 @@ fn shr(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
         (ValueBase::Number(l), ValueBase::Number(r)) => {
             Value::number(((l as i64 as i32) >> (r as i64 as i32)) as f64)
         }
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => {
+            Value::bigint(l.shr(&r).map_err(bigint_range_error)?)
+        }
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
@@ -1636,89 +4209,129 @@ fn zfshr(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
         (ValueBase::Number(l), ValueBase::Number(r)) => {
             Value::number(((l as u64 as u32) >> (r as u64 as u32)) as f64)
         }
+        (ValueBase::BigInt(_), ValueBase::BigInt(_)) => {
+            return Err(RuntimeError::Type(
+                "type error: BigInts have no unsigned right shift, use >> instead".to_string(),
+            ))
+        }
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
+        _ => return Err(RuntimeError::Unimplemented),
+    });
+    Ok(())
+}
+
+fn exp(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1; // $name
+    let rhs = self_.state.stack.pop().unwrap();
+    let lhs = self_.state.stack.pop().unwrap();
+    self_.state.stack.push(match (lhs.val, rhs.val) {
+        (ValueBase::Number(l), ValueBase::Number(r)) => Value::number(js_pow(l, r)),
+        (ValueBase::BigInt(l), ValueBase::BigInt(r)) => {
+            Value::bigint(l.pow(&r).map_err(bigint_range_error)?)
+        }
+        (ValueBase::BigInt(_), _) | (_, ValueBase::BigInt(_)) => return Err(cannot_mix_bigint()),
         _ => return Err(RuntimeError::Unimplemented),
     });
     Ok(())
 }
 
+fn bitwise_not(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1; // $name
+    let expr = self_.state.stack.last_mut().unwrap();
+    expr.val = match expr.val {
+        ValueBase::BigInt(ref n) => ValueBase::BigInt(n.bitnot()),
+        ref val => ValueBase::Number(!(val.to_uint32() as u32 as i32) as f64),
+    };
+    Ok(())
+}
+
+// Converts the key of a member access. BigInts are converted into strings, so [1, 2][1n] is 2.
+// https://tc39.github.io/ecma262/#sec-topropertykey
+fn property_key(member: Value) -> ValueBase {
+    match member.val {
+        ValueBase::BigInt(ref n) => ValueBase::String(JsString::from(n.to_string())),
+        val => val,
+    }
+}
+
 fn get_member(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1; // get_global
-    let member = self_.state.stack.pop().unwrap();
+    let member = property_key(self_.state.stack.pop().unwrap());
     let parent = self_.state.stack.pop().unwrap();
-    let val = parent.get_property(member.val, Some(self_.state.scope.last().unwrap()));
+    let val = match parent.val {
+        ValueBase::Proxy(_) => unsafe { proxy::get_property(self_, &parent, member, &parent)? },
+        _ => parent.get_property(member, Some(&self_.function_scope())),
+    };
     self_.state.stack.push(val);
     Ok(())
 }
 
 fn set_member(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1; // get_global
-    let member = self_.state.stack.pop().unwrap();
+    let member = property_key(self_.state.stack.pop().unwrap());
     let parent = self_.state.stack.pop().unwrap();
     let val = self_.state.stack.pop().unwrap();
-    // TODO: The following code should be a function (like Value::set_property).
+    let strict = self_.is_strict_mode();
     match parent.val {
-        ValueBase::Object(map) | ValueBase::Function(box (_, _, map, _)) => unsafe {
-            *(*map)
-                .entry(member.to_string())
-                .or_insert_with(|| Value::undefined()) = val;
+        ValueBase::Proxy(_) => unsafe {
+            proxy::set_property(self_, &parent, member, val, &parent, strict)
         },
-        ValueBase::Array(map) => unsafe {
-            fn set_by_idx(map: &mut ArrayValue, n: usize, val: Value) {
-                if n >= map.length as usize {
-                    map.length = n + 1;
-                    while map.elems.len() < n + 1 {
-                        map.elems.push(Value::empty());
-                    }
-                }
-                map.elems[n] = val;
-            };
+        _ => parent.set_property(member, val, Some(&self_.function_scope()), strict),
+    }
+}
 
-            let mut map = &mut *map;
+fn delete(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1; // delete
+    let member = property_key(self_.state.stack.pop().unwrap());
+    let parent = self_.state.stack.pop().unwrap();
+    let strict = self_.is_strict_mode();
+    let deleted = unsafe { proxy::delete_property(self_, &parent, member, strict)? };
+    self_.state.stack.push(Value::bool(deleted));
+    Ok(())
+}
 
-            match member.val {
-                // Index
-                ValueBase::Number(n) if n - n.floor() == 0.0 && n >= 0.0 => {
-                    set_by_idx(map, n as usize, val)
-                }
-                ValueBase::String(ref s) if s.to_str().unwrap() == "length" => match val.val {
-                    ValueBase::Number(n) if n - n.floor() == 0.0 && n >= 0.0 => {
-                        map.length = n as usize;
-                        while map.elems.len() < n as usize + 1 {
-                            map.elems.push(Value::empty());
-                        }
-                    }
-                    _ => {}
-                },
-                // https://www.ecma-international.org/ecma-262/9.0/index.html#sec-array-exotic-objects
-                ValueBase::String(ref s)
-                    if Value::number(member.val.to_uint32()).to_string() == s.to_str().unwrap() =>
-                {
-                    let num = member.val.to_uint32();
-                    set_by_idx(map, num as usize, val)
-                }
-                _ => {
-                    *map.obj
-                        .entry(member.to_string())
-                        .or_insert_with(|| Value::undefined()) = val
-                }
-            }
-        },
-        ValueBase::Arguments => {
-            match member.val {
-                // Index
-                ValueBase::Number(n) if n - n.floor() == 0.0 => unsafe {
-                    (**self_.state.scope.last().unwrap()).set_arguments_nth_value(n as usize, val);
-                },
-                // TODO: 'length'
-                _ => {}
-            }
-        }
-        _ => {}
-    };
+fn enter_try(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1; // enter_try
+    get_int32!(self_, iseq, dst, i32);
+    self_.state.try_stack.push(TryFrame {
+        handler_pc: self_.state.pc + dst as isize,
+        sp: self_.state.stack.len(),
+        scope_len: self_.state.scope.len(),
+        frames_len: self_.state.frames.len(),
+    });
+    Ok(())
+}
+
+fn leave_try(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1; // leave_try
+    self_.state.try_stack.pop();
+    Ok(())
+}
+
+// Binds the exception on the stack top to the parameter of a catch block, in a scope of its own.
+fn enter_catch(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1; // enter_catch
+    get_int32!(self_, iseq, param_id, usize);
+    let param = self_.const_table.string[param_id].clone();
+    let val = self_.state.stack.pop().unwrap();
+    let scope = unsafe { CallObject::new_catch(*self_.state.scope.last().unwrap(), param, val) };
+    self_.state.scope.push(scope);
+    Ok(())
+}
 
+// Leaves the scope of a catch block. Exceptions and returns leave it with the frame or handler.
+fn leave_scope(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1; // leave_scope
+    self_.state.scope.pop();
     Ok(())
 }
 
+fn throw(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1; // throw
+    let val = self_.state.stack.pop().unwrap();
+    Err(RuntimeError::Exception(val))
+}
+
 fn jmp(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1; // jmp
     get_int32!(self_, iseq, dst, i32);
@@ -1736,83 +4349,115 @@ fn jmp_if_false(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
     Ok(())
 }
 
+// Calls the JS function 'id' with 'args', and pushes what it returned. Used by builtin functions,
+// for which the function runs in a nested interpreter loop.
 pub fn call_function(
     self_: &mut VM,
     id: FuncId,
-    iseq: &ByteCode,
+    iseq: &Rc<ByteCode>,
     args: &Vec<Value>,
-    mut callobj: CallObject,
+    callobj: CallObject,
 ) -> Result<(), RuntimeError> {
-    let argc = args.len();
-    let mut args_all_numbers = true;
-    let mut rest_args = vec![];
-    let mut rest_param_name = None;
-    for (i, arg) in args.iter().enumerate() {
-        if let Some(name) = callobj.get_parameter_nth_name(i) {
-            // When rest parameter
-            if callobj.params[i].1 {
-                rest_param_name = Some(name);
-                rest_args.push(arg.clone());
-            } else {
-                callobj.set_value(name, arg.clone());
+    if enter_function(self_, id, iseq.clone(), args, callobj)? {
+        self_.run_frames()?;
+    }
+    Ok(())
+}
+
+// Calls 'callee' with 'this' and 'args'. Returns true if a frame is pushed for a JS function, and
+// false if the result is already pushed. The functions called through Function.prototype.call()
+// and apply() are called in the same way.
+pub fn enter_call(
+    self_: &mut VM,
+    callee: Value,
+    this: Value,
+    args: Vec<Value>,
+) -> Result<bool, RuntimeError> {
+    match callee.val {
+        ValueBase::BuiltinFunction(box (id, _, mut callobj)) => {
+            *callobj.this = this;
+            if id == builtin::FUNCTION_PROTOTYPE_CALL || id == builtin::FUNCTION_PROTOTYPE_APPLY {
+                let (callee, this, args) =
+                    unsafe { builtin::call_or_apply_target(self_, id, &callobj, args) };
+                return enter_call(self_, callee, this, args);
             }
-        } else {
-            rest_args.push(arg.clone());
+            unsafe { self_.call_builtin_function(id, callobj, args)? };
         }
-
-        match &arg.val {
-            &ValueBase::Number(_) => {}
-            _ => args_all_numbers = false,
+        ValueBase::Function(box (id, iseq, _, mut callobj)) => {
+            *callobj.this = this;
+            callobj.vals = gc::new(PropertyMap::default());
+            return enter_function(self_, id, iseq, &args, callobj);
         }
-    }
-    if let Some(rest_param_name) = rest_param_name {
-        callobj.set_value(
-            rest_param_name,
-            Value::array(gc::new(ArrayValue::new(rest_args))),
-        );
-    } else {
-        for arg in rest_args {
-            callobj.arg_rest_vals.push(arg.clone());
+        ValueBase::Proxy(p) => {
+            let val = unsafe { proxy::call(self_, p, this, args)? };
+            self_.state.stack.push(val);
         }
+        _ => self_.state.stack.push(Value::undefined()),
     }
+    Ok(false)
+}
+
+// Calls the JS function 'id' with 'args'. Returns true if a frame is pushed for it, and false if
+// it's JIT-compiled and has already run, pushing what it returned.
+fn enter_function(
+    self_: &mut VM,
+    id: FuncId,
+    iseq: Rc<ByteCode>,
+    args: &Vec<Value>,
+    mut callobj: CallObject,
+) -> Result<bool, RuntimeError> {
+    let argc = args.len();
+    let args_all_numbers = args.iter().all(|arg| match arg.val {
+        ValueBase::Number(_) => true,
+        _ => false,
+    });
+    let locals = bind_arguments(&mut callobj, args);
 
-    self_.state.scope.push(gc::new(callobj));
+    let scope = gc::new(callobj);
 
     if args_all_numbers {
-        let scope = (*self_.state.scope.last().unwrap()).clone();
         if let Some(f) = unsafe {
             self_
                 .jit
-                .can_jit(id, iseq, &*scope, &self_.const_table, argc)
+                .can_jit(id, &iseq, &*scope, &self_.const_table, &self_.limits, argc)
         } {
-            self_
-                .state
-                .stack
-                .push(unsafe { self_.jit.run_llvm_func(id, f, &args) });
-            self_.state.scope.pop();
-            return Ok(());
+            self_.limits.enter_jit(self_.state.frames.len());
+            let val = unsafe { self_.jit.run_llvm_func(id, f, &args) };
+            self_.limits.check_jit_stack_overflow()?;
+            self_.state.stack.push(val);
+            return Ok(false);
         }
     }
 
-    self_
-        .state
-        .history
-        .push((self_.state.stack.len(), self_.state.pc));
-    self_.state.pc = 0;
-
-    let save_id = self_.cur_func_id;
-    self_.cur_func_id = id;
-
-    self_.do_run(iseq)?;
-
-    self_.cur_func_id = save_id;
-    self_.state.scope.pop();
-
-    self_
-        .jit
-        .record_function_return_type(id, self_.state.stack.last().unwrap());
+    self_.push_frame(iseq, id, scope, FrameKind::Call)?;
+    self_.state.stack.extend(locals);
+    Ok(true)
+}
 
-    Ok(())
+// Binds 'args' to the parameters of the function called with 'callobj'. Returns the initial values
+// of its local variable slots, which the parameters take first. The arguments beyond the
+// parameters are kept for 'arguments'.
+fn bind_arguments(callobj: &mut CallObject, args: &Vec<Value>) -> Vec<Value> {
+    let mut locals = vec![Value::undefined(); callobj.locals];
+    let mut rest_args = vec![];
+    let mut rest_param = None;
+    for (i, arg) in args.iter().enumerate() {
+        match callobj.params.get(i) {
+            // When rest parameter
+            Some(&(_, true)) => {
+                rest_param = Some(i);
+                rest_args.push(arg.clone());
+            }
+            Some(_) => locals[i] = arg.clone(),
+            None => rest_args.push(arg.clone()),
+        }
+    }
+    if let Some(i) = rest_param {
+        locals[i] = Value::array(gc::new(ArrayValue::new(rest_args)));
+    } else {
+        callobj.arg_rest_vals.extend(rest_args);
+    }
+    locals
 }
 
 fn call(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
@@ -1827,23 +4472,37 @@ fn call(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
             for _ in 0..argc {
                 args.push(self_.state.stack.pop().unwrap());
             }
-            unsafe { self_.builtin_functions[x](callobj, args, self_) };
+            // Calls through call() and apply() run in this loop like the other calls.
+            if x == builtin::FUNCTION_PROTOTYPE_CALL || x == builtin::FUNCTION_PROTOTYPE_APPLY {
+                let (callee, this, args) =
+                    unsafe { builtin::call_or_apply_target(self_, x, &callobj, args) };
+                enter_call(self_, callee, this, args)?;
+                return Ok(());
+            }
+            unsafe { self_.call_builtin_function(x, callobj, args)? };
         }
-        ValueBase::Function(box (id, ref iseq, _, ref callobj)) => {
-            let mut callobj = callobj.clone();
-            callobj.vals = gc::new(FxHashMap::default());
+        ValueBase::Function(box (id, iseq, _, mut callobj)) => {
+            callobj.vals = gc::new(PropertyMap::default());
 
             let mut args = vec![];
             for _ in 0..argc {
                 args.push(self_.state.stack.pop().unwrap());
             }
 
-            call_function(self_, id, iseq, &args, callobj)?;
+            enter_function(self_, id, iseq, &args, callobj)?;
+        }
+        ValueBase::Proxy(p) => {
+            let mut args = vec![];
+            for _ in 0..argc {
+                args.push(self_.state.stack.pop().unwrap());
+            }
+            let val = unsafe { proxy::call(self_, p, Value::undefined(), args)? };
+            self_.state.stack.push(val);
         }
         c => {
             return Err(RuntimeError::Type(format!(
-                "type error(pc:{}): '{:?}' is not a function but called",
-                self_.state.pc, c
+                "type error: '{:?}' is not a function but called",
+                c
             )));
         }
     };
@@ -1851,14 +4510,37 @@ fn call(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
     Ok(())
 }
 
+// A call to the builtin eval() written as 'eval(...)' is a direct eval, which runs the code in the
+// scope of the caller. Other functions named 'eval' are just called.
+fn call_eval(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
+    match self_.state.stack.last().unwrap().val {
+        ValueBase::BuiltinFunction(box (builtin::EVAL, _, _)) => {}
+        _ => return call(self_, iseq),
+    }
+
+    self_.state.pc += 1; // call_eval
+    get_int32!(self_, iseq, argc, usize);
+
+    self_.state.stack.pop(); // eval
+    let mut args = vec![];
+    for _ in 0..argc {
+        args.push(self_.state.stack.pop().unwrap());
+    }
+
+    let scope = *self_.state.scope.last().unwrap();
+    unsafe { builtin::eval_in_scope(self_, args.first().cloned(), scope) }
+}
+
 fn return_(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError> {
-    let len = self_.state.stack.len();
-    if let Some((previous_sp, return_pc)) = self_.state.history.pop() {
-        self_.state.stack.drain(previous_sp..len - 1);
-        self_.state.pc = return_pc;
-    } else {
-        unreachable!()
+    let mut val = self_.state.stack.pop().unwrap();
+    let frame = self_.pop_frame();
+    self_.state.stack.truncate(frame.sp);
+    match frame.kind {
+        FrameKind::Call => self_.jit.record_function_return_type(frame.func_id, &val),
+        FrameKind::Construct(new_this) if !val.is_object() => val = Value::object(new_this),
+        _ => {}
     }
+    self_.state.stack.push(val);
     Ok(())
 }
 
@@ -1895,6 +4577,8 @@ fn set_cur_callobj(self_: &mut VM, _iseq: &ByteCode) -> Result<(), RuntimeError>
     }) = self_.state.stack.last_mut()
     {
         callobj.parent = Some(self_.state.scope.last().unwrap().clone());
+        // Functions defined in strict mode code are also strict.
+        callobj.strict |= unsafe { (**self_.state.scope.last().unwrap()).strict };
     }
     Ok(())
 }
@@ -1923,9 +4607,8 @@ fn set_name(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
         }
     }
 
-    unsafe { (**self_.state.scope.last().unwrap()).set_value_if_exist(name, val) };
-
-    Ok(())
+    let strict = self_.is_strict_mode();
+    unsafe { (**self_.state.scope.last().unwrap()).assign_value(name, val, strict) }
 }
 
 fn decl_var(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
@@ -1944,9 +4627,60 @@ fn decl_var(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
     }
 
     unsafe {
-        (**self_.state.scope.last().unwrap()).set_value(name, val);
+        let scope = CallObject::function_scope(*self_.state.scope.last().unwrap());
+        (*scope).set_value(name, val);
+    }
+
+    Ok(())
+}
+
+// Local variables are in the slots above the stack pointer of the frame.
+fn get_local(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1;
+    get_int32!(self_, iseq, slot, usize);
+    let sp = self_.state.frames.last().unwrap().sp;
+    let val = self_.state.stack[sp + slot].clone();
+    self_.state.stack.push(val);
+    Ok(())
+}
+
+fn set_local(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1;
+    get_int32!(self_, iseq, slot, usize);
+    let mut val = self_.state.stack.pop().unwrap();
+
+    // We have to change cobj.this to the current scope one. (./examples/this.js)
+    if let ValueBase::Function(box (_, _, _, ref mut cobj))
+    | ValueBase::BuiltinFunction(box (_, _, ref mut cobj)) = &mut val.val
+    {
+        unsafe {
+            cobj.this = (**self_.state.scope.last().unwrap()).this.clone();
+        }
     }
 
+    let sp = self_.state.frames.last().unwrap().sp;
+    self_.state.stack[sp + slot] = val;
+    Ok(())
+}
+
+// Reads a variable captured from the function 'depth' scopes out, skipping the lookups in the
+// scopes between.
+fn get_upvar(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
+    self_.state.pc += 1;
+    get_int32!(self_, iseq, depth, usize);
+    get_int32!(self_, iseq, name_id, usize);
+    let name = &self_.const_table.string[name_id];
+    let val = unsafe {
+        let mut scope = CallObject::function_scope(*self_.state.scope.last().unwrap());
+        for _ in 0..depth {
+            match (*scope).parent {
+                Some(parent) => scope = CallObject::function_scope(parent),
+                None => break,
+            }
+        }
+        (*scope).get_value(name)?
+    };
+    self_.state.stack.push(val);
     Ok(())
 }
 
@@ -1962,19 +4696,23 @@ fn loop_start(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
     self_.state.pc += 1;
     get_int32!(self_, iseq, loop_end, usize);
 
-    let id = self_.cur_func_id;
+    let id = self_.cur_func_id();
 
+    // The loop may call JIT-compiled functions.
+    self_.limits.enter_jit(self_.state.frames.len());
     if let Some(pc) = unsafe {
         self_.jit.can_loop_jit(
             id,
             &iseq,
             &self_.const_table,
             &mut self_.state,
+            &self_.limits,
             loop_start,
             loop_end,
         )
     } {
         self_.state.pc = pc;
+        self_.limits.check_jit_stack_overflow()?;
     }
 
     Ok(())
@@ -2073,3 +4811,426 @@ fn loop_start(self_: &mut VM, iseq: &ByteCode) -> Result<(), RuntimeError> {
 //         RETURN, // Return
 //     ]);
 // }
+
+#[cfg(test)]
+pub struct ScriptRunner {
+    vm_codegen: ::vm_codegen::VMCodeGen,
+    vm: VM,
+}
+
+#[cfg(test)]
+impl ScriptRunner {
+    pub fn new() -> ScriptRunner {
+        let vm_codegen = ::vm_codegen::VMCodeGen::new();
+        let vm = VM::new(vm_codegen.global_varmap);
+        ScriptRunner { vm_codegen, vm }
+    }
+
+    // Runs 'source' like the REPL does and returns the value of the last expression statement.
+    pub fn run(&mut self, source: &str) -> Result<Value, RuntimeError> {
+        use extract_anony_func;
+        use parser;
+
+        let mut parser = parser::Parser::new(source.to_string());
+        let mut node = parser.parse_all().unwrap();
+        extract_anony_func::AnonymousFunctionExtractor::new().run_toplevel(&mut node);
+        let mut iseq = vec![];
+        self.vm_codegen.set_source("<anonymous>", &parser.lexer);
+        self.vm_codegen.compile(&node, &mut iseq, true);
+        self.vm.const_table = self.vm_codegen.bytecode_gen.const_table.clone();
+        self.vm.state.pc = 0;
+        self.vm.state.stack.clear();
+        if let Err(err) = self.vm.run(iseq) {
+            self.vm.unwind();
+            return Err(err);
+        }
+        self.vm_codegen.bytecode_gen.const_table = self.vm.const_table.clone();
+        Ok(self.vm.state.stack.pop().unwrap_or(Value::undefined()))
+    }
+
+    pub fn vm(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    // Runs 'source', which must throw, and returns the name of the uncaught error.
+    pub fn error_name(&mut self, source: &str) -> String {
+        match self.run(source) {
+            Err(RuntimeError::Exception(err)) => err
+                .get_property(ValueBase::String(JsString::from("name")), None)
+                .to_string(),
+            other => panic!("{}: expected an error but got {:?}", source, other),
+        }
+    }
+}
+
+#[test]
+fn property_attributes() {
+    let mut runner = ScriptRunner::new();
+
+    let source = "
+        var o = {a: 1};
+        Object.defineProperty(o, 'c', {value: 3});
+        o.c = 10;
+        var desc = Object.getOwnPropertyDescriptor(o, 'c');
+        [o.c, desc.writable, desc.enumerable, desc.configurable]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "3,false,false,false");
+
+    let source = "
+        var frozen = Object.freeze({a: 1}), sealed = Object.seal({b: 2});
+        frozen.a = 2; frozen.x = 1; delete frozen.a;
+        sealed.b = 3; sealed.y = 1; delete sealed.b;
+        [frozen.a, frozen.x, Object.isFrozen(frozen), sealed.b, sealed.y, Object.isSealed(sealed),
+         Object.isFrozen(sealed), Object.isExtensible(sealed), Object.isExtensible({})]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "1,,true,3,,true,false,false,true");
+
+    // A read-only 'length' leaves the elements writable.
+    let source = "
+        var a = [1, 2, 3];
+        Object.defineProperty(a, 'length', {writable: false});
+        a[0] = 10; a[5] = 1;
+        [a[0], a.length, Object.getOwnPropertyDescriptor(a, '0').writable,
+         Object.getOwnPropertyDescriptor(a, 'length').writable, Object.isExtensible(a),
+         Object.isFrozen(a)]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "10,3,true,false,true,false");
+
+    let source = "
+        var b = [1, 2, 3];
+        b.length = 1;
+        Object.freeze(b);
+        b[0] = 5;
+        [b[0], b.length, Object.isFrozen(b)]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "1,1,true");
+
+    for source in &[
+        "'use strict'; var frozen = Object.freeze({a: 1}); frozen.a = 3",
+        "'use strict'; var sealed = Object.seal({b: 2}); delete sealed.b",
+        "'use strict'; Function('')(); var frozen = Object.freeze({a: 1}); frozen.a = 3",
+        "Object.defineProperty(Object.freeze({a: 1}), 'a', {value: 5})",
+        "var a = [1]; Object.defineProperty(a, 'length', {writable: false}); a.push(4)",
+        "'use strict'; var a = [1]; Object.defineProperty(a, 'length', {writable: false}); a.length = 0",
+        "var a = [1]; Object.defineProperty(a, 'length', {writable: false});
+         Object.defineProperty(a, 'length', {writable: true})",
+    ] {
+        assert_eq!(runner.error_name(source), "TypeError", "{}", source);
+    }
+
+    // Strict mode doesn't last beyond the script that enabled it.
+    let source = "var frozen = Object.freeze({a: 1}); frozen.a = 3; frozen.a";
+    assert_eq!(runner.run(source), Ok(Value::number(1.0)));
+
+    for source in &[
+        "var b = [1]; b.length = -1",
+        "Object.defineProperty([], 'length', {value: 1.5})",
+        "[].length = 0 / 0",
+    ] {
+        assert_eq!(runner.error_name(source), "RangeError", "{}", source);
+    }
+}
+
+#[test]
+fn object_statics() {
+    let mut runner = ScriptRunner::new();
+
+    let source = "
+        var o = {a: 1, b: 'x'};
+        var p = Object.create(o, {c: {value: 3, enumerable: true}});
+        [Object.keys(o), Object.values(o), Object.entries(o).length, o.hasOwnProperty('a'),
+         o.hasOwnProperty('hasOwnProperty'), p.a, p.c, Object.keys(p), o.isPrototypeOf(p),
+         Object.getPrototypeOf(p) === o]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "a,b,1,x,2,true,false,1,3,c,true,true");
+
+    let source = "
+        var n = Object.create(null), a = Object.assign({z: 0}, {x: 1}, {y: 2});
+        [Object.getPrototypeOf(n) === null, n.hasOwnProperty, Object.keys(a),
+         Object.is(0 / 0, 0 / 0), Object.is(0, -0), Object.prototype.toString.call([1]),
+         ({}).toString()]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "true,,z,x,y,true,false,[object Array],[object Object]"
+    );
+
+    let source = "
+        var q = {q: 1}, r = new Object();
+        Object.setPrototypeOf(q, null);
+        [Object.getPrototypeOf(q) === null, q.propertyIsEnumerable, r.valueOf() === r,
+         Object(r) === r]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "true,,true,true");
+
+    // Array indices come first in ascending order, then the other keys in insertion order.
+    let source = "Object.keys({z: 1, a: 1, 2: 1, 1: 1})";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "1,2,z,a");
+
+    let source = "
+        var o = {b: 1, a: 2};
+        o[10] = 3; o.c = 4; o[2] = 5;
+        delete o.b; o.b = 6;
+        [Object.keys(o), Object.values(o), Object.getOwnPropertyNames([5, 6]),
+         Object.entries({x: 1, y: 2})]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "2,10,a,c,b,5,3,2,4,6,0,1,length,x,1,y,2");
+
+    for source in &["Object.keys(null)", "Object.entries(undefined)"] {
+        assert_eq!(runner.error_name(source), "TypeError", "{}", source);
+    }
+}
+
+#[test]
+fn lone_surrogates() {
+    let mut runner = ScriptRunner::new();
+
+    let source = "
+        var s = '\\uD83D', t = '\\uD83D' + '\\uDE00';
+        [s.length, s.charCodeAt(0), t === '😀', '\\u{1F600}' === t, 'a\\x00b'.length]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "1,55357,true,true,3");
+
+    // Lone surrogates are distinct property keys and survive the round trip through keys.
+    let source = "
+        var o = {}, hi = String.fromCharCode(0xD800), lo = String.fromCharCode(0xDC00);
+        o[hi] = 1; o[lo] = 2; o['\\u{1b}'] = 3;
+        var k = Object.keys(o);
+        [o[hi], o[lo], o['\\u{1b}'], k.length, k[0] === hi, k[1] === lo, k[2].charCodeAt(0),
+         {'\\uD800': 4}[hi]]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "1,2,3,3,true,true,27,4");
+}
+
+#[test]
+fn errors() {
+    let mut runner = ScriptRunner::new();
+
+    let source = "
+        var e = new RangeError('out of range');
+        [e.toString(), e.name, e.message, Object.keys(e).length,
+         RangeError.prototype.isPrototypeOf(e), Error.prototype.isPrototypeOf(e),
+         Error('no new').message, (new Error()).toString(), e.stack.split('\\n')[0]].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "RangeError: out of range;RangeError;out of range;0;true;true;no new;Error;\
+         RangeError: out of range"
+    );
+
+    // Runtime errors are thrown as instances of the matching constructor.
+    let source = "
+        var log = [];
+        try { undefinedVariable } catch (e) { log.push(e.name) }
+        try { (1).foo() } catch (e) { log.push(TypeError.prototype.isPrototypeOf(e)) }
+        try { throw 1 } catch (e) { log.push(e) } finally { log.push('finally') }
+        function f() { try { return 'try' } finally { log.push('returning') } }
+        log.push(f());
+        for (var i = 0; i < 3; i++) {
+            try { if (i == 1) continue; if (i == 2) break; log.push(i) } finally { log.push('f' + i) }
+        }
+        log.join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "ReferenceError,true,1,finally,returning,try,0,f0,f1,f2"
+    );
+
+    // The catch parameter doesn't overwrite the variable of the same name outside.
+    let source = "
+        var e = 'outer', saved;
+        try { throw 'inner' } catch (e) { saved = function() { return e } }
+        function f() { var e = 1; try { throw 2 } catch (e) { e = 3 } return e }
+        function g(e) { try { throw 2 } catch (e) { try { throw 3 } catch (e) {} return e } }
+        [e, saved(), f(), g(1)].join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "outer,inner,1,2");
+
+    // The parameter is bound in a scope of the catch block, which a var declaration of the same
+    // name doesn't change.
+    let source = "
+        function f() { try { throw 1 } catch (e) { var e = 2 } return e }
+        function g(a) { try { throw 2 } catch (x) { return arguments[0] + x + eval('x') } }
+        function k(v) { try { throw 2 } catch (x) { return function() { return v + x } } }
+        function h() {
+            for (var i = 0; i < 3; i++) { try { throw i } catch (x) { if (x == 1) break } }
+            try { return eval('x') } catch (err) { return err.name + i }
+        }
+        var caught;
+        try { throw 'thrown' } catch (x) { caught = eval('x') }
+        [f(), g(1), k(1)(), h(), caught, Object.getOwnPropertyNames(this).indexOf('x')].join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), ",5,3,ReferenceError1,thrown,-1");
+}
+
+#[test]
+fn backtrace() {
+    let mut runner = ScriptRunner::new();
+    let source = "
+        function inner() {
+            notDefined()
+        }
+        function outer() { inner() }
+        function thrower() { throw new Error('boom') }
+        var stack;
+        try { outer() } catch (e) { stack = e.stack }
+        stack";
+    // Calls are located at their arguments.
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "ReferenceError: 'notDefined' is not defined\n    at inner (<anonymous>:3:13)\n    \
+         at outer (<anonymous>:5:33)\n    at <anonymous> (<anonymous>:8:20)"
+    );
+
+    // Uncaught exceptions show the first line of the stack trace.
+    match runner.run("\n  thrower()") {
+        Err(RuntimeError::Exception(err)) => {
+            assert_eq!(uncaught_exception_message(&err), "uncaught Error: boom")
+        }
+        other => panic!("expected an exception but got {:?}", other),
+    }
+    let source = "try { thrower() } catch (e) { e.stack }";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "Error: boom\n    at thrower (<anonymous>:6:45)\n    at <anonymous> (<anonymous>:1:14)"
+    );
+}
+
+#[test]
+fn internal_slots() {
+    let mut runner = ScriptRunner::new();
+
+    // Properties named like internal slots are ordinary properties.
+    let source = "
+        var m = new Map([[1, 2]]), it = m.keys();
+        m.__collection_data__ = 1;
+        var o = {__collection_data__: 5, __iterator_kind__: 0};
+        var p = JSON.parse('{\"__collection_data__\": 1, \"a\": 2}');
+        [m.get(1), m.size, m.__collection_data__, Object.getOwnPropertyNames(m),
+         Object.getOwnPropertyNames(it).length, it.next().value, Object.keys(o), Object.keys(p)]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "2,1,1,__collection_data__,0,1,__collection_data__,__iterator_kind__,__collection_data__,a"
+    );
+
+    let source = "
+        var d = new Date(5);
+        d.__date_value__ = 7;
+        [d.getTime(), d.__date_value__, Object.getOwnPropertyNames(d)]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "5,7,__date_value__");
+
+    let source = "
+        var a = new Int8Array(2), v = new DataView(a.buffer);
+        a.__typed_array_data__ = 1;
+        v.__data_view_data__ = 2;
+        [a.length, a.__typed_array_data__, Object.getOwnPropertyNames(a),
+         v.byteLength, Object.getOwnPropertyNames(v)]";
+    let val = runner.run(source).unwrap();
+    assert_eq!(
+        val.to_string(),
+        "2,1,0,1,__typed_array_data__,2,__data_view_data__"
+    );
+
+    // Internal slots can't be forged.
+    for source in &[
+        "Map.prototype.get.call({__collection_data__: new Map()}, 1)",
+        "new Map().keys().next.call({__iterator_kind__: 0})",
+        "Date.prototype.getTime.call({__date_value__: 5})",
+        "Int8Array.prototype.join.call({__typed_array_data__: new Int8Array(1)})",
+        "DataView.prototype.getInt8.call({__data_view_data__: new Int8Array(1)}, 0)",
+        "ArrayBuffer.prototype.slice.call({__array_buffer_data__: new ArrayBuffer(1)})",
+    ] {
+        assert_eq!(runner.error_name(source), "TypeError", "{}", source);
+    }
+}
+
+#[test]
+fn bigint_conversions() {
+    let mut runner = ScriptRunner::new();
+
+    // Member keys are converted into strings, and Number() converts BigInts.
+    let source = "
+        var o = {}, a = [1, 2];
+        o[2n] = 'two';
+        a[2n] = 3;
+        [a[1n], o['2'], a.length, Number(5n), Number(), delete a[2n], a.length].join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "2,two,3,5,0,true,3");
+
+    // Operations taking numbers don't convert BigInts.
+    for source in &[
+        "Math.abs(-5n)",
+        "Math.max(1n)",
+        "Math.pow(2n, 1)",
+        "isNaN(1n)",
+        "new Uint8Array(2n)",
+        "new Uint8Array([1n])",
+        "var t = new Uint8Array(1); t[0] = 1n",
+        "[1, 2].slice(1n)",
+        "+1n",
+    ] {
+        assert_eq!(runner.error_name(source), "TypeError", "{}", source);
+    }
+}
+
+#[test]
+fn typed_array_numeric_keys() {
+    let mut runner = ScriptRunner::new();
+
+    // Numeric keys that aren't valid indices are neither stored nor looked up in prototypes.
+    let source = "
+        Int8Array.prototype[5] = 'proto';
+        var a = new Int8Array(2);
+        a[-1] = 5; a['1.5'] = 3; a['-0'] = 1; a[2] = 4; a['01'] = 6;
+        Object.defineProperty(a, '1', {value: 7});
+        [Object.keys(a), a[-1], a['1.5'], a[5], Reflect.has(a, 5), Reflect.has(a, 1), a[1],
+         a['01'], delete a[-1], Reflect.deleteProperty(a, 0)].join(';')";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "0,1,01;;;;false;true;7;6;true;false");
+
+    // The number -0 is converted to the key "0".
+    let source = "
+        var b = new Int8Array(2);
+        b[-0] = 3;
+        [b[0], b[-0], b['-0'], Reflect.has(b, -0)].join()";
+    let val = runner.run(source).unwrap();
+    assert_eq!(val.to_string(), "3,3,,true");
+
+    for source in &[
+        "Object.defineProperty(new Int8Array(2), '2', {value: 1})",
+        "Object.defineProperty(new Int8Array(2), '0', {value: 1, writable: false})",
+        "'use strict'; var t = new Int8Array(2); delete t[0]",
+    ] {
+        assert_eq!(runner.error_name(source), "TypeError", "{}", source);
+    }
+}
+
+#[test]
+fn call_frames() {
+    use engine::Engine;
+
+    let mut engine = Engine::new();
+    let source = "
+        function Point(x) { this.x = x; return 1 }
+        function Wrapper() { return [2] }
+        function find(ary) {
+            try { ary.forEach(function(x) { if (x > 1) throw x }) } finally { ary.push(0) }
+        }
+        function early() { try { return 'early' } catch (e) {} }
+        var point = new Point(3), wrapper = new Wrapper(), caught;
+        try { find([1, 2, 3]) } catch (e) { caught = e }
+        [point.x, wrapper[0], caught, early(), [1, 2].map(function(x) { return x * 2 })]";
+    let val = engine.eval(source).unwrap();
+    assert_eq!(val.to_string(), "3,2,2,early,2,4");
+    assert_eq!(engine.vm().state.frames.len(), 1);
+    assert!(engine.vm().state.try_stack.is_empty());
+
+    engine.vm().set_max_call_depth(Some(100));
+    let source = "function f(n) { return n == 0 ? 0 : 1 + f(n - 1) } f(50)";
+    assert_eq!(engine.eval(source), Ok(Value::number(50.0)));
+}
@@ -1,39 +1,102 @@
 use bytecode_gen::{ByteCode, ByteCodeGen, VMInst};
 use id;
+use js_string::JsString;
+use lexer::Lexer;
 use node::{BinOp, FormalParameter, FormalParameters, Node, NodeBase, PropertyDefinition, UnaryOp};
-use vm::{new_value_function, CallObject, CallObjectRef, Value};
+use realm::{self, Realm};
+use scope_analysis::{Binding, ScopeAnalyzer};
+use vm::{new_value_function, CallObject, CallObjectRef, DebugInfo, SourcePosition, Value};
 
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::mem;
+use std::rc::Rc;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct FunctionInfo {
     pub name: String,
     pub params: FormalParameters,
     pub iseq: ByteCode,
+    pub strict: bool,
+    pub locals: usize, // The number of local variable slots
+    pub debug_info: DebugInfo,
 }
 
 impl FunctionInfo {
-    pub fn new(name: String, params: FormalParameters, iseq: ByteCode) -> FunctionInfo {
+    pub fn new(
+        name: String,
+        params: FormalParameters,
+        iseq: ByteCode,
+        strict: bool,
+        locals: usize,
+        debug_info: DebugInfo,
+    ) -> FunctionInfo {
         FunctionInfo {
             name: name,
             params: params,
             iseq: iseq,
+            strict: strict,
+            locals: locals,
+            debug_info: debug_info,
         }
     }
 }
 
+// Returns the 'name' property of a function named 'name' by AnonymousFunctionExtractor, which is
+// empty for anonymous functions.
+fn function_name(name: &str) -> String {
+    if !name.starts_with("anonymous.") {
+        return name.to_string();
+    }
+    let mut parts = name.split('.').collect::<Vec<&str>>();
+    parts.pop(); // random suffix
+    parts[1..].join(".")
+}
+
+// Returns the name shown in stack traces for a function named 'name' by AnonymousFunctionExtractor.
+fn function_display_name(name: &str) -> String {
+    match function_name(name) {
+        ref name if name.is_empty() => "<anonymous>".to_string(),
+        name => name,
+    }
+}
+
+// Returns the 'length' property of a function, the number of parameters before the first one
+// with a default value or the rest parameter.
+fn function_length(params: &FormalParameters) -> usize {
+    params
+        .iter()
+        .take_while(|param| param.init.is_none() && !param.is_rest_param)
+        .count()
+}
+
+// Returns true if the directive prologue of the given statement list contains 'use strict'.
+// https://tc39.github.io/ecma262/#sec-directive-prologues-and-the-use-strict-directive
+pub fn has_use_strict_directive(node: &Node) -> bool {
+    if let NodeBase::StatementList(ref body) = node.base {
+        for stmt in body {
+            match stmt.base {
+                NodeBase::String(ref s) if s.eq_str("use strict") => return true,
+                NodeBase::String(_) => {}
+                _ => break,
+            }
+        }
+    }
+    false
+}
+
 #[derive(Clone, Debug)]
 pub struct Labels {
     continue_jmp_list: Vec<isize>,
     break_jmp_list: Vec<isize>,
+    try_depth: usize, // The number of try statements surrounding the loop
 }
 
 impl Labels {
-    pub fn new() -> Labels {
+    pub fn new(try_depth: usize) -> Labels {
         Labels {
             continue_jmp_list: vec![],
             break_jmp_list: vec![],
+            try_depth: try_depth,
         }
     }
 
@@ -74,53 +137,127 @@ pub struct VMCodeGen {
     pub functions: HashMap<String, FunctionInfo>,
     pub bytecode_gen: ByteCodeGen,
     pub labels: Vec<Labels>,
+    pub try_blocks: Vec<(bool, Node, bool)>, // has an exception handler, finally, catch scope
+    pub catch_params: Vec<String>,           // The parameters of the catch blocks being compiled
+    pub source: Option<(String, Lexer)>,     // file name, lexer
+    pub positions: Vec<SourcePosition>,
+    pub scope_analyzer: ScopeAnalyzer,
+    pub function: Option<String>, // The function being compiled, or None for the top level code
+    pub realm: Rc<Realm>, // Where the global object and the functions declared are allocated
 }
 
 impl VMCodeGen {
+    /// Makes a code generator with a realm of its own, which the VM made for its global object
+    /// belongs to (see VM::new()).
     pub fn new() -> VMCodeGen {
+        VMCodeGen::with_realm(Realm::new())
+    }
+
+    /// Makes a code generator for code that runs in 'realm', e.g. in a VM running now.
+    pub fn with_realm(realm: Rc<Realm>) -> VMCodeGen {
+        realm::enter(&realm);
         VMCodeGen {
             global_varmap: CallObject::new_global(),
             functions: HashMap::new(),
             bytecode_gen: ByteCodeGen::new(),
-            labels: vec![Labels::new()],
+            labels: vec![Labels::new(0)],
+            try_blocks: vec![],
+            catch_params: vec![],
+            source: None,
+            positions: vec![],
+            scope_analyzer: ScopeAnalyzer::new(),
+            function: None,
+            realm: realm,
         }
     }
+
+    /// Lets the code generator map the positions of nodes to lines and columns of the source
+    /// through 'lexer', which must be the one used to parse the nodes compiled next.
+    pub fn set_source(&mut self, file_name: &str, lexer: &Lexer) {
+        self.source = Some((file_name.to_string(), lexer.clone()));
+    }
+
+    fn file_name(&self) -> String {
+        self.source
+            .as_ref()
+            .map_or("<unknown>".to_string(), |(file_name, _)| file_name.clone())
+    }
 }
 
 impl VMCodeGen {
     pub fn compile(&mut self, node: &Node, iseq: &mut ByteCode, use_value: bool) {
+        realm::enter(&self.realm);
+
+        // Each script is strict mode code only if it has the directive itself. The scope strict
+        // mode eval() code creates for itself stays strict.
+        let global_strict = unsafe {
+            let scope = &mut *self.global_varmap;
+            scope.strict = has_use_strict_directive(node) || scope.parent.is_some() && scope.strict;
+            scope.strict
+        };
+
+        self.scope_analyzer.run_toplevel(node);
+
         self.bytecode_gen.gen_create_context(iseq);
 
         self.run(node, iseq, use_value);
 
         self.bytecode_gen.gen_end(iseq);
 
+        let positions = mem::replace(&mut self.positions, vec![]);
+        self.bytecode_gen.const_table.debug_info.insert(
+            0,
+            DebugInfo {
+                name: "<anonymous>".to_string(),
+                file: self.file_name(),
+                positions: positions,
+                source: String::new(),
+            },
+        );
+
         for (
             _,
             FunctionInfo {
                 name,
                 params,
                 iseq: func_iseq,
+                strict,
+                locals,
+                debug_info,
             },
         ) in &self.functions
         {
-            let val = new_value_function(id::get_unique_id(), func_iseq.clone(), {
-                let mut callobj =
-                    CallObject::new(unsafe { Value::object((*self.global_varmap).vals.clone()) });
-                callobj.params = params
-                    .clone()
-                    .iter()
-                    .map(
-                        |FormalParameter {
-                             name,
-                             is_rest_param,
-                             ..
-                         }| (name.clone(), *is_rest_param),
-                    )
-                    .collect();
-                callobj.parent = Some(self.global_varmap.clone());
-                callobj
-            });
+            let id = id::get_unique_id();
+            self.bytecode_gen
+                .const_table
+                .debug_info
+                .insert(id, debug_info.clone());
+            let val = new_value_function(
+                id,
+                func_iseq.clone(),
+                {
+                    let mut callobj = CallObject::new(unsafe {
+                        Value::object((*self.global_varmap).vals.clone())
+                    });
+                    callobj.params = params
+                        .clone()
+                        .iter()
+                        .map(
+                            |FormalParameter {
+                                 name,
+                                 is_rest_param,
+                                 ..
+                             }| (name.clone(), *is_rest_param),
+                        )
+                        .collect();
+                    callobj.parent = Some(self.global_varmap.clone());
+                    callobj.strict = *strict || global_strict;
+                    callobj.locals = *locals;
+                    callobj
+                },
+                function_name(name.as_str()),
+                function_length(params),
+            );
             unsafe {
                 (*self.global_varmap).set_value(name.clone(), val.clone());
             }
@@ -132,7 +269,7 @@ impl VMCodeGen {
             match constant {
                 NodeBase::String(ref s) => self
                     .bytecode_gen
-                    .gen_push_const(Value::string(CString::new(s.as_str()).unwrap()), iseq),
+                    .gen_push_const(Value::string(s.clone()), iseq),
                 NodeBase::Number(n) => self.bytecode_gen.gen_push_number(n, iseq),
                 NodeBase::Boolean(b) => self.bytecode_gen.gen_push_bool(b, iseq),
                 _ => unreachable!(),
@@ -140,12 +277,14 @@ impl VMCodeGen {
             return;
         }
 
+        let start = iseq.len();
+
         match &node.base {
             &NodeBase::StatementList(ref node_list) => {
                 self.run_statement_list(node_list, iseq, use_value)
             }
-            &NodeBase::FunctionDecl(ref name, ref params, ref body) => {
-                self.run_function_decl(name, params, &*body)
+            &NodeBase::FunctionDecl(ref name, ref params, ref body, ref source) => {
+                self.run_function_decl(name, params, &*body, source)
             }
             &NodeBase::VarDecl(ref name, ref init) => {
                 self.run_var_decl(name, init, iseq);
@@ -169,6 +308,10 @@ impl VMCodeGen {
             &NodeBase::Member(ref parent, ref member) => self.run_member(&*parent, member, iseq),
             &NodeBase::Index(ref parent, ref idx) => self.run_index(&*parent, &*idx, iseq),
             &NodeBase::Return(ref val) => self.run_return(val, iseq),
+            &NodeBase::Throw(ref val) => self.run_throw(&*val, iseq),
+            &NodeBase::Try(ref block, ref param, ref catch, ref finally) => {
+                self.run_try(&*block, param, &*catch, &*finally, iseq)
+            }
             &NodeBase::Break => self.run_break(iseq),
             &NodeBase::Continue => self.run_continue(iseq),
             &NodeBase::New(ref expr) => self.run_new_expr(&*expr, iseq),
@@ -178,10 +321,14 @@ impl VMCodeGen {
             &NodeBase::This => self.bytecode_gen.gen_push_this(iseq),
             &NodeBase::Arguments => self.bytecode_gen.gen_push_arguments(iseq),
             &NodeBase::Undefined => self.bytecode_gen.gen_push_undefined(iseq),
+            &NodeBase::Null => self.bytecode_gen.gen_push_const(Value::null(), iseq),
             &NodeBase::String(ref s) => self
                 .bytecode_gen
-                .gen_push_const(Value::string(CString::new(s.as_str()).unwrap()), iseq),
+                .gen_push_const(Value::string(s.clone()), iseq),
             &NodeBase::Number(n) => self.bytecode_gen.gen_push_number(n, iseq),
+            &NodeBase::BigInt(ref n) => self
+                .bytecode_gen
+                .gen_push_const(Value::bigint(n.clone()), iseq),
             &NodeBase::Boolean(b) => self.bytecode_gen.gen_push_bool(b, iseq),
             &NodeBase::SetCurCallObj(ref name) => {
                 self.bytecode_gen.gen_get_name(name, iseq);
@@ -193,6 +340,28 @@ impl VMCodeGen {
 
             _ => {}
         }
+
+        self.record_position(start, iseq.len(), node);
+    }
+
+    // Records that the instructions in [start, end) are generated from 'node'. The entries of
+    // inner nodes come first, which DebugInfo::find_position relies on.
+    fn record_position(&mut self, start: usize, end: usize, node: &Node) {
+        if start == end {
+            return;
+        }
+        if let NodeBase::StatementList(_) = node.base {
+            return;
+        }
+        if let Some((_, ref lexer)) = self.source {
+            let (line, column) = lexer.get_line_and_column(node.pos);
+            self.positions.push(SourcePosition {
+                start: start,
+                end: end,
+                line: line,
+                column: column,
+            });
+        }
     }
 }
 
@@ -210,12 +379,28 @@ impl VMCodeGen {
 }
 
 impl VMCodeGen {
-    pub fn run_function_decl(&mut self, name: &String, params: &FormalParameters, body: &Node) {
+    pub fn run_function_decl(
+        &mut self,
+        name: &String,
+        params: &FormalParameters,
+        body: &Node,
+        source: &String,
+    ) {
         let mut func_iseq = vec![];
+        let outer_positions = mem::replace(&mut self.positions, vec![]);
+        let outer_try_blocks = mem::replace(&mut self.try_blocks, vec![]);
+        let outer_catch_params = mem::replace(&mut self.catch_params, vec![]);
+        let outer_function = mem::replace(&mut self.function, Some(name.clone()));
 
         self.bytecode_gen.gen_create_context(&mut func_iseq);
 
-        // TODO: Implement Rest Parameter ASAP
+        // The arguments are passed in the slots of the parameters. Move those captured by inner
+        // functions or read by 'arguments' to the environment.
+        let captured_params = self.scope_analyzer.functions[name].captured_params.clone();
+        for (slot, param) in captured_params {
+            self.bytecode_gen.gen_get_local(slot, &mut func_iseq);
+            self.bytecode_gen.gen_decl_var(&param, &mut func_iseq);
+        }
 
         self.run(body, &mut func_iseq, false);
 
@@ -228,9 +413,26 @@ impl VMCodeGen {
             }
         }
 
+        self.try_blocks = outer_try_blocks;
+        self.catch_params = outer_catch_params;
+        self.function = outer_function;
+        let positions = mem::replace(&mut self.positions, outer_positions);
+
         self.functions.insert(
             name.clone(),
-            FunctionInfo::new(name.clone(), params.clone(), func_iseq),
+            FunctionInfo::new(
+                name.clone(),
+                params.clone(),
+                func_iseq,
+                has_use_strict_directive(body),
+                self.scope_analyzer.locals_len(name),
+                DebugInfo {
+                    name: function_display_name(name.as_str()),
+                    file: self.file_name(),
+                    positions: positions,
+                    source: source.clone(),
+                },
+            ),
         );
     }
 
@@ -240,12 +442,124 @@ impl VMCodeGen {
         } else {
             self.bytecode_gen.gen_push_const(Value::undefined(), iseq);
         }
+        self.leave_try_blocks(0, iseq);
         self.bytecode_gen.gen_return(iseq);
     }
 }
 
+impl VMCodeGen {
+    pub fn run_throw(&mut self, val: &Node, iseq: &mut ByteCode) {
+        self.run(val, iseq, true);
+        self.bytecode_gen.gen_throw(iseq);
+    }
+
+    pub fn run_try(
+        &mut self,
+        block: &Node,
+        param: &Option<String>,
+        catch: &Node,
+        finally: &Node,
+        iseq: &mut ByteCode,
+    ) {
+        let has_catch = catch.base != NodeBase::Nope;
+        let has_finally = finally.base != NodeBase::Nope;
+        let mut end_jmp_list = vec![];
+
+        let enter_try_pos = iseq.len() as isize;
+        self.bytecode_gen.gen_enter_try(0, iseq);
+        self.try_blocks.push((true, finally.clone(), false));
+        self.run(block, iseq, false);
+        self.try_blocks.pop();
+        self.bytecode_gen.gen_leave_try(iseq);
+        end_jmp_list.push(iseq.len() as isize);
+        self.bytecode_gen.gen_jmp(0, iseq);
+
+        // The exception handlers start with the thrown value pushed.
+        let handler_pos = iseq.len() as isize;
+        self.bytecode_gen.replace_int32(
+            (handler_pos - enter_try_pos) as i32 - 5,
+            &mut iseq[enter_try_pos as usize + 1..enter_try_pos as usize + 5],
+        );
+
+        if has_catch {
+            // An exception thrown in the catch block must go through the finally block.
+            let rethrow_try_pos = iseq.len() as isize;
+            if has_finally {
+                self.bytecode_gen.gen_enter_try(0, iseq);
+            }
+
+            // The parameter is bound in a scope of the catch block.
+            if let &Some(ref name) = param {
+                self.bytecode_gen.gen_enter_catch(name, iseq);
+                self.catch_params.push(name.clone());
+            } else {
+                self.bytecode_gen.gen_pop(iseq);
+            }
+
+            self.try_blocks
+                .push((has_finally, finally.clone(), param.is_some()));
+            self.run(catch, iseq, false);
+            self.try_blocks.pop();
+
+            if param.is_some() {
+                self.bytecode_gen.gen_leave_scope(iseq);
+                self.catch_params.pop();
+            }
+
+            if has_finally {
+                self.bytecode_gen.gen_leave_try(iseq);
+            }
+            end_jmp_list.push(iseq.len() as isize);
+            self.bytecode_gen.gen_jmp(0, iseq);
+
+            if has_finally {
+                let rethrow_pos = iseq.len() as isize;
+                self.bytecode_gen.replace_int32(
+                    (rethrow_pos - rethrow_try_pos) as i32 - 5,
+                    &mut iseq[rethrow_try_pos as usize + 1..rethrow_try_pos as usize + 5],
+                );
+            }
+        }
+
+        if has_finally {
+            self.run(finally, iseq, false);
+            self.bytecode_gen.gen_throw(iseq);
+        }
+
+        let end_pos = iseq.len() as isize;
+        for jmp_pos in end_jmp_list {
+            self.bytecode_gen.replace_int32(
+                (end_pos - jmp_pos) as i32 - 5,
+                &mut iseq[jmp_pos as usize + 1..jmp_pos as usize + 5],
+            );
+        }
+
+        self.run(finally, iseq, false);
+    }
+
+    // Leaves the try statements entered after the 'depth'-th one, running their finally blocks.
+    // Used when 'break', 'continue' or 'return' jumps out of try blocks.
+    fn leave_try_blocks(&mut self, depth: usize, iseq: &mut ByteCode) {
+        let try_blocks = self.try_blocks.clone();
+        for i in (depth..try_blocks.len()).rev() {
+            self.try_blocks.truncate(i);
+            let (has_handler, ref finally, has_scope) = try_blocks[i];
+            if has_scope {
+                self.bytecode_gen.gen_leave_scope(iseq);
+            }
+            if has_handler {
+                self.bytecode_gen.gen_leave_try(iseq);
+            }
+            self.run(finally, iseq, false);
+        }
+        self.try_blocks = try_blocks;
+    }
+}
+
 impl VMCodeGen {
     pub fn run_break(&mut self, iseq: &mut ByteCode) {
+        let try_depth = self.labels.last().unwrap().try_depth;
+        self.leave_try_blocks(try_depth, iseq);
         let break_jmp_pos = iseq.len() as isize;
         self.bytecode_gen.gen_jmp(0, iseq);
         self.labels
@@ -256,6 +570,8 @@ impl VMCodeGen {
     }
 
     pub fn run_continue(&mut self, iseq: &mut ByteCode) {
+        let try_depth = self.labels.last().unwrap().try_depth;
+        self.leave_try_blocks(try_depth, iseq);
         let continue_jmp_pos = iseq.len() as isize;
         self.bytecode_gen.gen_jmp(0, iseq);
         self.labels
@@ -280,12 +596,31 @@ impl VMCodeGen {
 
 impl VMCodeGen {
     pub fn run_var_decl(&mut self, name: &String, init: &Option<Box<Node>>, iseq: &mut ByteCode) {
+        // A var declaration of the parameter in a catch block declares the variable of the
+        // function, but its initializer assigns to the parameter.
+        // https://tc39.github.io/ecma262/#sec-variablestatements-in-catch-blocks
+        if self.catch_params.contains(name) {
+            if let &Some(ref init) = init {
+                self.run(&*init, iseq, true);
+                self.bytecode_gen.gen_set_name(name, iseq);
+            }
+            return;
+        }
+
         if let &Some(ref init) = init {
             self.run(&*init, iseq, true);
         } else {
             self.bytecode_gen.gen_push_const(Value::undefined(), iseq);
         }
-        self.bytecode_gen.gen_decl_var(name, iseq);
+        self.decl_var(name, iseq);
+    }
+
+    // Declares 'name' with the value on the stack top.
+    fn decl_var(&mut self, name: &String, iseq: &mut ByteCode) {
+        match self.resolve(name) {
+            Binding::Local(slot) => self.bytecode_gen.gen_set_local(slot, iseq),
+            _ => self.bytecode_gen.gen_decl_var(name, iseq),
+        }
     }
 }
 
@@ -326,7 +661,7 @@ impl VMCodeGen {
 
     pub fn run_while(&mut self, cond: &Node, body: &Node, iseq: &mut ByteCode) {
         let pos1 = iseq.len() as isize;
-        self.labels.push(Labels::new());
+        self.labels.push(Labels::new(self.try_blocks.len()));
 
         self.bytecode_gen.gen_loop_start(iseq);
 
@@ -376,7 +711,7 @@ impl VMCodeGen {
         self.run(init, iseq, false);
 
         let pos = iseq.len() as isize;
-        self.labels.push(Labels::new());
+        self.labels.push(Labels::new(self.try_blocks.len()));
 
         self.bytecode_gen.gen_loop_start(iseq);
 
@@ -427,11 +762,20 @@ impl VMCodeGen {
         iseq: &mut ByteCode,
         use_value: bool,
     ) {
+        if op == &UnaryOp::Delete {
+            self.run_delete(expr, iseq);
+            if !use_value {
+                self.bytecode_gen.gen_pop(iseq);
+            }
+            return;
+        }
+
         self.run(expr, iseq, true);
         match op {
             &UnaryOp::Plus => self.bytecode_gen.gen_posi(iseq),
             &UnaryOp::Minus => self.bytecode_gen.gen_neg(iseq),
             &UnaryOp::Not => self.bytecode_gen.gen_lnot(iseq),
+            &UnaryOp::BitwiseNot => self.bytecode_gen.gen_bitwise_not(iseq),
             &UnaryOp::PrInc => {
                 self.bytecode_gen.gen_push_int8(1, iseq);
                 self.bytecode_gen.gen_add(iseq);
@@ -468,6 +812,29 @@ impl VMCodeGen {
         }
     }
 
+    pub fn run_delete(&mut self, expr: &Node, iseq: &mut ByteCode) {
+        match expr.base {
+            NodeBase::Member(ref parent, ref member) => {
+                self.run(&*parent, iseq, true);
+                self.bytecode_gen
+                    .gen_push_const(Value::string(JsString::from(member.as_str())), iseq);
+                self.bytecode_gen.gen_delete(iseq);
+            }
+            NodeBase::Index(ref parent, ref idx) => {
+                self.run(&*parent, iseq, true);
+                self.run(&*idx, iseq, true);
+                self.bytecode_gen.gen_delete(iseq);
+            }
+            // Variables cannot be deleted.
+            NodeBase::Identifier(_) => self.bytecode_gen.gen_push_bool(false, iseq),
+            _ => {
+                self.run(expr, iseq, true);
+                self.bytecode_gen.gen_pop(iseq);
+                self.bytecode_gen.gen_push_bool(true, iseq);
+            }
+        }
+    }
+
     pub fn run_binary_op(&mut self, lhs: &Node, rhs: &Node, op: &BinOp, iseq: &mut ByteCode) {
         // Following code has influence on JIT(src/jit.rs) code.
         match op {
@@ -533,12 +900,14 @@ impl VMCodeGen {
             &BinOp::Mul => self.bytecode_gen.gen_mul(iseq),
             &BinOp::Div => self.bytecode_gen.gen_div(iseq),
             &BinOp::Rem => self.bytecode_gen.gen_rem(iseq),
+            &BinOp::Exp => self.bytecode_gen.gen_exp(iseq),
             &BinOp::Eq => self.bytecode_gen.gen_eq(iseq),
             &BinOp::Ne => self.bytecode_gen.gen_ne(iseq),
             &BinOp::SEq => self.bytecode_gen.gen_seq(iseq),
             &BinOp::SNe => self.bytecode_gen.gen_sne(iseq),
             &BinOp::And => self.bytecode_gen.gen_and(iseq),
             &BinOp::Or => self.bytecode_gen.gen_or(iseq),
+            &BinOp::Xor => self.bytecode_gen.gen_xor(iseq),
             &BinOp::Lt => self.bytecode_gen.gen_lt(iseq),
             &BinOp::Gt => self.bytecode_gen.gen_gt(iseq),
             &BinOp::Le => self.bytecode_gen.gen_le(iseq),
@@ -590,13 +959,14 @@ impl VMCodeGen {
 
     pub fn assign_stack_top(&mut self, dst: &Node, iseq: &mut ByteCode) {
         match dst.base {
-            NodeBase::Identifier(ref name) => {
-                self.bytecode_gen.gen_set_name(name, iseq);
-            }
+            NodeBase::Identifier(ref name) => match self.resolve(name) {
+                Binding::Local(slot) => self.bytecode_gen.gen_set_local(slot, iseq),
+                _ => self.bytecode_gen.gen_set_name(name, iseq),
+            },
             NodeBase::Member(ref parent, ref member) => {
                 self.run(&*parent, iseq, true);
                 self.bytecode_gen
-                    .gen_push_const(Value::string(CString::new(member.as_str()).unwrap()), iseq);
+                    .gen_push_const(Value::string(JsString::from(member.as_str())), iseq);
                 self.bytecode_gen.gen_set_member(iseq);
             }
             NodeBase::Index(ref parent, ref idx) => {
@@ -623,7 +993,13 @@ impl VMCodeGen {
 
         self.run(callee, iseq, true);
 
-        self.bytecode_gen.gen_call(args.len() as u32, iseq);
+        match callee.base {
+            // May be a direct eval, which runs in the scope of the caller.
+            NodeBase::Identifier(ref name) if name == "eval" => {
+                self.bytecode_gen.gen_call_eval(args.len() as u32, iseq)
+            }
+            _ => self.bytecode_gen.gen_call(args.len() as u32, iseq),
+        }
 
         if !use_value {
             self.bytecode_gen.gen_pop(iseq);
@@ -639,7 +1015,7 @@ impl VMCodeGen {
                 PropertyDefinition::Property(name, node) => {
                     self.run(&node, iseq, true);
                     self.bytecode_gen
-                        .gen_push_const(Value::string(CString::new(name.as_str()).unwrap()), iseq);
+                        .gen_push_const(Value::string(JsString::from_key(name)), iseq);
                 }
             }
         }
@@ -663,7 +1039,7 @@ impl VMCodeGen {
         self.run(parent, iseq, true);
 
         self.bytecode_gen
-            .gen_push_const(Value::string(CString::new(member.as_str()).unwrap()), iseq);
+            .gen_push_const(Value::string(JsString::from(member.as_str())), iseq);
         self.bytecode_gen.gen_get_member(iseq);
     }
 
@@ -675,7 +1051,19 @@ impl VMCodeGen {
     }
 
     fn run_identifier(&mut self, name: &String, iseq: &mut ByteCode) {
-        self.bytecode_gen.gen_get_name(name, iseq);
+        match self.resolve(name) {
+            Binding::Local(slot) => self.bytecode_gen.gen_get_local(slot, iseq),
+            Binding::Upvar(depth) => self.bytecode_gen.gen_get_upvar(depth, name, iseq),
+            Binding::Name => self.bytecode_gen.gen_get_name(name, iseq),
+        }
+    }
+
+    // The parameters of the catch blocks are looked up by name through their scopes.
+    fn resolve(&self, name: &String) -> Binding {
+        if self.catch_params.contains(name) {
+            return Binding::Name;
+        }
+        self.scope_analyzer.resolve(&self.function, name)
     }
 }
 